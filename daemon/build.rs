@@ -0,0 +1,23 @@
+use fomat_macros::fomat;
+use std::{env, fs::File, io::Write};
+
+fn service(name: &str, exec: &str) -> String {
+    fomat!(
+        "[D-BUS Service]\n"
+        "Name=" (name) "\n"
+        "Exec=" (exec) "\n"
+    )
+}
+
+fn main() {
+    let appid = env::var("APPID").unwrap();
+    let prefix = env::var("prefix").unwrap();
+
+    let service_path = ["../target/", &appid, ".service"].concat();
+    let exec = [&prefix, "/bin/", &appid].concat();
+
+    File::create(service_path)
+        .expect("failed to create D-Bus service")
+        .write_all(service(&appid, &exec).as_bytes())
+        .expect("failed to write D-Bus service");
+}
@@ -0,0 +1,248 @@
+use firmware_manager::{
+    CancellationToken, DeviceId, Entity, FirmwareEvent, FirmwareInfo, FirmwareSignal, FwupdDevice,
+    FwupdRelease, PolicyReason, System76Digest, UpdateBatch, UpdateRequest,
+};
+use slotmap::SlotMap;
+use std::sync::Arc;
+
+/// The ID of System76 system firmware, as exposed on the bus.
+const SYSTEM76_ID: &str = "system76";
+
+/// The ID of the embedded controller of System76 systems, as exposed on the bus.
+const SYSTEM76_EC_ID: &str = "system76-ec";
+
+/// The ID of the Intel Management Engine, as exposed on the bus.
+const MANAGEMENT_ENGINE_ID: &str = "intel-me";
+
+/// The ID of the microcode of the processor, as exposed on the bus.
+const MICROCODE_ID: &str = "cpu-microcode";
+
+/// The prefix of the IDs of Thelio I/O boards, as exposed on the bus.
+const THELIO_IO_ID: &str = "thelio-io";
+
+/// A device which was discovered by the last scan.
+pub struct Device {
+    /// Identifies the device to clients of the service.
+    pub id: Box<str>,
+    /// Information about the device and its firmware.
+    pub info: FirmwareInfo,
+    /// Cancels the update that is in progress, if any.
+    pub cancellable: Option<CancellationToken>,
+    /// Describes how the device is updated.
+    request: Request,
+}
+
+/// The details required to request an update from the event loop.
+enum Request {
+    Device(DeviceId),
+    Fwupd(Arc<FwupdDevice>, Option<Arc<FwupdRelease>>),
+    S76System(Option<System76Digest>),
+    /// Updated with the system firmware, rather than on its own.
+    S76Ec,
+    /// Updated with the system firmware, rather than on its own.
+    ManagementEngine,
+    /// Loaded by the kernel from the packages of the distribution.
+    Microcode,
+    ThelioIo(Box<str>, Option<System76Digest>),
+}
+
+/// An error that may occur when requesting an update.
+pub enum UpdateError {
+    /// No device has the given ID.
+    NotFound,
+    /// The device does not have an update available.
+    NoUpdate,
+    /// The device is already being updated.
+    InProgress,
+    /// The device does not support flashing older releases, or switching branches.
+    Unsupported,
+    /// The firmware policy of the system does not approve the update.
+    NotApproved(PolicyReason),
+}
+
+/// All devices that are known to the service.
+#[derive(Default)]
+pub struct Devices {
+    devices: SlotMap<Entity, Device>,
+}
+
+impl Devices {
+    /// Removes every device, in preparation for a new scan.
+    pub fn clear(&mut self) { self.devices.clear(); }
+
+    /// The device associated with an entity.
+    pub fn get(&self, entity: Entity) -> Option<&Device> { self.devices.get(entity) }
+
+    /// Finds the entity of the device with the given ID.
+    pub fn find(&self, id: &str) -> Option<Entity> {
+        self.devices.iter().find(|(_, device)| &*device.id == id).map(|(entity, _)| entity)
+    }
+
+    /// Iterates on every known device.
+    pub fn iter(&self) -> impl Iterator<Item = &Device> { self.devices.values() }
+
+    /// Records a device which was described by a discovery signal.
+    ///
+    /// Returns `None` if the signal does not describe a device.
+    pub fn discovered(&mut self, signal: FirmwareSignal) -> Option<Entity> {
+        let (id, info, request): (Box<str>, _, _) = match signal {
+            FirmwareSignal::Device(signal) => {
+                let id = [&*signal.device.backend, "/", &*signal.device.id].concat().into();
+                (id, signal.info, Request::Device(signal.device))
+            }
+            FirmwareSignal::Fwupd(signal) | FirmwareSignal::FwupdChanged(signal) => {
+                let release = signal.releases.into_iter().last().map(Arc::new);
+                let id = signal.device.device_id.clone();
+                (id, signal.info, Request::Fwupd(Arc::new(signal.device), release))
+            }
+            FirmwareSignal::S76System(info, digest) => {
+                let digest = digest.map(|(digest, _)| digest);
+                (SYSTEM76_ID.into(), info, Request::S76System(digest))
+            }
+            FirmwareSignal::S76Ec(info) => (SYSTEM76_EC_ID.into(), info, Request::S76Ec),
+            FirmwareSignal::ManagementEngine(info) => {
+                (MANAGEMENT_ENGINE_ID.into(), info, Request::ManagementEngine)
+            }
+            FirmwareSignal::Microcode(info) => (MICROCODE_ID.into(), info, Request::Microcode),
+            FirmwareSignal::ThelioIo(board, info, digest) => {
+                let id = [THELIO_IO_ID, "/", &board].concat().into();
+                (id, info, Request::ThelioIo(board, digest))
+            }
+            _ => return None,
+        };
+
+        if let Some(entity) = self.find(&id) {
+            self.devices.remove(entity);
+        }
+
+        Some(self.devices.insert(Device { id, info, cancellable: None, request }))
+    }
+
+    /// Forgets the device with the given ID.
+    pub fn remove(&mut self, id: &str) -> Option<Device> {
+        self.find(id).and_then(|entity| self.devices.remove(entity))
+    }
+
+    /// The name or ID which the device with the given ID is ignored by in the configuration.
+    pub fn ignore_key(&self, id: &str) -> Option<Box<str>> {
+        let device = &self.devices[self.find(id)?];
+        let key = match &device.request {
+            Request::Device(device) => device.id.clone(),
+            Request::Fwupd(device, _) => device.device_id.clone(),
+            Request::S76System(_)
+            | Request::S76Ec
+            | Request::ManagementEngine
+            | Request::Microcode
+            | Request::ThelioIo(..) => device.info.name.clone(),
+        };
+
+        Some(key)
+    }
+
+    /// The entity of the System76 system firmware, if it was found.
+    pub fn system76(&self) -> Option<Entity> { self.find(SYSTEM76_ID) }
+
+    /// The entity of the Thelio I/O board with the given ID, if it was found.
+    pub fn thelio_io(&self, board: &str) -> Option<Entity> {
+        self.find(&[THELIO_IO_ID, "/", board].concat())
+    }
+
+    /// Creates the request to update the device with the given ID.
+    pub fn update(&mut self, id: &str) -> Result<FirmwareEvent, UpdateError> {
+        let entity = self.find(id).ok_or(UpdateError::NotFound)?;
+        let request = Self::request_of(&self.devices[entity])?;
+
+        let cancellable = CancellationToken::default();
+        self.devices[entity].cancellable = Some(cancellable.clone());
+        Ok(request.event(entity, cancellable))
+    }
+
+    /// Creates the batch of updates of every device which has an update available, and which is
+    /// not already being updated. System firmware is only included if `system` is set.
+    pub fn update_all(&mut self, system: bool) -> UpdateBatch {
+        let mut batch = UpdateBatch::new(system);
+
+        for (entity, device) in &mut self.devices {
+            if let Ok(request) = Self::request_of(device) {
+                device.cancellable = batch.push(entity, &request);
+            }
+        }
+
+        batch
+    }
+
+    /// The update of the `device`, if it may be updated.
+    fn request_of(device: &Device) -> Result<UpdateRequest, UpdateError> {
+        if device.cancellable.is_some() {
+            return Err(UpdateError::InProgress);
+        }
+
+        match device.info.latest {
+            Some(ref latest) if *latest != device.info.current => (),
+            _ => return Err(UpdateError::NoUpdate),
+        }
+
+        if let Some(reason) = device.info.policy {
+            return Err(UpdateError::NotApproved(reason));
+        }
+
+        let request = match &device.request {
+            Request::Device(device) => UpdateRequest::Device(device.clone()),
+            Request::Fwupd(device, Some(release)) => {
+                UpdateRequest::Fwupd(device.clone(), release.clone())
+            }
+            Request::S76System(Some(digest)) => UpdateRequest::S76System(digest.clone()),
+            Request::ThelioIo(board, Some(digest)) => {
+                UpdateRequest::ThelioIo(board.clone(), digest.clone())
+            }
+            _ => return Err(UpdateError::NoUpdate),
+        };
+
+        Ok(request)
+    }
+
+    /// Creates the request to flash an older release of the device with the given ID.
+    pub fn downgrade(&mut self, id: &str, version: &str) -> Result<FirmwareEvent, UpdateError> {
+        let entity = self.find(id).ok_or(UpdateError::NotFound)?;
+        let device = &mut self.devices[entity];
+
+        if device.cancellable.is_some() {
+            return Err(UpdateError::InProgress);
+        }
+
+        let fwupd = match &device.request {
+            Request::Fwupd(fwupd, _) => fwupd.clone(),
+            _ => return Err(UpdateError::Unsupported),
+        };
+
+        let cancellable = CancellationToken::default();
+        device.cancellable = Some(cancellable.clone());
+        Ok(FirmwareEvent::Downgrade(entity, fwupd, version.into(), cancellable))
+    }
+
+    /// Creates the request to switch the device with the given ID to another firmware branch.
+    pub fn switch_branch(&mut self, id: &str, branch: &str) -> Result<FirmwareEvent, UpdateError> {
+        let entity = self.find(id).ok_or(UpdateError::NotFound)?;
+        let device = &mut self.devices[entity];
+
+        if device.cancellable.is_some() {
+            return Err(UpdateError::InProgress);
+        }
+
+        let fwupd = match &device.request {
+            Request::Fwupd(fwupd, _) => fwupd.clone(),
+            _ => return Err(UpdateError::Unsupported),
+        };
+
+        let cancellable = CancellationToken::default();
+        device.cancellable = Some(cancellable.clone());
+        Ok(FirmwareEvent::SwitchBranch(entity, fwupd, branch.into(), cancellable))
+    }
+
+    /// Marks the update of a device as no longer being in progress.
+    pub fn finished(&mut self, entity: Entity) {
+        if let Some(device) = self.devices.get_mut(entity) {
+            device.cancellable = None;
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! A D-Bus service which allows other desktop components, such as control centers and shell
+//! extensions, to list devices and update their firmware without linking to the firmware manager.
+
+#[macro_use]
+extern crate tracing;
+
+mod devices;
+mod service;
+
+use std::{env, io, process::exit};
+use tracing_subscriber::filter::EnvFilter;
+
+/// The environment variable which overrides the default log filter.
+const FILTER_VAR: &str = "FIRMWARE_MANAGER_LOG";
+
+fn main() {
+    let directives = env::var(FILTER_VAR)
+        .unwrap_or_else(|_| "firmware_manager_daemon=info,firmware_manager=info".into());
+
+    match EnvFilter::try_new(directives) {
+        Ok(filter) => {
+            let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+            let _res = subscriber.with_writer(io::stderr).try_init();
+        }
+        Err(why) => eprintln!("invalid log filter: {}", why),
+    }
+
+    if let Err(why) = service::run() {
+        error!("{} stopped: {}", service::NAME, why);
+        exit(1);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::devices::{Device, Devices, UpdateError};
+use dbus::{
+    arg::AppendAll,
+    blocking::Connection,
+    channel::{MatchingReceiver, Sender as _},
+    message::MatchRule,
+    Message, MethodErr,
+};
+use dbus_crossroads::{Crossroads, IfaceBuilder};
+use firmware_manager::{DeferReason, Entity, FirmwareEvent, FirmwareSignal, InterruptedUpdate};
+use slotmap::SecondaryMap;
+use std::{
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Sender},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+/// The well-known name of the service on the session bus.
+pub const NAME: &str = "com.system76.FirmwareManager.Daemon";
+
+/// The path of the object which implements the interface.
+const PATH: &str = "/com/system76/FirmwareManager";
+
+/// The interface through which devices are listed and updated.
+const INTERFACE: &str = "com.system76.FirmwareManager.Daemon";
+
+/// Set when the service is asked to stop by SIGTERM or SIGINT.
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// A device as it is listed on the bus: its ID, name, current version, and latest version.
+type DeviceInfo = (String, String, String, String);
+
+/// State which is shared between the method handlers and the event loop.
+struct Service {
+    devices: Devices,
+    downloads: SecondaryMap<Entity, (u64, u64)>,
+    sender: Sender<FirmwareEvent>,
+}
+
+type SharedService = Arc<Mutex<Service>>;
+
+fn lock(service: &SharedService) -> MutexGuard<'_, Service> {
+    service.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Requests for the service to stop, once the updates in progress have stopped.
+extern "C" fn request_stop(_signal: libc::c_int) { STOP.store(true, Ordering::SeqCst); }
+
+/// Serves requests on the session bus until the connection is lost, or the service is asked to
+/// stop.
+pub fn run() -> Result<(), dbus::Error> {
+    // Devices which are flashing when the service is stopped are left to finish flashing.
+    let handler = request_stop as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+
+    let (event_tx, event_rx) = mpsc::channel();
+    let (signal_tx, signal_rx) = mpsc::channel();
+
+    let background = thread::spawn(move || firmware_manager::event_loop(event_rx, signal_tx));
+
+    let connection = Connection::new_session()?;
+    connection.request_name(NAME, false, true, true)?;
+
+    let service = Arc::new(Mutex::new(Service {
+        devices: Devices::default(),
+        downloads: SecondaryMap::new(),
+        sender: event_tx.clone(),
+    }));
+
+    let mut crossroads = Crossroads::new();
+    let interface = crossroads.register(INTERFACE, register);
+    crossroads.insert(PATH, &[interface], service.clone());
+
+    connection.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |message, connection| {
+            let _res = crossroads.handle_message(message, connection);
+            true
+        }),
+    );
+
+    info!("serving {} on the session bus", NAME);
+    let _res = event_tx.send(FirmwareEvent::Scan);
+
+    let result = loop {
+        if STOP.load(Ordering::SeqCst) {
+            info!("stopping {}", NAME);
+            break Ok(());
+        }
+
+        if let Err(why) = connection.process(Duration::from_millis(100)) {
+            break Err(why);
+        }
+
+        for signal in signal_rx.try_iter() {
+            relay(&connection, &mut lock(&service), signal);
+        }
+    };
+
+    let _res = event_tx.send(FirmwareEvent::Stop);
+    let _res = background.join();
+
+    result
+}
+
+/// Declares the methods and signals of the interface.
+fn register(builder: &mut IfaceBuilder<SharedService>) {
+    builder.method("Scan", (), (), |_, service, ()| {
+        let _res = lock(service).sender.send(FirmwareEvent::Scan);
+        Ok(())
+    });
+
+    builder.method("ListDevices", (), ("devices",), |_, service, ()| {
+        let devices = lock(service).devices.iter().map(device_info).collect::<Vec<_>>();
+        Ok((devices,))
+    });
+
+    builder.method("Update", ("id",), (), |_, service, (id,): (String,)| {
+        let mut service = lock(service);
+        let event = service.devices.update(&id).map_err(|why| method_error(why, &id))?;
+        let _res = service.sender.send(event);
+        Ok(())
+    });
+
+    builder.method("UpdateAll", ("system",), (), |_, service, (system,): (bool,)| {
+        let mut service = lock(service);
+        let batch = service.devices.update_all(system);
+        let _res = service.sender.send(FirmwareEvent::UpdateAll(batch));
+        Ok(())
+    });
+
+    builder.method(
+        "Downgrade",
+        ("id", "version"),
+        (),
+        |_, service, (id, version): (String, String)| {
+            let mut service = lock(service);
+            let event =
+                service.devices.downgrade(&id, &version).map_err(|why| method_error(why, &id))?;
+
+            let _res = service.sender.send(event);
+            Ok(())
+        },
+    );
+
+    builder.method(
+        "SwitchBranch",
+        ("id", "branch"),
+        (),
+        |_, service, (id, branch): (String, String)| {
+            let mut service = lock(service);
+            let event =
+                service.devices.switch_branch(&id, &branch).map_err(|why| method_error(why, &id))?;
+
+            let _res = service.sender.send(event);
+            Ok(())
+        },
+    );
+
+    builder.method("Ignore", ("id",), (), |ctx, service, (id,): (String,)| {
+        let mut service = lock(service);
+        let key = service.devices.ignore_key(&id).ok_or_else(|| MethodErr::invalid_arg(&id))?;
+        firmware_manager::set_device_ignored(&key, true)
+            .map_err(|why| MethodErr::failed(&format_error(&why)))?;
+
+        service.devices.remove(&id);
+        ctx.push_msg(ctx.make_signal("DeviceRemoved", (id,)));
+        Ok(())
+    });
+
+    builder.method("Cancel", ("id",), (), |_, service, (id,): (String,)| {
+        let service = lock(service);
+        let entity = service.devices.find(&id).ok_or_else(|| MethodErr::invalid_arg(&id))?;
+
+        let device = service.devices.get(entity);
+        if let Some(cancellable) = device.and_then(|device| device.cancellable.as_ref()) {
+            cancellable.cancel();
+        }
+
+        Ok(())
+    });
+
+    builder.signal::<(), _>("ScanStarted", ());
+    builder.signal::<(), _>("ScanCompleted", ());
+    builder.signal::<DeviceInfo, _>("DeviceAdded", ("id", "name", "current", "latest"));
+    builder.signal::<(String,), _>("DeviceRemoved", ("id",));
+    builder.signal::<(String,), _>("DeviceRequest", ("message",));
+    builder.signal::<(String, u64, u64), _>("DownloadProgress", ("id", "downloaded", "total"));
+    builder.signal::<(String, u32), _>("Queued", ("id", "position"));
+    builder.signal::<(String,), _>("Verifying", ("id",));
+    builder.signal::<(String,), _>("Flashing", ("id",));
+    builder.signal::<(String,), _>("Updated", ("id",));
+    builder.signal::<(String,), _>("Scheduled", ("id",));
+    builder.signal::<(String,), _>("Staged", ("id",));
+    builder.signal::<(String,), _>("ReplugRequired", ("id",));
+    builder.signal::<(String,), _>("Cancelled", ("id",));
+    builder.signal::<(String, String), _>("Deferred", ("id", "reason"));
+    builder.signal::<(String, String), _>("Error", ("id", "message"));
+    builder.signal::<(String, String), _>("VerificationFailed", ("id", "message"));
+    builder.signal::<(String, String, String), _>("Interrupted", ("name", "from", "to"));
+}
+
+/// Describes why an update of the device with the given ID could not be requested.
+fn method_error(why: UpdateError, id: &str) -> MethodErr {
+    match why {
+        UpdateError::NotFound => MethodErr::invalid_arg(id),
+        UpdateError::NoUpdate => MethodErr::failed("no update is available for this device"),
+        UpdateError::InProgress => MethodErr::failed("this device is already being updated"),
+        UpdateError::Unsupported => {
+            MethodErr::failed("only fwupd devices may be downgraded or switched to another branch")
+        }
+        UpdateError::NotApproved(reason) => MethodErr::failed(&reason),
+    }
+}
+
+/// Records the effects of a signal from the event loop, and relays it to the bus.
+fn relay(connection: &Connection, service: &mut Service, signal: FirmwareSignal) {
+    let id = |service: &Service, entity: Entity| {
+        service.devices.get(entity).map_or_else(String::new, |device| device.id.to_string())
+    };
+
+    match signal {
+        FirmwareSignal::Scanning => {
+            service.devices.clear();
+            emit(connection, "ScanStarted", ());
+        }
+        FirmwareSignal::ScanningComplete => emit(connection, "ScanCompleted", ()),
+        FirmwareSignal::FwupdRemoved(id) => {
+            if service.devices.remove(&id).is_some() {
+                emit(connection, "DeviceRemoved", (id.to_string(),));
+            }
+        }
+        FirmwareSignal::DeviceRequest(request) => emit(connection, "DeviceRequest", (request,)),
+        FirmwareSignal::DownloadBegin(entity, size) => {
+            service.downloads.insert(entity, (0, size));
+        }
+        FirmwareSignal::DownloadUpdate(entity, downloaded) => {
+            if let Some(progress) = service.downloads.get_mut(entity) {
+                progress.0 += downloaded as u64;
+                let (downloaded, total) = *progress;
+                emit(connection, "DownloadProgress", (id(service, entity), downloaded, total));
+            }
+        }
+        FirmwareSignal::DownloadComplete(entity) => {
+            service.downloads.remove(entity);
+        }
+        FirmwareSignal::Queued(entity, position) => {
+            emit(connection, "Queued", (id(service, entity), position as u32));
+        }
+        FirmwareSignal::Verifying(entity) => {
+            emit(connection, "Verifying", (id(service, entity),));
+        }
+        FirmwareSignal::DeviceFlashing(entity) => {
+            emit(connection, "Flashing", (id(service, entity),));
+        }
+        FirmwareSignal::DeviceUpdated(entity) => {
+            service.devices.finished(entity);
+            emit(connection, "Updated", (id(service, entity),));
+        }
+        FirmwareSignal::SystemScheduled => {
+            if let Some(entity) = service.devices.system76() {
+                service.devices.finished(entity);
+                emit(connection, "Scheduled", (id(service, entity),));
+            }
+        }
+        // Other Thelio I/O boards are flashed along with the board which was requested, and
+        // are relayed as though their own updates were requested.
+        FirmwareSignal::ThelioIoFlashing(board) => {
+            if let Some(entity) = service.devices.thelio_io(&board) {
+                relay(connection, service, FirmwareSignal::DeviceFlashing(entity));
+            }
+        }
+        FirmwareSignal::ThelioIoFlashed(board, error) => {
+            if let Some(entity) = service.devices.thelio_io(&board) {
+                let signal = match error {
+                    Some(why) => FirmwareSignal::Error(Some(entity), why),
+                    None => FirmwareSignal::DeviceUpdated(entity),
+                };
+
+                relay(connection, service, signal);
+            }
+        }
+        FirmwareSignal::Staged(entity) => {
+            service.devices.finished(entity);
+            emit(connection, "Staged", (id(service, entity),));
+        }
+        FirmwareSignal::ReplugRequired(entity) => {
+            service.devices.finished(entity);
+            emit(connection, "ReplugRequired", (id(service, entity),));
+        }
+        FirmwareSignal::Cancelled(entity) | FirmwareSignal::Paused(entity, _) => {
+            service.devices.finished(entity);
+            service.downloads.remove(entity);
+            emit(connection, "Cancelled", (id(service, entity),));
+        }
+        FirmwareSignal::UpdateDeferred(entity, reason, _) => {
+            service.devices.finished(entity);
+            let reason = match reason {
+                DeferReason::Offline => "offline",
+                DeferReason::Metered | DeferReason::MeteredAsk => "metered",
+            };
+
+            emit(connection, "Deferred", (id(service, entity), reason.to_owned()));
+        }
+        FirmwareSignal::Error(entity, why) => {
+            let id = entity.map_or_else(String::new, |entity| {
+                service.devices.finished(entity);
+                service.downloads.remove(entity);
+                id(service, entity)
+            });
+
+            let why = format_error(&why);
+            error!("{}", why);
+            emit(connection, "Error", (id, why));
+        }
+        FirmwareSignal::VerificationFailed(entity, why) => {
+            service.devices.finished(entity);
+            let why = format_error(&why);
+            error!("{}", why);
+            emit(connection, "VerificationFailed", (id(service, entity), why));
+        }
+        // Interrupted updates are identified by name, as their devices have yet to be scanned.
+        // They are left for a graphical frontend to clear once it has told the user of them.
+        FirmwareSignal::Interrupted(updates) => {
+            for InterruptedUpdate { device, from, to, .. } in updates {
+                warn!("the update of {} to {} was interrupted", device, to);
+                let args = (String::from(device), String::from(from), String::from(to));
+                emit(connection, "Interrupted", args);
+            }
+        }
+        signal => {
+            if let Some(entity) = service.devices.discovered(signal) {
+                let device = service.devices.get(entity).expect("discovered device was lost");
+                emit(connection, "DeviceAdded", device_info(device));
+            }
+        }
+    }
+}
+
+/// Emits a signal from the interface.
+fn emit<A: AppendAll>(connection: &Connection, member: &'static str, args: A) {
+    let mut message = Message::signal(&PATH.into(), &INTERFACE.into(), &member.into());
+    message.append_all(args);
+    let _res = connection.send(message);
+}
+
+/// Describes a device to clients of the service.
+fn device_info(device: &Device) -> DeviceInfo {
+    let info = &device.info;
+    let latest = info.latest.as_deref().unwrap_or_default();
+    (device.id.to_string(), info.name.to_string(), info.current.to_string(), latest.to_owned())
+}
+
+/// Formats an error, and every error that caused it.
+fn format_error(why: &dyn Error) -> String {
+    let mut message = why.to_string();
+    let mut cause = why.source();
+    while let Some(why) = cause {
+        message.push_str(": ");
+        message.push_str(&why.to_string());
+        cause = why.source();
+    }
+
+    message
+}
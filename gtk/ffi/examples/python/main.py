@@ -0,0 +1,26 @@
+#!/usr/bin/env python3
+
+import gi
+
+gi.require_version('Gtk', '3.0')
+gi.require_version('S76Firmware', '0.1')
+
+from gi.repository import Gtk, S76Firmware
+
+
+def activate(app):
+    firmware = S76Firmware.FirmwareWidget.new()
+    firmware.scan()
+
+    header = Gtk.HeaderBar(title='System76 Firmware Manager (Python Example)', show_close_button=True)
+
+    window = Gtk.ApplicationWindow(application=app, icon_name='firmware-manager')
+    window.set_titlebar(header)
+    window.set_position(Gtk.WindowPosition.CENTER)
+    window.add(firmware.container())
+    window.show_all()
+
+
+app = Gtk.Application(application_id='com.system76.FirmwareManagerExample')
+app.connect('activate', activate)
+app.run(None)
@@ -8,4 +8,14 @@ GtkWidget *s76_firmware_widget_container (const S76FirmwareWidget *self);
 
 int s76_firmware_widget_scan (S76FirmwareWidget *self);
 
-void s76_firmware_widget_free (S76FirmwareWidget *self);
\ No newline at end of file
+void s76_firmware_widget_free (S76FirmwareWidget *self);
+
+typedef struct {
+    const char *name;
+    const char *current;
+    const char *latest;
+} S76FirmwareDevice;
+
+typedef void (*S76FirmwareScanFunc) (const S76FirmwareDevice *device, void *user_data);
+
+int s76_firmware_scan (S76FirmwareScanFunc func, void *user_data);
\ No newline at end of file
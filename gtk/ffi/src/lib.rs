@@ -1,7 +1,14 @@
+use firmware_manager::{FirmwareEvent, FirmwareInfo, FirmwareSignal};
 use firmware_manager_gtk::FirmwareWidget;
 use glib::object::ObjectType;
 use i18n_embed::DesktopLanguageRequester;
-use std::ptr;
+use std::{
+    ffi::{c_void, CString},
+    os::raw::c_char,
+    ptr,
+    sync::mpsc,
+    thread,
+};
 
 pub struct S76FirmwareWidget;
 
@@ -40,6 +47,74 @@ pub extern "C" fn s76_firmware_widget_scan(ptr: *mut S76FirmwareWidget) -> i32 {
     })
 }
 
+/// A device which was discovered by `s76_firmware_scan`.
+///
+/// The strings are only valid for the duration of the callback.
+#[repr(C)]
+pub struct S76FirmwareDevice {
+    name: *const c_char,
+    current: *const c_char,
+    latest: *const c_char,
+}
+
+pub type S76FirmwareScanFunc =
+    Option<extern "C" fn(device: *const S76FirmwareDevice, user_data: *mut c_void)>;
+
+/// Scans every firmware backend, and calls `func` for every device that was discovered.
+///
+/// This blocks until the scan has completed, and returns the number of devices found.
+#[no_mangle]
+pub extern "C" fn s76_firmware_scan(func: S76FirmwareScanFunc, user_data: *mut c_void) -> i32 {
+    let (event_tx, event_rx) = mpsc::channel();
+    let (signal_tx, signal_rx) = mpsc::channel();
+
+    let background = thread::spawn(move || firmware_manager::event_loop(event_rx, signal_tx));
+    let _res = event_tx.send(FirmwareEvent::Scan);
+
+    let mut found = 0;
+
+    for signal in signal_rx.iter() {
+        let info = match signal {
+            FirmwareSignal::ScanningComplete => break,
+            FirmwareSignal::Device(signal) => signal.info,
+            FirmwareSignal::Fwupd(signal) => signal.info,
+            FirmwareSignal::S76System(info, _)
+            | FirmwareSignal::S76Ec(info)
+            | FirmwareSignal::ManagementEngine(info)
+            | FirmwareSignal::Microcode(info)
+            | FirmwareSignal::ThelioIo(_, info, _) => info,
+            _ => continue,
+        };
+
+        found += 1;
+
+        if let Some(func) = func {
+            let FirmwareInfo { name, current, latest, .. } = info;
+            let name = c_string(&name);
+            let current = c_string(&current);
+            let latest = latest.as_deref().map(c_string);
+
+            let device = S76FirmwareDevice {
+                name: name.as_ptr(),
+                current: current.as_ptr(),
+                latest: latest.as_ref().map_or(ptr::null(), |latest| latest.as_ptr()),
+            };
+
+            func(&device, user_data);
+        }
+    }
+
+    let _res = event_tx.send(FirmwareEvent::Stop);
+    let _res = background.join();
+
+    found
+}
+
+/// Converts a string for C, removing interior nul bytes.
+fn c_string(string: &str) -> CString {
+    CString::new(string.replace('\0', "")).expect("nul bytes were removed")
+}
+
 fn translate() {
     let localizer = firmware_manager_gtk::localizer();
     let requested_languages = DesktopLanguageRequester::requested_languages();
@@ -1,4 +1,5 @@
 use crate::fl;
+use firmware_manager::{NoteBlock, NoteSpan, ReleaseIssues, ReleaseUrgency};
 use gtk::prelude::*;
 
 pub fn generate_widget_none() -> gtk::Box {
@@ -18,6 +19,15 @@ pub fn generate_widget<I, S>(changelog: I) -> gtk::Box
 where
     S: AsRef<str>,
     I: Iterator<Item = (S, S, S)>,
+{
+    generate_release_widget(changelog.map(|(version, date, entry)| (version, date, entry, None)))
+}
+
+/// Generates a changelog whose releases list the security issues that they fix.
+pub fn generate_release_widget<'a, I, S>(changelog: I) -> gtk::Box
+where
+    S: AsRef<str>,
+    I: Iterator<Item = (S, S, S, Option<&'a ReleaseIssues>)>,
 {
     let changelog_entries = cascade! {
         gtk::Box::new(gtk::Orientation::Vertical, 12);
@@ -25,12 +35,12 @@ where
     };
 
     let mut initiated = false;
-    changelog.for_each(|(version, date, entry)| {
-        let markdown = if entry.as_ref().is_empty() {
-            fl!("changelog-unavailable")
-        } else {
-            html2md::parse_html(entry.as_ref()).trim().to_string()
-        };
+    changelog.for_each(|(version, date, entry, issues)| {
+        let mut blocks = firmware_manager::parse_release_notes(entry.as_ref());
+        if blocks.is_empty() {
+            let unavailable = NoteSpan::Text(fl!("changelog-unavailable").into());
+            blocks.push(NoteBlock::Paragraph(vec![unavailable]));
+        }
 
         // NOTE: If we don't set a max width in chars, the label resizes its parent.
         // Even though we set a max width of chars, this will be ignored by GTK as the
@@ -53,14 +63,36 @@ where
             .margin_end(PADDING)
             .build();
 
-        let changelog = gtk::Label::builder()
-            .label(&*markdown)
-            .wrap(true)
-            .xalign(0.0)
-            .max_width_chars(40)
-            .margin_start(PADDING)
-            .margin_end(PADDING)
-            .build();
+        let changelog = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 6);
+            ..set_margin_start(PADDING);
+            ..set_margin_end(PADDING);
+        };
+
+        if let Some(fixes) = issues.and_then(fixes) {
+            changelog.add(&fixes);
+        }
+
+        for block in &blocks {
+            match block {
+                NoteBlock::Paragraph(spans) => changelog.add(&note_label(&markup(spans))),
+                NoteBlock::BulletList(items) => {
+                    for item in items {
+                        changelog.add(&list_item("•", item));
+                    }
+                }
+                NoteBlock::NumberedList(items) => {
+                    for (number, item) in items.iter().enumerate() {
+                        let number = fl!("changelog-list-number", number = number + 1);
+                        changelog.add(&list_item(&number, item));
+                    }
+                }
+            }
+
+            if let Some(links) = links(block) {
+                changelog.add(&links);
+            }
+        }
 
         if initiated {
             changelog_entries.add(&gtk::Separator::new(gtk::Orientation::Horizontal));
@@ -73,3 +105,128 @@ where
 
     changelog_entries
 }
+
+/// A row of badges which name the security issues that a release fixes, and their severity.
+fn fixes(issues: &ReleaseIssues) -> Option<gtk::FlowBox> {
+    if issues.ids.is_empty() {
+        return None;
+    }
+
+    let fixes = cascade! {
+        gtk::FlowBox::new();
+        ..set_selection_mode(gtk::SelectionMode::None);
+        ..set_max_children_per_line(4);
+        ..add(&gtk::Label::builder().label(&fl!("changelog-fixes")).xalign(0.0).build());
+    };
+
+    let severity = match issues.urgency {
+        ReleaseUrgency::Unknown => None,
+        ReleaseUrgency::Low => Some(fl!("severity-low")),
+        ReleaseUrgency::Medium => Some(fl!("severity-medium")),
+        ReleaseUrgency::High => Some(fl!("severity-high")),
+        ReleaseUrgency::Critical => Some(fl!("severity-critical")),
+    };
+
+    let style = match issues.urgency {
+        ReleaseUrgency::Critical | ReleaseUrgency::High => Some(gtk::STYLE_CLASS_ERROR),
+        ReleaseUrgency::Medium => Some(gtk::STYLE_CLASS_WARNING),
+        ReleaseUrgency::Low | ReleaseUrgency::Unknown => None,
+    };
+
+    for id in &issues.ids {
+        let label = match severity {
+            Some(ref severity) => fl!("changelog-fix", id = &**id, severity = &**severity),
+            None => id.to_string(),
+        };
+
+        // Only CVEs have a page which describes them.
+        let badge = if id.starts_with("CVE-") {
+            let uri = firmware_manager::nvd_url(id);
+            cascade! {
+                gtk::LinkButton::with_label(&uri, &label);
+                ..set_tooltip_text(Some(&uri));
+            }
+            .upcast::<gtk::Widget>()
+        } else {
+            gtk::Label::new(Some(&label)).upcast::<gtk::Widget>()
+        };
+
+        if let Some(style) = style {
+            badge.style_context().add_class(style);
+        }
+
+        fixes.add(&badge);
+    }
+
+    Some(fixes)
+}
+
+/// An item of a list in the release notes, with its text aligned after its `marker`.
+fn list_item(marker: &str, spans: &[NoteSpan]) -> gtk::Box {
+    cascade! {
+        gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        ..set_margin_start(12);
+        ..add(&gtk::Label::builder().label(marker).valign(gtk::Align::Start).build());
+        ..add(&note_label(&markup(spans)));
+    }
+}
+
+/// The URLs and CVEs of a block of release notes, as buttons which open them.
+fn links(block: &NoteBlock) -> Option<gtk::FlowBox> {
+    let mut seen = Vec::new();
+    for link in block.links() {
+        if !seen.contains(&link) {
+            seen.push(link);
+        }
+    }
+
+    if seen.is_empty() {
+        return None;
+    }
+
+    let links = cascade! {
+        gtk::FlowBox::new();
+        ..set_selection_mode(gtk::SelectionMode::None);
+        ..set_max_children_per_line(4);
+    };
+
+    for (text, uri) in seen {
+        links.add(&cascade! {
+            gtk::LinkButton::with_label(uri, text);
+            ..set_tooltip_text(Some(uri));
+            ..set_halign(gtk::Align::Start);
+        });
+    }
+
+    Some(links)
+}
+
+fn note_label(markup: &str) -> gtk::Label {
+    gtk::Label::builder()
+        .label(markup)
+        .use_markup(true)
+        .wrap(true)
+        .xalign(0.0)
+        .max_width_chars(40)
+        .build()
+}
+
+/// Renders the spans of a block of release notes as Pango markup.
+fn markup(spans: &[NoteSpan]) -> String {
+    let mut markup = String::new();
+    for span in spans {
+        let (open, text, close) = match span {
+            NoteSpan::Text(text) => ("", text, ""),
+            NoteSpan::Emphasis(text) => ("<i>", text, "</i>"),
+            NoteSpan::Strong(text) => ("<b>", text, "</b>"),
+            NoteSpan::Code(text) => ("<tt>", text, "</tt>"),
+            NoteSpan::Link { text, .. } => ("<u>", text, "</u>"),
+        };
+
+        markup.push_str(open);
+        markup.push_str(&glib::markup_escape_text(text));
+        markup.push_str(close);
+    }
+
+    markup
+}
@@ -0,0 +1,47 @@
+use super::{FirmwareUpdateDialog, UpdateDetails};
+use crate::{power::Power, widgets::DeviceWidget};
+use firmware_manager::{BatteryPolicy, CancellationToken, DeviceId, Entity, FirmwareEvent};
+use gtk::prelude::*;
+use std::{iter, sync::mpsc::Sender};
+
+/// An instance of the firmware update dialog specific to the system devices of other backends,
+/// such as UEFI capsules, which have no changelog.
+pub struct DeviceDialog<'a> {
+    pub battery_policy: BatteryPolicy,
+    pub cancellable: CancellationToken,
+    pub device: &'a DeviceId,
+    pub entity: Entity,
+    pub power: &'a Power,
+    pub latest: &'a str,
+    pub sender: &'a Sender<FirmwareEvent>,
+    pub widgets: &'a DeviceWidget,
+}
+
+impl<'a> DeviceDialog<'a> {
+    /// Asks the user to confirm the update, returning whether it was requested.
+    pub fn run(self) -> bool {
+        let dialog = FirmwareUpdateDialog::new(
+            self.latest,
+            iter::empty::<(&str, &str, &str)>(),
+            UpdateDetails::default(),
+            self.power,
+            self.battery_policy,
+            false,
+        );
+
+        let response = dialog.run();
+        super::snooze(&self.widgets.name.text(), self.latest, response);
+        dialog.close();
+
+        if gtk::ResponseType::Accept != response {
+            return false;
+        }
+
+        // Exchange the button for a progress bar.
+        self.widgets.stack.switch_to_waiting();
+
+        let event = FirmwareEvent::Device(self.entity, self.device.clone(), self.cancellable);
+        let _ = self.sender.send(event);
+        true
+    }
+}
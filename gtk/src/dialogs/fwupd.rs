@@ -1,23 +1,36 @@
-use super::FirmwareUpdateDialog;
-use crate::widgets::DeviceWidget;
-use firmware_manager::{Entity, FirmwareEvent, FwupdDevice, FwupdRelease};
+use super::{FirmwareUpdateDialog, UpdateDetails};
+use crate::{power::Power, widgets::DeviceWidget};
+use firmware_manager::{
+    BatteryPolicy, CancellationToken, Entity, FirmwareEvent, FwupdDevice, FwupdRelease,
+    ReleaseUrgency,
+};
 use gtk::prelude::*;
-use std::sync::{mpsc::Sender, Arc};
+use std::{
+    sync::{mpsc::Sender, Arc},
+    time::Duration,
+};
 
 /// An instance of the firmware update dialog specific to fwupd-managed system devices.
 pub struct FwupdDialog<'a> {
+    pub battery_policy: BatteryPolicy,
+    pub cancellable: CancellationToken,
     pub device: &'a FwupdDevice,
+    /// Installs the `release` though it is older than the installed version.
+    pub downgrade: bool,
     pub entity: Entity,
-    pub has_battery: bool,
-    pub latest: &'a str,
+    pub power: &'a Power,
     pub needs_reboot: bool,
+    /// The release to install, which is usually the latest of the `releases`.
+    pub release: &'a FwupdRelease,
     pub releases: &'a [FwupdRelease],
     pub sender: &'a Sender<FirmwareEvent>,
+    pub urgency: ReleaseUrgency,
     pub widgets: &'a DeviceWidget,
 }
 
 impl<'a> FwupdDialog<'a> {
-    pub fn run(self) {
+    /// Requests the update once the user has confirmed it, returning whether it was requested.
+    pub fn run(self) -> bool {
         let log_entries = self
             .releases
             .iter()
@@ -26,24 +39,49 @@ impl<'a> FwupdDialog<'a> {
             .map(|release| (release.version.as_ref(), "", release.description.as_ref()));
 
         let response = if self.needs_reboot {
-            let dialog = FirmwareUpdateDialog::new(self.latest, log_entries, self.has_battery);
+            let dialog = FirmwareUpdateDialog::new(
+                &self.release.version,
+                log_entries,
+                UpdateDetails {
+                    // fwupd reports zero where the size or install duration are not known.
+                    size: Some(self.release.size).filter(|&size| size != 0),
+                    install_duration: Some(self.release.install_duration)
+                        .filter(|&secs| secs != 0)
+                        .map(|secs| Duration::from_secs(secs.into())),
+                },
+                self.power,
+                self.battery_policy,
+                self.urgency == ReleaseUrgency::Critical,
+            );
 
             let response = dialog.run();
             dialog.close();
+            if !self.downgrade {
+                super::snooze(&self.widgets.name.text(), &self.release.version, response);
+            }
+
             response
         } else {
             gtk::ResponseType::Accept
         };
 
-        if gtk::ResponseType::Accept == response {
-            // Exchange the button for a progress bar.
-            self.widgets.stack.switch_to_waiting();
-
-            let _ = self.sender.send(FirmwareEvent::Fwupd(
-                self.entity,
-                Arc::new(self.device.clone()),
-                Arc::new(self.releases.iter().last().expect("no release found").clone()),
-            ));
+        if gtk::ResponseType::Accept != response {
+            return false;
         }
+
+        // Exchange the button for a progress bar.
+        self.widgets.stack.switch_to_waiting();
+
+        let device = Arc::new(self.device.clone());
+        let event = if self.downgrade {
+            let version = Box::from(&*self.release.version);
+            FirmwareEvent::Downgrade(self.entity, device, version, self.cancellable)
+        } else {
+            let release = Arc::new(self.release.clone());
+            FirmwareEvent::Fwupd(self.entity, device, release, self.cancellable)
+        };
+
+        let _ = self.sender.send(event);
+        true
     }
 }
@@ -1,16 +1,235 @@
+mod device;
 mod fwupd;
+mod open_firmware;
 mod system76;
 
-pub use self::{fwupd::FwupdDialog, system76::System76Dialog};
+pub use self::{
+    device::DeviceDialog,
+    fwupd::FwupdDialog,
+    open_firmware::OpenFirmwareAssistant,
+    system76::{system76_log_entries, System76Dialog},
+};
 
-use crate::fl;
+use crate::{fl, power::Power};
+use firmware_manager::{BatteryPolicy, BranchWarning, DowngradeWarning, RebootPolicy};
 use gtk::prelude::*;
+use std::time::Duration;
+
+/// Asks the user if firmware should be downloaded while the connection is metered.
+pub fn confirm_metered_download() -> bool {
+    let dialog = gtk::MessageDialog::builder()
+        .message_type(gtk::MessageType::Question)
+        .buttons(gtk::ButtonsType::None)
+        .text(&fl!("metered-title"))
+        .secondary_text(&fl!("metered-body"))
+        .build();
+
+    dialog.add_button(&fl!("button-cancel"), gtk::ResponseType::Cancel);
+    dialog.add_button(&fl!("button-download-anyway"), gtk::ResponseType::Accept);
+
+    let response = dialog.run();
+    dialog.close();
+
+    response == gtk::ResponseType::Accept
+}
+
+/// Asks the user to confirm that a UEFI capsule should be staged while Secure Boot is enabled.
+pub fn confirm_capsule_secure_boot(device: &str) -> bool {
+    let dialog = gtk::MessageDialog::builder()
+        .message_type(gtk::MessageType::Warning)
+        .buttons(gtk::ButtonsType::None)
+        .text(&fl!("capsule-secure-boot-title", device = device))
+        .secondary_text(&fl!("capsule-secure-boot-body"))
+        .build();
+
+    dialog.add_button(&fl!("button-cancel"), gtk::ResponseType::Cancel);
+    dialog.add_button(&fl!("button-update"), gtk::ResponseType::Accept);
+
+    let response = dialog.run();
+    dialog.close();
+
+    response == gtk::ResponseType::Accept
+}
+
+/// Asks the user to confirm the risks of switching the firmware of a device to another `branch`.
+pub fn confirm_branch_switch(branch: &str, warnings: &[BranchWarning]) -> bool {
+    let warnings = warnings.iter().copied().map(branch_warning).collect::<Vec<_>>();
+
+    let dialog = gtk::MessageDialog::builder()
+        .message_type(gtk::MessageType::Warning)
+        .buttons(gtk::ButtonsType::None)
+        .text(&fl!("branch-switch-title", branch = branch))
+        .secondary_text(&warnings.join("\n\n"))
+        .build();
+
+    dialog.add_button(&fl!("button-cancel"), gtk::ResponseType::Cancel);
+    dialog.add_button(&fl!("button-switch-branch", branch = branch), gtk::ResponseType::Accept);
+
+    let response = dialog.run();
+    dialog.close();
+
+    response == gtk::ResponseType::Accept
+}
+
+/// Describes a risk of switching the firmware of a device to another branch.
+fn branch_warning(warning: BranchWarning) -> String {
+    match warning {
+        BranchWarning::Unsupported => fl!("branch-warning-unsupported"),
+        BranchWarning::SystemFirmware => fl!("branch-warning-system-firmware"),
+        BranchWarning::SecureBoot => fl!("branch-warning-secure-boot"),
+    }
+}
+
+/// Asks the user to connect the system to power, as updates may not begin while it is running on
+/// battery. The update continues once the user confirms, which they may do as soon as the system
+/// is connected to power.
+pub fn wait_for_ac(power: &Power) -> bool {
+    let dialog = gtk::MessageDialog::builder()
+        .message_type(gtk::MessageType::Warning)
+        .buttons(gtk::ButtonsType::None)
+        .text(&fl!("ac-required-title"))
+        .secondary_text(&fl!("ac-required-body"))
+        .build();
+
+    dialog.add_button(&fl!("button-cancel"), gtk::ResponseType::Cancel);
+    let proceed = cascade! {
+        dialog.add_button(&fl!("button-continue"), gtk::ResponseType::Accept);
+        ..set_sensitive(!power.on_battery());
+        ..style_context().add_class(&gtk::STYLE_CLASS_SUGGESTED_ACTION);
+    };
+
+    let proceed = proceed.downgrade();
+    power.connect_changed(&dialog, move |on_battery| {
+        if let Some(proceed) = proceed.upgrade() {
+            proceed.set_sensitive(!on_battery);
+        }
+    });
+
+    let response = dialog.run();
+    dialog.close();
+
+    response == gtk::ResponseType::Accept
+}
+
+/// Asks the user to confirm that the firmware of the `device` should be downgraded to an older
+/// `version`, despite its risks.
+pub fn confirm_downgrade(device: &str, version: &str, warnings: &[DowngradeWarning]) -> bool {
+    let warnings = warnings.iter().copied().map(downgrade_warning).collect::<Vec<_>>();
+
+    let dialog = gtk::MessageDialog::builder()
+        .message_type(gtk::MessageType::Warning)
+        .buttons(gtk::ButtonsType::None)
+        .text(&fl!("downgrade-title", device = device, version = version))
+        .secondary_text(&warnings.join("\n\n"))
+        .build();
+
+    dialog.add_button(&fl!("button-cancel"), gtk::ResponseType::Cancel);
+    dialog
+        .add_button(&fl!("button-downgrade"), gtk::ResponseType::Accept)
+        .style_context()
+        .add_class(&gtk::STYLE_CLASS_DESTRUCTIVE_ACTION);
+
+    // Downgrading is never the default.
+    dialog.set_default_response(gtk::ResponseType::Cancel);
+
+    let response = dialog.run();
+    dialog.close();
+
+    response == gtk::ResponseType::Accept
+}
+
+/// Describes a risk of downgrading the firmware of a device.
+fn downgrade_warning(warning: DowngradeWarning) -> String {
+    match warning {
+        DowngradeWarning::Security => fl!("downgrade-warning-security"),
+        DowngradeWarning::SystemFirmware => fl!("downgrade-warning-system-firmware"),
+    }
+}
+
+/// Asks the user if the outcome of updating the `device` may be reported to the LVFS.
+pub fn confirm_upload_report(device: &str) -> bool {
+    let dialog = gtk::MessageDialog::builder()
+        .message_type(gtk::MessageType::Question)
+        .buttons(gtk::ButtonsType::None)
+        .text(&fl!("report-title"))
+        .secondary_text(&fl!("report-body", device = device))
+        .build();
+
+    dialog.add_button(&fl!("button-dont-send"), gtk::ResponseType::Reject);
+    dialog.add_button(&fl!("button-send-report"), gtk::ResponseType::Accept);
+
+    let response = dialog.run();
+    dialog.close();
+
+    response == gtk::ResponseType::Accept
+}
+
+/// Warns the user that firmware was not installed, as its signature could not be verified.
+///
+/// The `details` describe the error, and are displayed beneath the warning.
+pub fn warn_verification_failed(details: &str) {
+    let dialog = gtk::MessageDialog::builder()
+        .message_type(gtk::MessageType::Error)
+        .buttons(gtk::ButtonsType::Close)
+        .text(&fl!("verification-failed-title"))
+        .secondary_text(&[&fl!("verification-failed-body"), "\n\n", details].concat())
+        .build();
+
+    dialog.run();
+    dialog.close();
+}
+
+/// Instructs the user to unplug the `device` and plug it in again, so that its new firmware takes
+/// effect.
+pub fn instruct_replug(device: &str) {
+    let dialog = gtk::MessageDialog::builder()
+        .message_type(gtk::MessageType::Info)
+        .buttons(gtk::ButtonsType::Ok)
+        .text(&fl!("replug-title", device = device))
+        .secondary_text(&fl!("replug-body"))
+        .build();
+
+    dialog.run();
+    dialog.close();
+}
+
+/// The response of the update dialog which asks to be reminded of the update in four hours.
+const REMIND_LATER: gtk::ResponseType = gtk::ResponseType::Other(1);
+
+/// The response of the update dialog which asks to be reminded of the update tomorrow.
+const REMIND_TOMORROW: gtk::ResponseType = gtk::ResponseType::Other(2);
+
+/// Snoozes the update of the `device` to the `version`, if the response of the update dialog
+/// asked to be reminded of it later.
+pub fn snooze(device: &str, version: &str, response: gtk::ResponseType) {
+    let hours = if response == REMIND_LATER {
+        4
+    } else if response == REMIND_TOMORROW {
+        24
+    } else {
+        return;
+    };
+
+    let duration = Duration::from_secs(hours * 60 * 60);
+    if let Err(why) = firmware_manager::snooze_update(device, version, duration) {
+        error!("failed to snooze the update of {}: {}", device, why);
+    }
+}
+
+/// Facts about an update which are summarized before it is installed, where they are known.
+#[derive(Default)]
+pub struct UpdateDetails {
+    /// The size of the firmware payload to download, in bytes.
+    pub size: Option<u64>,
+    /// How long the firmware is estimated to take to install.
+    pub install_duration: Option<Duration>,
+}
 
 /// A generic GTK dialog which is displayed for firmware which requires a system reboot.
 ///
 /// This dialog displays a changelog covering the details of the updates, and all prior updates, as
 /// well as a confirmation button that will initiate configuring the system to be rebooted into the
-/// firmware upgrade environment.
+/// firmware upgrade environment. Critical updates are announced more urgently.
 #[derive(Shrinkwrap)]
 pub struct FirmwareUpdateDialog(gtk::Dialog);
 
@@ -18,30 +237,35 @@ impl FirmwareUpdateDialog {
     pub fn new<S: AsRef<str>, I: Iterator<Item = (S, S, S)>>(
         version: &str,
         changelog: I,
-        has_battery: bool,
+        details: UpdateDetails,
+        power: &Power,
+        battery_policy: BatteryPolicy,
+        critical: bool,
     ) -> Self {
         let changelog_entries = crate::changelog::generate_widget(changelog);
 
-        let mut header = fl!("update-available", version = version);
-        header.push(' ');
+        let require_ac =
+            |on_battery: bool| on_battery && battery_policy == BatteryPolicy::RequireAc;
 
-        if has_battery {
-            header.push_str(&fl!("update-connect-to-ac"));
-            header.push_str("\n\n")
-        }
+        let header = gtk::Label::builder()
+            .label(&update_header(version, critical, battery_policy, power.on_battery()))
+            .wrap(true)
+            .xalign(0.0)
+            .use_markup(true)
+            .build();
+
+        let reboot_policy = firmware_manager::config().reboot_policy;
 
-        header.push_str(&fl!(
-            "update-guide",
-            url_tag_start = "<a href=\"https://support.system76.com/articles/system-firmware/\">",
-            url_tag_end = "</a>"
-        ));
+        let power_status = gtk::Label::new(Some(&power_text(power.on_battery())));
+        let summary = update_summary(&details, reboot_policy, &power_status);
 
         let changelog_text = format!("<b>{}</b>", fl!("changelog"));
 
         let changelog_container = cascade! {
             gtk::Box::new(gtk::Orientation::Vertical, 12);
             ..set_vexpand(true);
-            ..add(&gtk::Label::builder().label(&*header).wrap(true).xalign(0.0).use_markup(true).build());
+            ..add(&header);
+            ..add(&summary);
             ..add(&gtk::Label::builder().label(&*changelog_text).use_markup(true).xalign(0.0).build());
             ..add(&changelog_entries);
             ..show_all();
@@ -49,11 +273,33 @@ impl FirmwareUpdateDialog {
 
         let cancel = gtk::Button::with_label(&fl!("button-cancel"));
 
+        // Snoozes the update, which is not notified of again until the snooze expires.
+        let remind_later = gtk::MenuItem::with_label(&fl!("menu-remind-later"));
+        let remind_tomorrow = gtk::MenuItem::with_label(&fl!("menu-remind-tomorrow"));
+        let remind = cascade! {
+            gtk::MenuButton::new();
+            ..set_label(&fl!("button-remind"));
+            ..set_popup(Some(&cascade! {
+                gtk::Menu::new();
+                ..append(&remind_later);
+                ..append(&remind_tomorrow);
+                ..show_all();
+            }));
+        };
+
+        // The firmware may be staged to be installed when the user next reboots.
+        let reboot_label = match reboot_policy {
+            RebootPolicy::Immediate => fl!("button-reboot-and-install"),
+            RebootPolicy::NextBoot => fl!("button-install-on-reboot"),
+        };
+
         let reboot = cascade! {
             gtk::Button::builder()
-                .label(&fl!("button-reboot-and-install"))
+                .label(&reboot_label)
                 .build();
             ..style_context().add_class(&gtk::STYLE_CLASS_SUGGESTED_ACTION);
+            // The update may not begin until the system has been connected to power.
+            ..set_sensitive(!require_ac(power.on_battery()));
         };
 
         let dialog = gtk::Dialog::builder()
@@ -82,6 +328,7 @@ impl FirmwareUpdateDialog {
             ..set_show_close_button(false);
             ..pack_start(&cancel);
             ..pack_end(&reboot);
+            ..pack_end(&remind);
         };
 
         cascade! {
@@ -116,6 +363,15 @@ impl FirmwareUpdateDialog {
             });
         }
 
+        for (item, response) in [(remind_later, REMIND_LATER), (remind_tomorrow, REMIND_TOMORROW)] {
+            let dialog = dialog.downgrade();
+            item.connect_activate(move |_| {
+                if let Some(dialog) = dialog.upgrade() {
+                    dialog.response(response);
+                }
+            });
+        }
+
         {
             let dialog = dialog.downgrade();
             reboot.connect_clicked(move |_| {
@@ -125,6 +381,113 @@ impl FirmwareUpdateDialog {
             });
         }
 
+        // The system may be plugged in or unplugged while the dialog is open.
+        {
+            let (header, reboot) = (header.downgrade(), reboot.downgrade());
+            let power_status = power_status.downgrade();
+            let version = version.to_owned();
+            power.connect_changed(&dialog, move |on_battery| {
+                if let (Some(header), Some(reboot)) = (header.upgrade(), reboot.upgrade()) {
+                    let text = update_header(&version, critical, battery_policy, on_battery);
+                    header.set_markup(&text);
+                    reboot.set_sensitive(!require_ac(on_battery));
+                }
+
+                if let Some(power_status) = power_status.upgrade() {
+                    power_status.set_text(&power_text(on_battery));
+                }
+            });
+        }
+
         Self(dialog)
     }
 }
+
+/// Describes an update, asking that the system be connected to power if it is running on battery
+/// and the battery policy does not ignore it.
+fn update_header(
+    version: &str,
+    critical: bool,
+    battery_policy: BatteryPolicy,
+    on_battery: bool,
+) -> String {
+    let mut header = if critical {
+        fl!("update-critical", version = version)
+    } else {
+        fl!("update-available", version = version)
+    };
+    header.push(' ');
+
+    if on_battery && battery_policy != BatteryPolicy::Ignore {
+        header.push_str(&fl!("update-connect-to-ac"));
+        header.push_str("\n\n")
+    }
+
+    header.push_str(&fl!(
+        "update-guide",
+        url_tag_start = "<a href=\"https://support.system76.com/articles/system-firmware/\">",
+        url_tag_end = "</a>"
+    ));
+
+    header
+}
+
+/// Summarizes what installing an update involves: how much is downloaded, how long it takes to
+/// install, whether the system restarts, and whether the system is connected to power, which is
+/// shown by the `power_status` label so that it may be kept current.
+///
+/// Facts which are not known, such as the install duration of most firmware, are omitted.
+fn update_summary(
+    details: &UpdateDetails,
+    reboot_policy: RebootPolicy,
+    power_status: &gtk::Label,
+) -> gtk::Grid {
+    let grid = cascade! {
+        gtk::Grid::new();
+        ..set_column_spacing(12);
+        ..set_row_spacing(4);
+    };
+
+    let restart = match reboot_policy {
+        RebootPolicy::Immediate => fl!("update-summary-restart-immediate"),
+        RebootPolicy::NextBoot => fl!("update-summary-restart-next-boot"),
+    };
+
+    let size = details.size.map(|size| glib::format_size(size).to_string());
+    let duration = details.install_duration.map(|duration| {
+        fl!("update-summary-duration-minutes", minutes = ((duration.as_secs() + 59) / 60).max(1))
+    });
+
+    let fields = [
+        (fl!("update-summary-size"), size.map(|size| gtk::Label::new(Some(&size)))),
+        (fl!("update-summary-duration"), duration.map(|time| gtk::Label::new(Some(&time)))),
+        (fl!("update-summary-restart"), Some(gtk::Label::new(Some(&restart)))),
+        (fl!("update-summary-power"), Some(power_status.clone())),
+    ];
+
+    let fields = fields.into_iter().filter_map(|(name, value)| Some((name, value?)));
+
+    for (row, (name, value)) in (0..).zip(fields) {
+        let name = cascade! {
+            gtk::Label::new(Some(name.as_str()));
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+            ..set_xalign(0.0);
+        };
+
+        value.set_xalign(0.0);
+
+        grid.attach(&name, 0, row, 1, 1);
+        grid.attach(&value, 1, row, 1, 1);
+    }
+
+    grid
+}
+
+/// Describes whether the system is connected to power, or running on battery.
+fn power_text(on_battery: bool) -> String {
+    if on_battery {
+        fl!("update-summary-power-battery")
+    } else {
+        fl!("update-summary-power-ac")
+    }
+}
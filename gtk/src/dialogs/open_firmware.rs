@@ -0,0 +1,165 @@
+use super::branch_warning;
+use crate::{fl, power::Power, Event, UiEvent};
+use firmware_manager::{BranchWarning, Entity, TransitionBlocker, OPEN_FIRMWARE_BRANCH};
+use gtk::prelude::*;
+
+/// Guides the user through moving proprietary system firmware to System76 Open Firmware.
+///
+/// The assistant checks that the system is eligible, asks the user to back up their data, and
+/// has them confirm the risks of the switch before the firmware is switched to the
+/// [`OPEN_FIRMWARE_BRANCH`] with a [`UiEvent::BranchConfirmed`].
+pub struct OpenFirmwareAssistant<'a> {
+    pub entity: Entity,
+    pub eligibility: Result<(), TransitionBlocker>,
+    pub power: &'a Power,
+    pub sender: &'a glib::Sender<Event>,
+    pub warnings: &'a [BranchWarning],
+}
+
+impl<'a> OpenFirmwareAssistant<'a> {
+    pub fn run(self) {
+        let assistant = gtk::Assistant::builder()
+            .title(&fl!("open-firmware-title"))
+            .modal(true)
+            .default_width(480)
+            .build();
+
+        let (eligibility, power_check) = self.eligibility_page();
+        assistant.append_page(&eligibility);
+        assistant.set_page_title(&eligibility, &fl!("open-firmware-eligibility-title"));
+        assistant.set_page_type(&eligibility, gtk::AssistantPageType::Intro);
+
+        let eligible = self.eligibility.is_ok();
+        assistant.set_page_complete(&eligibility, eligible && !self.power.on_battery());
+
+        // The system may be plugged in or unplugged while the assistant is open.
+        {
+            let (weak_assistant, page) = (assistant.downgrade(), eligibility.downgrade());
+            self.power.connect_changed(&assistant, move |on_battery| {
+                if let (Some(assistant), Some(page)) = (weak_assistant.upgrade(), page.upgrade()) {
+                    power_check.set_on_battery(on_battery);
+                    assistant.set_page_complete(&page, eligible && !on_battery);
+                }
+            });
+        }
+
+        let backup = acknowledgement_page(
+            &assistant,
+            &fl!("open-firmware-backup"),
+            &fl!("open-firmware-backup-confirm"),
+        );
+        assistant.set_page_title(&backup, &fl!("open-firmware-backup-title"));
+
+        let warnings = self.warnings.iter().copied().map(branch_warning).collect::<Vec<_>>();
+        let confirm = acknowledgement_page(
+            &assistant,
+            &warnings.join("\n\n"),
+            &fl!("open-firmware-confirm"),
+        );
+        assistant.set_page_title(&confirm, &fl!("open-firmware-confirm-title"));
+        assistant.set_page_type(&confirm, gtk::AssistantPageType::Confirm);
+
+        let sender = self.sender.clone();
+        let entity = self.entity;
+        assistant.connect_apply(move |_| {
+            let event = UiEvent::BranchConfirmed(entity, OPEN_FIRMWARE_BRANCH.into());
+            let _ = sender.send(Event::Ui(event));
+        });
+
+        assistant.connect_cancel(|assistant| assistant.close());
+        assistant.connect_close(|assistant| assistant.close());
+        assistant.show_all();
+    }
+
+    /// Lists the checks which the system must pass before the transition may be made, with the
+    /// check of its power source, which changes as the system is plugged in and unplugged.
+    fn eligibility_page(&self) -> (gtk::Box, Check) {
+        let (passed, available) = match self.eligibility {
+            Ok(()) => (true, fl!("open-firmware-check-available")),
+            Err(TransitionBlocker::Unsupported) => (false, fl!("open-firmware-check-unsupported")),
+            Err(TransitionBlocker::AlreadyOpen) => {
+                (false, fl!("open-firmware-check-already-open"))
+            }
+        };
+
+        let available = Check::new(passed, &available);
+        let power = Check::new(true, "");
+        power.set_on_battery(self.power.on_battery());
+
+        let page = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 12);
+            ..set_border_width(12);
+            ..add(&wrapped_label(&fl!("open-firmware-eligibility")));
+            ..add(&available.container);
+            ..add(&power.container);
+        };
+
+        (page, power)
+    }
+}
+
+/// A check which the system must pass, with an icon showing whether it has.
+struct Check {
+    container: gtk::Box,
+    icon: gtk::Image,
+    label: gtk::Label,
+}
+
+impl Check {
+    fn new(passed: bool, text: &str) -> Self {
+        let icon = gtk::Image::new();
+        let label = wrapped_label(text);
+
+        let container = cascade! {
+            gtk::Box::new(gtk::Orientation::Horizontal, 12);
+            ..add(&icon);
+            ..add(&label);
+        };
+
+        let check = Self { container, icon, label };
+        check.set_passed(passed);
+        check
+    }
+
+    fn set_passed(&self, passed: bool) {
+        let icon = if passed { "emblem-ok-symbolic" } else { "dialog-error-symbolic" };
+        self.icon.set_from_icon_name(Some(icon), gtk::IconSize::Button);
+    }
+
+    /// Shows whether the system is running on battery, which the transition may not be made on.
+    fn set_on_battery(&self, on_battery: bool) {
+        self.set_passed(!on_battery);
+        self.label.set_text(&if on_battery {
+            fl!("open-firmware-check-battery")
+        } else {
+            fl!("open-firmware-check-ac")
+        });
+    }
+}
+
+/// A page which may only be completed once the user acknowledges its `message`.
+fn acknowledgement_page(assistant: &gtk::Assistant, message: &str, acknowledge: &str) -> gtk::Box {
+    let check = gtk::CheckButton::with_label(acknowledge);
+
+    let page = cascade! {
+        gtk::Box::new(gtk::Orientation::Vertical, 12);
+        ..set_border_width(12);
+        ..add(&wrapped_label(message));
+        ..add(&check);
+    };
+
+    assistant.append_page(&page);
+
+    let (assistant, weak_page) = (assistant.downgrade(), page.downgrade());
+    check.connect_toggled(move |check| {
+        if let (Some(assistant), Some(page)) = (assistant.upgrade(), weak_page.upgrade()) {
+            assistant.set_page_complete(&page, check.is_active());
+        }
+    });
+
+    page
+}
+
+fn wrapped_label(text: &str) -> gtk::Label {
+    gtk::Label::builder().label(text).wrap(true).xalign(0.0).max_width_chars(60).build()
+}
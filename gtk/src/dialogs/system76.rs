@@ -1,15 +1,16 @@
-use super::FirmwareUpdateDialog;
-use crate::widgets::DeviceWidget;
-use firmware_manager::{Entity, FirmwareEvent, System76Changelog, System76Digest};
+use super::{FirmwareUpdateDialog, UpdateDetails};
+use crate::{fl, power::Power, widgets::DeviceWidget};
+use firmware_manager::{BatteryPolicy, Entity, FirmwareEvent, System76Changelog, System76Digest};
 use gtk::prelude::*;
-use std::sync::mpsc::Sender;
+use std::{borrow::Cow, sync::mpsc::Sender};
 
 /// An instance of the firmware update dialog specific to system76-managed system devices.
 pub struct System76Dialog<'a> {
+    pub battery_policy: BatteryPolicy,
     pub changelog: &'a System76Changelog,
     pub digest: &'a System76Digest,
     pub entity: Entity,
-    pub has_battery: bool,
+    pub power: &'a Power,
     pub latest: &'a str,
     pub sender: &'a Sender<FirmwareEvent>,
     pub widgets: &'a DeviceWidget,
@@ -17,13 +18,19 @@ pub struct System76Dialog<'a> {
 
 impl<'a> System76Dialog<'a> {
     pub fn run(self) {
-        let log_entries = self.changelog.versions.iter().map(|version| {
-            (version.bios.as_ref(), version.date.as_ref(), version.description.as_ref())
-        });
+        let dialog = FirmwareUpdateDialog::new(
+            self.latest,
+            system76_log_entries(self.changelog),
+            UpdateDetails::default(),
+            self.power,
+            self.battery_policy,
+            false,
+        );
 
-        let dialog = FirmwareUpdateDialog::new(self.latest, log_entries, self.has_battery);
+        let response = dialog.run();
+        super::snooze(&self.widgets.name.text(), self.latest, response);
 
-        if gtk::ResponseType::Accept == dialog.run() {
+        if gtk::ResponseType::Accept == response {
             // Exchange the button for a progress bar.
             self.widgets.stack.switch_to_waiting();
 
@@ -34,3 +41,29 @@ impl<'a> System76Dialog<'a> {
         dialog.close();
     }
 }
+
+/// The entries of a System76 changelog, noting the embedded controller and Management Engine
+/// firmware that each version of the system firmware bundles.
+pub fn system76_log_entries(
+    changelog: &System76Changelog,
+) -> impl Iterator<Item = (Cow<'_, str>, Cow<'_, str>, Cow<'_, str>)> {
+    changelog.versions.iter().map(|version| {
+        let mut notes = Vec::new();
+
+        if let Some(ref ec) = version.ec {
+            notes.push(fl!("changelog-ec", version = ec.as_ref()));
+        }
+
+        if let Some(ref me) = version.me {
+            notes.push(fl!("changelog-me", version = me.as_ref()));
+        }
+
+        let description = if notes.is_empty() {
+            Cow::Borrowed(version.description.as_ref())
+        } else {
+            Cow::Owned([&*version.description, "\n\n", &notes.join("\n")].concat())
+        };
+
+        (Cow::Borrowed(version.bios.as_ref()), Cow::Borrowed(version.date.as_ref()), description)
+    })
+}
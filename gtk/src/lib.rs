@@ -20,6 +20,8 @@ extern crate shrinkwraprs;
 mod changelog;
 mod dialogs;
 mod localize;
+mod notification;
+mod power;
 mod state;
 mod traits;
 mod views;
@@ -27,7 +29,7 @@ mod widgets;
 
 pub use self::localize::localizer;
 
-use self::{state::State, views::*};
+use self::{state::State, views::*, widgets::Toast};
 use firmware_manager::*;
 use gtk::{self, prelude::*};
 use slotmap::DefaultKey as Entity;
@@ -64,14 +66,50 @@ pub struct FirmwareWidget {
 /// An event which the GTK UI may propagate to the event loop in the main context.
 #[derive(Debug)]
 enum UiEvent {
-    /// It was requested to hide the upgrade stack of an entity
-    HideStack(Entity),
+    /// A device was requested to be hidden from now on
+    Ignore(Entity),
     /// An entity is scheduled to be revealed
     Reveal(Entity),
     /// An entity has been revealed
     Revealed(Entity, bool),
+    /// Every changelog was requested to be revealed or hidden
+    RevealAll(bool),
+    /// An entity's changelog was requested to be revealed alongside the others
+    Expand(Entity),
+    /// An automatic rescan of the devices is due
+    Refresh,
+    /// A rescan of the devices was requested
+    Rescan,
+    /// The search query of the devices view was changed
+    Search(Box<str>),
+    /// Another order was selected for the devices
+    Sort(DeviceSort),
+    /// The user confirmed that a fwupd device should be switched to another firmware branch
+    BranchConfirmed(Entity, Box<str>),
+    /// A fwupd device was requested to be switched to another firmware branch
+    SwitchBranch(Entity, Box<str>),
+    /// The download of an entity's update was requested to be stopped
+    Cancel(Entity),
+    /// The download of an entity's update was requested to be paused
+    Pause(Entity),
+    /// The paused download of an entity's update was requested to be resumed
+    Resume(Entity),
+    /// The failed update of an entity was requested to be retried
+    Retry(Entity),
     /// The update button of an entity was triggered
     Update(Entity),
+    /// The history page was shown
+    History,
+    /// A fwupd device was requested to be downgraded to an older release
+    Downgrade(Entity, Box<str>),
+    /// A fwupd device was requested to be updated to a release other than its latest
+    UpdateTo(Entity, Box<str>),
+    /// The Update All button was triggered
+    UpdateAll,
+    /// Devices which already have their latest firmware were hidden or shown
+    UpdatableOnly(bool),
+    /// The update button of a composite device was triggered, by the device ID of its root
+    UpdateGroup(Box<str>),
 }
 
 /// An event that requests for the UI to perform a specific action.
@@ -91,12 +129,42 @@ impl FirmwareWidget {
     ///
     /// # Notes
     /// - This will spawn a background thread to handle non-UI events.
-    /// - On drop, the background thread will exit
-    pub fn new() -> Self {
+    /// - On drop, the background thread will exit, cancelling any download in progress. The drop
+    ///   blocks until any device that is flashing has finished.
+    pub fn new() -> Self { Self::build(user_is_admin(), firmware_manager::event_loop) }
+
+    /// Create a firmware manager widget which displays the synthetic devices of the mock
+    /// backend, rather than the firmware of this system.
+    #[cfg(feature = "mock")]
+    pub fn dry_run() -> Self {
+        Self::build(true, |receiver, sender| {
+            let mut backends = Backends::default();
+            backends.register(MockBackend::default());
+            firmware_manager::event_loop_for(receiver, sender, backends);
+        })
+    }
+
+    /// Creates the widget, with the event loop that will be run in the background thread.
+    fn build<F>(is_admin: bool, event_loop: F) -> Self
+    where
+        F: FnOnce(Receiver<FirmwareEvent>, Sender<FirmwareSignal>) + Send + 'static,
+    {
         let (sender, rx) = channel();
 
-        let view_devices = DevicesView::new();
+        let view_devices = DevicesView::new(firmware_manager::config().sort);
         let view_empty = EmptyView::new();
+        let view_history = HistoryView::new();
+
+        // Firmware is scanned again once the fwupd service has started.
+        let start_sender = sender.clone();
+        view_empty.connect_start(move || {
+            let _ = start_sender.send(FirmwareEvent::StartFwupd);
+        });
+
+        let retry_sender = sender.clone();
+        view_empty.connect_retry(move || {
+            let _ = retry_sender.send(FirmwareEvent::Scan);
+        });
 
         let info_bar_label = cascade! {
             gtk::Label::new(None);
@@ -126,6 +194,30 @@ impl FirmwareWidget {
         let area = info_bar.content_area();
         area.add(&info_bar_label);
 
+        // Offers to start the fwupd service when devices were found without it.
+        let fwupd_bar = {
+            let sender = sender.clone();
+            cascade! {
+                gtk::InfoBar::new();
+                ..set_message_type(gtk::MessageType::Warning);
+                ..set_show_close_button(true);
+                ..add_button(&fl!("button-start-fwupd"), gtk::ResponseType::Accept);
+                ..connect_response(move |info_bar, response| {
+                    info_bar.set_visible(false);
+                    if response == gtk::ResponseType::Accept {
+                        let _ = sender.send(FirmwareEvent::StartFwupd);
+                    }
+                });
+                ..set_no_show_all(true);
+            }
+        };
+
+        fwupd_bar.content_area().add(&cascade! {
+            gtk::Label::new(Some(&fl!("scan-fwupd-unavailable")));
+            ..set_line_wrap(true);
+            ..show();
+        });
+
         let stack = cascade! {
             gtk::Stack::new();
             ..add(view_empty.as_ref());
@@ -133,8 +225,6 @@ impl FirmwareWidget {
             ..set_no_show_all(true);
         };
 
-        let is_admin = user_is_admin();
-
         if is_admin {
             stack.set_visible_child(view_empty.as_ref());
         } else {
@@ -144,12 +234,41 @@ impl FirmwareWidget {
             stack.show();
         }
 
+        // Switches between the devices, and the history of their updates.
+        let pages = cascade! {
+            gtk::Stack::new();
+            ..add_titled(
+                &cascade! {
+                    gtk::Box::new(gtk::Orientation::Vertical, 0);
+                    ..add(&fwupd_bar);
+                    ..pack_start(&stack, true, true, 0);
+                },
+                "devices",
+                &fl!("page-devices"),
+            );
+            ..add_titled(view_history.as_ref(), "history", &fl!("page-history"));
+        };
+
+        let switcher = cascade! {
+            gtk::StackSwitcher::new();
+            ..set_stack(Some(&pages));
+            ..set_halign(gtk::Align::Center);
+            ..set_margin_top(12);
+        };
+
+        let toast = Toast::new();
+
         let container = {
             let sender = sender.clone();
             let container = cascade! {
                 gtk::Overlay::new();
                 ..add_overlay(&info_bar);
-                ..add(&stack);
+                ..add_overlay(&*toast);
+                ..add(&cascade! {
+                    gtk::Box::new(gtk::Orientation::Vertical, 0);
+                    ..add(&switcher);
+                    ..pack_start(&pages, true, true, 0);
+                });
                 ..set_can_default(true);
                 ..connect_key_press_event(move |_, event| {
                     gtk::Inhibit(if event.keyval() == gdk::keys::constants::F5 {
@@ -171,7 +290,11 @@ impl FirmwareWidget {
         let (tx_events, rx_events) = glib::MainContext::channel::<Event>(glib::PRIORITY_DEFAULT);
 
         // Spawns a background thread to handle all background events.
-        let background = Self::background(rx, tx_events.clone());
+        let background = Self::background(rx, tx_events.clone(), event_loop);
+
+        if is_admin {
+            Self::connect_auto_refresh(tx_events.clone());
+        }
 
         let state = State::new(
             sender.clone(),
@@ -180,8 +303,11 @@ impl FirmwareWidget {
             stack.clone(),
             info_bar,
             info_bar_label,
+            fwupd_bar,
             view_devices,
             view_empty,
+            view_history,
+            toast,
         );
 
         Self::attach_main_event_loop(state, rx_events);
@@ -236,6 +362,14 @@ impl FirmwareWidget {
 
             trace!("received UI event: {:#?}", Paint::yellow(&event));
             match event {
+                // An update was cancelled before flashing began.
+                Firmware(Cancelled(entity)) => state.device_cancelled(entity),
+                // The download of an update was paused, which returns the request to resume it.
+                Firmware(Paused(entity, event)) => state.device_paused(entity, *event),
+                // An update failed, which returns the request to retry it.
+                Firmware(Failed(entity, event)) => state.device_failed(entity, *event),
+                // An event that occurs when a device from another backend is found.
+                Firmware(Device(signal)) => state.device(signal),
                 // When a device begins flashing, we can begin moving the progress bar based on
                 // its duration.
                 Firmware(DeviceFlashing(entity)) => {
@@ -255,31 +389,39 @@ impl FirmwareWidget {
                     state.widgets.info_bar.set_visible(false);
                     firmware_flashing.store(false, Ordering::SeqCst);
                     let latest = state.components.latest.remove(entity);
-                    state.device_updated(entity, latest.expect("updated device without version"))
+                    state.device_updated(entity, latest)
                 }
                 // Firmware for a device has begun downloading.
                 Firmware(DownloadBegin(entity, size)) => {
                     let widget = &state.components.device_widgets[entity];
-                    state.components.firmware_download.insert(entity, (0, size));
-                    widget.stack.switch_to_progress(&fl!("action-downloading"));
+                    state.components.firmware_download.insert(entity, DownloadProgress::new(size));
+                    widget.stack.switch_to_download();
                 }
                 // Firmware for a device has finished downloading.
                 Firmware(DownloadComplete(entity)) => {
                     state.components.firmware_download.remove(entity);
                     let widget = &state.components.device_widgets[entity];
                     widget.stack.progress.set_fraction(1.0);
+                    widget.stack.transferred.set_visible(false);
+                    widget.stack.speed.set_visible(false);
+                    widget.stack.set_download_controls(false);
                 }
                 // Update the progress for the firmware being downloaded.
                 Firmware(DownloadUpdate(entity, downloaded)) => {
                     let widget = &state.components.device_widgets[entity];
                     let progress = &mut state.components.firmware_download[entity];
-                    progress.0 += downloaded as u64;
-                    widget.stack.progress.set_fraction(progress.0 as f64 / progress.1 as f64);
+                    progress.advance(downloaded as u64);
+                    widget.stack.set_download(progress);
                 }
                 // Device has requested interaction.
                 Firmware(DeviceRequest(message)) => {
                     state.widgets.info_bar.set_visible(true);
                     state.widgets.info_bar_label.set_text(message.as_str());
+                    state.widgets.info_bar_label.set_tooltip_text(None);
+                }
+                // Older versions of fwupd do not report security attributes, which is only logged.
+                Firmware(Error(None, FirmwareError::HostSecurity(why))) => {
+                    warn!("security attributes are unavailable: {}", why);
                 }
                 // An error occurred in the background thread, which we shall display in the UI.
                 Firmware(Error(entity, why)) => {
@@ -294,54 +436,177 @@ impl FirmwareWidget {
 
                     error!("firmware widget error: {}", error_message);
 
-                    state.widgets.info_bar.set_visible(true);
-                    state.widgets.info_bar_label.set_text(error_message.as_str());
-
+                    // Errors of an update are shown in the row of its device, to be retried there.
                     if let Some(entity) = entity {
+                        state.components.cancellation.remove(entity);
+                        state.components.failed.remove(entity);
                         let widget = &state.components.device_widgets[entity];
-                        widget.stack.set_visible_child(&widget.stack.button);
+                        let summary = error_summary(&why);
+                        widget.stack.switch_to_failed(&summary, &why.localized());
                         state.components.firmware_download.remove(entity);
                         state.progress_deactivate(&widget.stack.progress);
+
+                        let device = widget.name.text();
+                        let title = fl!("notification-failed-title");
+                        let body = fl!(
+                            "notification-failed-body",
+                            device = device.as_str(),
+                            reason = summary
+                        );
+                        notification::update_finished(&widget.container, &device, &title, &body);
+                    } else {
+                        state.widgets.info_bar.set_visible(true);
+                        state.widgets.info_bar_label.set_text(&error_summary(&why));
+                        state.widgets.info_bar_label.set_tooltip_text(Some(&why.localized()));
                     }
                 }
                 // An event that occurs when fwupd firmware is found.
                 Firmware(Fwupd(signal)) => state.fwupd(signal),
+                // The details of a fwupd device have changed.
+                Firmware(FwupdChanged(signal)) => state.fwupd_changed(signal),
+                // A fwupd device was unplugged. Devices may briefly disappear while flashing.
+                Firmware(FwupdRemoved(device_id)) => {
+                    if !firmware_flashing.load(Ordering::SeqCst) {
+                        state.fwupd_removed(&device_id);
+                    }
+                }
+                // The widget does not export or import offline bundles.
+                Firmware(BundleExported(..) | BundleImported(_)) => (),
+                // The widget does not manage fwupd remotes.
+                Firmware(FwupdRemotes(_)) => (),
+                // The widget does not create recovery drives.
+                Firmware(Recovery(_) | UsbDrives(_)) => (),
+                // The protections which the firmware enables are listed below the devices.
+                Firmware(HostSecurity(attributes)) => {
+                    state.widgets.view_devices.security(&attributes);
+                }
+                // Updates were interrupted the last time that the firmware manager ran.
+                Firmware(Interrupted(updates)) => state.interrupted(updates),
+                // An update is waiting for the updates queued before it.
+                Firmware(Queued(entity, position)) => {
+                    if let Some(widget) = state.components.device_widgets.get(entity) {
+                        widget.stack.switch_to_queued(position);
+                    }
+                }
+                // The outcome of an update may be uploaded to the LVFS.
+                Firmware(ReportAvailable(report)) => state.report_available(report),
+                // New firmware takes effect once the device has been reconnected.
+                Firmware(ReplugRequired(entity)) => {
+                    firmware_flashing.store(false, Ordering::SeqCst);
+                    let latest = state.components.latest.remove(entity);
+                    state.device_replug(entity, latest)
+                }
+                // Devices are kept while they are being refreshed.
+                Firmware(Scanning) if state.refreshing.is_some() => (),
                 // Begins searching for devices that have firmware upgrade support
                 Firmware(Scanning) => {
                     state.widgets.view_devices.clear();
                     last_active_revealer = None;
                     state.entities.clear();
+                    state.groups.clear();
+                    state.topology = Topology::default();
 
                     let _ = state.progress_sender.send(ActivateEvent::Clear);
 
                     state.widgets.stack.hide();
-                    state.widgets.view_devices.hide_systems();
-                    state.widgets.view_devices.hide_devices();
+                    state.widgets.view_devices.hide_sections();
                 }
+                // Explains why no firmware was found, if none was.
+                Firmware(ScanResult(result)) => state.scan_result(&result),
                 // Signal is received when scanning has completed.
                 Firmware(ScanningComplete) => {
                     info!("scanning for firmware is complete");
+                    let _ = state.sender.send(FirmwareEvent::HostSecurity);
+                    state.refresh_complete();
+                    state.widgets.view_devices.set_last_check(last_check(), next_check());
                     if state.entities.entities.is_empty() {
                         state.widgets.stack.show();
                         state.widgets.view_empty.show_all();
                         state.widgets.stack.set_visible_child(state.widgets.view_empty.as_ref());
                     }
+
+                    if let Some(position) = state.scroll.take() {
+                        state.widgets.view_devices.restore_scroll(position);
+                    }
+                }
+                // System firmware will be installed when the user next reboots the system.
+                Firmware(Staged(entity)) => {
+                    state.widgets.info_bar.set_visible(false);
+                    firmware_flashing.store(false, Ordering::SeqCst);
+                    let latest = state.components.latest.remove(entity);
+                    state.device_staged(entity, latest)
                 }
                 // When system firmwmare is successfully scheduled, reboot the system.
                 Firmware(SystemScheduled) => reboot(),
+                // The system was described, which is shown above the system firmware.
+                Firmware(SystemSummary(summary)) => state.system_summary(summary),
                 // An event that occurs when System76 system firmware has been found.
                 Firmware(S76System(info, data)) => state.system76_system(info, data),
+                // The embedded controller is updated with the system firmware.
+                Firmware(S76Ec(info)) => state.system76_ec(info),
+                // The Management Engine is updated with the system firmware.
+                Firmware(ManagementEngine(info)) => state.management_engine(info),
+                // The microcode of the processor was found.
+                Firmware(Microcode(info)) => state.microcode(info),
                 // An event that occurs when a Thelio I/O board was discovered.
-                Firmware(ThelioIo(info, digest)) => state.thelio_io(info, digest),
-                // Schedules the given firmware for an update, and show a dialog if it requires a
-                // reboot.
-                Ui(Update(entity)) => state.update(entity),
-                // Hides the entity's stack.
-                Ui(HideStack(entity)) => {
+                Firmware(ThelioIo(board, info, digest)) => state.thelio_io(board, info, digest),
+                // Another Thelio I/O board is flashed along with the one that was requested, and
+                // is shown as though its own update is flashing.
+                Firmware(ThelioIoFlashing(board)) => {
+                    if let Some(entity) = state.thelio_entity(&board) {
+                        let _ = state.ui_sender.send(Firmware(DeviceFlashing(entity)));
+                    }
+                }
+                // Another Thelio I/O board finished flashing, with its own result.
+                Firmware(ThelioIoFlashed(board, error)) => {
+                    if let Some(entity) = state.thelio_entity(&board) {
+                        let signal = match error {
+                            Some(why) => Error(Some(entity), why),
+                            None => DeviceUpdated(entity),
+                        };
+
+                        let _ = state.ui_sender.send(Firmware(signal));
+                    }
+                }
+                // An update was deferred because the system is offline or metered.
+                Firmware(UpdateDeferred(entity, reason, event)) => {
+                    state.update_deferred(entity, reason, *event)
+                }
+                // The downloaded firmware is being checked against its checksum.
+                Firmware(Verifying(entity)) => {
                     if let Some(widget) = state.components.device_widgets.get(entity) {
-                        widget.stack.hide();
+                        widget.stack.switch_to_progress(&fl!("action-verifying"));
+                        state.progress_activate(&widget.stack.progress);
                     }
                 }
+                // Firmware was rejected, as it was not signed by a trusted key.
+                Firmware(VerificationFailed(entity, why)) => {
+                    firmware_flashing.store(false, Ordering::SeqCst);
+                    state.verification_failed(entity, why);
+                }
+                // Schedules the given firmware for an update, and show a dialog if it requires a
+                // reboot.
+                Ui(Update(entity)) => state.update(entity),
+                // Updates a fwupd device to the release that the user picked.
+                Ui(UpdateTo(entity, version)) => state.update_to(entity, version),
+                // Downgrades a fwupd device to an older release, if the user confirms it.
+                Ui(Downgrade(entity, version)) => state.downgrade(entity, version),
+                // Stops the download of an update, which restores the update button.
+                Ui(Cancel(entity)) => state.cancel(entity),
+                // Stops the download of an update, keeping what was downloaded.
+                Ui(Pause(entity)) => state.pause(entity),
+                // Resumes a paused download from where it was paused.
+                Ui(Resume(entity)) => state.resume(entity),
+                // Sends the request of an update which failed again.
+                Ui(Retry(entity)) => state.retry(entity),
+                // Queues the updates of every device which may be updated without a reboot.
+                Ui(UpdateAll) => state.update_all(),
+                // Lists the updates that were attempted, as the history page is shown.
+                Ui(History) => state.show_history(),
+                // Queues the updates of every device of a composite device, such as a dock.
+                Ui(UpdateGroup(root)) => state.update_group(&root),
+                // Hides a device, and remembers to ignore it in later scans.
+                Ui(Ignore(entity)) => state.ignore_device(entity),
                 // Reveals a widget's changelog in a revealer, and generate that changelog if it has
                 // not been revealed yet.
                 Ui(Reveal(entity)) => state.reveal(entity),
@@ -350,8 +615,9 @@ impl FirmwareWidget {
                 Ui(Revealed(entity, revealed)) => {
                     if revealed {
                         if let Some(previous) = last_active_revealer {
-                            let widgets = &state.components.device_widgets[previous];
-                            widgets.revealer.set_reveal_child(false);
+                            if let Some(widgets) = state.components.device_widgets.get(previous) {
+                                widgets.revealer.set_reveal_child(false);
+                            }
                         }
 
                         last_active_revealer = Some(entity);
@@ -359,6 +625,24 @@ impl FirmwareWidget {
                         last_active_revealer = None;
                     }
                 }
+                // Reveals the changelog of every shown device, or hides them all.
+                Ui(RevealAll(reveal)) => state.reveal_all(reveal),
+                // Reveals one changelog which was queued by revealing them all.
+                Ui(Expand(entity)) => state.expand(entity),
+                // Scans for devices again, preserving the scroll position of the devices view.
+                Ui(Rescan) => state.rescan(),
+                // Rescans the devices in the background, replacing only those which changed.
+                Ui(Refresh) => state.auto_refresh(),
+                // Shows only the devices which match the search query.
+                Ui(Search(query)) => state.search(query),
+                // Hides or shows the devices which already have their latest firmware.
+                Ui(UpdatableOnly(active)) => state.updatable_only(active),
+                // Lists the devices in the selected order, and remembers it for next time.
+                Ui(Sort(order)) => state.sort(order),
+                // Switches a fwupd device to another branch, if the user confirms it.
+                Ui(SwitchBranch(entity, branch)) => state.switch_branch(entity, branch),
+                // Switches a fwupd device to another branch, once the user has confirmed it.
+                Ui(BranchConfirmed(entity, branch)) => state.branch_confirmed(entity, branch),
                 // This is the last message sent before the background thread exits.
                 Stop => {
                     trace!("glib channel receiver closed");
@@ -366,15 +650,34 @@ impl FirmwareWidget {
                 }
             }
 
+            // Follows the updates queued by Update All, and the devices which it may update.
+            state.refresh_update_all();
+
             glib::Continue(true)
         });
     }
 
+    /// Periodically requests a rescan of the devices, if automatic refreshes are configured.
+    fn connect_auto_refresh(sender: glib::Sender<Event>) {
+        let minutes = match firmware_manager::config().auto_refresh {
+            Some(minutes) if minutes != 0 => minutes,
+            _ => return,
+        };
+
+        glib::timeout_add_seconds_local(minutes.saturating_mul(60), move || {
+            glib::Continue(sender.send(Event::Ui(UiEvent::Refresh)).is_ok())
+        });
+    }
+
     /// Manages all firmware client interactions from a background thread.
-    fn background(
+    fn background<F>(
         receiver: Receiver<FirmwareEvent>,
         sender: glib::Sender<Event>,
-    ) -> JoinHandle<()> {
+        event_loop: F,
+    ) -> JoinHandle<()>
+    where
+        F: FnOnce(Receiver<FirmwareEvent>, Sender<FirmwareSignal>) + Send + 'static,
+    {
         let (tx, rx) = std::sync::mpsc::channel::<FirmwareSignal>();
 
         thread::spawn({
@@ -390,7 +693,7 @@ impl FirmwareWidget {
 
 
         thread::spawn(move || {
-            firmware_manager::event_loop(receiver, tx);
+            event_loop(receiver, tx);
             info!("firmware manager event loop stopped");
             let _ = sender.send(Event::Stop);
         })
@@ -459,6 +762,35 @@ impl Drop for FirmwareWidget {
     }
 }
 
+/// A translated description of an error, to be displayed to the user.
+fn error_summary(why: &FirmwareError) -> String {
+    match why {
+        FirmwareError::Branch(_) => fl!("error-branch"),
+        FirmwareError::Bundle(_) => fl!("error-bundle"),
+        FirmwareError::Channel(..) => fl!("error-channel"),
+        FirmwareError::Download(_) => fl!("error-download"),
+        FirmwareError::Esp(EspError::NotMounted) => fl!("error-esp-not-mounted"),
+        FirmwareError::Esp(EspError::ReadOnly(_)) => fl!("error-esp-read-only"),
+        FirmwareError::Esp(EspError::Space(_)) => fl!("error-esp-space"),
+        FirmwareError::FwupdStart(_) => fl!("error-fwupd-start"),
+        FirmwareError::Backend(..)
+        | FirmwareError::Fwupd(_)
+        | FirmwareError::HostSecurity(_)
+        | FirmwareError::Remote(..)
+        | FirmwareError::System76(_)
+        | FirmwareError::ThelioIoBoard(..)
+        | FirmwareError::UnknownRemote(_) => fl!("error-daemon"),
+        FirmwareError::Open(_) => fl!("error-open"),
+        FirmwareError::Permission(_) => fl!("error-permission"),
+        FirmwareError::Policy(..) => fl!("error-policy"),
+        FirmwareError::Recovery(_) => fl!("error-recovery"),
+        FirmwareError::Release(_) => fl!("error-release"),
+        FirmwareError::Schedule(_) => fl!("error-schedule"),
+        FirmwareError::Space(_) => fl!("error-space"),
+        FirmwareError::Verification(_) => fl!("error-verification"),
+    }
+}
+
 /// Convenience function for rebooting the system.
 ///
 /// Currently only supports rebooting via `systemctl`. Feature flags could use other init system
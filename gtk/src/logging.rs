@@ -1,52 +1,31 @@
-use fern::{Dispatch, InitError};
-use log::{Level, LevelFilter, Record};
-use std::io;
-use yansi::Paint;
+use std::{env, error::Error, io};
+use tracing_subscriber::{
+    filter::{EnvFilter, LevelFilter},
+    fmt::format::FmtSpan,
+};
 
-/// Installs the fern logger, which manages all logs created with `log` statements.
-pub fn install(filter: LevelFilter) -> Result<(), InitError> {
-    let location = |record: &Record| {
-        let mut target = record.target();
-        if let Some(pos) = target.find(':') {
-            target = &target[..pos];
-        }
+/// The environment variable which overrides the default log filter.
+const FILTER_VAR: &str = "FIRMWARE_MANAGER_LOG";
 
-        match (record.file(), record.line()) {
-            (Some(file), Some(line)) => format!(
-                "{} {}{}{}",
-                Paint::cyan(target).bold(),
-                Paint::blue(file).bold(),
-                Paint::new(":").bold(),
-                Paint::magenta(line).bold()
-            ),
-            _ => String::new(),
-        }
-    };
+/// Crates whose logs are included by the default filter.
+const CRATES: &[&str] =
+    &["firmware_manager_gtk", "firmware_manager", "fwupd_dbus", "system76_firmware_daemon"];
 
-    let format_level = |record: &Record| match record.level() {
-        level @ Level::Trace => Paint::green(level).bold(),
-        level @ Level::Warn => Paint::yellow(level).bold(),
-        level @ Level::Error => Paint::red(level).bold(),
-        level => Paint::new(level).bold(),
-    };
+/// Installs the tracing subscriber, which manages all spans and logs created with `tracing`
+/// and `log` statements.
+///
+/// By default, only logs from the crates of interest are included, at the given level. The
+/// filter may be replaced with the `FIRMWARE_MANAGER_LOG` environment variable, which accepts
+/// directives such as `firmware_manager=trace,fwupd_dbus=debug`. The duration of each scan,
+/// download, and flash is logged when it completes.
+pub fn install(level: LevelFilter) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let directives = env::var(FILTER_VAR).unwrap_or_else(|_| {
+        CRATES.iter().map(|name| format!("{}={}", name, level)).collect::<Vec<_>>().join(",")
+    });
 
-    Dispatch::new()
-        // Exclude logs for crates that we use
-        .level(LevelFilter::Off)
-        // Include only the logs for relevant crates of interest
-        .level_for("firmware_manager_gtk", filter)
-        .level_for("firmware_manager", filter)
-        .level_for("fwupd_dbus", filter)
-        .level_for("system76_firmware_daemon", filter)
-        .format(move |out, message, record| {
-            out.finish(format_args!(
-                "[{:5}] {}: {}",
-                format_level(record),
-                location(record),
-                message
-            ))
-        })
-        .chain(io::stderr())
-        .apply()?;
-    Ok(())
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_new(directives)?)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(io::stderr)
+        .try_init()
 }
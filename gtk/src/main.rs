@@ -6,19 +6,23 @@ mod logging;
 use firmware_manager_gtk::FirmwareWidget;
 use gio::prelude::*;
 use gtk::prelude::*;
-use i18n_embed::DesktopLanguageRequester;
+use i18n_embed::{unic_langid::CharacterDirection, DesktopLanguageRequester};
 use std::rc::Rc;
 
 pub const APP_ID: &str = "com.system76.FirmwareManager";
 
 fn main() {
-    translate();
-    argument_parsing();
+    let direction = translate();
+    let dry_run = argument_parsing();
 
     better_panic::install();
     glib::set_program_name(APP_ID.into());
     gtk::init().expect("failed to init GTK");
 
+    // GTK takes the direction from its own translations, which may not be installed for the
+    // language that the interface is shown in.
+    gtk::Widget::set_default_direction(direction);
+
     let application = gtk::Application::builder().application_id(APP_ID).build();
 
     application.connect_activate(|app| {
@@ -27,8 +31,8 @@ fn main() {
         }
     });
 
-    application.connect_startup(|app| {
-        let widget = Rc::new(FirmwareWidget::new());
+    application.connect_startup(move |app| {
+        let widget = Rc::new(firmware_widget(dry_run));
         widget.scan();
 
         let weak_widget = Rc::downgrade(&widget);
@@ -85,43 +89,97 @@ fn main() {
                 })
             });
         };
+
+        // Closing the windows drops the firmware widget, which waits for any device that is
+        // flashing to finish before the application exits.
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            let app = app.clone();
+            glib::unix_signal_add_local(signal, move || {
+                for window in app.windows() {
+                    window.close();
+                }
+
+                glib::Continue(false)
+            });
+        }
     });
 
     application.run();
 }
 
+/// Creates the firmware widget, which displays mock devices in a dry run.
+#[cfg(feature = "mock")]
+fn firmware_widget(dry_run: bool) -> FirmwareWidget {
+    if dry_run {
+        FirmwareWidget::dry_run()
+    } else {
+        FirmwareWidget::new()
+    }
+}
+
+#[cfg(not(feature = "mock"))]
+fn firmware_widget(_dry_run: bool) -> FirmwareWidget {
+    FirmwareWidget::new()
+}
+
 /// Manages argument parsing for the GTK application via clap.
 ///
-/// Currently the primary purpose is to determine the logging level.
-fn argument_parsing() {
+/// Determines the logging level, and returns whether a dry run was requested.
+fn argument_parsing() -> bool {
     use clap::{Command, Arg, ArgAction};
-    use log::LevelFilter;
+    use tracing_subscriber::filter::LevelFilter;
 
-    let matches = Command::new("com.system76.FirmwareManager")
+    let command = Command::new("com.system76.FirmwareManager")
         .arg(
             Arg::new("verbose")
                 .short('v')
                 .action(ArgAction::Count)
                 .help("define the logging level; multiple occurrences increases the logging level"),
-        )
-        .get_matches();
+        );
+
+    #[cfg(feature = "mock")]
+    let command = command.arg(
+        Arg::new("dry-run")
+            .long("dry-run")
+            .action(ArgAction::SetTrue)
+            .help("display simulated devices, rather than updating the firmware of this system"),
+    );
+
+    let matches = command.get_matches();
 
     let logging_level = match matches.get_count("verbose") {
-        0 => LevelFilter::Info,
-        1 => LevelFilter::Debug,
-        _ => LevelFilter::Trace,
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
     };
 
     if let Err(why) = logging::install(logging_level) {
         eprintln!("failed to initiate logging: {}", why);
     }
+
+    matches.try_get_one::<bool>("dry-run").ok().flatten().copied().unwrap_or(false)
 }
 
-fn translate() {
+/// Selects the translations for the languages of the desktop, returning the direction in which
+/// the selected language is written.
+fn translate() -> gtk::TextDirection {
     let localizer = firmware_manager_gtk::localizer();
     let requested_languages = DesktopLanguageRequester::requested_languages();
 
-    if let Err(error) = localizer.select(&requested_languages) {
-        eprintln!("Error while loading languages for firmware-manager-gtk {}", error);
+    let direction = match localizer.select(&requested_languages) {
+        Ok(languages) => match languages.first().map(|language| language.character_direction()) {
+            Some(CharacterDirection::RTL) => gtk::TextDirection::Rtl,
+            _ => gtk::TextDirection::Ltr,
+        },
+        Err(error) => {
+            eprintln!("Error while loading languages for firmware-manager-gtk {}", error);
+            gtk::TextDirection::Ltr
+        }
+    };
+
+    if let Err(error) = firmware_manager::localizer().select(&requested_languages) {
+        eprintln!("Error while loading languages for firmware-manager {}", error);
     }
+
+    direction
 }
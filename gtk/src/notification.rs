@@ -0,0 +1,36 @@
+//! Tells of updates which finished while the firmware manager was not being looked at.
+
+use gio::prelude::*;
+use gtk::prelude::*;
+
+/// Sends a desktop notification of the result of an update to the `device`, if the window which
+/// shows the `widget` is not focused, as when it was minimized or the user walked away from it.
+///
+/// Notifications are sent through the application which hosts the widget, so none are sent if
+/// there is no such application.
+pub fn update_finished<W: IsA<gtk::Widget>>(widget: &W, device: &str, title: &str, body: &str) {
+    let focused = widget
+        .toplevel()
+        .and_then(|toplevel| toplevel.downcast::<gtk::Window>().ok())
+        .map_or(false, |window| window.is_active());
+
+    if focused {
+        return;
+    }
+
+    let application = match gio::Application::default() {
+        Some(application) => application,
+        None => {
+            info!("not notifying of the update of {}: no application to notify with", device);
+            return;
+        }
+    };
+
+    let notification = gio::Notification::new(title);
+    notification.set_body(Some(body));
+    notification.set_icon(&gio::ThemedIcon::new("firmware-manager"));
+
+    // Each device has its own notification, which replaces that of its previous update.
+    let id = ["firmware-update-", device].concat();
+    application.send_notification(Some(&id), &notification);
+}
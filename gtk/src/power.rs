@@ -0,0 +1,75 @@
+//! Tracks whether the system is running on battery, as UPower reports it.
+
+use gtk::prelude::*;
+use std::cell::Cell;
+
+/// The D-Bus name, object path, and interface of UPower.
+const UPOWER: (&str, &str, &str) =
+    ("org.freedesktop.UPower", "/org/freedesktop/UPower", "org.freedesktop.UPower");
+
+/// Whether the system is running on battery, which is kept current as the system is plugged in
+/// and unplugged.
+#[derive(Clone)]
+pub struct Power(Option<gio::DBusProxy>);
+
+impl Power {
+    pub fn new() -> Self {
+        let (name, path, interface) = UPOWER;
+        let proxy = gio::DBusProxy::for_bus_sync(
+            gio::BusType::System,
+            gio::DBusProxyFlags::NONE,
+            None,
+            name,
+            path,
+            interface,
+            gio::Cancellable::NONE,
+        );
+
+        match proxy {
+            Ok(proxy) => Self(Some(proxy)),
+            Err(why) => {
+                warn!("battery state is unavailable: {}", why);
+                Self(None)
+            }
+        }
+    }
+
+    /// Whether the system is running on battery. Without UPower, it is assumed that it is not.
+    pub fn on_battery(&self) -> bool {
+        self.0
+            .as_ref()
+            .and_then(|proxy| proxy.cached_property("OnBattery"))
+            .and_then(|on_battery| on_battery.get::<bool>())
+            .unwrap_or(false)
+    }
+
+    /// Calls `func` with whether the system is running on battery whenever UPower reports a
+    /// change, until the `widget` is destroyed.
+    pub fn connect_changed<W, F>(&self, widget: &W, func: F)
+    where
+        W: IsA<gtk::Widget>,
+        F: Fn(bool) + 'static,
+    {
+        let proxy = match self.0.as_ref() {
+            Some(proxy) => proxy,
+            None => return,
+        };
+
+        let weak_proxy = proxy.downgrade();
+        let handler = proxy.connect_local("g-properties-changed", false, move |_| {
+            if let Some(proxy) = weak_proxy.upgrade() {
+                func(Power(Some(proxy)).on_battery());
+            }
+
+            None
+        });
+
+        let proxy = proxy.clone();
+        let handler = Cell::new(Some(handler));
+        widget.connect_destroy(move |_| {
+            if let Some(handler) = handler.take() {
+                proxy.disconnect(handler);
+            }
+        });
+    }
+}
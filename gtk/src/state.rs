@@ -1,22 +1,51 @@
-use crate::{dialogs::*, fl, views::*, widgets::*, ActivateEvent, Event, UiEvent};
+use crate::{dialogs::*, fl, power::Power, views::*, widgets::*, ActivateEvent, Event, UiEvent};
 use firmware_manager::*;
 
 use gtk::prelude::*;
 use slotmap::{DefaultKey as Entity, SecondaryMap, SparseSecondaryMap};
-use std::sync::mpsc::Sender;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error as _,
+    sync::{mpsc::Sender, Arc},
+};
+
+/// The updates which were queued together by the Update All button.
+pub(crate) struct Batch {
+    /// The devices which were queued.
+    entities: Vec<Entity>,
+    /// The update buttons which are disabled until the queued updates have finished.
+    disabled: Vec<gtk::Button>,
+}
 
 /// Manages all state and state interactions with the UI.
 pub(crate) struct State {
+    /// The updates queued by the Update All button, until they have all finished.
+    pub(crate) batch: Option<Batch>,
+    /// How updates are handled while the system is running on battery.
+    pub(crate) battery_policy: BatteryPolicy,
     /// Components that have been associated with entities.
     pub(crate) components: Components,
     /// All devices will be created as an entity here
     pub(crate) entities: Entities,
-    /// If this system has a battery.
-    pub(crate) has_battery: bool,
+    /// Groups the devices of composite devices, such as docks, by the device ID of their root.
+    pub(crate) groups: HashMap<Box<str>, DeviceGroup>,
+    /// Whether the system is running on battery, which is kept current by UPower.
+    pub(crate) power: Power,
+    /// The scroll position of the devices view, which is restored once a rescan has completed.
+    pub(crate) scroll: Option<f64>,
+    /// The search query which devices are filtered by.
+    pub(crate) search: Box<str>,
+    /// Whether devices which already have their latest firmware are hidden.
+    pub(crate) updatable_only: bool,
     /// Sends events to the progress signal
     pub(crate) progress_sender: Sender<ActivateEvent>,
+    /// The devices which were found again by an automatic rescan that is in progress.
+    pub(crate) refreshing: Option<HashSet<Entity>>,
     /// A sender to send firmware requests to the background thread
     pub(crate) sender: Sender<FirmwareEvent>,
+    /// The parents of the fwupd devices which belong to composite devices.
+    pub(crate) topology: Topology,
     /// Events to be processed by the main event loop
     pub(crate) ui_sender: glib::Sender<Event>,
     /// Widgets that will be actively managed.
@@ -29,22 +58,42 @@ pub(crate) struct Widgets {
     pub(crate) info_bar: gtk::InfoBar,
     /// Error messages will be set in this label.
     pub(crate) info_bar_label: gtk::Label,
+    /// Offers to start the fwupd service, when firmware was found without it.
+    pub(crate) fwupd_bar: gtk::InfoBar,
     /// Controls which view to display in the UI
     pub(crate) stack: gtk::Stack,
     /// The devices view shows a list of all supported devices.
     pub(crate) view_devices: DevicesView,
     /// The empty view is displayed when a scan found no devices.
     pub(crate) view_empty: EmptyView,
+    /// The history view lists the updates that were attempted.
+    pub(crate) view_history: HistoryView,
+    /// Briefly tells of updates which were completed.
+    pub(crate) toast: Toast,
 }
 
 /// Components are optional pieces of data that are assigned to entities
 #[derive(Default)]
 pub(crate) struct Components {
+    /// Cancels an update that is in progress.
+    pub(crate) cancellation: SparseSecondaryMap<Entity, CancellationToken>,
+
+    /// Identifies devices which were discovered by other backends.
+    pub(crate) devices: SparseSecondaryMap<Entity, DeviceId>,
+
     /// The GTK widgets associated with a device are stored here.
     pub(crate) device_widgets: SecondaryMap<Entity, DeviceWidget>,
 
-    /// Tracks progress of a firmware download.
-    pub(crate) firmware_download: SecondaryMap<Entity, (u64, u64)>,
+    /// Tracks progress of a firmware download, and its speed.
+    pub(crate) firmware_download: SecondaryMap<Entity, DownloadProgress>,
+
+    /// The requests of updates whose downloads were paused, which are sent again to resume them.
+    pub(crate) paused: SparseSecondaryMap<Entity, FirmwareEvent>,
+    /// The requests of updates which failed, which are sent again when they are retried.
+    pub(crate) failed: SparseSecondaryMap<Entity, FirmwareEvent>,
+
+    /// The firmware of a device, as it was when the device was found.
+    pub(crate) info: SecondaryMap<Entity, FirmwareInfo>,
 
     /// The latest version associated with a device, if one exists.
     pub(crate) latest: SecondaryMap<Entity, Box<str>>,
@@ -52,11 +101,23 @@ pub(crate) struct Components {
     /// Details about a fwupd device
     pub(crate) fwupd: SparseSecondaryMap<Entity, (FwupdDevice, Vec<FwupdRelease>)>,
 
+    /// Other firmware branches which a fwupd device may be switched to.
+    pub(crate) branches: SparseSecondaryMap<Entity, Vec<Box<str>>>,
+
+    /// The release channel of each fwupd release, in the same order as the releases.
+    pub(crate) release_channels: SparseSecondaryMap<Entity, Vec<ReleaseChannel>>,
+
+    /// The security issues which each fwupd release fixes, in the same order as the releases.
+    pub(crate) issues: SparseSecondaryMap<Entity, Vec<ReleaseIssues>>,
+
+    /// Whether the measurements of updated system firmware match those expected of it.
+    pub(crate) attestations: SparseSecondaryMap<Entity, Attestation>,
+
     /// Details about system76 system firmware.
     pub(crate) system76: SparseSecondaryMap<Entity, (System76Digest, System76Changelog)>,
 
-    /// Details about thelio I/O firmware
-    pub(crate) thelio: SparseSecondaryMap<Entity, System76Digest>,
+    /// Details about thelio I/O firmware, with the ID of each board
+    pub(crate) thelio: SparseSecondaryMap<Entity, (Box<str>, System76Digest)>,
 }
 
 impl State {
@@ -70,85 +131,1189 @@ impl State {
         stack: gtk::Stack,
         info_bar: gtk::InfoBar,
         info_bar_label: gtk::Label,
+        fwupd_bar: gtk::InfoBar,
         view_devices: DevicesView,
         view_empty: EmptyView,
+        view_history: HistoryView,
+        toast: Toast,
     ) -> Self {
-        let has_battery =
-            upower_dbus::UPower::new(-1).and_then(|upower| upower.on_battery()).unwrap_or(false);
+        let power = Power::new();
+
+        let rescan_sender = ui_sender.clone();
+        view_devices.connect_rescan(move || {
+            let _ = rescan_sender.send(Event::Ui(UiEvent::Rescan));
+        });
+
+        let update_all_sender = ui_sender.clone();
+        view_devices.connect_update_all(move || {
+            let _ = update_all_sender.send(Event::Ui(UiEvent::UpdateAll));
+        });
+
+        let sort_sender = ui_sender.clone();
+        view_devices.connect_sort(move |order| {
+            let _ = sort_sender.send(Event::Ui(UiEvent::Sort(order)));
+        });
+
+        let updatable_sender = ui_sender.clone();
+        view_devices.connect_updatable_only(move |active| {
+            let _ = updatable_sender.send(Event::Ui(UiEvent::UpdatableOnly(active)));
+        });
+
+        let reveal_sender = ui_sender.clone();
+        view_devices.connect_reveal_all(move |reveal| {
+            let _ = reveal_sender.send(Event::Ui(UiEvent::RevealAll(reveal)));
+        });
+
+        let search_sender = ui_sender.clone();
+        view_devices.connect_search(move |query| {
+            let _ = search_sender.send(Event::Ui(UiEvent::Search(query.into())));
+        });
+
+        let history_sender = ui_sender.clone();
+        view_history.connect_shown(move || {
+            let _ = history_sender.send(Event::Ui(UiEvent::History));
+        });
+
+        Self {
+            batch: None,
+            battery_policy: firmware_manager::config().battery_policy,
+            entities: Entities::default(),
+            components: Components::default(),
+            groups: HashMap::new(),
+            power,
+            progress_sender,
+            refreshing: None,
+            scroll: None,
+            search: Box::default(),
+            updatable_only: false,
+            sender,
+            topology: Topology::default(),
+            widgets: Widgets {
+                info_bar,
+                info_bar_label,
+                fwupd_bar,
+                stack,
+                view_devices,
+                view_empty,
+                view_history,
+                toast,
+            },
+            ui_sender,
+        }
+    }
+
+    /// The base method for creating a new firmware device entity.
+    ///
+    /// While devices are being refreshed, a device which is found again unchanged keeps its
+    /// widget, rather than being created again.
+    pub fn create_device<F: FnOnce(&mut Self, Entity) -> DeviceWidget>(
+        &mut self,
+        info: FirmwareInfo,
+        func: F,
+    ) {
+        if !self.rediscovered(&info) {
+            self.insert_device(info, func);
+        }
+    }
+
+    /// Creates a device entity, and its widget.
+    fn insert_device<F: FnOnce(&mut Self, Entity) -> DeviceWidget>(
+        &mut self,
+        info: FirmwareInfo,
+        func: F,
+    ) {
+        let entity = self.entities.create();
+        let widget = func(self, entity);
+
+        let sender = self.ui_sender.clone();
+        widget.connect_cancel_clicked(move || {
+            let _ = sender.send(Event::Ui(UiEvent::Cancel(entity)));
+        });
+
+        let sender = self.ui_sender.clone();
+        widget.connect_pause_clicked(move || {
+            let _ = sender.send(Event::Ui(UiEvent::Pause(entity)));
+        });
+
+        let sender = self.ui_sender.clone();
+        widget.connect_resume_clicked(move || {
+            let _ = sender.send(Event::Ui(UiEvent::Resume(entity)));
+        });
+
+        let sender = self.ui_sender.clone();
+        widget.connect_retry_clicked(move || {
+            let _ = sender.send(Event::Ui(UiEvent::Retry(entity)));
+        });
+
+        self.components.device_widgets.insert(entity, widget);
+        self.components.info.insert(entity, info);
+        if let Some(seen) = self.refreshing.as_mut() {
+            seen.insert(entity);
+        }
+
+        self.sort_device(entity);
+        self.describe_device(entity);
+
+        self.filter_devices();
+        self.widgets.stack.show();
+        self.widgets.stack.set_visible_child(self.widgets.view_devices.as_ref());
+    }
+
+    /// Describes a device to screen readers, with the version that it may be updated to if its
+    /// update button is shown.
+    fn describe_device(&self, entity: Entity) {
+        let widget = match self.components.device_widgets.get(entity) {
+            Some(widget) => widget,
+            None => return,
+        };
+
+        let current = self.components.info.get(entity).map(|info| &*info.current);
+        let latest = self
+            .components
+            .latest
+            .get(entity)
+            .map(|latest| &**latest)
+            .filter(|&latest| Some(latest) != current && widget.stack.is_visible());
+
+        widget.describe(latest);
+    }
+
+    /// Scans for devices again, remembering the scroll position of the devices view so that it
+    /// may be restored once the scan has completed.
+    ///
+    /// Devices are not rescanned while they are being updated, as their widgets would be lost.
+    pub fn rescan(&mut self) {
+        if self.components.cancellation.keys().any(|entity| self.entities.contains_key(entity)) {
+            info!("not rescanning while devices are being updated");
+            return;
+        }
+
+        self.scroll = Some(self.widgets.view_devices.scroll_position());
+        let _ = self.sender.send(FirmwareEvent::Scan);
+    }
+
+    /// Rescans the devices in the background, as is periodically done while the widget is open.
+    ///
+    /// Unlike a requested rescan, the devices view is not cleared. Only the devices which have
+    /// changed are replaced once they are found again, and those which are gone are removed once
+    /// the scan has completed.
+    pub fn auto_refresh(&mut self) {
+        if self.refreshing.is_some() || self.scroll.is_some() {
+            return;
+        }
+
+        if self.components.cancellation.keys().any(|entity| self.entities.contains_key(entity)) {
+            info!("not refreshing while devices are being updated");
+            return;
+        }
+
+        self.refreshing = Some(HashSet::new());
+        let _ = self.sender.send(FirmwareEvent::Scan);
+    }
+
+    /// Whether a device which was found while refreshing is one whose widget is kept.
+    ///
+    /// Devices are matched by their names, and kept if their versions are unchanged. Otherwise,
+    /// the stale device is removed, so that it will be created again.
+    fn rediscovered(&mut self, info: &FirmwareInfo) -> bool {
+        let seen = match self.refreshing.as_ref() {
+            Some(seen) => seen,
+            None => return false,
+        };
+
+        let found = self.components.info.iter().find(|(entity, found)| {
+            let live = self.entities.contains_key(*entity) && !seen.contains(entity);
+            live && found.name == info.name
+        });
+
+        let (entity, unchanged) = match found {
+            Some((entity, found)) => (entity, !is_changed(found, info)),
+            None => return false,
+        };
+
+        if unchanged {
+            if let Some(seen) = self.refreshing.as_mut() {
+                seen.insert(entity);
+            }
+        } else {
+            self.remove_device(entity);
+        }
+
+        unchanged
+    }
+
+    /// Explains what the scan could not check. When no firmware was found, the empty view explains
+    /// why, and otherwise failures and the absence of the fwupd service are shown in info bars.
+    pub fn scan_result(&mut self, result: &ScanResult) {
+        self.widgets.view_empty.set_result(result);
+        self.widgets.fwupd_bar.set_visible(result.found && result.fwupd_unavailable);
+
+        if result.found && result.failed() {
+            self.widgets.info_bar.set_visible(true);
+            self.widgets.info_bar_label.set_text(&fl!("scan-incomplete"));
+            self.widgets.info_bar_label.set_tooltip_text(Some(&result.errors.join("\n")));
+        }
+    }
+
+    /// An event that occurs when the scan of an automatic refresh has completed, which removes
+    /// the devices that were not found again.
+    pub fn refresh_complete(&mut self) {
+        let seen = match self.refreshing.take() {
+            Some(seen) => seen,
+            None => return,
+        };
+
+        let gone: Vec<Entity> =
+            self.entities.keys().filter(|entity| !seen.contains(entity)).collect();
+
+        for entity in gone {
+            self.remove_device(entity);
+        }
+
+        // Devices which were updated are shown as such only until they are scanned again.
+        for widget in self.components.device_widgets.values() {
+            if widget.stack.is_updated() {
+                widget.stack.hide();
+            }
+        }
+    }
+
+    /// Filters the devices by the search query.
+    pub fn search(&mut self, query: Box<str>) {
+        self.search = query;
+        self.filter_devices();
+    }
+
+    /// Hides or shows the devices which already have their latest firmware.
+    pub fn updatable_only(&mut self, active: bool) {
+        self.updatable_only = active;
+        self.filter_devices();
+    }
+
+    /// Lists the updates that were attempted, which may have changed since the history was last
+    /// shown.
+    pub fn show_history(&self) {
+        match firmware_manager::update_history() {
+            Ok(history) => self.widgets.view_history.set_history(&history),
+            Err(why) => error!("failed to read the update history: {}", why),
+        }
+    }
+
+    /// Shows only the devices which match the search query, by their name, vendor, or version,
+    /// and which have updates if devices without them are hidden.
+    fn filter_devices(&self) {
+        let view = &self.widgets.view_devices;
+        let widgets = self.components.device_widgets.iter();
+        for (entity, widget) in widgets.filter(|(entity, _)| self.entities.contains_key(*entity)) {
+            let name = widget.name.text();
+            let version = widget.label.text();
+            let latest = self.components.latest.get(entity).map_or("", |latest| &**latest);
+            let vendor = self.fwupd_device(entity).map_or("", |device| &*device.vendor);
+
+            let fields = [name.as_str(), version.as_str(), latest, vendor];
+            let updatable = !self.updatable_only || self.is_upgradeable(entity);
+            view.filter(widget, updatable && matches_search(&self.search, &fields));
+        }
+
+        for group in self.groups.values() {
+            view.filter_group(group);
+        }
+
+        view.filter_sections();
+    }
+
+    /// Lists the devices in another order, which is saved to the configuration file.
+    pub fn sort(&mut self, order: DeviceSort) {
+        self.widgets.view_devices.sort(order, self.groups.values());
+
+        if let Err(why) = set_device_sort(order) {
+            error!("failed to save the order of the devices: {}", why);
+            self.widgets.info_bar.set_visible(true);
+            self.widgets.info_bar_label.set_text(&fl!("error-sort"));
+            self.widgets.info_bar_label.set_tooltip_text(Some(&why.to_string()));
+        }
+    }
+
+    /// Assigns what the row of a device is sorted by, which moves it to its place in the order.
+    fn sort_device(&self, entity: Entity) {
+        if let (Some(widget), Some(key)) =
+            (self.components.device_widgets.get(entity), self.sort_key(entity))
+        {
+            self.widgets.view_devices.set_sort_key(&widget.container, key);
+        }
+    }
+
+    /// What a device is sorted by.
+    fn sort_key(&self, entity: Entity) -> Option<SortKey> {
+        let info = self.components.info.get(entity)?;
+        let vendor = self.fwupd_device(entity).map(|device| device.vendor.clone());
+        Some(SortKey {
+            name: info.name.clone(),
+            vendor: vendor.unwrap_or_default(),
+            upgradeable: self.is_upgradeable(entity),
+            critical: self.urgency(entity) == ReleaseUrgency::Critical,
+        })
+    }
+
+    /// How urgently a fwupd device should be updated.
+    fn urgency(&self, entity: Entity) -> ReleaseUrgency {
+        let components = &self.components;
+        match (components.info.get(entity), components.fwupd.get(entity)) {
+            (Some(info), Some((_, releases))) => {
+                let issues = components.issues.get(entity).map_or(&[][..], Vec::as_slice);
+                update_urgency(&info.current, releases, issues)
+            }
+            _ => ReleaseUrgency::Unknown,
+        }
+    }
+
+    /// Whether the latest firmware of a device has not been installed.
+    fn is_upgradeable(&self, entity: Entity) -> bool {
+        match (self.components.info.get(entity), self.components.latest.get(entity)) {
+            (Some(info), Some(latest)) => *latest != info.current,
+            _ => false,
+        }
+    }
+
+    /// An event that occurs when a device from another backend is found.
+    pub fn device(&mut self, signal: DeviceSignal) {
+        self.create_device(signal.info.clone(), move |state, entity| {
+            let DeviceSignal { device, info, read_only } = signal;
+            let widget = state.widgets.view_devices.device(&info, device.category());
+            widget.stack.hide();
+
+            // System firmware of other backends is installed on reboot, as that of fwupd.
+            if device.category() == DeviceCategory::System {
+                state.entities.associate_system(entity);
+            }
+
+            if read_only {
+                let label = match info.latest.as_deref() {
+                    Some(latest) if latest != &*info.current => {
+                        fl!("read-only-update", version = &*info.current, latest = latest)
+                    }
+                    _ => fl!("read-only-version", version = &*info.current),
+                };
+
+                widget.label.set_text(&label);
+            } else if let Some(latest) = info.latest {
+                if latest != info.current {
+                    widget.stack.show();
+                    let sender = state.ui_sender.clone();
+                    widget.connect_upgrade_clicked(move || {
+                        let _ = sender.send(Event::Ui(UiEvent::Update(entity)));
+                    });
+                }
+
+                state.components.latest.insert(entity, latest);
+            }
+
+            state.components.devices.insert(entity, device);
+
+            let sender = state.ui_sender.clone();
+            widget.connect_clicked(move |_| {
+                let _ = sender.send(Event::Ui(UiEvent::Reveal(entity)));
+            });
+
+            widget
+        });
+    }
+
+    /// Cancels the download of an update. The update button is restored once the backend has
+    /// stopped the download.
+    pub fn cancel(&mut self, entity: Entity) {
+        if let Some(cancellable) = self.components.cancellation.get(entity) {
+            cancellable.cancel();
+        }
+
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            widget.stack.cancel.set_sensitive(false);
+        }
+    }
+
+    /// Pauses the download of an update, keeping what was downloaded so that it may be resumed.
+    pub fn pause(&mut self, entity: Entity) {
+        if let Some(cancellable) = self.components.cancellation.get(entity) {
+            cancellable.pause();
+        }
+
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            widget.stack.pause.set_sensitive(false);
+            widget.stack.cancel.set_sensitive(false);
+        }
+    }
+
+    /// Requests the update of a paused download again, which resumes from the bytes that were
+    /// kept.
+    pub fn resume(&mut self, entity: Entity) {
+        let event = match self.components.paused.remove(entity) {
+            Some(event) => event,
+            None => return,
+        };
+
+        if let Some(cancellable) = event.cancellable() {
+            cancellable.resume();
+            self.components.cancellation.insert(entity, cancellable.clone());
+        }
+
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            widget.stack.switch_to_waiting();
+        }
+
+        let _ = self.sender.send(event);
+    }
+
+    /// An event that occurs when an update failed, which keeps its request to retry it.
+    ///
+    /// A downgrade which failed did not install the version that it recorded as the latest.
+    pub fn device_failed(&mut self, entity: Entity, event: FirmwareEvent) {
+        if let FirmwareEvent::Downgrade(..) = event {
+            self.components.latest.remove(entity);
+        }
+
+        self.components.failed.insert(entity, event);
+    }
+
+    /// Retries an update which failed, by sending its request again. Updates whose requests were
+    /// not returned are started again, as though their update button was clicked.
+    pub fn retry(&mut self, entity: Entity) {
+        let event = match self.components.failed.remove(entity) {
+            Some(event) => event,
+            None => {
+                self.update(entity);
+                return;
+            }
+        };
+
+        if let Some(cancellable) = event.cancellable() {
+            cancellable.resume();
+            self.components.cancellation.insert(entity, cancellable.clone());
+        }
+
+        if let FirmwareEvent::Downgrade(_, _, version, _) = &event {
+            self.components.latest.insert(entity, version.clone());
+        }
+
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            widget.stack.switch_to_waiting();
+        }
+
+        let _ = self.sender.send(event);
+    }
+
+    /// An event that occurs when an update was cancelled, which restores the update button.
+    pub fn device_cancelled(&mut self, entity: Entity) {
+        self.components.cancellation.remove(entity);
+        self.components.firmware_download.remove(entity);
+
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            self.progress_deactivate(&widget.stack.progress);
+            widget.stack.set_visible_child(&widget.stack.button);
+        }
+    }
+
+    /// An event that occurs when the download of an update was paused, which shows how much was
+    /// downloaded, and keeps the request to send again when it is resumed.
+    pub fn device_paused(&mut self, entity: Entity, event: FirmwareEvent) {
+        self.components.cancellation.remove(entity);
+        let download = self.components.firmware_download.remove(entity);
+        self.components.paused.insert(entity, event);
+
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            self.progress_deactivate(&widget.stack.progress);
+            widget.stack.switch_to_paused(download.as_ref());
+        }
+    }
+
+    /// An event that occurs when an update was deferred due to the state of the network.
+    ///
+    /// If the connection is metered, the user may choose to download the firmware regardless,
+    /// in which case the deferred request is sent again, to be downloaded this once.
+    pub fn update_deferred(&mut self, entity: Entity, reason: DeferReason, event: FirmwareEvent) {
+        let message = match reason {
+            DeferReason::Offline => fl!("deferred-offline"),
+            DeferReason::Metered => fl!("deferred-metered"),
+            DeferReason::MeteredAsk => {
+                if confirm_metered_download() {
+                    if let Some(widget) = self.components.device_widgets.get(entity) {
+                        widget.stack.switch_to_waiting();
+                    }
+
+                    let _ = self.sender.send(FirmwareEvent::DownloadAnyway(Box::new(event)));
+                    return;
+                }
+
+                fl!("deferred-metered")
+            }
+        };
+
+        self.device_cancelled(entity);
+        self.widgets.info_bar.set_visible(true);
+        self.widgets.info_bar_label.set_text(&message);
+        self.widgets.info_bar_label.set_tooltip_text(None);
+    }
+
+    /// Hides a device from now on, by adding it to the ignored devices of the configuration.
+    pub fn ignore_device(&mut self, entity: Entity) {
+        let device: Box<str> = if let Some((device, _)) = self.components.fwupd.get(entity) {
+            device.device_id.clone()
+        } else if let Some(device) = self.components.devices.get(entity) {
+            device.id.clone()
+        } else if let Some(widget) = self.components.device_widgets.get(entity) {
+            widget.name.text().as_str().into()
+        } else {
+            return;
+        };
+
+        if let Err(why) = set_device_ignored(&device, true) {
+            error!("failed to ignore {}: {}", device, why);
+            self.widgets.info_bar.set_visible(true);
+            self.widgets.info_bar_label.set_text(&fl!("error-ignore"));
+            self.widgets.info_bar_label.set_tooltip_text(Some(&why.to_string()));
+            return;
+        }
+
+        self.remove_device(entity);
+    }
+
+    /// Switches the firmware of a fwupd device to another branch, once the user has confirmed
+    /// the risks of doing so.
+    pub fn switch_branch(&mut self, entity: Entity, branch: Box<str>) {
+        let (device, _) = match self.components.fwupd.get(entity) {
+            Some(fwupd) => fwupd,
+            None => return,
+        };
+
+        // Moving to open firmware is guided by an assistant, which confirms it asynchronously.
+        if &*branch == OPEN_FIRMWARE_BRANCH && device.needs_reboot() {
+            let branches = self.components.branches.get(entity).map_or(&[][..], Vec::as_slice);
+            let assistant = OpenFirmwareAssistant {
+                entity,
+                eligibility: open_firmware_transition(device, branches),
+                power: &self.power,
+                sender: &self.ui_sender,
+                warnings: &branch_warnings(device),
+            };
+
+            assistant.run();
+            return;
+        }
+
+        if confirm_branch_switch(&branch, &branch_warnings(device)) {
+            self.branch_confirmed(entity, branch);
+        }
+    }
+
+    /// Switches the firmware of a fwupd device to another branch, which the user has confirmed.
+    pub fn branch_confirmed(&mut self, entity: Entity, branch: Box<str>) {
+        let (device, _) = match self.components.fwupd.get(entity) {
+            Some(fwupd) => fwupd,
+            None => return,
+        };
+
+        let cancellable = CancellationToken::default();
+        self.components.cancellation.insert(entity, cancellable.clone());
+
+        // The version which will be installed is not known until fwupd reports it.
+        self.components.latest.remove(entity);
+
+        let widget = &self.components.device_widgets[entity];
+        widget.stack.show();
+        widget.stack.switch_to_waiting();
+
+        let device = Arc::new(device.clone());
+        let _ = self.sender.send(FirmwareEvent::SwitchBranch(entity, device, branch, cancellable));
+    }
+
+    /// An event that occurs when the outcome of an update may be reported to the LVFS.
+    ///
+    /// The user is only asked once, as their answer is remembered by the core.
+    pub fn report_available(&mut self, report: Box<UpdateReport>) {
+        let upload = report_consent().unwrap_or_else(|| confirm_upload_report(report.device()));
+        let _ = self.sender.send(FirmwareEvent::ReportConsent(report, upload));
+    }
+
+    /// An event that occurs when firmware was rejected because its signature is not trusted.
+    pub fn verification_failed(&mut self, entity: Entity, why: SignatureError) {
+        // Convert the error and its causes into a string.
+        let mut details = format!("{}", why);
+        let mut cause = why.source();
+        while let Some(error) = cause {
+            details.push_str(format!(": {}", error).as_str());
+            cause = error.source();
+        }
+
+        error!("firmware verification failed: {}", details);
+
+        self.device_cancelled(entity);
+        self.widgets.info_bar.set_visible(true);
+        self.widgets.info_bar_label.set_text(&fl!("verification-failed-title"));
+        self.widgets.info_bar_label.set_tooltip_text(Some(&details));
+
+        warn_verification_failed(&details);
+    }
+
+    /// An event that occurs when firmware has successfully updated.
+    ///
+    /// If the installed version is not known, such as after switching branches, the label is
+    /// left to be updated when fwupd reports that the device changed.
+    pub fn device_updated(&mut self, entity: Entity, latest: Option<Box<str>>) {
+        self.components.cancellation.remove(entity);
+
+        // The device is sorted and filtered by the version that it now has.
+        if let (Some(info), Some(latest)) = (self.components.info.get_mut(entity), &latest) {
+            info.current = latest.clone();
+        }
+
+        self.refresh_groups();
+        self.sort_device(entity);
+        self.filter_devices();
+
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            widget.set_critical(false);
+            widget.stack.progress.set_fraction(1.0);
+            if let Some(latest) = latest.as_deref() {
+                widget.label.set_text(latest);
+            }
+
+            let device = widget.name.text();
+            let message = match latest {
+                Some(latest) => fl!("toast-updated", device = device.as_str(), version = &*latest),
+                None => fl!("toast-updated-unknown", device = device.as_str()),
+            };
+
+            self.widgets.toast.show_message(&message);
+
+            let title = fl!("notification-updated-title");
+            crate::notification::update_finished(&widget.container, &device, &title, &message);
+
+            self.progress_deactivate(&widget.stack.progress);
+            widget.stack.switch_to_updated();
+            widget.describe(None);
+            if self.entities.is_system(entity) {
+                crate::reboot();
+            }
+        }
+    }
+
+    /// An event that occurs when system firmware was staged to be installed on the next reboot,
+    /// which the user is left to choose the time of.
+    pub fn device_staged(&mut self, entity: Entity, latest: Option<Box<str>>) {
+        self.components.cancellation.remove(entity);
+        self.refresh_groups();
+        self.sort_device(entity);
+        self.filter_devices();
+
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            if let Some(latest) = latest {
+                widget.label.set_text(&fl!("staged-version", version = &*latest));
+            }
+
+            self.progress_deactivate(&widget.stack.progress);
+            widget.stack.hide();
+            widget.describe(None);
+        }
+
+        self.widgets.info_bar.set_visible(true);
+        self.widgets.info_bar_label.set_text(&fl!("staged-reboot"));
+        self.widgets.info_bar_label.set_tooltip_text(None);
+    }
+
+    /// An event that occurs when updates were interrupted by a crash or a loss of power.
+    ///
+    /// Closing the info bar rescans the devices, to verify the firmware that they have installed,
+    /// after which the user may retry any update that is still available.
+    pub fn interrupted(&mut self, updates: Vec<InterruptedUpdate>) {
+        let mut message = String::new();
+        for update in &updates {
+            message.push_str(&fl!(
+                "interrupted-update",
+                device = &*update.device,
+                from = &*update.from,
+                to = &*update.to
+            ));
+            message.push('\n');
+        }
+
+        message.push_str(&fl!("interrupted-retry"));
+
+        self.widgets.info_bar.set_visible(true);
+        self.widgets.info_bar_label.set_text(&message);
+        self.widgets.info_bar_label.set_tooltip_text(None);
+
+        // The user has been told, so the updates are not reported again on the next launch.
+        let _ = self.sender.send(FirmwareEvent::ClearInterrupted);
+    }
+
+    /// An event that occurs when new firmware was installed on a device which must be unplugged
+    /// and plugged in again for it to take effect, such as a Thunderbolt dock.
+    ///
+    /// The label is updated with the installed version once fwupd reports the reconnected device.
+    pub fn device_replug(&mut self, entity: Entity, latest: Option<Box<str>>) {
+        self.components.cancellation.remove(entity);
+
+        let widget = match self.components.device_widgets.get(entity) {
+            Some(widget) => widget,
+            None => return,
+        };
+
+        if let Some(latest) = latest {
+            widget.label.set_text(&fl!("replug-version", version = &*latest));
+        }
+
+        self.progress_deactivate(&widget.stack.progress);
+        widget.stack.hide();
+        widget.describe(None);
+
+        instruct_replug(widget.name.text().as_str());
+    }
+
+    /// An event that occurs when fwupd firmware is found.
+    pub fn fwupd(&mut self, signal: FwupdSignal) {
+        let info = signal.info.clone();
+        match self.fwupd_entity(&signal.device.device_id) {
+            // Devices added while scanning may be reported by both the scan and the daemon.
+            Some(entity) if !self.is_outdated(entity, &info) => return self.fwupd_changed(signal),
+            // Devices which changed since they were found are replaced while refreshing.
+            Some(entity) => {
+                self.remove_device(entity);
+                self.insert_device(info, move |state, entity| state.fwupd_widget(entity, signal));
+            }
+            None => {
+                self.create_device(info, move |state, entity| state.fwupd_widget(entity, signal))
+            }
+        }
+
+        self.refresh_groups();
+    }
+
+    /// Whether a fwupd device has changed since it was found, while devices are being refreshed.
+    fn is_outdated(&self, entity: Entity, info: &FirmwareInfo) -> bool {
+        self.refreshing.is_some()
+            && self.components.info.get(entity).map_or(true, |found| is_changed(found, info))
+    }
+
+    /// Creates the widget of a fwupd device.
+    fn fwupd_widget(&mut self, entity: Entity, signal: FwupdSignal) -> DeviceWidget {
+        let FwupdSignal {
+            info,
+            device,
+            upgradeable,
+            releases,
+            channels,
+            issues,
+            branches,
+            replug_pending,
+            attestation,
+            ..
+        } = signal;
+        let widget = if device.needs_reboot() {
+            self.entities.associate_system(entity);
+            self.widgets.view_devices.system(&info)
+        } else {
+            self.peripheral(&device, &info)
+        };
+
+        widget.stack.hide();
+        widget.set_icon(&device.icon.iter().map(AsRef::as_ref).collect::<Vec<_>>());
+
+        if let Some(attestation) = attestation {
+            self.components.attestations.insert(entity, attestation);
+        }
+
+        if replug_pending {
+            widget.label.set_text(&fl!("replug-version", version = &*info.current));
+        }
+
+        if let Some(latest) = info.latest {
+            let urgency = update_urgency(&info.current, &releases, &issues);
+            widget.set_critical(urgency == ReleaseUrgency::Critical);
+            self.components.latest.insert(entity, latest);
+            self.components.release_channels.insert(entity, channels);
+            self.components.issues.insert(entity, issues);
+            self.components.branches.insert(entity, branches);
+            if upgradeable {
+                let sender = self.ui_sender.clone();
+                widget.stack.show();
+                widget.connect_upgrade_clicked(move || {
+                    let _ = sender.send(Event::Ui(UiEvent::Update(entity)));
+                });
+            }
+        }
+
+        // Every fwupd device is kept, as its details are shown when it is revealed.
+        self.components.fwupd.insert(entity, (device, releases));
+
+        let sender = self.ui_sender.clone();
+        widget.connect_clicked(move |_| {
+            let _ = sender.send(Event::Ui(UiEvent::Reveal(entity)));
+        });
+
+        widget
+    }
+
+    /// An event that occurs when the details of a fwupd device have changed.
+    pub fn fwupd_changed(&mut self, signal: FwupdSignal) {
+        let entity = match self.fwupd_entity(&signal.device.device_id) {
+            Some(entity) => entity,
+            None => return self.fwupd(signal),
+        };
+
+        if let Some(seen) = self.refreshing.as_mut() {
+            seen.insert(entity);
+        }
+
+        self.components.info.insert(entity, signal.info.clone());
+
+        let FwupdSignal {
+            info,
+            device,
+            releases,
+            channels,
+            issues,
+            branches,
+            replug_pending,
+            attestation,
+            ..
+        } = signal;
+
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            if replug_pending {
+                widget.label.set_text(&fl!("replug-version", version = &*info.current));
+            } else {
+                widget.label.set_text(info.current.as_ref());
+            }
+        }
+
+        if let Some(latest) = info.latest {
+            self.components.latest.insert(entity, latest);
+        }
+
+        self.components.fwupd.insert(entity, (device, releases));
+        self.components.release_channels.insert(entity, channels);
+        self.components.issues.insert(entity, issues);
+        self.components.branches.insert(entity, branches);
+        self.sort_device(entity);
+
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            widget.set_critical(self.urgency(entity) == ReleaseUrgency::Critical);
+        }
+
+        self.describe_device(entity);
+        self.filter_devices();
+
+        match attestation {
+            Some(attestation) => self.components.attestations.insert(entity, attestation),
+            None => self.components.attestations.remove(entity),
+        };
+
+        self.refresh_groups();
+    }
+
+    /// An event that occurs when a fwupd device has been removed.
+    pub fn fwupd_removed(&mut self, device_id: &str) {
+        if let Some(entity) = self.fwupd_entity(device_id) {
+            info!("fwupd device removed: {}", device_id);
+            self.remove_device(entity);
+        }
+
+        self.topology.remove(device_id);
+    }
+
+    /// Creates the widget of a fwupd device in the device section, grouping the devices of
+    /// composite devices, such as docks, beneath their root.
+    fn peripheral(&mut self, device: &FwupdDevice, info: &FirmwareInfo) -> DeviceWidget {
+        let device_id = &*device.device_id;
+        self.topology.insert(device_id, device.parent_device_id.as_deref());
+
+        let root: Box<str> = self.topology.root(device_id).into();
+        if &*root == device_id && !self.groups.contains_key(&root) {
+            return self.widgets.view_devices.device(info, device_category(device));
+        }
+
+        let category = device_category(device);
+
+        if !self.groups.contains_key(&root) {
+            // The group is listed in the section of its root, once the root has been found.
+            let root_category = self
+                .fwupd_entity(&root)
+                .and_then(|root| self.fwupd_device(root))
+                .map_or(category, device_category);
+            let group = self.widgets.view_devices.group(root_category);
+
+            // The root may have been found before its children, outside of the group.
+            if let Some(entity) = self.fwupd_entity(&root) {
+                if let Some(widget) = self.components.device_widgets.get(entity) {
+                    self.widgets.view_devices.move_to_group(widget, &group);
+                    group.set_title(&widget.name.text());
+                }
+            }
+
+            let sender = self.ui_sender.clone();
+            let id = root.clone();
+            group.update.connect_clicked(move |_| {
+                let _ = sender.send(Event::Ui(UiEvent::UpdateGroup(id.clone())));
+            });
+
+            self.groups.insert(root.clone(), group);
+        }
+
+        let group = &self.groups[&root];
+        if &*root == device_id {
+            group.set_title(&info.name);
+            self.widgets.view_devices.move_group(group, category);
+        }
+
+        self.widgets.view_devices.grouped_device(group, info, category)
+    }
+
+    /// Shows the update button of each group which has a device with an update, and sorts the
+    /// group by its root device.
+    fn refresh_groups(&self) {
+        for (root, group) in &self.groups {
+            let upgradeable = !self.group_updates(root).is_empty();
+            group.update.set_visible(upgradeable);
+
+            let root = self.fwupd_entity(root).and_then(|root| self.fwupd_device(root));
+            let key = SortKey {
+                name: group.title().into(),
+                vendor: root.map(|device| device.vendor.clone()).unwrap_or_default(),
+                upgradeable,
+            };
+
+            self.widgets.view_devices.set_sort_key(&group.expander, key);
+        }
+    }
+
+    /// The devices of a composite device which have updates, in the order that they should be
+    /// updated, which is children before their parents.
+    fn group_updates(&self, root: &str) -> Vec<(Entity, &FwupdDevice, &FwupdRelease)> {
+        let mut updates = self
+            .components
+            .fwupd
+            .iter()
+            .filter(|(entity, (device, _))| {
+                self.entities.contains_key(*entity)
+                    && !self.components.cancellation.contains_key(*entity)
+                    && self.topology.root(&device.device_id) == root
+                    && self
+                        .components
+                        .latest
+                        .get(*entity)
+                        .map_or(false, |latest| *latest != device.version)
+            })
+            .filter_map(|(entity, (device, releases))| Some((entity, device, releases.last()?)))
+            .collect::<Vec<_>>();
+
+        self.topology.sort_for_update(&mut updates, |(_, device, _)| &device.device_id);
+        updates
+    }
+
+    /// Queues the updates of every device of a composite device, children before their parents.
+    pub fn update_group(&mut self, root: &str) {
+        if !self.power_connected() {
+            return;
+        }
+
+        let updates = self
+            .group_updates(root)
+            .into_iter()
+            .map(|(entity, device, release)| {
+                (entity, Arc::new(device.clone()), Arc::new(release.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        let mut events = Vec::with_capacity(updates.len());
+        for (entity, device, release) in updates {
+            let cancellable = CancellationToken::default();
+            self.components.cancellation.insert(entity, cancellable.clone());
+            self.components.device_widgets[entity].stack.switch_to_waiting();
+            events.push(FirmwareEvent::Fwupd(entity, device, release, cancellable));
+        }
+
+        if let Some(group) = self.groups.get(root) {
+            group.update.hide();
+        }
+
+        let _ = self.sender.send(FirmwareEvent::Enqueue(events));
+    }
+
+    /// Queues the update of every device which may be updated without a reboot, disabling the
+    /// update buttons of the other devices until the queued updates have finished.
+    ///
+    /// System firmware is left to be updated on its own, as installing it reboots the system.
+    pub fn update_all(&mut self) {
+        if self.batch.is_some() || !self.power_connected() {
+            return;
+        }
+
+        let mut entities = Vec::new();
+        let mut batch = UpdateBatch::new(false);
+        for entity in self.updatable() {
+            let cancellable = match self.update_request(entity) {
+                Some(request) => batch.push(entity, &request),
+                None => continue,
+            };
+
+            if let Some(cancellable) = cancellable {
+                self.components.cancellation.insert(entity, cancellable);
+                self.components.device_widgets[entity].stack.switch_to_waiting();
+                entities.push(entity);
+            }
+        }
+
+        if batch.is_empty() {
+            return;
+        }
 
-        Self {
-            entities: Entities::default(),
-            components: Components::default(),
-            has_battery,
-            progress_sender,
-            sender,
-            widgets: Widgets { info_bar, info_bar_label, stack, view_devices, view_empty },
-            ui_sender,
+        let disabled = self
+            .components
+            .device_widgets
+            .iter()
+            .filter(|(entity, widget)| {
+                self.entities.contains_key(*entity) && widget.stack.button.is_sensitive()
+            })
+            .map(|(_, widget)| widget.stack.button.clone())
+            .collect::<Vec<_>>();
+
+        for button in &disabled {
+            button.set_sensitive(false);
         }
-    }
 
-    /// The base method for creating a new firmware device entity.
-    pub fn create_device<F: FnOnce(&mut Self, Entity) -> DeviceWidget>(&mut self, func: F) {
-        let entity = self.entities.create();
-        let widget = func(self, entity);
-        self.components.device_widgets.insert(entity, widget);
-        self.widgets.stack.show();
-        self.widgets.stack.set_visible_child(self.widgets.view_devices.as_ref());
+        self.batch = Some(Batch { entities, disabled });
+        self.refresh_groups();
+        self.refresh_update_all();
+        let _ = self.sender.send(FirmwareEvent::UpdateAll(batch));
     }
 
-    /// An event that occurs when firmware has successfully updated.
-    pub fn device_updated(&mut self, entity: Entity, latest: Box<str>) {
-        if let Some(widget) = self.components.device_widgets.get(entity) {
-            widget.stack.progress.set_fraction(1.0);
-            widget.label.set_text(latest.as_ref());
+    /// Shows the progress of the updates queued by Update All, and restores the update buttons
+    /// once they have all finished. Otherwise, the Update All button is shown if there are
+    /// devices for it to update.
+    pub fn refresh_update_all(&mut self) {
+        let view = &self.widgets.view_devices;
 
-            self.progress_deactivate(&widget.stack.progress);
-            if self.entities.is_system(entity) {
-                crate::reboot();
+        if let Some(batch) = &self.batch {
+            let done = batch
+                .entities
+                .iter()
+                .filter(|&&entity| {
+                    !self.entities.contains_key(entity)
+                        || !self.components.cancellation.contains_key(entity)
+                })
+                .count();
+
+            if done < batch.entities.len() {
+                view.update_all_available(false);
+                view.update_all_progress(Some((done, batch.entities.len())));
+                return;
             }
 
-            // Wait 1 second before changing the visibility of the stack.
-            let sender = self.ui_sender.clone();
-            glib::timeout_add_seconds_local(1, move || {
-                let _ = sender.send(Event::Ui(UiEvent::HideStack(entity)));
+            for button in &batch.disabled {
+                button.set_sensitive(true);
+            }
 
-                glib::Continue(false)
-            });
+            self.batch = None;
         }
+
+        view.update_all_progress(None);
+        view.update_all_available(!self.updatable().is_empty());
     }
 
-    /// An event that occurs when fwupd firmware is found.
-    pub fn fwupd(&mut self, signal: FwupdSignal) {
-        self.create_device(move |state, entity| {
-            let FwupdSignal { info, device, upgradeable, releases } = signal;
-            let widget = if device.needs_reboot() {
-                state.entities.associate_system(entity);
-                state.widgets.view_devices.system(&info)
-            } else {
-                state.widgets.view_devices.device(&info)
-            };
+    /// The devices whose updates are offered, and which may be updated without a reboot.
+    fn updatable(&self) -> Vec<Entity> {
+        self.components
+            .device_widgets
+            .iter()
+            .filter(|(entity, widget)| {
+                let stack = &widget.stack;
+                let button: &gtk::Widget = stack.button.upcast_ref();
+                self.entities.contains_key(*entity)
+                    && !self.entities.is_system(*entity)
+                    && !self.components.cancellation.contains_key(*entity)
+                    && stack.is_visible()
+                    && stack.visible_child().as_ref() == Some(button)
+                    && stack.button.is_sensitive()
+                    && self
+                        .components
+                        .devices
+                        .get(*entity)
+                        .map_or(true, |device| !CapsuleBackend::manages(device))
+            })
+            .map(|(entity, _)| entity)
+            .collect()
+    }
 
-            widget.stack.hide();
+    /// The update which was found for the device, if it has any.
+    fn update_request(&self, entity: Entity) -> Option<UpdateRequest> {
+        if let Some((device, releases)) = self.components.fwupd.get(entity) {
+            let (device, release) = (Arc::new(device.clone()), Arc::new(releases.last()?.clone()));
+            Some(UpdateRequest::Fwupd(device, release))
+        } else if let Some((board, digest)) = self.components.thelio.get(entity) {
+            Some(UpdateRequest::ThelioIo(board.clone(), digest.clone()))
+        } else {
+            let device = self.components.devices.get(entity)?;
+            Some(UpdateRequest::Device(device.clone()))
+        }
+    }
 
-            if let Some(latest) = info.latest {
-                state.components.latest.insert(entity, latest);
-                state.components.fwupd.insert(entity, (device, releases));
-                if upgradeable {
-                    let sender = state.ui_sender.clone();
-                    widget.stack.show();
-                    widget.connect_upgrade_clicked(move || {
-                        let _ = sender.send(Event::Ui(UiEvent::Update(entity)));
-                    });
-                }
-            }
+    /// Finds the entity that is associated with a fwupd device ID.
+    fn fwupd_entity(&self, device_id: &str) -> Option<Entity> {
+        self.components
+            .fwupd
+            .iter()
+            .find(|(entity, (device, _))| {
+                self.entities.contains_key(*entity) && &*device.device_id == device_id
+            })
+            .map(|(entity, _)| entity)
+    }
 
-            let sender = state.ui_sender.clone();
-            widget.connect_clicked(move |_| {
-                let _ = sender.send(Event::Ui(UiEvent::Reveal(entity)));
-            });
+    /// The fwupd device of an entity, as it was last described.
+    fn fwupd_device(&self, entity: Entity) -> Option<&FwupdDevice> {
+        self.components.fwupd.get(entity).map(|(device, _)| device)
+    }
 
-            widget
+    /// Finds the entity of the Thelio I/O board with the given ID, if it has an update.
+    pub fn thelio_entity(&self, board: &str) -> Option<Entity> {
+        self.components
+            .thelio
+            .iter()
+            .find(|(entity, (id, _))| self.entities.contains_key(*entity) && &**id == board)
+            .map(|(entity, _)| entity)
+    }
+
+    /// Removes a device entity, along with its widget.
+    pub fn remove_device(&mut self, entity: Entity) {
+        if let Some(widget) = self.components.device_widgets.remove(entity) {
+            self.progress_deactivate(&widget.stack.progress);
+            self.widgets.view_devices.remove(&widget);
+        }
+
+        self.components.devices.remove(entity);
+        self.components.fwupd.remove(entity);
+        self.components.info.remove(entity);
+        self.components.latest.remove(entity);
+        self.components.release_channels.remove(entity);
+        self.components.issues.remove(entity);
+        self.components.branches.remove(entity);
+        self.components.attestations.remove(entity);
+        self.components.paused.remove(entity);
+        self.components.failed.remove(entity);
+        self.entities.remove(entity);
+
+        // Groups are removed along with the last of their devices.
+        let view = &self.widgets.view_devices;
+        self.groups.retain(|_, group| {
+            let empty = group.devices.children().is_empty();
+            if empty {
+                view.remove_group(group);
+            }
+
+            !empty
         });
+
+        if self.entities.entities.is_empty() {
+            self.widgets.stack.set_visible_child(self.widgets.view_empty.as_ref());
+        }
     }
 
     /// Activates progress bar handling for the given widget.
@@ -164,37 +1329,183 @@ impl State {
     }
 
     /// Reveals a widget's changelog in a revealer, and generate that changelog if it has not been
-    /// revealed yet.
+    /// revealed yet. The changelog is hidden if it was already revealed.
     pub fn reveal(&mut self, entity: Entity) {
+        let revealer = match self.components.device_widgets.get(entity) {
+            Some(widget) => widget.revealer.clone(),
+            None => return,
+        };
+
+        let reveal = !revealer.reveals_child();
+        if reveal {
+            self.generate_changelog(entity);
+        }
+
+        let _ = self.ui_sender.send(Event::Ui(UiEvent::Revealed(entity, reveal)));
+        revealer.set_reveal_child(reveal);
+    }
+
+    /// Reveals the changelog of every device which is shown, or hides every changelog.
+    ///
+    /// Changelogs are generated one at a time whenever the main loop is idle, so that generating
+    /// many of them at once does not freeze the window.
+    pub fn reveal_all(&mut self, reveal: bool) {
+        let widgets = self.components.device_widgets.iter();
+        if !reveal {
+            widgets.for_each(|(_, widget)| widget.revealer.set_reveal_child(false));
+            return;
+        }
+
+        let mut pending = widgets
+            .filter(|(_, widget)| widget.container.parent().map_or(false, |row| row.is_visible()))
+            .filter(|(_, widget)| !widget.revealer.reveals_child())
+            .map(|(entity, _)| entity)
+            .collect::<VecDeque<_>>();
+
+        let sender = self.ui_sender.clone();
+        glib::idle_add_local(move || match pending.pop_front() {
+            Some(entity) => {
+                let _ = sender.send(Event::Ui(UiEvent::Expand(entity)));
+                glib::Continue(!pending.is_empty())
+            }
+            None => glib::Continue(false),
+        });
+    }
+
+    /// Reveals a widget's changelog, without hiding the changelog which was last revealed.
+    pub fn expand(&mut self, entity: Entity) {
+        if let Some(widget) = self.components.device_widgets.get(entity) {
+            let revealer = widget.revealer.clone();
+            self.generate_changelog(entity);
+            revealer.set_reveal_child(true);
+        }
+    }
+
+    /// Generates a widget's changelog if it has not been revealed yet.
+    fn generate_changelog(&self, entity: Entity) {
         let widget = &self.components.device_widgets[entity];
         let revealer = &widget.revealer;
         let sender = &self.ui_sender;
+        let details = self.fwupd_device(entity);
+
+        if let Some((_, releases)) =
+            self.components.fwupd.get(entity).filter(|(_, releases)| !releases.is_empty())
+        {
+            let current = self.components.info.get(entity).map_or("", |info| &*info.current);
+            let channels = self.components.release_channels.get(entity);
+            let issues = self.components.issues.get(entity);
+            let branches = self.components.branches.get(entity);
+            let attestation = self.components.attestations.get(entity);
+            generate(revealer, sender, entity, details, move || {
+                let channel = |index: usize| {
+                    channels.and_then(|channels| channels.get(index).copied()).unwrap_or_default()
+                };
 
-        if let Some((_, releases)) = self.components.fwupd.get(entity) {
-            reveal(revealer, sender, entity, move || {
-                let releases = &releases;
-                let log_entries = releases
+                // The releases which the update includes are listed apart from the earlier ones.
+                let (pending, earlier): (Vec<_>, Vec<_>) = releases
                     .iter()
+                    .enumerate()
                     .rev()
                     // TODO: Add release date
-                    .map(|release| (release.version.as_ref(), "", release.description.as_ref()));
+                    .map(|(index, release)| {
+                        let version = channel_label(&release.version, channel(index));
+                        let description = Cow::Borrowed(release.description.as_ref());
+                        let issues = issues.and_then(|issues| issues.get(index));
+                        let entry = (version, Cow::Borrowed(""), description, issues);
+                        (is_newer(current, &release.version), entry)
+                    })
+                    .partition(|(newer, _)| *newer);
 
-                crate::changelog::generate_widget(log_entries).upcast::<gtk::Container>()
+                let log_entries =
+                    |releases: Vec<(bool, _)>| releases.into_iter().map(|(_, entry)| entry);
+
+                let versions = || releases.iter().rev().map(|release| &*release.version);
+
+                // Any newer release may be installed, when there is more than one.
+                let picker = (pending.len() > 1).then(|| {
+                    let label = fl!("changelog-install-version");
+                    let button = gtk::Button::with_label(&fl!("button-install-version"));
+                    let versions = versions().take(pending.len());
+                    version_picker(sender, &label, button, versions, move |version| {
+                        UiEvent::UpdateTo(entity, version)
+                    })
+                });
+
+                // Older releases may be reinstalled, after the user is warned of the risks.
+                let older: Vec<&str> =
+                    versions().filter(|&version| is_newer(version, current)).collect();
+                let downgrade = (!older.is_empty()).then(|| {
+                    let label = fl!("changelog-downgrade");
+                    let button = cascade! {
+                        gtk::Button::with_label(&fl!("button-downgrade-ellipsis"));
+                        ..style_context().add_class(&gtk::STYLE_CLASS_DESTRUCTIVE_ACTION);
+                    };
+
+                    version_picker(sender, &label, button, older.into_iter(), move |version| {
+                        UiEvent::Downgrade(entity, version)
+                    })
+                });
+
+                let changelog = if pending.is_empty() {
+                    crate::changelog::generate_release_widget(log_entries(earlier))
+                } else {
+                    let all_issues = issues.map(Vec::as_slice).unwrap_or_default();
+                    let summary = pending_releases(current, releases, all_issues);
+
+                    let changelog = cascade! {
+                        gtk::Box::new(gtk::Orientation::Vertical, 12);
+                        ..add(&pending_label(summary, current));
+                        ..add(&crate::changelog::generate_release_widget(log_entries(pending)));
+                    };
+
+                    if !earlier.is_empty() {
+                        changelog.add(&gtk::Separator::new(gtk::Orientation::Horizontal));
+                        changelog.add(&earlier_label());
+                        let earlier = log_entries(earlier);
+                        changelog.add(&crate::changelog::generate_release_widget(earlier));
+                    }
+
+                    changelog
+                };
+
+                if let Some(attestation) = attestation {
+                    let label = attestation_label(attestation);
+                    changelog.add(&label);
+                    changelog.reorder_child(&label, 0);
+                }
+
+                if let Some(picker) = picker {
+                    changelog.add(&picker);
+                }
+
+                if let Some(downgrade) = downgrade {
+                    changelog.add(&downgrade);
+                }
+
+                for branch in branches.into_iter().flatten() {
+                    let sender = sender.clone();
+                    let branch = branch.clone();
+                    let button = cascade! {
+                        gtk::Button::with_label(&fl!("button-switch-branch", branch = &*branch));
+                        ..set_halign(gtk::Align::Center);
+                        ..connect_clicked(move |_| {
+                            let event = UiEvent::SwitchBranch(entity, branch.clone());
+                            let _ = sender.send(Event::Ui(event));
+                        });
+                    };
+
+                    changelog.add(&button);
+                }
+
+                changelog.upcast::<gtk::Container>()
             });
 
             return;
         }
 
         if let Some((_, changelog)) = self.components.system76.get(entity) {
-            reveal(revealer, &sender, entity, || {
-                let log_entries = changelog.versions.iter().map(|version| {
-                    (
-                        version.bios.as_ref(),
-                        version.date.as_ref(),
-                        version.description.as_ref(),
-                    )
-                });
-
+            generate(revealer, sender, entity, None, || {
+                let log_entries = system76_log_entries(changelog);
                 crate::changelog::generate_widget(log_entries).upcast::<gtk::Container>()
             });
 
@@ -202,7 +1513,7 @@ impl State {
         }
 
         // When changelog information is not available.
-        reveal(revealer, &sender, entity, || {
+        generate(revealer, sender, entity, details, || {
             crate::changelog::generate_widget_none().upcast::<gtk::Container>()
         });
     }
@@ -213,7 +1524,7 @@ impl State {
         info: FirmwareInfo,
         downloaded: Option<(System76Digest, System76Changelog)>,
     ) {
-        self.create_device(move |state, entity| {
+        self.create_device(info.clone(), move |state, entity| {
             let widget = state.widgets.view_devices.system(&info);
             widget.stack.hide();
             state.entities.associate_system(entity);
@@ -242,10 +1553,74 @@ impl State {
         });
     }
 
+    /// An event that occurs when the embedded controller of a System76 system was discovered.
+    ///
+    /// It is updated along with the system firmware, so it has no update button of its own.
+    pub fn system76_ec(&mut self, info: FirmwareInfo) {
+        self.create_device(info.clone(), move |state, entity| {
+            let widget = state.widgets.view_devices.system(&info);
+            widget.stack.hide();
+
+            if let Some(latest) = info.latest {
+                let label = fl!("ec-bundled", current = &*info.current, latest = &*latest);
+                widget.label.set_text(&label);
+            }
+
+            let sender = state.ui_sender.clone();
+            widget.connect_clicked(move |_| {
+                let _ = sender.send(Event::Ui(UiEvent::Reveal(entity)));
+            });
+
+            widget
+        });
+    }
+
+    /// An event that occurs when the system was described from its SMBIOS tables.
+    pub fn system_summary(&mut self, summary: SystemSummary) {
+        self.widgets.view_devices.summary(&summary);
+    }
+
+    /// An event that occurs when the Intel Management Engine was discovered.
+    ///
+    /// It is updated along with the system firmware, so it has no update button of its own.
+    pub fn management_engine(&mut self, info: FirmwareInfo) {
+        self.create_device(info.clone(), move |state, _| {
+            let widget = state.widgets.view_devices.system(&info);
+            widget.stack.hide();
+            widget
+        });
+    }
+
+    /// An event that occurs when the microcode of the processor was discovered.
+    ///
+    /// Microcode is loaded from the packages of the distribution, so it has no update button. A
+    /// newer revision which is installed is loaded when the system restarts.
+    pub fn microcode(&mut self, info: FirmwareInfo) {
+        self.create_device(info.clone(), move |state, _| {
+            let widget = state.widgets.view_devices.system(&info);
+            widget.stack.hide();
+
+            if let Some(latest) = info.latest.as_deref() {
+                let pending = fl!("microcode-pending", current = &*info.current, latest = latest);
+                widget.label.set_text(&pending);
+            }
+
+            widget
+        });
+    }
+
     /// An event that occurs when a Thelio I/O board was discovered.
-    pub fn thelio_io(&mut self, info: FirmwareInfo, digest: Option<System76Digest>) {
-        self.create_device(move |state, entity| {
-            let widget = state.widgets.view_devices.device(&info);
+    ///
+    /// Each board of a system is its own device, with its own progress and update button.
+    pub fn thelio_io(
+        &mut self,
+        board: Box<str>,
+        info: FirmwareInfo,
+        digest: Option<System76Digest>,
+    ) {
+        self.create_device(info.clone(), move |state, entity| {
+            // The boards are built into the system, though they are not installed on reboot.
+            let widget = state.widgets.view_devices.system(&info);
 
             let sender = state.ui_sender.clone();
             let mut upgradeable = false;
@@ -257,7 +1632,7 @@ impl State {
                 });
 
                 state.components.latest.insert(entity, latest);
-                state.components.thelio.insert(entity, digest);
+                state.components.thelio.insert(entity, (board, digest));
             }
 
             {
@@ -278,45 +1653,205 @@ impl State {
         });
     }
 
+    /// Whether updates may begin under the battery policy, which requires the system to be
+    /// connected to power. If it is running on battery, the user is asked to connect it.
+    fn power_connected(&self) -> bool {
+        self.battery_policy != BatteryPolicy::RequireAc
+            || !self.power.on_battery()
+            || wait_for_ac(&self.power)
+    }
+
+    /// Downgrades a fwupd device to an older release, once the user has confirmed that they
+    /// accept the risks of it.
+    pub fn downgrade(&mut self, entity: Entity, version: Box<str>) {
+        if self.components.cancellation.contains_key(entity) {
+            info!("not downgrading a device which is already being updated");
+            return;
+        }
+
+        if !self.power_connected() {
+            return;
+        }
+
+        let (device, releases) = match self.components.fwupd.get(entity) {
+            Some(fwupd) => fwupd,
+            None => return,
+        };
+
+        let release = match releases.iter().find(|release| *release.version == *version) {
+            Some(release) => release,
+            None => {
+                error!("{} has no release of version {}", device.name, version);
+                return;
+            }
+        };
+
+        if !confirm_downgrade(&device.name, &version, &downgrade_warnings(device)) {
+            return;
+        }
+
+        let cancellable = CancellationToken::default();
+        self.components.cancellation.insert(entity, cancellable.clone());
+        let previous = self.components.latest.insert(entity, version);
+        let widget = &self.components.device_widgets[entity];
+
+        // Firmware which is installed on reboot offers the same choices as an update of it.
+        let dialog = FwupdDialog {
+            battery_policy: self.battery_policy,
+            cancellable,
+            device,
+            downgrade: true,
+            entity,
+            power: &self.power,
+            needs_reboot: device.needs_reboot(),
+            release,
+            releases: std::slice::from_ref(release),
+            urgency: ReleaseUrgency::Unknown,
+            sender: &self.sender,
+            widgets: widget,
+        };
+
+        if dialog.run() {
+            widget.stack.show();
+        } else {
+            self.components.cancellation.remove(entity);
+            match previous {
+                Some(latest) => self.components.latest.insert(entity, latest),
+                None => self.components.latest.remove(entity),
+            };
+        }
+    }
+
+    /// Schedules the update of a fwupd device to one of its releases, which need not be its
+    /// latest, and shows a dialog if it requires a reboot.
+    pub fn update_to(&mut self, entity: Entity, version: Box<str>) {
+        if self.components.cancellation.contains_key(entity) {
+            info!("not updating a device which is already being updated");
+            return;
+        }
+
+        if !self.power_connected() {
+            return;
+        }
+
+        let (device, releases) = match self.components.fwupd.get(entity) {
+            Some(fwupd) => fwupd,
+            None => return,
+        };
+
+        let release = match releases.iter().find(|release| *release.version == *version) {
+            Some(release) => release,
+            None => {
+                error!("{} has no release of version {}", device.name, version);
+                return;
+            }
+        };
+
+        let cancellable = CancellationToken::default();
+        self.components.cancellation.insert(entity, cancellable.clone());
+
+        // The installed version is recorded from this once the update has finished.
+        let previous = self.components.latest.insert(entity, version);
+
+        let dialog = FwupdDialog {
+            battery_policy: self.battery_policy,
+            cancellable,
+            device,
+            downgrade: false,
+            entity,
+            power: &self.power,
+            needs_reboot: self.entities.is_system(entity),
+            release,
+            releases,
+            urgency: self.urgency(entity),
+            sender: &self.sender,
+            widgets: &self.components.device_widgets[entity],
+        };
+
+        if !dialog.run() {
+            self.components.cancellation.remove(entity);
+            match previous {
+                Some(latest) => self.components.latest.insert(entity, latest),
+                None => self.components.latest.remove(entity),
+            };
+        }
+    }
+
     /// Schedules the given firmware for an update, and show a dialog if it requires a reboot.
     pub fn update(&mut self, entity: Entity) {
         if let Some(latest) = self.components.latest.get(entity) {
-            let widgets = &self.components.device_widgets[entity];
-
-            if let Some((device, releases)) = self.components.fwupd.get(entity) {
-                let dialog = FwupdDialog {
-                    device: &device,
-                    entity,
-                    has_battery: self.has_battery,
-                    latest: &latest,
-                    needs_reboot: self.entities.is_system(entity),
-                    releases: &releases,
-                    sender: &self.sender,
-                    widgets,
-                };
+            if let Some((_, releases)) = self.components.fwupd.get(entity) {
+                if let Some(release) = releases.last() {
+                    let version = Box::from(&*release.version);
+                    self.update_to(entity, version);
+                }
 
-                dialog.run();
+                return;
+            }
 
+            if !self.power_connected() {
                 return;
             }
 
+            let widgets = &self.components.device_widgets[entity];
+
             if let Some((digest, changelog)) = self.components.system76.get(entity) {
                 let dialog = System76Dialog {
+                    battery_policy: self.battery_policy,
                     changelog: &changelog,
                     digest: &digest,
                     entity,
-                    has_battery: self.has_battery,
+                    power: &self.power,
                     latest: &latest,
                     sender: &self.sender,
                     widgets,
                 };
 
                 dialog.run();
-            } else if let Some(digest) = self.components.thelio.get(entity) {
+            } else if let Some((board, digest)) = self.components.thelio.get(entity) {
                 // Exchange the button for a progress bar.
                 widgets.stack.switch_to_waiting();
                 self.progress_activate(&widgets.stack.progress);
-                let _ = self.sender.send(FirmwareEvent::ThelioIo(entity, digest.clone()));
+                let cancellable = CancellationToken::default();
+                self.components.cancellation.insert(entity, cancellable.clone());
+                let event =
+                    FirmwareEvent::ThelioIo(entity, board.clone(), digest.clone(), cancellable);
+                let _ = self.sender.send(event);
+            } else if let Some(device) = self.components.devices.get(entity) {
+                if CapsuleBackend::manages(device)
+                    && secure_boot() == Some(SecureBoot::Enabled)
+                    && !confirm_capsule_secure_boot(&widgets.name.text())
+                {
+                    return;
+                }
+
+                let cancellable = CancellationToken::default();
+
+                // The system reboots to install system firmware, which the user must confirm.
+                if self.entities.is_system(entity) {
+                    let dialog = DeviceDialog {
+                        battery_policy: self.battery_policy,
+                        cancellable: cancellable.clone(),
+                        device,
+                        entity,
+                        power: &self.power,
+                        latest,
+                        sender: &self.sender,
+                        widgets,
+                    };
+
+                    if dialog.run() {
+                        self.components.cancellation.insert(entity, cancellable);
+                    }
+
+                    return;
+                }
+
+                widgets.stack.switch_to_waiting();
+                self.progress_activate(&widgets.stack.progress);
+                self.components.cancellation.insert(entity, cancellable.clone());
+                let event = FirmwareEvent::Device(entity, device.clone(), cancellable);
+                let _ = self.sender.send(event);
             }
         } else {
             error!("attempted to update firmware for a device which did not have updated firmware");
@@ -324,36 +1859,205 @@ impl State {
     }
 }
 
-/// Reveals a device's changelog, and generates that changelog if it hasn't been generated yet.
-fn reveal<F: FnMut() -> gtk::Container>(
+/// Labels versions which were not released to the stable channel with their channel.
+fn channel_label(version: &str, channel: ReleaseChannel) -> Cow<'_, str> {
+    match channel {
+        ReleaseChannel::Stable => Cow::Borrowed(version),
+        ReleaseChannel::Beta => Cow::Owned(fl!("channel-beta", version = version)),
+        ReleaseChannel::Testing => Cow::Owned(fl!("channel-testing", version = version)),
+    }
+}
+
+/// Picks one of the given versions of a fwupd device, which the `button` requests the `event`
+/// for.
+fn version_picker<'a, F>(
+    sender: &glib::Sender<Event>,
+    label: &str,
+    button: gtk::Button,
+    versions: impl Iterator<Item = &'a str>,
+    event: F,
+) -> gtk::Box
+where
+    F: Fn(Box<str>) -> UiEvent + 'static,
+{
+    let picker = gtk::ComboBoxText::new();
+    for version in versions {
+        picker.append(Some(version), version);
+    }
+
+    picker.set_active(Some(0));
+
+    let sender = sender.clone();
+    let weak_picker = picker.downgrade();
+    button.connect_clicked(move |_| {
+        if let Some(version) = weak_picker.upgrade().and_then(|picker| picker.active_id()) {
+            let _ = sender.send(Event::Ui(event(version.as_str().into())));
+        }
+    });
+
+    cascade! {
+        gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        ..set_halign(gtk::Align::Center);
+        ..add(&gtk::Label::new(Some(label)));
+        ..add(&picker);
+        ..add(&button);
+    }
+}
+
+/// Summarizes the releases which an update from the `current` version includes, above them.
+fn pending_label(pending: PendingReleases, current: &str) -> gtk::Label {
+    let summary = if pending.fixes == 0 {
+        fl!("changelog-pending", releases = pending.releases, version = current)
+    } else {
+        fl!(
+            "changelog-pending-fixes",
+            releases = pending.releases,
+            fixes = pending.fixes,
+            version = current
+        )
+    };
+
+    gtk::Label::builder()
+        .label(&format!("<b>{}</b>", glib::markup_escape_text(&summary)))
+        .use_markup(true)
+        .wrap(true)
+        .xalign(0.0)
+        .margin_start(48)
+        .margin_end(48)
+        .build()
+}
+
+/// Heads the releases which are not newer than the installed version.
+fn earlier_label() -> gtk::Label {
+    gtk::Label::builder()
+        .label(&format!("<b>{}</b>", fl!("changelog-earlier")))
+        .use_markup(true)
+        .xalign(0.0)
+        .margin_start(48)
+        .margin_end(48)
+        .build()
+}
+
+/// Describes whether the measurements of system firmware match those expected of it.
+fn attestation_label(attestation: &Attestation) -> gtk::Box {
+    let (icon, message) = match attestation {
+        Attestation::Passed => ("emblem-ok-symbolic", fl!("attestation-passed")),
+        Attestation::Failed(_) => ("dialog-warning-symbolic", fl!("attestation-failed")),
+    };
+
+    let label = gtk::Label::builder().label(&message).wrap(true).xalign(0.0).build();
+    if let Attestation::Failed(why) = attestation {
+        label.set_tooltip_text(Some(&**why));
+    }
+
+    cascade! {
+        gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        ..set_margin_start(48);
+        ..set_margin_end(48);
+        ..add(&gtk::Image::from_icon_name(Some(icon), gtk::IconSize::SmallToolbar));
+        ..add(&label);
+        ..show_all();
+    }
+}
+
+/// Generates a device's changelog, if it hasn't been generated yet.
+fn generate<F: FnOnce() -> gtk::Container>(
     revealer: &gtk::Revealer,
     sender: &glib::Sender<Event>,
     entity: Entity,
-    mut func: F,
+    details: Option<&FwupdDevice>,
+    func: F,
 ) {
-    let reveal = if revealer.reveals_child() {
-        false
-    } else {
-        // If the content to be revealed has not been generated yet, do so.
-        if revealer.child().is_none() {
-            let widget = func();
-
-            let container = cascade! {
-                gtk::Box::new(gtk::Orientation::Vertical, 12);
-                ..set_vexpand(true);
-                ..add(&gtk::Separator::new(gtk::Orientation::Horizontal));
-                ..add(&gtk::Label::builder().label(&format!("<b>{}</b>", fl!("changelog"))).use_markup(true).xalign(0.0).build());
-                ..add(&widget);
-                ..show_all();
-            };
+    if revealer.child().is_some() {
+        return;
+    }
 
-            revealer.add(&container);
-            revealer.show_all();
-        }
+    let widget = func();
+
+    let sender = sender.clone();
+    let ignore = cascade! {
+        gtk::Button::with_label(&fl!("button-ignore"));
+        ..set_halign(gtk::Align::End);
+        ..connect_clicked(move |_| {
+            let _ = sender.send(Event::Ui(UiEvent::Ignore(entity)));
+        });
+    };
+
+    let container = cascade! {
+        gtk::Box::new(gtk::Orientation::Vertical, 12);
+        ..set_vexpand(true);
+        ..add(&gtk::Separator::new(gtk::Orientation::Horizontal));
+        ..add(&gtk::Label::builder().label(&format!("<b>{}</b>", fl!("changelog"))).use_markup(true).xalign(0.0).build());
+        ..add(&widget);
+    };
 
-        true
+    if let Some(device) = details {
+        container.add(&details_expander(device));
+    }
+
+    container.add(&ignore);
+    container.show_all();
+
+    revealer.add(&container);
+    revealer.show_all();
+}
+
+/// Describes a fwupd device for diagnostics, in an expander beneath its changelog.
+fn details_expander(device: &FwupdDevice) -> gtk::Expander {
+    let flags = flag_names(device.flags).join(", ");
+    let guids = device.guid.join("\n");
+    let fields = [
+        (fl!("details-device-id"), Some(&*device.device_id)),
+        (fl!("details-guids"), Some(&*guids)),
+        (fl!("details-plugin"), Some(&*device.plugin)),
+        (fl!("details-flags"), Some(&*flags)),
+        (fl!("details-vendor-id"), Some(&*device.vendor_id)),
+        (fl!("details-serial"), device.serial.as_deref()),
+    ];
+
+    let grid = cascade! {
+        gtk::Grid::new();
+        ..set_column_spacing(12);
+        ..set_row_spacing(4);
+        ..set_margin_top(6);
     };
 
-    let _ = sender.send(Event::Ui(UiEvent::Revealed(entity, reveal)));
-    revealer.set_reveal_child(reveal);
+    let fields = fields.iter().filter_map(|(name, value)| Some((name, (*value)?)));
+    let fields = fields.filter(|(_, value)| !value.is_empty());
+
+    for (row, (name, value)) in (0..).zip(fields) {
+        let name = cascade! {
+            gtk::Label::new(Some(name.as_str()));
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+            ..set_valign(gtk::Align::Start);
+            ..set_xalign(0.0);
+        };
+
+        let value = cascade! {
+            gtk::Label::new(Some(value));
+            ..set_line_wrap(true);
+            ..set_selectable(true);
+            ..set_xalign(0.0);
+        };
+
+        grid.attach(&name, 0, row, 1, 1);
+        grid.attach(&value, 1, row, 1, 1);
+    }
+
+    cascade! {
+        gtk::Expander::new(Some(&fl!("details-title")));
+        ..add(&grid);
+    }
+}
+
+/// Whether the versions of a device have changed since it was found.
+fn is_changed(found: &FirmwareInfo, info: &FirmwareInfo) -> bool {
+    found.current != info.current || found.latest != info.latest
+}
+
+/// Whether every word of the search query appears in one of the fields, ignoring case.
+fn matches_search(query: &str, fields: &[&str]) -> bool {
+    let fields = fields.iter().map(|field| field.to_lowercase()).collect::<Vec<_>>();
+    let query = query.to_lowercase();
+    query.split_whitespace().all(|word| fields.iter().any(|field| field.contains(word)))
 }
@@ -1,110 +1,229 @@
 use crate::{fl, traits::DynamicGtkResize, widgets::DeviceWidget};
-use firmware_manager::FirmwareInfo;
+use firmware_manager::{
+    hsi_level, DeviceCategory, DeviceSort, FirmwareInfo, SecureBoot, SecurityAttribute, SortKey,
+    SystemSummary,
+};
 use gtk::prelude::*;
-use std::num::NonZeroU8;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    num::NonZeroU8,
+    rc::Rc,
+    sync::Once,
+    time::SystemTime,
+};
+
+/// Highlights the row which has the keyboard focus, so that it is clear which device the
+/// shortcuts act upon.
+const FOCUS_CSS: &[u8] = b"list.device-list > row:focus { \
+    background-color: alpha(@theme_selected_bg_color, 0.2); \
+}";
+
+/// What each row of the list boxes is sorted by, by the widget which the row contains.
+type SortKeys = Rc<RefCell<HashMap<gtk::Widget, SortKey>>>;
+
+/// A collapsible row of a device section, which groups the devices of a composite device, such
+/// as a dock, so that they may be updated together.
+pub struct DeviceGroup {
+    pub expander: gtk::Expander,
+    pub devices: gtk::ListBox,
+    pub update: gtk::Button,
+}
+
+impl DeviceGroup {
+    /// Names the group after the root of the composite device.
+    pub fn set_title(&self, title: &str) {
+        let title = format!("<b>{}</b>", glib::markup_escape_text(title));
+        self.expander.set_label(Some(&title));
+    }
+
+    /// The name of the group, without its markup.
+    pub fn title(&self) -> String {
+        self.expander
+            .label_widget()
+            .and_then(|label| label.downcast::<gtk::Label>().ok())
+            .map_or_else(String::new, |label| label.text().into())
+    }
+}
+
+/// A collapsible section of the devices view, which lists the devices of a category.
+struct DeviceSection {
+    category: DeviceCategory,
+    expander: gtk::Expander,
+    devices: gtk::ListBox,
+}
 
 /// The devices view is displayed when devices are found.
 ///
-/// It consists of a collection of system firmware which requires a reboot to flash, and device
-/// firmware which does not. The `system()` and `device()` methods will create and add device
-/// widges to their appropriate list boxes, and will return the created device widget so that we
-/// can program its signals.
+/// Devices are listed in a section for each [`DeviceCategory`], beginning with the system firmware
+/// which requires a reboot to flash. The `system()` and `device()` methods will create and add
+/// device widgets to the list boxes of their sections, and will return the created device widget
+/// so that we can program its signals. Sections are only shown while they have devices.
+///
+/// Rows are kept in the order which the user selected, by sort functions over each list box which
+/// compare the [`SortKey`] assigned to the widget of each row.
 #[derive(Shrinkwrap)]
 pub struct DevicesView {
     #[shrinkwrap(main_field)]
     container: gtk::Container,
-    device_firmware: gtk::ListBox,
-    device_header: gtk::Label,
+    checked: gtk::Label,
+    checked_at: Rc<Cell<Option<SystemTime>>>,
+    collapse_all: gtk::MenuItem,
+    expand_all: gtk::MenuItem,
+    search: gtk::SearchEntry,
+    sections: Vec<DeviceSection>,
+    update_all: gtk::Button,
+    update_all_progress: gtk::ProgressBar,
+    rescan: gtk::Button,
+    scroll: gtk::ScrolledWindow,
+    security: gtk::Expander,
+    sort: gtk::ComboBoxText,
+    sort_keys: SortKeys,
+    sort_order: Rc<Cell<DeviceSort>>,
+    updatable_only: gtk::ToggleButton,
+    security_attributes: gtk::ListBox,
     sg: gtk::SizeGroup,
-    system_firmware: gtk::ListBox,
-    system_header: gtk::Label,
+    system_summary: gtk::Grid,
 }
 
 impl DevicesView {
-    pub fn new() -> Self {
-        let system_firmware = cascade! {
-            gtk::ListBox::new();
+    pub fn new(order: DeviceSort) -> Self {
+        let sort_keys = SortKeys::default();
+        let sort_order = Rc::new(Cell::new(order));
+
+        let sections: Vec<DeviceSection> = DeviceCategory::ALL
+            .iter()
+            .map(|&category| DeviceSection::new(category, sort_func(&sort_order, &sort_keys)))
+            .collect();
+
+        connect_navigation(&sections);
+        load_focus_style();
+
+        // Describes the system above its firmware, once the system has been scanned.
+        let system_summary = cascade! {
+            gtk::Grid::new();
             ..set_no_show_all(true);
-            ..set_margin_bottom(12);
-            ..set_selection_mode(gtk::SelectionMode::None);
-            ..connect_row_activated(move |_, row| {
-                let widget = row.child()
-                    .and_then(|w| w.downcast::<gtk::Box>().ok())
-                    .and_then(|w| w.children().into_iter().next());
+            ..set_column_spacing(12);
+            ..set_row_spacing(4);
+        };
 
-                if let Some(widget) = widget {
-                    let _ = widget.emit_by_name::<()>("button_press_event", &[&gdk::Event::new(gdk::EventType::ButtonPress)]);
-                }
-            });
+        // Filters the devices by their name, vendor, or version, as the user types.
+        let search = cascade! {
+            gtk::SearchEntry::new();
+            ..set_placeholder_text(Some(&fl!("search-placeholder")));
         };
 
-        let upper = system_firmware.downgrade();
-        let device_firmware = cascade! {
-            gtk::ListBox::new();
-            ..set_no_show_all(true);
-            ..set_selection_mode(gtk::SelectionMode::None);
-            ..connect_row_activated(move |_, row| {
-                let widget = row.child()
-                    .and_then(|w| w.downcast::<gtk::Box>().ok())
-                    .and_then(|w| w.children().into_iter().next());
+        // Hides the devices which already have their latest firmware.
+        let updatable_only = cascade! {
+            gtk::ToggleButton::with_label(&fl!("button-updatable-only"));
+            ..set_valign(gtk::Align::Center);
+        };
 
-                if let Some(widget) = widget {
-                    let _ = widget.emit_by_name::<()>("button_press_event", &[&gdk::Event::new(gdk::EventType::ButtonPress)]);
-                }
-            });
-            ..connect_key_press_event(move |listbox, event| {
-                gtk::Inhibit(
-                    if event.keyval() == gdk::keys::constants::Up {
-                        listbox.children()
-                            .into_iter()
-                            .filter_map(|widget| widget.downcast::<gtk::ListBoxRow>().ok())
-                            .next()
-                            .and_then(|row| if row.has_focus() { upper.upgrade() } else { None })
-                            .and_then(|upper| upper.children().into_iter().last())
-                            .map_or(false, |child| {
-                                child.grab_focus();
-                                true
-                            })
-                    } else {
-                        false
-                    }
-                )
-            });
+        // Scans for devices again, without restarting the widget.
+        let rescan = cascade! {
+            gtk::Button::from_icon_name(Some("view-refresh-symbolic"), gtk::IconSize::Button);
+            ..set_tooltip_text(Some(&fl!("button-rescan")));
+            ..set_valign(gtk::Align::Center);
         };
 
-        let lower = device_firmware.downgrade();
-        system_firmware.connect_key_press_event(move |listbox, event| {
-            gtk::Inhibit(if event.keyval() == gdk::keys::constants::Down {
-                listbox
-                    .children()
-                    .into_iter()
-                    .filter_map(|widget| widget.downcast::<gtk::ListBoxRow>().ok())
-                    .last()
-                    .and_then(|row| if row.has_focus() { lower.upgrade() } else { None })
-                    .and_then(|lower| lower.children().into_iter().next())
-                    .map_or(false, |child| {
-                        child.grab_focus();
-                        true
-                    })
-            } else {
-                false
-            })
-        });
+        // Selects the order which devices are listed in.
+        let sort = cascade! {
+            gtk::ComboBoxText::new();
+            ..set_tooltip_text(Some(&fl!("sort-tooltip")));
+            ..set_valign(gtk::Align::Center);
+        };
+
+        for order in DeviceSort::ALL {
+            sort.append(Some(order.as_str()), &sort_label(order));
+        }
+
+        sort.set_active_id(Some(order.as_str()));
+
+        let expand_all = gtk::MenuItem::with_label(&fl!("menu-expand-all"));
+        let collapse_all = gtk::MenuItem::with_label(&fl!("menu-collapse-all"));
+
+        let menu = cascade! {
+            gtk::Menu::new();
+            ..append(&expand_all);
+            ..append(&collapse_all);
+            ..show_all();
+        };
+
+        // Offers the actions which apply to every device in the list.
+        let menu_button = cascade! {
+            gtk::MenuButton::new();
+            ..set_image(Some(&gtk::Image::from_icon_name(
+                Some("view-more-symbolic"),
+                gtk::IconSize::Button
+            )));
+            ..set_popup(Some(&menu));
+            ..set_tooltip_text(Some(&fl!("menu-tooltip")));
+            ..set_valign(gtk::Align::Center);
+        };
 
-        let system_text = format!("<b>{}</b>", fl!("header-system-firmware"));
-        let system_header = cascade! {
-            gtk::Label::new(Some(&system_text));
+        // Queues the update of every device which may be updated without a reboot.
+        let update_all = cascade! {
+            gtk::Button::with_label(&fl!("button-update-all"));
             ..set_no_show_all(true);
-            ..set_use_markup(true);
-            ..set_xalign(0.0);
+            ..style_context().add_class(&gtk::STYLE_CLASS_SUGGESTED_ACTION);
         };
 
-        let device_text = format!("<b>{}</b>", fl!("header-device-firmware"));
-        let device_header = cascade! {
-            gtk::Label::new(Some(&device_text));
+        // Shows how many of the devices queued by Update All have been updated.
+        let update_all_progress = cascade! {
+            gtk::ProgressBar::new();
             ..set_no_show_all(true);
-            ..set_use_markup(true);
-            ..set_xalign(0.0);
+            ..set_show_text(true);
+            ..set_valign(gtk::Align::Center);
+        };
+
+        let header = cascade! {
+            gtk::Box::new(gtk::Orientation::Horizontal, 12);
+            ..pack_start(&search, true, true, 0);
+            ..add(&updatable_only);
+            ..add(&sort);
+            ..add(&rescan);
+            ..add(&menu_button);
+            ..add(&update_all_progress);
+            ..add(&update_all);
+        };
+
+        // Tells when firmware was last checked for, with the next automatic check in its tooltip.
+        let checked = cascade! {
+            gtk::Label::builder().halign(gtk::Align::End).no_show_all(true).build();
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+        };
+
+        let checked_at = Rc::new(Cell::new(None));
+
+        // Keeps the time since the last check current while the view is open.
+        {
+            let checked = checked.downgrade();
+            let checked_at = checked_at.clone();
+            glib::timeout_add_seconds_local(60, move || match checked.upgrade() {
+                Some(checked) => {
+                    if let Some(time) = checked_at.get() {
+                        checked.set_text(&last_checked(time));
+                    }
+
+                    glib::Continue(true)
+                }
+                None => glib::Continue(false),
+            });
+        }
+
+        let security_attributes = cascade! {
+            gtk::ListBox::new();
+            ..set_margin_top(12);
+            ..set_selection_mode(gtk::SelectionMode::None);
+        };
+
+        security_attributes.set_header_func(Some(Box::new(separator_header)));
+
+        // Lists the protections that the firmware enables, once fwupd has reported them.
+        let security = cascade! {
+            gtk::Expander::new(None);
+            ..set_no_show_all(true);
+            ..add(&security_attributes);
         };
 
         let layout: gtk::Box = cascade! {
@@ -112,97 +231,697 @@ impl DevicesView {
             ..set_halign(gtk::Align::Center);
             ..set_margin_top(24);
             ..set_margin_bottom(24);
-            ..add(&system_header);
-            ..add(&system_firmware);
-            ..add(&device_header);
-            ..add(&device_firmware);
+            ..add(&header);
+            ..add(&checked);
+            ..add(&system_summary);
             ..set_no_show_all(true);
         };
 
-        cascade! {
+        let width = cascade! {
             gtk::SizeGroup::new(gtk::SizeGroupMode::Horizontal);
-            ..add_widget(&system_firmware);
-            ..add_widget(&device_firmware);
+            ..add_widget(&header);
+            ..add_widget(&security);
         };
 
-        device_firmware.set_header_func(Some(Box::new(separator_header)));
-        system_firmware.set_header_func(Some(Box::new(separator_header)));
+        for section in &sections {
+            layout.add(&section.expander);
+            width.add_widget(&section.expander);
+        }
+
+        layout.add(&security);
 
-        let container = cascade! {
+        let scroll = cascade! {
             gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
             ..add(&layout);
             ..show_all();
-            ..dynamic_resize(layout, NonZeroU8::new(66), None);
         };
 
+        // Scrolls to the row which the keyboard focus moves to.
+        layout.set_focus_vadjustment(&scroll.vadjustment());
+        scroll.dynamic_resize(layout, NonZeroU8::new(66), None);
+
         Self {
-            container: container.upcast(),
-            device_firmware,
-            device_header,
+            checked,
+            checked_at,
+            collapse_all,
+            container: scroll.clone().upcast(),
+            expand_all,
+            rescan,
+            scroll,
+            search,
+            sections,
+            update_all,
+            update_all_progress,
+            security,
+            security_attributes,
+            sort,
+            sort_keys,
+            sort_order,
+            updatable_only,
             sg: gtk::SizeGroup::new(gtk::SizeGroupMode::Vertical),
-            system_firmware,
-            system_header,
+            system_summary,
         }
     }
 
-    /// Clears all device widgets from the system and device list boxes.
+    /// Clears all device widgets from the list boxes of every section.
     pub fn clear(&self) {
-        self.system_firmware.foreach(|x| unsafe {
-            x.destroy();
+        self.sort_keys.borrow_mut().clear();
+        for section in &self.sections {
+            section.devices.foreach(|x| unsafe {
+                x.destroy();
+            });
+        }
+    }
+
+    /// Removes a device widget from its section, hiding the section if it is now empty.
+    pub fn remove(&self, widget: &DeviceWidget) {
+        self.sort_keys.borrow_mut().remove(widget.container.upcast_ref::<gtk::Widget>());
+
+        if let Some(row) = widget.container.parent() {
+            unsafe {
+                row.destroy();
+            }
+        }
+
+        self.hide_empty_sections();
+    }
+
+    /// Shows when firmware was last checked for, with the time of the next automatic check in its
+    /// tooltip if checks are scheduled.
+    pub fn set_last_check(&self, last: Option<SystemTime>, next: Option<SystemTime>) {
+        self.checked_at.set(last);
+
+        let last = match last {
+            Some(last) => last,
+            None => return self.checked.hide(),
+        };
+
+        let tooltip = match next.and_then(local_time) {
+            Some(time) => fl!("next-check", time = time),
+            None => fl!("next-check-disabled"),
+        };
+
+        self.checked.set_text(&last_checked(last));
+        self.checked.set_tooltip_text(Some(&tooltip));
+        self.checked.show();
+    }
+
+    /// Programs the action that is triggered when the search query is changed.
+    pub fn connect_search<F: Fn(&str) + 'static>(&self, func: F) {
+        self.search.connect_search_changed(move |entry| func(&entry.text()));
+    }
+
+    /// Programs the action that is triggered when devices without updates are hidden or shown.
+    pub fn connect_updatable_only<F: Fn(bool) + 'static>(&self, func: F) {
+        self.updatable_only.connect_toggled(move |button| func(button.is_active()));
+    }
+
+    /// Programs the action that is triggered when every changelog is to be revealed or hidden.
+    pub fn connect_reveal_all<F: Fn(bool) + 'static>(&self, func: F) {
+        let func = Rc::new(func);
+
+        let expand = func.clone();
+        self.expand_all.connect_activate(move |_| expand(true));
+        self.collapse_all.connect_activate(move |_| func(false));
+    }
+
+    /// Programs the action that is triggered when the rescan button is clicked.
+    pub fn connect_rescan<F: Fn() + 'static>(&self, func: F) {
+        self.rescan.connect_clicked(move |_| func());
+    }
+
+    /// How far the view has been scrolled, so that it may be restored after a rescan.
+    pub fn scroll_position(&self) -> f64 { self.scroll.vadjustment().value() }
+
+    /// Scrolls the view back to a position from before a rescan, once the devices which were
+    /// found again have been allocated their space.
+    pub fn restore_scroll(&self, position: f64) {
+        let adjustment = self.scroll.vadjustment();
+        glib::idle_add_local(move || {
+            adjustment.set_value(position);
+            glib::Continue(false)
         });
-        self.device_firmware.foreach(|x| unsafe {
-            x.destroy();
+    }
+
+    /// Programs the action that is triggered when another order is selected for the devices.
+    pub fn connect_sort<F: Fn(DeviceSort) + 'static>(&self, func: F) {
+        self.sort.connect_changed(move |sort| {
+            if let Some(order) = sort.active_id().and_then(|id| DeviceSort::from_name(&id)) {
+                func(order);
+            }
         });
     }
 
-    /// Creates and attaches a new device widget to the device section.
-    pub fn device(&self, info: &FirmwareInfo) -> DeviceWidget {
-        self.show_devices();
-        self.append(&self.device_firmware, info)
+    /// Lists the devices in another order. The rows of groups are sorted with `groups`.
+    pub fn sort<'a>(&self, order: DeviceSort, groups: impl Iterator<Item = &'a DeviceGroup>) {
+        self.sort_order.set(order);
+        for section in &self.sections {
+            section.devices.invalidate_sort();
+        }
+
+        for group in groups {
+            group.devices.invalidate_sort();
+        }
+    }
+
+    /// Assigns what the row of a device widget, or of a group, is sorted by, moving the row to
+    /// its place in the order.
+    pub fn set_sort_key(&self, widget: &impl IsA<gtk::Widget>, key: SortKey) {
+        let widget = widget.as_ref();
+        let mut keys = self.sort_keys.borrow_mut();
+        if keys.get(widget) == Some(&key) {
+            return;
+        }
+
+        keys.insert(widget.clone(), key);
+        drop(keys);
+
+        if let Some(row) = widget.parent().and_then(|row| row.downcast::<gtk::ListBoxRow>().ok()) {
+            row.changed();
+        }
+    }
+
+    /// Programs the action that is triggered when the Update All button is clicked.
+    pub fn connect_update_all<F: Fn() + 'static>(&self, func: F) {
+        self.update_all.connect_clicked(move |_| func());
+    }
+
+    /// Shows the Update All button only while there are devices for it to update.
+    pub fn update_all_available(&self, available: bool) {
+        self.update_all.set_visible(available);
+    }
+
+    /// Shows how many of the devices queued by Update All have been updated, or hides the
+    /// progress once they all have.
+    pub fn update_all_progress(&self, progress: Option<(usize, usize)>) {
+        match progress {
+            Some((done, total)) => {
+                let text = fl!("update-all-progress", done = done, total = total);
+                self.update_all_progress.set_text(Some(&text));
+                self.update_all_progress.set_fraction(done as f64 / total as f64);
+                self.update_all_progress.show();
+            }
+            None => self.update_all_progress.hide(),
+        }
+    }
+
+    /// Shows or hides the row of a device widget, as it matches the filters or not.
+    pub fn filter(&self, widget: &DeviceWidget, visible: bool) {
+        if let Some(row) = widget.container.parent() {
+            row.set_visible(visible);
+        }
+    }
+
+    /// Shows the row of a group only if any of its devices match the filters.
+    pub fn filter_group(&self, group: &DeviceGroup) {
+        let visible = group.devices.children().iter().any(|row| row.is_visible());
+        if let Some(row) = group.expander.parent() {
+            row.set_visible(visible);
+        }
+    }
+
+    /// Shows only the sections which have devices that match the filters.
+    pub fn filter_sections(&self) {
+        for section in &self.sections {
+            let visible = section.devices.children().iter().any(|row| row.is_visible());
+            section.expander.set_visible(visible);
+        }
+    }
+
+    /// Creates and attaches a new device widget to the section of its category.
+    pub fn device(&self, info: &FirmwareInfo, category: DeviceCategory) -> DeviceWidget {
+        let section = self.section(category);
+        section.expander.show();
+        self.append(&section.devices, info, category)
+    }
+
+    /// Creates a collapsible group for the devices of a composite device, in the section of the
+    /// category of its root device.
+    pub fn group(&self, category: DeviceCategory) -> DeviceGroup {
+        let section = self.section(category);
+        section.expander.show();
+
+        let devices = cascade! {
+            gtk::ListBox::new();
+            ..set_margin_start(24);
+            ..set_selection_mode(gtk::SelectionMode::None);
+            ..style_context().add_class("device-list");
+            ..connect_row_activated(|_, row| activate_row(row));
+        };
+
+        devices.set_header_func(Some(Box::new(separator_header)));
+        devices.set_sort_func(Some(sort_func(&self.sort_order, &self.sort_keys)));
+
+        let update = cascade! {
+            gtk::Button::with_label(&group_update_label(category));
+            ..set_halign(gtk::Align::End);
+            ..set_no_show_all(true);
+        };
+
+        let expander = cascade! {
+            gtk::Expander::new(None);
+            ..set_expanded(true);
+            ..set_use_markup(true);
+            ..add(&cascade! {
+                gtk::Box::new(gtk::Orientation::Vertical, 12);
+                ..add(&devices);
+                ..add(&update);
+            });
+        };
+
+        let group = DeviceGroup { expander, devices, update };
+        group.set_title(&match category {
+            DeviceCategory::Dock => fl!("dock-unnamed"),
+            _ => fl!("group-unnamed"),
+        });
+
+        section.devices.add(&group.expander);
+        group.expander.show_all();
+        group
+    }
+
+    /// Creates and attaches a new device widget to a group of a device section.
+    pub fn grouped_device(
+        &self,
+        group: &DeviceGroup,
+        info: &FirmwareInfo,
+        category: DeviceCategory,
+    ) -> DeviceWidget {
+        self.append(&group.devices, info, category)
+    }
+
+    /// Moves a group to the section of the given category, as when its root device was found
+    /// after the other devices of the group.
+    pub fn move_group(&self, group: &DeviceGroup, category: DeviceCategory) {
+        let section = self.section(category);
+        let row = match group.expander.parent() {
+            Some(row) => row,
+            None => return,
+        };
+
+        if row.parent().as_ref() == Some(section.devices.upcast_ref::<gtk::Widget>()) {
+            return;
+        }
+
+        if let Ok(row) = row.downcast::<gtk::Container>() {
+            row.remove(&group.expander);
+            unsafe {
+                row.destroy();
+            }
+        }
+
+        group.update.set_label(&group_update_label(category));
+        section.devices.add(&group.expander);
+        section.expander.show();
+        self.hide_empty_sections();
+    }
+
+    /// Moves a device widget from its section into a group.
+    pub fn move_to_group(&self, widget: &DeviceWidget, group: &DeviceGroup) {
+        if let Some(row) = widget.container.parent() {
+            if let Ok(row) = row.downcast::<gtk::Container>() {
+                row.remove(&widget.container);
+                unsafe {
+                    row.destroy();
+                }
+            }
+        }
+
+        group.devices.add(&widget.container);
+
+        if let Some(row) = widget.container.parent() {
+            connect_update_shortcut(&row, &widget.stack.button);
+        }
+
+        self.hide_empty_sections();
+    }
+
+    /// Removes a group from its section, hiding the section if it is now empty.
+    pub fn remove_group(&self, group: &DeviceGroup) {
+        self.sort_keys.borrow_mut().remove(group.expander.upcast_ref::<gtk::Widget>());
+
+        if let Some(row) = group.expander.parent() {
+            unsafe {
+                row.destroy();
+            }
+        }
+
+        self.hide_empty_sections();
     }
 
     /// Creates and attaches a new device widget to the system section.
     pub fn system(&self, info: &FirmwareInfo) -> DeviceWidget {
-        self.show_systems();
-        self.append(&self.system_firmware, info)
+        self.device(info, DeviceCategory::System)
     }
 
-    /// Hides the device section so that it does not appear to the end user.
-    pub fn hide_devices(&self) {
-        self.device_firmware.hide();
-        self.device_header.hide();
+    /// Shows the summary of the system as the header of the system section.
+    ///
+    /// Fields which are not known, such as the serial number when not running as root, are
+    /// omitted from the summary.
+    pub fn summary(&self, summary: &SystemSummary) {
+        self.system_summary.foreach(|x| unsafe {
+            x.destroy();
+        });
+
+        let title = format!("<b>{}</b>", fl!("summary-title"));
+        let title = cascade! {
+            gtk::Label::new(Some(&title));
+            ..set_use_markup(true);
+            ..set_xalign(0.0);
+        };
+
+        self.system_summary.attach(&title, 0, 0, 2, 1);
+
+        let secure_boot = summary.secure_boot.map(|state| match state {
+            SecureBoot::Enabled => fl!("summary-secure-boot-enabled"),
+            SecureBoot::Disabled => fl!("summary-secure-boot-disabled"),
+            SecureBoot::SetupMode => fl!("summary-secure-boot-setup"),
+        });
+
+        let fields = [
+            (fl!("summary-vendor"), summary.vendor.as_deref()),
+            (fl!("summary-product"), summary.product.as_deref()),
+            (fl!("summary-serial"), summary.serial.as_deref()),
+            (fl!("summary-bios-date"), summary.bios_date.as_deref()),
+            (fl!("summary-secure-boot"), secure_boot.as_deref()),
+        ];
+
+        let fields = fields.iter().filter_map(|(name, value)| Some((name, (*value)?)));
+
+        for (row, (name, value)) in (1..).zip(fields) {
+            let name = cascade! {
+                gtk::Label::new(Some(name.as_str()));
+                ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+                ..set_xalign(0.0);
+            };
+
+            let value = cascade! {
+                gtk::Label::new(Some(value));
+                ..set_selectable(true);
+                ..set_xalign(0.0);
+            };
+
+            self.system_summary.attach(&name, 0, row, 1, 1);
+            self.system_summary.attach(&value, 1, row, 1, 1);
+        }
+
+        // The grid is excluded from `show_all()`, so its labels are shown with it.
+        self.system_summary.foreach(|x| x.show());
+        self.system_summary.show();
     }
 
-    /// Hides the system section so that it does not appear to the end user.
-    pub fn hide_systems(&self) {
-        self.system_firmware.hide();
-        self.system_header.hide();
+    /// Lists the security attributes of the system in the security expander, which shows the HSI
+    /// level of the system in its label.
+    pub fn security(&self, attributes: &[SecurityAttribute]) {
+        self.security_attributes.foreach(|x| unsafe {
+            x.destroy();
+        });
+
+        if attributes.is_empty() {
+            self.security.hide();
+            return;
+        }
+
+        let label = format!("<b>{}</b>", fl!("security-title", level = hsi_level(attributes)));
+        self.security.set_label(Some(&label));
+        self.security.set_use_markup(true);
+
+        for attribute in attributes {
+            let icon =
+                if attribute.success { "emblem-ok-symbolic" } else { "dialog-warning-symbolic" };
+
+            let name = cascade! {
+                gtk::Label::new(Some(&*attribute.name));
+                ..set_hexpand(true);
+                ..set_xalign(0.0);
+            };
+
+            let result = cascade! {
+                gtk::Label::new(attribute.result.as_deref());
+                ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+            };
+
+            let row = cascade! {
+                gtk::Box::new(gtk::Orientation::Horizontal, 12);
+                ..set_margin_start(12);
+                ..set_margin_end(12);
+                ..set_margin_top(6);
+                ..set_margin_bottom(6);
+                ..add(&gtk::Image::from_icon_name(Some(icon), gtk::IconSize::Button));
+                ..add(&name);
+                ..add(&result);
+            };
+
+            self.security_attributes.add(&row);
+        }
+
+        self.security_attributes.show_all();
+        self.security.show();
     }
 
-    /// Shows the device section, which is triggered when a device widget is added.
-    fn show_devices(&self) {
-        self.device_firmware.show();
-        self.device_header.show();
+    /// Hides every section so that they do not appear to the end user.
+    pub fn hide_sections(&self) {
+        for section in &self.sections {
+            section.expander.hide();
+        }
+    }
+
+    /// Hides the sections which no longer have any devices.
+    fn hide_empty_sections(&self) {
+        for section in &self.sections {
+            if section.devices.children().is_empty() {
+                section.expander.hide();
+            }
+        }
     }
 
-    /// Shows the system section, which is triggered when a device widget is added.
-    fn show_systems(&self) {
-        self.system_firmware.show();
-        self.system_header.show();
+    /// The section which lists the devices of a category.
+    fn section(&self, category: DeviceCategory) -> &DeviceSection {
+        self.sections
+            .iter()
+            .find(|section| section.category == category)
+            .expect("every category has a section")
     }
 
     /// Convenience method shared by both the device and system methods.
     ///
     /// This is responsible for creating a device widget and assigning it to the given parent
-    /// container.
-    fn append(&self, parent: &impl gtk::traits::ContainerExt, info: &FirmwareInfo) -> DeviceWidget {
+    /// container, with the icon of its category until a more specific icon is set.
+    fn append(
+        &self,
+        parent: &impl gtk::traits::ContainerExt,
+        info: &FirmwareInfo,
+        category: DeviceCategory,
+    ) -> DeviceWidget {
         let widget = DeviceWidget::new(info);
+        widget.set_icon(category_icons(category));
         self.sg.add_widget(&widget.event_box);
         parent.add(widget.as_ref());
+
+        if let Some(row) = widget.container.parent() {
+            connect_update_shortcut(&row, &widget.stack.button);
+        }
+
         widget
     }
 }
 
+/// The label of the button which updates the devices of a group in the section of the category.
+fn group_update_label(category: DeviceCategory) -> String {
+    match category {
+        DeviceCategory::Dock => fl!("button-update-dock"),
+        _ => fl!("button-update-group"),
+    }
+}
+
+impl DeviceSection {
+    fn new(
+        category: DeviceCategory,
+        sort: Box<dyn Fn(&gtk::ListBoxRow, &gtk::ListBoxRow) -> i32>,
+    ) -> Self {
+        let devices = cascade! {
+            gtk::ListBox::new();
+            ..set_margin_top(12);
+            ..set_selection_mode(gtk::SelectionMode::None);
+            ..style_context().add_class("device-list");
+            ..connect_row_activated(|_, row| activate_row(row));
+            ..show();
+        };
+
+        devices.set_header_func(Some(Box::new(separator_header)));
+        devices.set_sort_func(Some(sort));
+
+        let title = format!("<b>{}</b>", section_title(category));
+        let expander = cascade! {
+            gtk::Expander::new(Some(&title));
+            ..set_expanded(true);
+            ..set_no_show_all(true);
+            ..set_use_markup(true);
+            ..add(&devices);
+        };
+
+        Self { category, expander, devices }
+    }
+}
+
+/// Moves the focus between the first and last rows of adjacent sections with the arrow keys, as
+/// if the sections were one list.
+fn connect_navigation(sections: &[DeviceSection]) {
+    for (index, section) in sections.iter().enumerate() {
+        let above: Vec<_> =
+            sections[..index].iter().rev().map(|section| section.devices.downgrade()).collect();
+        let below: Vec<_> =
+            sections[index + 1..].iter().map(|section| section.devices.downgrade()).collect();
+
+        section.devices.connect_key_press_event(move |listbox, event| {
+            let rows = listbox.children();
+            let focused = |row: Option<&gtk::Widget>| row.map_or(false, |row| row.has_focus());
+
+            // Collapsed and empty sections are skipped over.
+            let adjacent = |lists: &[glib::WeakRef<gtk::ListBox>]| {
+                lists.iter().filter_map(|list| list.upgrade()).find(|list| list.is_mapped())
+            };
+
+            let key = event.keyval();
+            let target = if key == gdk::keys::constants::Up && focused(rows.first()) {
+                adjacent(&above).and_then(|list| list.children().into_iter().last())
+            } else if key == gdk::keys::constants::Down && focused(rows.last()) {
+                adjacent(&below).and_then(|list| list.children().into_iter().next())
+            } else {
+                None
+            };
+
+            gtk::Inhibit(target.map_or(false, |row| {
+                row.grab_focus();
+                true
+            }))
+        });
+    }
+}
+
+/// Describes how long ago firmware was last checked for.
+fn last_checked(time: SystemTime) -> String {
+    let minutes = SystemTime::now().duration_since(time).unwrap_or_default().as_secs() / 60;
+    let ago = match minutes {
+        0 => fl!("time-just-now"),
+        1..=59 => fl!("time-minutes-ago", minutes = minutes),
+        60..=1439 => fl!("time-hours-ago", hours = minutes / 60),
+        _ => fl!("time-days-ago", days = minutes / 1440),
+    };
+
+    fl!("last-checked", time = ago)
+}
+
+/// Formats a time in the local time zone, as the locale of the user formats dates and times.
+fn local_time(time: SystemTime) -> Option<String> {
+    let seconds = time.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs();
+    let time = glib::DateTime::from_unix_local(seconds as i64).ok()?;
+    time.format("%c").ok().map(String::from)
+}
+
+/// Ctrl+U updates the device of the focused row, as if its update button was clicked.
+fn connect_update_shortcut(row: &gtk::Widget, button: &gtk::Button) {
+    let button = button.downgrade();
+    row.connect_key_press_event(move |_, event| {
+        let shortcut = event.keyval().to_lower() == gdk::keys::constants::u
+            && event.state().contains(gdk::ModifierType::CONTROL_MASK);
+
+        let button = button.upgrade().filter(|button| button.is_mapped() && button.is_sensitive());
+
+        gtk::Inhibit(match button {
+            Some(button) if shortcut => {
+                button.clicked();
+                true
+            }
+            _ => false,
+        })
+    });
+}
+
+/// Styles the focused row of every device list, once for the whole screen, however many views
+/// are created.
+fn load_focus_style() {
+    static LOADED: Once = Once::new();
+
+    LOADED.call_once(|| {
+        let screen = match gdk::Screen::default() {
+            Some(screen) => screen,
+            None => return,
+        };
+
+        let provider = gtk::CssProvider::new();
+        if provider.load_from_data(FOCUS_CSS).is_ok() {
+            gtk::StyleContext::add_provider_for_screen(
+                &screen,
+                &provider,
+                gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+            );
+        }
+    });
+}
+
+/// The header of the section which lists the devices of a category.
+fn section_title(category: DeviceCategory) -> String {
+    match category {
+        DeviceCategory::System => fl!("header-system-firmware"),
+        DeviceCategory::Input => fl!("header-input"),
+        DeviceCategory::Storage => fl!("header-storage"),
+        DeviceCategory::Dock => fl!("header-docks"),
+        DeviceCategory::Other => fl!("header-other"),
+    }
+}
+
+/// The icons of the devices of a category, in order of preference.
+fn category_icons(category: DeviceCategory) -> &'static [&'static str] {
+    match category {
+        DeviceCategory::System => &["computer"],
+        DeviceCategory::Input => &["input-keyboard"],
+        DeviceCategory::Storage => &["drive-harddisk-solidstate", "drive-harddisk"],
+        DeviceCategory::Dock => &["dock", "thunderbolt", "video-display"],
+        DeviceCategory::Other => &[],
+    }
+}
+
+/// Activating a row of a list box clicks the device widget which it contains.
+fn activate_row(row: &gtk::ListBoxRow) {
+    let widget = row
+        .child()
+        .and_then(|w| w.downcast::<gtk::Box>().ok())
+        .and_then(|w| w.children().into_iter().next());
+
+    if let Some(widget) = widget {
+        let event = gdk::Event::new(gdk::EventType::ButtonPress);
+        let _ = widget.emit_by_name::<()>("button_press_event", &[&event]);
+    }
+}
+
+/// Compares the rows of a list box by the sort keys of their widgets, in the selected order.
+///
+/// Rows whose widgets have no sort key yet are left where they are.
+fn sort_func(
+    order: &Rc<Cell<DeviceSort>>,
+    keys: &SortKeys,
+) -> Box<dyn Fn(&gtk::ListBoxRow, &gtk::ListBoxRow) -> i32> {
+    let (order, keys) = (order.clone(), keys.clone());
+    Box::new(move |a, b| {
+        let keys = keys.borrow();
+        let key = |row: &gtk::ListBoxRow| row.child().and_then(|child| keys.get(&child));
+        match (key(a), key(b)) {
+            (Some(a), Some(b)) => order.get().compare(a, b) as i32,
+            _ => 0,
+        }
+    })
+}
+
+/// Describes an order of the devices in the list of orders.
+fn sort_label(order: DeviceSort) -> String {
+    match order {
+        DeviceSort::Name => fl!("sort-name"),
+        DeviceSort::Vendor => fl!("sort-vendor"),
+        DeviceSort::UpgradeableFirst => fl!("sort-upgradeable-first"),
+    }
+}
+
 /// Inserts a separator as a header between rows in a list box.
 fn separator_header(current: &gtk::ListBoxRow, before: Option<&gtk::ListBoxRow>) {
     if before.is_some() {
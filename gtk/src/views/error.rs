@@ -1,13 +1,96 @@
 use crate::fl;
+use firmware_manager::ScanResult;
 use gtk::prelude::*;
 
 /// View displayed when scanning has completed, but no firmware was found.
+///
+/// The [`ScanResult`] explains why, so that the view may offer to start the fwupd service when it
+/// is not running, or to scan again when the scan failed.
 #[derive(Shrinkwrap)]
-pub struct EmptyView(gtk::Container);
+pub struct EmptyView {
+    #[shrinkwrap(main_field)]
+    container: gtk::Container,
+    image: gtk::Image,
+    reason: gtk::Label,
+    details: gtk::Label,
+    start: gtk::Button,
+    retry: gtk::Button,
+}
 
 impl EmptyView {
     pub fn new() -> Self {
-        Self(error_view("firmware-manager-symbolic", &fl!("view-empty")))
+        let image = error_image("firmware-manager-symbolic");
+        let reason = error_label(&fl!("view-empty"));
+
+        // The errors which the backends reported, when the scan failed.
+        let details = cascade! {
+            gtk::Label::builder()
+                .wrap(true)
+                .selectable(true)
+                .xalign(0.0)
+                .no_show_all(true)
+                .build();
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+        };
+
+        let start = cascade! {
+            gtk::Button::with_label(&fl!("button-start-fwupd"));
+            ..set_halign(gtk::Align::Start);
+            ..set_no_show_all(true);
+            ..style_context().add_class(&gtk::STYLE_CLASS_SUGGESTED_ACTION);
+        };
+
+        let retry = cascade! {
+            gtk::Button::with_label(&fl!("button-retry"));
+            ..set_halign(gtk::Align::Start);
+            ..set_no_show_all(true);
+        };
+
+        let container = cascade! {
+            gtk::Box::new(gtk::Orientation::Horizontal, 24);
+            ..set_halign(gtk::Align::Center);
+            ..set_valign(gtk::Align::Center);
+            ..add(&image);
+            ..add(&cascade! {
+                gtk::Box::new(gtk::Orientation::Vertical, 12);
+                ..add(&reason);
+                ..add(&details);
+                ..add(&start);
+                ..add(&retry);
+            });
+            ..show_all();
+        };
+
+        Self { container: container.upcast(), image, reason, details, start, retry }
+    }
+
+    /// Explains why the scan found no firmware, showing the actions which may resolve it.
+    pub fn set_result(&self, result: &ScanResult) {
+        let (icon, reason) = if result.failed() {
+            ("dialog-error-symbolic", fl!("view-scan-failed"))
+        } else if result.fwupd_unavailable {
+            ("dialog-warning-symbolic", fl!("view-fwupd-unavailable"))
+        } else {
+            ("firmware-manager-symbolic", fl!("view-empty"))
+        };
+
+        self.image.set_from_icon_name(Some(icon), gtk::IconSize::Dialog);
+        self.reason.set_text(&reason);
+
+        self.details.set_text(&result.errors.join("\n"));
+        self.details.set_visible(result.failed());
+        self.start.set_visible(result.fwupd_unavailable);
+        self.retry.set_visible(result.failed());
+    }
+
+    /// Programs the action that is triggered when the fwupd service is to be started.
+    pub fn connect_start<F: Fn() + 'static>(&self, func: F) {
+        self.start.connect_clicked(move |_| func());
+    }
+
+    /// Programs the action that is triggered when the failed scan is to be retried.
+    pub fn connect_retry<F: Fn() + 'static>(&self, func: F) {
+        self.retry.connect_clicked(move |_| func());
     }
 }
 
@@ -27,24 +110,32 @@ fn error_view(icon: &str, reason: &str) -> gtk::Container {
         gtk::Box::new(gtk::Orientation::Horizontal, 24);
         ..set_halign(gtk::Align::Center);
         ..set_valign(gtk::Align::Center);
-        ..add(
-            &gtk::Image::builder()
-                .icon_name(icon)
-                .icon_size(gtk::IconSize::Dialog.into())
-                .pixel_size(64)
-                .build()
-        );
-        ..add(&cascade! {
-            gtk::Label::builder()
-                .label(reason)
-                .wrap(true)
-                .xalign(0.0)
-                .yalign(0.0)
-                .build();
-            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
-        });
+        ..add(&error_image(icon));
+        ..add(&error_label(reason));
         ..show_all();
     };
 
     container.upcast::<gtk::Container>()
 }
+
+/// The icon beside the reason of an error view.
+fn error_image(icon: &str) -> gtk::Image {
+    gtk::Image::builder()
+        .icon_name(icon)
+        .icon_size(gtk::IconSize::Dialog.into())
+        .pixel_size(64)
+        .build()
+}
+
+/// The reason that an error view is displayed.
+fn error_label(reason: &str) -> gtk::Label {
+    cascade! {
+        gtk::Label::builder()
+            .label(reason)
+            .wrap(true)
+            .xalign(0.0)
+            .yalign(0.0)
+            .build();
+        ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+    }
+}
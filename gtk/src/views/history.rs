@@ -0,0 +1,157 @@
+use crate::{fl, traits::DynamicGtkResize};
+use firmware_manager::{HistoryEntry, UpdateResult};
+use gtk::prelude::*;
+use std::num::NonZeroU8;
+
+/// View which lists the updates that were attempted, from newest to oldest.
+#[derive(Shrinkwrap)]
+pub struct HistoryView {
+    #[shrinkwrap(main_field)]
+    container: gtk::Container,
+    list: gtk::ListBox,
+    sg: gtk::SizeGroup,
+}
+
+impl HistoryView {
+    pub fn new() -> Self {
+        let placeholder = cascade! {
+            gtk::Label::new(Some(&fl!("history-empty")));
+            ..set_margin_top(24);
+            ..set_margin_bottom(24);
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+            ..show();
+        };
+
+        let list = cascade! {
+            gtk::ListBox::new();
+            ..set_selection_mode(gtk::SelectionMode::None);
+            ..set_placeholder(Some(&placeholder));
+        };
+
+        let title = format!("<b>{}</b>", fl!("history-title"));
+
+        let layout = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 12);
+            ..set_halign(gtk::Align::Center);
+            ..set_margin_top(24);
+            ..set_margin_bottom(24);
+            ..add(&cascade! {
+                gtk::Label::new(Some(&title));
+                ..set_use_markup(true);
+                ..set_xalign(0.0);
+            });
+            ..add(&cascade! {
+                gtk::Frame::new(None);
+                ..add(&list);
+            });
+        };
+
+        let scroll = cascade! {
+            gtk::ScrolledWindow::new(gtk::Adjustment::NONE, gtk::Adjustment::NONE);
+            ..set_hscrollbar_policy(gtk::PolicyType::Never);
+            ..add(&layout);
+            ..show_all();
+        };
+
+        scroll.dynamic_resize(layout, NonZeroU8::new(66), None);
+
+        Self {
+            container: scroll.upcast(),
+            list,
+            sg: gtk::SizeGroup::new(gtk::SizeGroupMode::Horizontal),
+        }
+    }
+
+    /// Programs the action that is triggered when the view is shown.
+    pub fn connect_shown<F: Fn() + 'static>(&self, func: F) {
+        self.container.connect_map(move |_| func());
+    }
+
+    /// Lists the updates in the history, which is ordered from oldest to newest.
+    pub fn set_history(&self, history: &[HistoryEntry]) {
+        self.list.foreach(|row| unsafe {
+            row.destroy();
+        });
+
+        for entry in history.iter().rev() {
+            self.list.add(&self.row(entry));
+        }
+    }
+
+    /// Describes an update by its device, the versions that it was updated between, when it was
+    /// attempted, and its outcome.
+    fn row(&self, entry: &HistoryEntry) -> gtk::ListBoxRow {
+        let device = cascade! {
+            gtk::Label::new(Some(&format!("<b>{}</b>", glib::markup_escape_text(&entry.device))));
+            ..set_use_markup(true);
+            ..set_hexpand(true);
+            ..set_xalign(0.0);
+        };
+
+        let versions = cascade! {
+            gtk::Label::new(Some(&fl!("history-versions", from = &*entry.from, to = &*entry.to)));
+            ..set_xalign(0.0);
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+        };
+
+        let date = cascade! {
+            gtk::Label::new(local_date(entry.timestamp).as_deref());
+            ..set_xalign(1.0);
+        };
+
+        let result = cascade! {
+            gtk::Label::new(Some(&result_text(&entry.result)));
+            ..set_xalign(1.0);
+        };
+
+        match &entry.result {
+            // The reason that the update failed is too long to fit in the row.
+            UpdateResult::Failed(why) => {
+                result.set_tooltip_text(Some(why));
+                result.style_context().add_class(&gtk::STYLE_CLASS_ERROR);
+            }
+            UpdateResult::Interrupted => {
+                result.style_context().add_class(&gtk::STYLE_CLASS_ERROR);
+            }
+            _ => (),
+        }
+
+        self.sg.add_widget(&date);
+
+        cascade! {
+            gtk::ListBoxRow::new();
+            ..set_activatable(false);
+            ..add(&cascade! {
+                gtk::Grid::new();
+                ..set_column_spacing(24);
+                ..set_row_spacing(4);
+                ..set_margin_start(12);
+                ..set_margin_end(12);
+                ..set_margin_top(6);
+                ..set_margin_bottom(6);
+                ..attach(&device, 0, 0, 1, 1);
+                ..attach(&versions, 0, 1, 1, 1);
+                ..attach(&date, 1, 0, 1, 1);
+                ..attach(&result, 1, 1, 1, 1);
+            });
+            ..show_all();
+        }
+    }
+}
+
+/// Formats the date of an update in the local time zone, as the locale of the user formats dates.
+fn local_date(timestamp: u64) -> Option<String> {
+    let time = glib::DateTime::from_unix_local(timestamp as i64).ok()?;
+    time.format("%x").ok().map(String::from)
+}
+
+/// Describes the outcome of an update.
+fn result_text(result: &UpdateResult) -> String {
+    match result {
+        UpdateResult::Success => fl!("history-success"),
+        UpdateResult::Scheduled => fl!("history-scheduled"),
+        UpdateResult::Cancelled => fl!("history-cancelled"),
+        UpdateResult::Failed(_) => fl!("history-failed"),
+        UpdateResult::Interrupted => fl!("history-interrupted"),
+    }
+}
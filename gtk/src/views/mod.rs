@@ -2,8 +2,10 @@
 
 mod devices;
 mod error;
+mod history;
 
 pub use self::{
-    devices::DevicesView,
+    devices::{DeviceGroup, DevicesView},
     error::{EmptyView, PermissionView},
+    history::HistoryView,
 };
@@ -1,6 +1,15 @@
+mod toast;
+
+pub use self::toast::Toast;
+
 use crate::fl;
-use firmware_manager::FirmwareInfo;
+use atk::prelude::*;
+use firmware_manager::{DownloadProgress, FirmwareInfo, PolicyReason};
 use gtk::prelude::*;
+use std::time::Duration;
+
+/// The icon of devices whose kind is not known, or whose icons the icon theme does not have.
+const FALLBACK_ICON: &str = "application-x-firmware";
 
 #[derive(Shrinkwrap)]
 pub struct DeviceWidgetStack {
@@ -8,19 +17,129 @@ pub struct DeviceWidgetStack {
     pub stack: gtk::Stack,
     pub button: gtk::Button,
     pub progress: gtk::ProgressBar,
+    /// The progress bar, with the bytes downloaded beside it and the speed beneath it.
+    pub transfer: gtk::Box,
+    pub transferred: gtk::Label,
+    /// Pauses and stops a download in progress, which are hidden once flashing begins.
+    pub pause: gtk::Button,
+    pub cancel: gtk::Button,
+    pub speed: gtk::Label,
+    /// How much of a paused download was kept, beside the button which resumes it.
+    pub paused: gtk::Box,
+    pub paused_label: gtk::Label,
+    pub resume: gtk::Button,
+    pub failed: gtk::Box,
+    pub failed_label: gtk::Label,
+    pub retry: gtk::Button,
     pub waiting: gtk::Label,
+    /// Shown once the device was updated, until the devices are scanned again.
+    pub updated: gtk::Box,
 }
 
 impl DeviceWidgetStack {
     pub fn switch_to_waiting(&self) {
+        self.waiting.set_text(&fl!("action-waiting"));
+        self.announce(&fl!("action-waiting"));
+        self.stack.set_visible_child(&self.waiting);
+        self.progress.set_fraction(0.0);
+    }
+
+    pub fn switch_to_queued(&self, position: usize) {
+        let queued = fl!("action-queued", position = position + 1);
+        self.waiting.set_text(&queued);
+        self.announce(&queued);
         self.stack.set_visible_child(&self.waiting);
         self.progress.set_fraction(0.0);
     }
 
+    /// Shows that the device was updated, in place of its progress.
+    pub fn switch_to_updated(&self) {
+        self.stack.set_visible_child(&self.updated);
+        self.announce(&fl!("action-updated"));
+    }
+
+    /// Whether the stack shows that the device was updated.
+    pub fn is_updated(&self) -> bool {
+        self.stack.visible_child().as_ref() == Some(self.updated.upcast_ref())
+    }
+
     pub fn switch_to_progress(&self, message: &str) {
-        self.stack.set_visible_child(&self.progress);
+        self.stack.set_visible_child(&self.transfer);
         self.progress.set_text(message.into());
         self.progress.set_fraction(0.0);
+        self.announce(message);
+        self.transferred.set_visible(false);
+        self.speed.set_visible(false);
+        self.set_download_controls(false);
+    }
+
+    /// Shows the progress of a download, which may be paused or cancelled until it completes.
+    pub fn switch_to_download(&self) {
+        self.switch_to_progress(&fl!("action-downloading"));
+        self.set_download_controls(true);
+    }
+
+    /// Shows that a download was paused, with how much of it was downloaded, if it is known.
+    pub fn switch_to_paused(&self, download: Option<&DownloadProgress>) {
+        let text = match download {
+            Some(download) => {
+                fl!("action-paused-transferred", transferred = transferred(download))
+            }
+            None => fl!("action-paused"),
+        };
+
+        self.paused_label.set_text(&text);
+        self.stack.set_visible_child(&self.paused);
+        self.announce(&text);
+    }
+
+    /// Shows why the update of the device failed, with a button which tries it again. The details
+    /// of the error are shown when hovering over the reason.
+    pub fn switch_to_failed(&self, reason: &str, details: &str) {
+        self.failed_label.set_text(reason);
+        self.failed_label.set_tooltip_text(Some(details));
+        self.stack.set_visible_child(&self.failed);
+        self.announce(reason);
+    }
+
+    /// Tells screen readers what the device is now doing, through the description of its
+    /// progress bar, whose changing value they report as it is flashed or downloaded.
+    fn announce(&self, status: &str) {
+        if let Some(accessible) = self.progress.accessible() {
+            accessible.set_description(status);
+        }
+    }
+
+    /// Shows or hides the buttons which pause and stop a download.
+    pub fn set_download_controls(&self, visible: bool) {
+        for button in &[&self.pause, &self.cancel] {
+            button.set_sensitive(true);
+            button.set_visible(visible);
+        }
+    }
+
+    /// Shows the progress of a download, with its speed and the time that remains once they are
+    /// known.
+    pub fn set_download(&self, download: &DownloadProgress) {
+        self.progress.set_fraction(download.fraction());
+        self.transferred.set_text(&transferred(download));
+        self.transferred.set_visible(true);
+
+        let speed = match download.speed() {
+            Some(speed) => fl!("download-speed", speed = &*glib::format_size(speed)),
+            None => return,
+        };
+
+        let text = match download.remaining() {
+            Some(remaining) => {
+                let remaining = remaining_time(remaining);
+                fl!("download-speed-remaining", speed = speed, remaining = remaining)
+            }
+            None => speed,
+        };
+
+        self.speed.set_text(&text);
+        self.speed.set_visible(true);
     }
 }
 
@@ -33,7 +152,10 @@ pub struct DeviceWidget {
     #[shrinkwrap(main_field)]
     pub container: gtk::Container,
     pub event_box: gtk::EventBox,
+    pub icon: gtk::Image,
     pub revealer: gtk::Revealer,
+    pub name: gtk::Label,
+    pub critical: gtk::Label,
     pub label: gtk::Label,
     pub stack: DeviceWidgetStack,
 }
@@ -41,12 +163,28 @@ pub struct DeviceWidget {
 impl DeviceWidget {
     /// Creates a new device widget using the generic information from the firmware manager.
     pub fn new(info: &FirmwareInfo) -> Self {
-        let device = gtk::Label::builder()
+        let icon = gtk::Image::builder()
+            .icon_name(FALLBACK_ICON)
+            .icon_size(gtk::IconSize::Dnd.into())
+            .valign(gtk::Align::Center)
+            .build();
+
+        let name = gtk::Label::builder()
             .label(info.name.as_ref())
             .xalign(0.0)
             .valign(gtk::Align::End)
             .build();
 
+        // Shown by `set_critical()` while a critical update is available.
+        let critical = cascade! {
+            gtk::Label::builder()
+                .label(&fl!("critical-badge"))
+                .valign(gtk::Align::End)
+                .no_show_all(true)
+                .build();
+            ..style_context().add_class(&gtk::STYLE_CLASS_ERROR);
+        };
+
         let label = cascade! {
             gtk::Label::builder()
                 .label(info.current.as_ref())
@@ -66,6 +204,12 @@ impl DeviceWidget {
             ..style_context().add_class(&gtk::STYLE_CLASS_SUGGESTED_ACTION);
         };
 
+        // Updates which the administrator has not approved are shown, but may not be installed.
+        if let Some(reason) = info.policy {
+            button.set_sensitive(false);
+            button.set_tooltip_text(Some(&policy_reason(reason)));
+        }
+
         let progress = cascade! {
             gtk::ProgressBar::builder()
                 .show_text(true)
@@ -76,13 +220,79 @@ impl DeviceWidget {
             ..pulse();
         };
 
+        let speed = cascade! {
+            gtk::Label::builder().no_show_all(true).build();
+            ..style_context().add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+        };
+
+        let transferred = gtk::Label::builder().no_show_all(true).build();
+
+        let pause_tooltip = fl!("button-pause-download");
+        let pause = download_control("media-playback-pause-symbolic", &pause_tooltip);
+        let cancel = download_control("process-stop-symbolic", &fl!("button-cancel-download"));
+
+        let transfer = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 3);
+            ..set_valign(gtk::Align::Center);
+            ..add(&cascade! {
+                gtk::Box::new(gtk::Orientation::Horizontal, 6);
+                ..pack_start(&progress, true, true, 0);
+                ..add(&transferred);
+                ..add(&pause);
+                ..add(&cancel);
+            });
+            ..add(&speed);
+        };
+
+        let paused_label = gtk::Label::new(Some(&fl!("action-paused")));
+        let resume = gtk::Button::with_label(&fl!("button-resume"));
+
+        let paused = cascade! {
+            gtk::Box::new(gtk::Orientation::Horizontal, 12);
+            ..set_halign(gtk::Align::End);
+            ..set_valign(gtk::Align::Center);
+            ..add(&paused_label);
+            ..add(&resume);
+        };
+
+        let failed_label = cascade! {
+            gtk::Label::builder()
+                .wrap(true)
+                .max_width_chars(32)
+                .xalign(1.0)
+                .build();
+            ..style_context().add_class(&gtk::STYLE_CLASS_ERROR);
+        };
+
+        let retry = gtk::Button::with_label(&fl!("button-retry"));
+
+        // Shown in place of the update button when the update fails.
+        let failed = cascade! {
+            gtk::Box::new(gtk::Orientation::Horizontal, 12);
+            ..set_halign(gtk::Align::End);
+            ..set_valign(gtk::Align::Center);
+            ..add(&failed_label);
+            ..add(&retry);
+        };
+
         let waiting = gtk::Label::builder().label(&fl!("action-waiting")).build();
 
+        let updated = cascade! {
+            gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            ..set_halign(gtk::Align::End);
+            ..set_valign(gtk::Align::Center);
+            ..add(&gtk::Image::from_icon_name(Some("emblem-ok-symbolic"), gtk::IconSize::Button));
+            ..add(&gtk::Label::new(Some(&fl!("action-updated"))));
+        };
+
         let stack = cascade! {
             gtk::Stack::new();
             ..add(&button);
-            ..add(&progress);
+            ..add(&transfer);
+            ..add(&paused);
+            ..add(&failed);
             ..add(&waiting);
+            ..add(&updated);
             ..set_visible_child(&button);
         };
 
@@ -122,9 +332,14 @@ impl DeviceWidget {
                     .row_spacing(3)
                     .build();
                 ..attach(&dropdown_image, 0, 0, 1, 2);
-                ..attach(&device, 1, 0, 1, 1);
-                ..attach(&label, 1, 1, 1, 1);
-                ..attach(&stack, 2, 0, 1, 2);
+                ..attach(&icon, 1, 0, 1, 2);
+                ..attach(&cascade! {
+                    gtk::Box::new(gtk::Orientation::Horizontal, 6);
+                    ..add(&name);
+                    ..add(&critical);
+                }, 2, 0, 1, 1);
+                ..attach(&label, 2, 1, 1, 1);
+                ..attach(&stack, 3, 0, 1, 2);
             });
         };
 
@@ -135,15 +350,77 @@ impl DeviceWidget {
             ..add(&event_box);
             ..add(&revealer);
             ..show_all();
+            // The row which holds the widget is what the keyboard focuses.
+            ..connect_parent_set(|container, _| describe_row(container));
         };
 
+        label_accessibles(&info.name, &event_box, &name, &label, &button, &progress);
+
         DeviceWidget {
             container: container.upcast::<gtk::Container>(),
             event_box,
+            icon,
+            name,
+            critical,
             label,
             revealer,
-            stack: DeviceWidgetStack { button, stack, progress, waiting },
+            stack: DeviceWidgetStack {
+                button,
+                stack,
+                progress,
+                transfer,
+                transferred,
+                pause,
+                cancel,
+                speed,
+                paused,
+                paused_label,
+                resume,
+                failed,
+                failed_label,
+                retry,
+                waiting,
+                updated,
+            },
+        }
+    }
+
+    /// Shows the first of the icons which the icon theme has, keeping the current icon if the
+    /// theme has none of them.
+    pub fn set_icon(&self, icons: &[&str]) {
+        let theme = match gtk::IconTheme::default() {
+            Some(theme) => theme,
+            None => return,
+        };
+
+        if let Some(icon) = icons.iter().find(|icon| theme.has_icon(icon)) {
+            self.icon.set_from_icon_name(Some(*icon), gtk::IconSize::Dnd);
+        }
+    }
+
+    /// Marks the device as having a critical update available.
+    pub fn set_critical(&self, critical: bool) { self.critical.set_visible(critical); }
+
+    /// Describes the device to screen readers by its name, the version shown beneath its name,
+    /// and the version which it may be updated to, if an update is available.
+    pub fn describe(&self, latest: Option<&str>) {
+        let version = self.label.text();
+        let version = version.as_str();
+
+        let description = match latest {
+            Some(latest) if self.critical.is_visible() => {
+                fl!("a11y-critical-update", version = version, latest = latest)
+            }
+            Some(latest) => fl!("a11y-update-available", version = version, latest = latest),
+            None => fl!("a11y-up-to-date", version = version),
+        };
+
+        if let Some(accessible) = self.container.accessible() {
+            accessible.set_name(&self.name.text());
+            accessible.set_description(&description);
         }
+
+        describe_row(&self.container);
     }
 
     /// Activates when the widget's container is clicked.
@@ -159,4 +436,107 @@ impl DeviceWidget {
     pub fn connect_upgrade_clicked<F: Fn() + 'static>(&self, func: F) {
         self.stack.button.connect_clicked(move |_| func());
     }
+
+    /// Activates when the button which stops a download is clicked.
+    pub fn connect_cancel_clicked<F: Fn() + 'static>(&self, func: F) {
+        self.stack.cancel.connect_clicked(move |_| func());
+    }
+
+    /// Activates when the button which pauses a download is clicked.
+    pub fn connect_pause_clicked<F: Fn() + 'static>(&self, func: F) {
+        self.stack.pause.connect_clicked(move |_| func());
+    }
+
+    /// Activates when the button which retries a failed update is clicked.
+    pub fn connect_retry_clicked<F: Fn() + 'static>(&self, func: F) {
+        self.stack.retry.connect_clicked(move |_| func());
+    }
+
+    /// Activates when the button which resumes a paused download is clicked.
+    pub fn connect_resume_clicked<F: Fn() + 'static>(&self, func: F) {
+        self.stack.resume.connect_clicked(move |_| func());
+    }
+}
+
+/// Relates the parts of a device widget to the labels which name and describe them, and names its
+/// update button and progress bar after the device, so that each may be told apart from those of
+/// the other devices.
+fn label_accessibles(
+    device: &str,
+    event_box: &gtk::EventBox,
+    name: &gtk::Label,
+    label: &gtk::Label,
+    button: &gtk::Button,
+    progress: &gtk::ProgressBar,
+) {
+    if let (Some(target), Some(name), Some(label)) =
+        (event_box.accessible(), name.accessible(), label.accessible())
+    {
+        target.add_relationship(atk::RelationType::LabelledBy, &name);
+        target.add_relationship(atk::RelationType::DescribedBy, &label);
+        name.add_relationship(atk::RelationType::LabelFor, &target);
+        label.add_relationship(atk::RelationType::DescriptionFor, &target);
+    }
+
+    if let Some(accessible) = button.accessible() {
+        accessible.set_name(&fl!("a11y-update", device = device));
+    }
+
+    if let Some(accessible) = progress.accessible() {
+        accessible.set_name(&fl!("a11y-progress", device = device));
+    }
+}
+
+/// Gives the row which holds a device widget the name and description of the widget.
+fn describe_row(container: &impl IsA<gtk::Widget>) {
+    let row = container.parent().and_then(|row| row.accessible());
+    if let (Some(row), Some(accessible)) = (row, container.accessible()) {
+        if let Some(name) = accessible.name() {
+            row.set_name(&name);
+        }
+
+        if let Some(description) = accessible.description() {
+            row.set_description(&description);
+        }
+    }
+}
+
+/// A button which controls a download, shown only while downloading.
+fn download_control(icon: &str, tooltip: &str) -> gtk::Button {
+    cascade! {
+        gtk::Button::from_icon_name(Some(icon), gtk::IconSize::Button);
+        ..set_tooltip_text(Some(tooltip));
+        ..set_valign(gtk::Align::Center);
+        ..set_no_show_all(true);
+    }
+}
+
+/// Describes how much of a download has been downloaded, in the units of the desktop.
+fn transferred(download: &DownloadProgress) -> String {
+    let downloaded = glib::format_size(download.downloaded);
+    if download.total == 0 {
+        return downloaded.to_string();
+    }
+
+    let total = glib::format_size(download.total);
+    fl!("download-transferred", downloaded = &*downloaded, total = &*total)
+}
+
+/// Describes roughly how long a download will take to complete.
+fn remaining_time(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+    if seconds < 60 {
+        // Counting down each second is distracting, so seconds are rounded up to the next five.
+        fl!("remaining-seconds", seconds = ((seconds + 4) / 5 * 5).max(5))
+    } else {
+        fl!("remaining-minutes", minutes = (seconds + 59) / 60)
+    }
+}
+
+/// Describes why the firmware policy of the system forbids an update.
+fn policy_reason(reason: PolicyReason) -> String {
+    match reason {
+        PolicyReason::DeviceNotApproved => fl!("policy-device-not-approved"),
+        PolicyReason::VersionNotApproved => fl!("policy-version-not-approved"),
+    }
 }
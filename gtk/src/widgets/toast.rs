@@ -0,0 +1,89 @@
+use crate::fl;
+use gtk::prelude::*;
+use std::{cell::Cell, rc::Rc};
+
+/// How long a toast is shown before it is dismissed.
+const TOAST_SECONDS: u32 = 5;
+
+/// A transient message which slides in over the bottom of the window, and is dismissed after a
+/// few seconds, or once its close button is clicked.
+#[derive(Shrinkwrap)]
+pub struct Toast {
+    #[shrinkwrap(main_field)]
+    revealer: gtk::Revealer,
+    label: gtk::Label,
+    /// The timeout which dismisses the toast, which is replaced when another message is shown.
+    timeout: Rc<Cell<Option<glib::SourceId>>>,
+}
+
+impl Toast {
+    pub fn new() -> Self {
+        let label = gtk::Label::builder().wrap(true).build();
+
+        let close = cascade! {
+            gtk::Button::from_icon_name(Some("window-close-symbolic"), gtk::IconSize::Button);
+            ..set_tooltip_text(Some(&fl!("button-dismiss")));
+            ..set_relief(gtk::ReliefStyle::None);
+        };
+
+        let revealer = cascade! {
+            gtk::Revealer::new();
+            ..set_halign(gtk::Align::Center);
+            ..set_valign(gtk::Align::End);
+            ..set_margin_bottom(24);
+            ..set_transition_type(gtk::RevealerTransitionType::SlideUp);
+            ..add(&cascade! {
+                gtk::Frame::new(None);
+                ..style_context().add_class("app-notification");
+                ..add(&cascade! {
+                    gtk::Box::new(gtk::Orientation::Horizontal, 12);
+                    ..add(&label);
+                    ..add(&close);
+                });
+            });
+        };
+
+        let timeout = Rc::new(Cell::new(None::<glib::SourceId>));
+
+        {
+            let revealer = revealer.downgrade();
+            let timeout = timeout.clone();
+            close.connect_clicked(move |_| {
+                if let Some(timeout) = timeout.take() {
+                    timeout.remove();
+                }
+
+                if let Some(revealer) = revealer.upgrade() {
+                    revealer.set_reveal_child(false);
+                }
+            });
+        }
+
+        Self { revealer, label, timeout }
+    }
+
+    /// Shows the `message`, in place of any message which is already shown.
+    pub fn show_message(&self, message: &str) {
+        if let Some(timeout) = self.timeout.take() {
+            timeout.remove();
+        }
+
+        self.label.set_text(message);
+        self.revealer.set_reveal_child(true);
+
+        let revealer = self.revealer.downgrade();
+        let timeout = self.timeout.clone();
+        let source = glib::timeout_add_seconds_local(TOAST_SECONDS, move || {
+            // The source is removed once this returns, so it must not be removed again.
+            timeout.take();
+
+            if let Some(revealer) = revealer.upgrade() {
+                revealer.set_reveal_child(false);
+            }
+
+            glib::Continue(false)
+        });
+
+        self.timeout.set(Some(source));
+    }
+}
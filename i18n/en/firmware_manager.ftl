@@ -0,0 +1,21 @@
+error-backend = error in {$backend} backend
+error-branch = no firmware is published on the {$branch} branch for this device
+error-bundle = failed to process the offline bundle
+error-channel = the {$backend} backend does not publish {$channel} releases
+error-download = failed to download firmware
+error-esp = failed to stage the update on the EFI System Partition
+error-fwupd-client = error in fwupd client
+error-fwupd-start = failed to start the fwupd daemon
+error-host-security = failed to get the host security attributes from fwupd
+error-open = failed to open firmware file for flashing
+error-permission = not authorized to update firmware
+error-policy = firmware version {$version} may not be installed
+error-recovery = failed to create a recovery drive
+error-release = firmware version {$version} is not available for this device
+error-remote = failed to modify the {$remote} fwupd remote
+error-schedule = failed to schedule system firmware update
+error-space = insufficient disk space for firmware update
+error-system76-client = error in system76-firmware client
+error-thelio-io-board = {$board} was not updated, and reports revision {$revision}
+error-unknown-remote = there is no fwupd remote named {$remote}
+error-verification = firmware at {$path} failed checksum verification
@@ -1,29 +1,291 @@
+a11y-critical-update = Version {$version}. A critical update to {$latest} is available.
+a11y-progress = Progress of {$device}
+a11y-up-to-date = Version {$version}. Up to date.
+a11y-update = Update {$device}
+a11y-update-available = Version {$version}. An update to {$latest} is available.
+
+ac-required-title = Connect your computer to power
+ac-required-body = Firmware may not be updated while your computer is running on battery, as it could lose power during the update. Connect your computer to power to continue.
+
 action-downloading = Downloading
 action-scheduling = Scheduling
 action-flashing = Flashing
+action-queued = Queued ({$position})
+action-paused = Paused
+action-paused-transferred = Paused at {$transferred}
+action-verifying = Verifying
 action-waiting = Waiting
+action-updated = Updated
+
+attestation-failed = The measurements of this firmware do not match those published for it. It may not have been installed as published, or it may have been tampered with.
+attestation-passed = The measurements of this firmware match those published for it.
+
+branch-switch-title = Switch to the {$branch} firmware branch?
+branch-warning-unsupported = Firmware on this branch may not be provided by the vendor of the device, who may no longer support the device once it is installed.
+branch-warning-secure-boot = Secure Boot is enabled. Firmware on this branch may not support Secure Boot, or may not trust the keys that it was set up with, so operating systems which require it may not start.
+branch-warning-system-firmware = This is system firmware. If the firmware on this branch is faulty, it may not be possible to switch back, or to start the computer.
 
 button-cancel = Cancel
+button-cancel-download = Stop downloading
+button-continue = Continue
+button-dismiss = Dismiss
+button-dont-send = Don't Send
+button-downgrade = Downgrade
+button-downgrade-ellipsis = Downgrade…
+button-download-anyway = Download Anyway
+button-ignore = Hide This Device
+button-install-on-reboot = Install on Next Restart
+button-install-version = Install
+button-pause-download = Pause downloading
 button-reboot-and-install = Reboot and Install
+button-remind = Remind Me Later
+button-rescan = Check for Updates Again
+button-resume = Resume
+button-retry = Try Again
+button-send-report = Send Report
+button-start-fwupd = Start Firmware Service
+button-switch-branch = Switch to {$branch}
 button-update = Update
+button-update-all = Update All
+button-update-dock = Update Dock
+button-update-group = Update Together
+button-updatable-only = Updates Only
+
+capsule-secure-boot-title = Update {$device} while Secure Boot is enabled?
+capsule-secure-boot-body = The firmware will only install this update if it is signed by the manufacturer of the system. Otherwise the update will be skipped when the system restarts.
+
+channel-beta = {$version} (beta)
+channel-testing = {$version} (testing)
 
 changelog = Changelog
 changelog-unavailable = No changelog available
+changelog-fixes = Fixes:
+changelog-downgrade = Older version
+changelog-fix = {$id} ({$severity})
+changelog-install-version = Install version
+changelog-list-number = {$number}.
+changelog-pending = {$releases ->
+    [one] 1 release
+   *[other] {$releases} releases
+} since {$version}
+changelog-pending-fixes = {$releases ->
+    [one] 1 release
+   *[other] {$releases} releases
+}, {$fixes ->
+    [one] 1 security fix
+   *[other] {$fixes} security fixes
+} since {$version}
+changelog-earlier = Earlier Releases
+changelog-ec = Includes embedded controller firmware {$version}.
+changelog-me = Includes Intel Management Engine firmware {$version}.
+
+critical-badge = Critical Update
+
+deferred-offline = Update deferred: no internet connection
+deferred-metered = Update deferred: metered connection
+
+details-title = Details
+details-device-id = Device ID
+details-guids = GUIDs
+details-plugin = Plugin
+details-flags = Flags
+details-vendor-id = Vendor ID
+details-serial = Serial Number
+
+dock-unnamed = Dock
+
+downgrade-title = Downgrade {$device} to {$version}?
+downgrade-warning-security = Older firmware may lack fixes for security issues which the installed firmware fixes, leaving this computer at risk. Only downgrade if the installed firmware causes problems.
+downgrade-warning-system-firmware = This is system firmware. If the older firmware is faulty, the computer may no longer start, and it may not be possible to update it again.
+
+download-speed = {$speed}/s
+download-speed-remaining = {$speed} — {$remaining}
+download-transferred = {$downloaded} of {$total}
+
+ec-bundled = {$current} ({$latest} with the system firmware update)
+
+error-branch = No firmware is available on the requested branch.
+error-bundle = The offline firmware bundle could not be processed.
+error-channel = The chosen release channel is not available for this firmware.
+error-daemon = The firmware service could not complete the request.
+error-download = Firmware could not be downloaded. Check your internet connection and try again.
+error-esp-not-mounted = The EFI System Partition is not mounted. Mount it at /boot/efi and try again.
+error-esp-read-only = The EFI System Partition is mounted read-only. Remount it with write access and try again.
+error-esp-space = There is not enough space on the EFI System Partition. Remove old kernels or boot entries from it and try again.
+error-fwupd-start = The firmware service could not be started.
+error-ignore = The device could not be hidden.
+error-open = The downloaded firmware could not be opened.
+error-permission = You are not authorized to update firmware.
+error-policy = Your administrator has not approved this firmware for installation.
+error-recovery = The recovery drive could not be created.
+error-release = The requested firmware version is no longer available.
+error-schedule = System firmware could not be scheduled for installation.
+error-sort = The order of the devices could not be saved.
+error-space = There is not enough disk space to update the firmware.
+error-verification = The downloaded firmware is corrupted. Try updating again.
+
+group-unnamed = Composite Device
 
 header-system-firmware = System Firmware
-header-device-firmware = Device Firmware
+header-input = Input Devices
+header-storage = Storage
+header-docks = Docks
+header-other = Other Devices
 header-firmware-update = Firmware Update
 
+history-cancelled = Cancelled
+history-empty = No firmware updates have been attempted.
+history-failed = Failed
+history-interrupted = Interrupted
+history-scheduled = Scheduled
+history-success = Updated
+history-title = Update History
+history-versions = {$from} → {$to}
+
+interrupted-update = The update of {$device} from {$from} to {$to} was interrupted.
+interrupted-retry = Close this message to rescan your devices and check their installed firmware. If an update is still available, update the device again.
+
+last-checked = Last checked: {$time}
+
+microcode-pending = {$current} ({$latest} loads on restart)
+
+metered-title = Download firmware on a metered connection?
+metered-body = Your network connection is metered. Downloading firmware may incur additional charges.
+
+menu-collapse-all = Collapse All Changelogs
+menu-expand-all = Expand All Changelogs
+menu-remind-later = In 4 Hours
+menu-remind-tomorrow = Tomorrow
+menu-tooltip = More Actions
+
+next-check = Next automatic check: {$time}
+next-check-disabled = Automatic checks are not scheduled
+
+notification-failed-title = Firmware update failed
+notification-failed-body = {$device} could not be updated: {$reason}
+notification-updated-title = Firmware updated
+
+open-firmware-title = Switch to System76 Open Firmware
+open-firmware-eligibility-title = Eligibility
+open-firmware-eligibility = This assistant switches the system firmware to System76 Open Firmware, which is based on coreboot. The system must pass these checks before continuing.
+open-firmware-check-available = Open firmware is available for this model.
+open-firmware-check-unsupported = Open firmware is not available for this model.
+open-firmware-check-already-open = This system already runs open firmware.
+open-firmware-check-ac = The system is connected to AC power.
+open-firmware-check-battery = The system is running on battery. Connect it to AC power to continue.
+open-firmware-backup-title = Back Up Your Data
+open-firmware-backup = Back up any data that you can not afford to lose before continuing. If the new firmware fails to start, the system may need to be recovered before it can start again.
+open-firmware-backup-confirm = I have backed up my data
+open-firmware-confirm-title = Confirm
+open-firmware-confirm = I understand the risks of switching to open firmware
+
+page-devices = Devices
+page-history = History
+
+policy-device-not-approved = Your administrator has not approved firmware updates for this device.
+policy-version-not-approved = Your administrator has not approved this firmware version.
+
+read-only-update = {$version} ({$latest} is available through fwupd)
+read-only-version = {$version} (not updatable here)
+
+remaining-seconds = about {$seconds} seconds left
+remaining-minutes = about {$minutes ->
+    [one] 1 minute
+   *[other] {$minutes} minutes
+} left
+
+replug-title = Reconnect {$device} to finish updating
+replug-body = The new firmware has been installed, and takes effect once the device is unplugged and plugged in again. If the device powers your computer, save your work first.
+replug-version = {$version} (reconnect to finish)
+
+report-title = Send a report of this update?
+report-body = Reports of whether firmware was installed help vendors to find and fix faulty firmware. The report of {$device} is sent anonymously to the Linux Vendor Firmware Service, along with the versions of its firmware and any error that occurred. Your choice will be remembered for future updates.
+
+scan-fwupd-unavailable = The firmware service is not running, so the firmware of most devices was not checked.
+scan-incomplete = The firmware of some devices could not be checked.
+
+search-placeholder = Search devices
+
+security-title = Security (HSI:{$level})
+
+severity-low = Low
+severity-medium = Medium
+severity-high = High
+severity-critical = Critical
+
+sort-tooltip = Order of the devices
+sort-name = Sort by Name
+sort-vendor = Sort by Vendor
+sort-upgradeable-first = Updates First
+
+staged-reboot = Firmware will be installed the next time you restart your computer.
+staged-version = {$version} (installs on restart)
+
+summary-title = System
+summary-vendor = Manufacturer
+summary-product = Model
+summary-serial = Serial Number
+summary-bios-date = Firmware Date
+summary-secure-boot = Secure Boot
+summary-secure-boot-disabled = Disabled
+summary-secure-boot-enabled = Enabled
+summary-secure-boot-setup = Setup Mode
+
+time-just-now = just now
+time-minutes-ago = {$minutes ->
+    [one] 1 minute ago
+   *[other] {$minutes} minutes ago
+}
+time-hours-ago = {$hours ->
+    [one] 1 hour ago
+   *[other] {$hours} hours ago
+}
+time-days-ago = {$days ->
+    [one] 1 day ago
+   *[other] {$days} days ago
+}
+
+toast-updated = {$device} firmware updated to {$version}
+toast-updated-unknown = {$device} firmware updated
+
+update-all-progress = Updated {$done} of {$total} devices
+
 update-available = Firmware version {$version} is available.
 
+update-critical = Firmware version {$version} is a <b>critical</b> update, which its vendor recommends installing as soon as possible.
+
 update-connect-to-ac = Connect your computer to power. <b>USB Type-C</b> charging is not supported for firmware updates.
 
 update-guide = After the firmware update is complete, it may be necessary to press the power button more than once. See {$url_tag_start}this support article{$url_tag_end} for more information.
 
+update-summary-size = Download size
+update-summary-duration = Estimated time
+update-summary-duration-minutes = about {$minutes ->
+    [one] 1 minute
+   *[other] {$minutes} minutes
+}
+update-summary-restart = Restart
+update-summary-restart-immediate = Your computer restarts to install the update
+update-summary-restart-next-boot = Installs the next time you restart your computer
+update-summary-power = Power
+update-summary-power-ac = Connected to power
+update-summary-power-battery = Running on battery
+
+verification-failed-title = Firmware failed signature verification
+verification-failed-body = The firmware was not installed, as it could not be verified to come from its vendor. It may have been tampered with.
+
 view-empty = Managed Firmware Unavailable
 
  No devices supporting automatic firmware updates detected.
 
+view-fwupd-unavailable = Firmware Service Not Running
+
+ The fwupd service, which updates the firmware of most devices, is not running. Start it to find firmware for your devices.
+
 view-permission = Permission Required
 
  Only administrator accounts may update firmware.
+
+view-scan-failed = Firmware Could Not Be Checked
+
+ The firmware of your devices could not be checked.
@@ -1,4 +1,6 @@
 summary = Firmware updates are available.
 body = Click here to install them.
+body-lvfs = Start the fwupd service to install them.
 
-error-fwupd = Failed to update fwupd remotes
\ No newline at end of file
+error-fwupd = Failed to update fwupd remotes
+error-lvfs = Failed to check the LVFS for updates
\ No newline at end of file
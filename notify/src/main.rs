@@ -1,6 +1,6 @@
 mod localize;
 
-use firmware_manager::{get_client, FirmwareSignal, FwupdError, FwupdSignal};
+use firmware_manager::{get_client, DeviceSignal, FirmwareSignal, FwupdError, FwupdSignal};
 use i18n_embed::DesktopLanguageRequester;
 use notify_rust::{Notification, Timeout};
 use std::{
@@ -13,7 +13,8 @@ const UPDATES_FOUND: i32 = 3;
 const GNOME_CONTROL_CENTER: &str = "/usr/share/applications/gnome-firmware-panel.desktop";
 
 use firmware_manager::{
-    fwupd_scan, fwupd_updates, s76_firmware_is_active, s76_scan, FwupdClient, System76Client,
+    fwupd_scan, fwupd_updates, lvfs_scan, s76_firmware_is_active, s76_scan, FwupdClient,
+    System76Client,
 };
 
 fn main() {
@@ -23,6 +24,10 @@ fn main() {
         return;
     }
 
+    if let Err(why) = firmware_manager::refresh_check_timer() {
+        eprintln!("failed to refresh the check timer: {}", why);
+    }
+
     let s76 = get_client("system76", s76_firmware_is_active, System76Client::new);
 
     let fwupd = get_client::<_, _, FwupdError>(
@@ -35,42 +40,69 @@ fn main() {
         },
     );
 
+    let config = firmware_manager::config();
     let (tx, rx) = std::sync::mpsc::channel();
 
     if let Some(ref client) = s76 {
         s76_scan(client, tx.clone());
     }
 
-    if let Some(ref client) = fwupd {
-        if let Err(why) = fwupd_updates(client) {
-            eprintln!("{}: {}", fl!("error-fwupd"), why);
+    match fwupd {
+        Some(ref client) => {
+            if let Err(why) = fwupd_updates(client) {
+                eprintln!("{}: {}", fl!("error-fwupd"), why);
+            }
+
+            fwupd_scan(client, tx.clone());
         }
+        // Updates may still be found, but can not be installed until fwupd is running.
+        None if config.lvfs_fallback => {
+            if let Err(why) = lvfs_scan(&tx) {
+                eprintln!("{}: {}", fl!("error-lvfs"), why);
+            }
+        }
+        None => (),
+    }
 
-        fwupd_scan(client, tx);
+    drop(tx);
+
+    if let Err(why) = firmware_manager::record_check() {
+        eprintln!("failed to record the time of the check: {}", why);
     }
 
     for message in rx {
+        if config.ignores(&message) || config.snoozes(&message) {
+            continue;
+        }
+
         match message {
-            FirmwareSignal::Fwupd(FwupdSignal { upgradeable, .. }) => {
-                if upgradeable {
-                    notify();
+            FirmwareSignal::Fwupd(FwupdSignal { info, upgradeable, .. }) => {
+                if upgradeable && info.policy.is_none() {
+                    notify(&fl!("body"));
                 }
             }
-            FirmwareSignal::S76System(info, ..) | FirmwareSignal::ThelioIo(info, ..) => {
+            FirmwareSignal::S76System(info, ..) | FirmwareSignal::ThelioIo(_, info, ..)
+                if info.policy.is_none() =>
+            {
                 if info.latest.as_ref().map_or(false, |latest| latest.as_ref() != info.current.as_ref())
                 {
-                    notify();
+                    notify(&fl!("body"));
                 }
             }
+            FirmwareSignal::Device(DeviceSignal { info, .. })
+                if info.policy.is_none() && info.latest.is_some() =>
+            {
+                notify(&fl!("body-lvfs"));
+            }
             _ => (),
         }
     }
 }
 
-fn notify() {
+fn notify(body: &str) {
     Notification::new()
         .summary(&fl!("summary"))
-        .body(&fl!("body"))
+        .body(body)
         .icon("firmware-manager")
         .appname("firmware-manager")
         .action("default", "default")
@@ -102,4 +134,8 @@ fn translate() {
     if let Err(error) = localizer.select(&requested_languages) {
         eprintln!("Error while loading languages for firmware-manager-notify {}", error);
     }
+
+    if let Err(error) = firmware_manager::localizer().select(&requested_languages) {
+        eprintln!("Error while loading languages for firmware-manager {}", error);
+    }
 }
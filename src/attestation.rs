@@ -0,0 +1,194 @@
+//! Checks the measurements of system firmware once an update of it has been installed.
+//!
+//! The TPM measures the system firmware into PCR0 each time that the system boots, and fwupd
+//! records these measurements as the checksums of the system firmware device. The LVFS may publish
+//! the measurements which are expected of a release, in which case fwupd is able to verify that
+//! the firmware which booted is the firmware which was installed. A mismatch may indicate that the
+//! update was not installed as published, or that the firmware was tampered with.
+//!
+//! The measurements are compared once, on the first boot after the update, and the outcome is
+//! kept until another version is installed, as fwupd would otherwise be asked to verify the
+//! device on every scan.
+
+use crate::{fwupd::FWUPD_DEST, history};
+use dbus::blocking::Connection;
+use fwupd_dbus::Device as FwupdDevice;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+/// Describes the system since it last booted, including when it booted.
+const PROC_STAT: &str = "/proc/stat";
+
+/// Serializes modifications of the attestations, as devices may be scanned concurrently.
+static ATTESTATIONS: Lazy<Mutex<()>> = Lazy::new(Mutex::default);
+
+/// The outcome of comparing the measurements of system firmware to those expected of it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Attestation {
+    /// The measurements match those expected of the installed release.
+    Passed,
+    /// The measurements differ from those expected of the installed release, as explained by
+    /// fwupd.
+    Failed(Box<str>),
+}
+
+/// The outcome of the attestation of a version of a device's firmware.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    device: Box<str>,
+    version: Box<str>,
+    /// `None` if the measurements which are expected of the version are not known.
+    attestation: Option<Attestation>,
+}
+
+/// How fwupd failed to verify the measurements of a device.
+#[derive(Debug, PartialEq, Eq)]
+enum VerifyError {
+    /// There are no measurements to compare against.
+    Unavailable,
+    /// The measurements differ from those expected, as explained by fwupd.
+    Mismatch(Box<str>),
+    /// fwupd could not be asked, which may succeed when it is next asked.
+    Other(Box<str>),
+}
+
+impl From<dbus::Error> for VerifyError {
+    fn from(why: dbus::Error) -> Self {
+        let message = Box::from(why.message().unwrap_or_default());
+        match why.name().and_then(|name| name.strip_prefix("org.freedesktop.fwupd.")) {
+            Some("NotSupported") | Some("NothingToDo") => VerifyError::Unavailable,
+            Some(_) => VerifyError::Mismatch(message),
+            None => VerifyError::Other(message),
+        }
+    }
+}
+
+/// Verifies the measurements of system firmware, if it was updated before the system last booted.
+///
+/// Returns `None` if the update has not been installed, or if the measurements which are expected
+/// of the installed release are not known.
+pub(crate) fn attest(device: &FwupdDevice, name: &str) -> Option<Attestation> {
+    if !device.needs_reboot() {
+        return None;
+    }
+
+    let mut entries = read();
+    if let Some(entry) = entries.iter().find(|entry| is_entry(entry, name, &device.version)) {
+        return entry.attestation.clone();
+    }
+
+    let update = history::last_update(name).ok()??;
+    if update.to != device.version || update.timestamp >= boot_time()? {
+        return None;
+    }
+
+    let attestation = match verify(&device.device_id) {
+        Ok(()) => Some(Attestation::Passed),
+        Err(VerifyError::Unavailable) => None,
+        Err(VerifyError::Mismatch(why)) => {
+            warn!("attestation of {} {} failed: {}", name, device.version, why);
+            Some(Attestation::Failed(why))
+        }
+        Err(VerifyError::Other(why)) => {
+            warn!("failed to verify the measurements of system firmware: {}", why);
+            return None;
+        }
+    };
+
+    // Only the attestation of the installed version is kept.
+    entries.retain(|entry| &*entry.device != name);
+    entries.push(Entry {
+        device: name.into(),
+        version: device.version.clone(),
+        attestation: attestation.clone(),
+    });
+
+    if let Err(why) = write(&entries) {
+        warn!("failed to record the attestation of {}: {}", name, why);
+    }
+
+    attestation
+}
+
+fn is_entry(entry: &Entry, device: &str, version: &str) -> bool {
+    &*entry.device == device && &*entry.version == version
+}
+
+/// Asks fwupd to compare the measurements of the device to those expected of its firmware.
+fn verify(device_id: &str) -> Result<(), VerifyError> {
+    let connection = Connection::new_system().map_err(VerifyError::from)?;
+    let proxy = connection.with_proxy(FWUPD_DEST, "/", Duration::from_secs(30));
+    proxy.method_call(FWUPD_DEST, "Verify", (device_id,)).map_err(VerifyError::from)
+}
+
+/// The attestations which have been made, which are discarded if they cannot be read.
+fn read() -> Vec<Entry> {
+    let _lock = ATTESTATIONS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let contents = match attestations_path().and_then(fs::read_to_string) {
+        Ok(contents) => contents,
+        Err(why) if why.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(why) => {
+            warn!("failed to read the attestations of system firmware: {}", why);
+            return Vec::new();
+        }
+    };
+
+    serde_json::from_str(&contents).unwrap_or_else(|why| {
+        warn!("discarding malformed attestations of system firmware: {}", why);
+        Vec::new()
+    })
+}
+
+fn write(entries: &[Entry]) -> io::Result<()> {
+    let _lock = ATTESTATIONS.lock().unwrap_or_else(PoisonError::into_inner);
+    let contents = serde_json::to_vec(entries).map_err(io::Error::from)?;
+    fs::write(attestations_path()?, contents)
+}
+
+/// The location of the attestations, in the XDG data directory.
+fn attestations_path() -> io::Result<PathBuf> {
+    xdg::BaseDirectories::with_prefix("com.system76.FirmwareManager")
+        .map_err(|why| io::Error::new(io::ErrorKind::Other, why))?
+        .place_data_file("attestations.json")
+}
+
+/// When the system last booted, in seconds since the UNIX epoch.
+fn boot_time() -> Option<u64> { btime(&fs::read_to_string(PROC_STAT).ok()?) }
+
+/// Parses the boot time from `/proc/stat`.
+fn btime(stat: &str) -> Option<u64> {
+    stat.lines().find_map(|line| line.strip_prefix("btime ")?.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VerifyError;
+    use dbus::Error;
+
+    #[test]
+    fn btime() {
+        let stat = "cpu  2255 34 2290 22625563 6290 127 456 0 0 0\nbtime 1697462400\n";
+        assert_eq!(super::btime(stat), Some(1_697_462_400));
+        assert_eq!(super::btime("cpu  2255 34 2290\n"), None);
+    }
+
+    #[test]
+    fn verify_error() {
+        let unavailable = Error::new_custom("org.freedesktop.fwupd.NothingToDo", "No checksums");
+        assert_eq!(VerifyError::from(unavailable), VerifyError::Unavailable);
+
+        let mismatch = "For UEFI Firmware 1.2.3 expected abc, got def";
+        let not_found = Error::new_custom("org.freedesktop.fwupd.NotFound", mismatch);
+        assert_eq!(VerifyError::from(not_found), VerifyError::Mismatch(mismatch.into()));
+
+        let no_reply = Error::new_custom("org.freedesktop.DBus.Error.NoReply", "timed out");
+        assert_eq!(VerifyError::from(no_reply), VerifyError::Other("timed out".into()));
+    }
+}
@@ -0,0 +1,366 @@
+//! A generic interface for the firmware backends managed by the event loop.
+
+use crate::{
+    history, inhibit, journal, Entity, FirmwareError, FirmwareEvent, FirmwareInfo, FirmwareSignal,
+};
+use std::{
+    sync::{
+        mpsc::{self, Sender},
+        PoisonError, RwLock, RwLockReadGuard,
+    },
+    thread,
+};
+use tracing::Span;
+
+/// A source of firmware devices which the event loop may scan and update.
+///
+/// The fwupd and System76 backends are implemented with this trait, and additional backends
+/// may be registered at runtime with [`Backends::register`].
+///
+/// Backends are shared by the worker threads, so that several devices may be updated at once.
+/// A backend must guard any state which can not be shared, such as a connection to its daemon.
+///
+/// The interface is deliberately narrow: a backend only scans and updates. The releases of a
+/// device are listed in the signal which [`FirmwareBackend::scan`] sends for it, as they are
+/// fetched along with the device. Downloading and flashing are the stages of
+/// [`FirmwareBackend::update`], which reports progress as signals through its sender, such as
+/// [`FirmwareSignal::DownloadUpdate`] and [`FirmwareSignal::DeviceFlashing`], rather than
+/// through separate methods and callbacks. Those stages differ between backends, as daemons such
+/// as fwupd download and flash in a single request, and some backends have nothing to download.
+pub trait FirmwareBackend: Send + Sync {
+    /// The name of the backend, which is used when logging.
+    fn name(&self) -> &str;
+
+    /// Scans for devices, sending a signal for every device that was discovered.
+    fn scan(&self, sender: &Sender<FirmwareSignal>);
+
+    /// Downloads and flashes the firmware requested by the `event`.
+    ///
+    /// Download and flashing progress is reported through the `sender` as the update proceeds,
+    /// and the signal that is returned is the result of the update. If the event is not meant
+    /// for this backend, `None` is returned.
+    fn update(&self, event: &FirmwareEvent, sender: &Sender<FirmwareSignal>)
+        -> Option<FirmwareSignal>;
+}
+
+/// Identifies a device which was discovered by a backend other than fwupd or System76.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct DeviceId {
+    /// The name of the backend which discovered the device.
+    pub backend: Box<str>,
+    /// An identifier of the device, which is unique within its backend.
+    pub id: Box<str>,
+}
+
+impl DeviceId {
+    /// The kind of device which the backend discovers.
+    pub fn category(&self) -> DeviceCategory {
+        match &*self.backend {
+            "capsule" => DeviceCategory::System,
+            "launch" => DeviceCategory::Input,
+            "nvme" => DeviceCategory::Storage,
+            _ => DeviceCategory::Other,
+        }
+    }
+}
+
+/// The kind of a device, by which frontends list devices in sections.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeviceCategory {
+    /// System firmware, the firmware which is updated along with it, and the boards which are
+    /// built into the system.
+    System,
+    /// Keyboards, mice, touchpads, and other input devices.
+    Input,
+    /// Drives and other storage devices.
+    Storage,
+    /// Docks, and the devices within them.
+    Dock,
+    /// Devices of any other kind.
+    Other,
+}
+
+impl DeviceCategory {
+    /// Every category, in the order that their sections are listed in.
+    pub const ALL: [DeviceCategory; 5] = [
+        DeviceCategory::System,
+        DeviceCategory::Input,
+        DeviceCategory::Storage,
+        DeviceCategory::Dock,
+        DeviceCategory::Other,
+    ];
+}
+
+/// A device which was discovered by a backend other than fwupd or System76.
+///
+/// Updates for the device are requested with [`FirmwareEvent::Device`].
+#[derive(Clone, Debug)]
+pub struct DeviceSignal {
+    /// Identifies the device when requesting it to be updated.
+    pub device: DeviceId,
+    /// Information about the device and its firmware.
+    pub info: FirmwareInfo,
+    /// The firmware is only reported, and can not be updated through the firmware manager, such
+    /// as firmware which is updated with the system firmware or by another tool.
+    pub read_only: bool,
+}
+
+/// The outcome of a scan, which explains why firmware may be missing from it.
+///
+/// Some backends, such as the microcode backend, find firmware on every system, so whether the
+/// fwupd daemon was unavailable, and whether other backends failed, is reported even when firmware
+/// was found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanResult {
+    /// Firmware was found on at least one device.
+    pub found: bool,
+    /// The fwupd daemon, which manages most devices, is not running, so its devices were not
+    /// scanned. It may be started with [`FirmwareEvent::StartFwupd`].
+    pub fwupd_unavailable: bool,
+    /// The errors that were reported by the backends which failed to scan.
+    pub errors: Vec<Box<str>>,
+}
+
+impl ScanResult {
+    /// Checks if a backend failed to scan.
+    pub fn failed(&self) -> bool { !self.errors.is_empty() }
+}
+
+/// All of the backends that the event loop is managing.
+///
+/// Backends are registered before the event loop starts, except for the fwupd backend, which is
+/// registered later if the fwupd daemon is started with [`FirmwareEvent::StartFwupd`].
+#[derive(Default)]
+pub struct Backends {
+    backends: RwLock<Vec<Box<dyn FirmwareBackend>>>,
+}
+
+impl Backends {
+    /// Checks if there are no registered backends.
+    pub fn is_empty(&self) -> bool { self.backends().is_empty() }
+
+    /// Checks if a backend with the given name is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.backends().iter().any(|backend| backend.name() == name)
+    }
+
+    /// Registers a new backend.
+    pub fn register<B: FirmwareBackend + 'static>(&mut self, backend: B) {
+        self.register_boxed(Box::new(backend));
+    }
+
+    /// Registers a backend which has already been boxed.
+    pub fn register_boxed(&mut self, backend: Box<dyn FirmwareBackend>) {
+        info!("registering {} firmware backend", backend.name());
+        self.backends.get_mut().unwrap_or_else(PoisonError::into_inner).push(backend);
+    }
+
+    /// Registers a backend while the event loop is running, in place of the backend with the
+    /// given name, which is removed if it was registered.
+    pub(crate) fn replace(&self, name: &str, backend: Box<dyn FirmwareBackend>) {
+        info!("registering {} firmware backend in place of {}", backend.name(), name);
+        let mut backends = self.backends.write().unwrap_or_else(PoisonError::into_inner);
+        backends.retain(|backend| backend.name() != name);
+        backends.push(backend);
+    }
+
+    /// The registered backends, which may be used by several threads at once.
+    fn backends(&self) -> RwLockReadGuard<'_, Vec<Box<dyn FirmwareBackend>>> {
+        self.backends.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Scans every backend for devices concurrently, so that a slow backend does not delay the
+    /// devices found by the others. Devices are reported as soon as each backend finds them.
+    ///
+    /// Devices which were ignored in the configuration file are not reported. The outcome of the
+    /// scan is returned once every backend has finished.
+    pub fn scan(&self, sender: &Sender<FirmwareSignal>) -> ScanResult {
+        let config = &crate::config();
+        let parent = &Span::current();
+        let backends = &*self.backends();
+        let (tx, rx) = mpsc::channel();
+        let mut found = false;
+        let mut errors = Vec::new();
+
+        thread::scope(|scope| {
+            for backend in backends {
+                let tx = tx.clone();
+                scope.spawn(move || {
+                    let _span =
+                        info_span!(parent: parent, "backend", name = backend.name()).entered();
+                    backend.scan(&tx);
+                });
+            }
+
+            drop(tx);
+
+            for mut signal in rx {
+                if config.ignores(&signal) {
+                    info!("ignoring {:?}", signal);
+                    continue;
+                }
+
+                // Devices of other backends are marked here, so that those backends need only
+                // enforce the firmware policy when updating.
+                if let FirmwareSignal::Device(DeviceSignal { device, info, .. }) = &mut signal {
+                    if let Some(latest) = &info.latest {
+                        info.policy = crate::check_policy(&info.name, &[&device.id], latest).err();
+                    }
+                }
+
+                match &signal {
+                    FirmwareSignal::Error(None, why) => errors.push(why.localized().into()),
+                    signal => found |= is_device(signal),
+                }
+
+                let _res = sender.send(signal);
+            }
+        });
+
+        ScanResult { found, fwupd_unavailable: !self.contains(crate::fwupd::BACKEND), errors }
+    }
+
+    /// Hands the update request to the first backend which accepts it.
+    pub fn update(
+        &self,
+        event: &FirmwareEvent,
+        sender: &Sender<FirmwareSignal>,
+    ) -> Option<FirmwareSignal> {
+        self.backends().iter().find_map(|backend| backend.update(event, sender))
+    }
+}
+
+/// Flashes a device of a backend other than fwupd or System76, as those backends flash theirs.
+///
+/// The firmware policy is enforced before the device is flashed. While it is flashed, the system
+/// is kept from sleeping and the update is journaled, and the outcome is recorded in the update
+/// history.
+pub(crate) fn flash_device<F>(
+    entity: Entity,
+    info: &FirmwareInfo,
+    ids: &[&str],
+    sender: &Sender<FirmwareSignal>,
+    flash: F,
+) -> FirmwareSignal
+where
+    F: FnOnce() -> FirmwareSignal,
+{
+    let latest = info.latest.as_deref().unwrap_or_default();
+
+    if let Err(why) = crate::check_policy(&info.name, ids, latest) {
+        return FirmwareSignal::Error(Some(entity), FirmwareError::Policy(latest.into(), why));
+    }
+
+    let signal = {
+        let _inhibitor = inhibit::flashing(&info.name);
+        let _flashing = journal::begin(&info.name, &info.current, latest);
+        let _res = sender.send(FirmwareSignal::DeviceFlashing(entity));
+        flash()
+    };
+
+    history::record(&info.name, &info.current, latest, &signal);
+    signal
+}
+
+/// Checks if a signal reports a device that was discovered.
+fn is_device(signal: &FirmwareSignal) -> bool {
+    matches!(
+        signal,
+        FirmwareSignal::Device(_)
+            | FirmwareSignal::Fwupd(_)
+            | FirmwareSignal::ManagementEngine(_)
+            | FirmwareSignal::Microcode(_)
+            | FirmwareSignal::S76Ec(_)
+            | FirmwareSignal::S76System(..)
+            | FirmwareSignal::ThelioIo(..)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A backend which finds a single device after a delay.
+    struct Delayed(&'static str, Duration);
+
+    impl FirmwareBackend for Delayed {
+        fn name(&self) -> &str { self.0 }
+
+        fn scan(&self, sender: &Sender<FirmwareSignal>) {
+            thread::sleep(self.1);
+            let _res = sender.send(FirmwareSignal::Device(DeviceSignal {
+                device: DeviceId { backend: self.0.into(), id: self.0.into() },
+                info: FirmwareInfo {
+                    name: self.0.into(),
+                    current: "1".into(),
+                    latest: None,
+                    install_duration: 0,
+                    policy: None,
+                },
+                read_only: false,
+            }));
+        }
+
+        fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+            None
+        }
+    }
+
+    #[test]
+    fn scan_concurrently() {
+        let mut backends = Backends::default();
+        backends.register(Delayed("slow", Duration::from_millis(200)));
+        backends.register(Delayed("fast", Duration::from_millis(0)));
+
+        let (tx, rx) = mpsc::channel();
+        let result = backends.scan(&tx);
+        assert!(result.found);
+        assert!(result.fwupd_unavailable);
+        assert!(!result.failed());
+
+        let found = rx
+            .try_iter()
+            .filter_map(|signal| match signal {
+                FirmwareSignal::Device(signal) => Some(signal.device.backend),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(found, vec!["fast".into(), "slow".into()] as Vec<Box<str>>);
+    }
+
+    /// A backend which fails to scan.
+    struct Failing;
+
+    impl FirmwareBackend for Failing {
+        fn name(&self) -> &str { "failing" }
+
+        fn scan(&self, sender: &Sender<FirmwareSignal>) {
+            let why = FirmwareError::ThelioIoBoard("failing".into(), "1".into());
+            let _res = sender.send(FirmwareSignal::Error(None, why));
+        }
+
+        fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+            None
+        }
+    }
+
+    #[test]
+    fn scan_result() {
+        let mut backends = Backends::default();
+        backends.register(Failing);
+
+        let (tx, _rx) = mpsc::channel();
+        let result = backends.scan(&tx);
+        assert!(!result.found);
+        assert!(result.fwupd_unavailable);
+        assert_eq!(result.errors.len(), 1);
+
+        // Failures are reported alongside the firmware that other backends found.
+        backends.register(Delayed("found", Duration::from_millis(0)));
+        let result = backends.scan(&tx);
+        assert!(result.found);
+        assert!(result.failed());
+    }
+}
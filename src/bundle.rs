@@ -0,0 +1,598 @@
+//! Offline bundles, which carry firmware updates to systems without internet access.
+//!
+//! A bundle is a directory holding the payload of each pending fwupd update of the system that
+//! exported it, named by its SHA256 checksum, the signed archive of each pending System76 update,
+//! named by its digest, and a [`MANIFEST`] which describes the firmware that each of them is for.
+//! The manifest is signed with the minisign key at [`BUNDLE_SIGNING_KEY`] when the bundle is
+//! exported. The signature is checked against the [`BUNDLE_KEYS`] when the bundle is imported,
+//! and must name the manifest in its trusted comment, and the payloads are checked against the
+//! checksums of the manifest, before each payload is installed by the backend which updates its
+//! device, as though the update had been requested by a frontend.
+//!
+//! The bundle keys belong to the sites which export bundles, and are trusted for nothing else.
+//! They are kept apart from the [`TRUSTED_KEYS`] of System76 firmware, so that a site key can not
+//! sign firmware archives.
+//!
+//! Devices are matched by their GUIDs, rather than by their fwupd device IDs, as the IDs differ
+//! between systems. The System76 archives carry their own signatures, which are checked again
+//! when they are installed. The system76-firmware daemon fetches the archives that it installs
+//! itself, so their updates are only installed if the daemon may reach its own source.
+//!
+//! [`TRUSTED_KEYS`]: crate::TRUSTED_KEYS
+
+use crate::{
+    checksum, download, fwupd, power, signature, system76, Backends, BatteryPolicy,
+    CancellationToken, DownloadError, Entity, FirmwareError, FirmwareEvent, FirmwareSignal,
+    SignatureError, System76Client, System76Digest, System76Error,
+};
+use fwupd_dbus::{Client as FwupdClient, Device as FwupdDevice, Release as FwupdRelease};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{mpsc, Arc},
+};
+
+/// The minisign secret key which the manifests of exported bundles are signed with.
+///
+/// The key must not be encrypted, as in `minisign -G -W -s /etc/firmware-manager/bundle.key`, as
+/// there is no one to ask for its password.
+pub const BUNDLE_SIGNING_KEY: &str = "/etc/firmware-manager/bundle.key";
+
+/// The directory of minisign public keys which the manifests of imported bundles must be signed
+/// with, which holds the public keys of the [`BUNDLE_SIGNING_KEY`]s of the exporting systems.
+pub const BUNDLE_KEYS: &str = "/etc/firmware-manager/bundle-keys";
+
+/// The name of the manifest of a bundle.
+const MANIFEST: &str = "manifest.toml";
+
+/// The name of the detached minisign signature of the manifest.
+const MANIFEST_SIGNATURE: &str = "manifest.toml.minisig";
+
+/// An error that may occur when exporting or importing a bundle.
+#[derive(Debug, Error)]
+pub enum BundleError {
+    /// The devices and releases could not be fetched from fwupd.
+    #[error("failed to get the firmware devices from fwupd")]
+    Fwupd(#[from] fwupd_dbus::Error),
+    /// The pending updates could not be fetched from the system76-firmware daemon.
+    #[error("failed to get the firmware from system76-firmware")]
+    System76(#[from] System76Error),
+    /// There is no key to sign the manifest with.
+    #[error("no signing key is installed at {}", BUNDLE_SIGNING_KEY)]
+    SigningKey,
+    /// The manifest could not be signed.
+    #[error("failed to sign the manifest of the bundle")]
+    Sign(#[source] io::Error),
+    /// The payload of an update could not be downloaded.
+    #[error("failed to download the firmware of {}", _0)]
+    Download(Box<str>, #[source] DownloadError),
+    /// A file of the bundle could not be read.
+    #[error("failed to read {}", _0.display())]
+    Read(PathBuf, #[source] io::Error),
+    /// A file of the bundle could not be written.
+    #[error("failed to write {}", _0.display())]
+    Write(PathBuf, #[source] io::Error),
+    /// The manifest of the bundle is invalid.
+    #[error("the manifest of the bundle is invalid")]
+    Parse(#[source] toml::de::Error),
+    /// The manifest of the bundle could not be serialized.
+    #[error("failed to serialize the manifest of the bundle")]
+    Serialize(#[source] toml::ser::Error),
+    /// The manifest was not signed by a trusted key.
+    #[error("the manifest of the bundle is not trusted")]
+    Signature(#[from] SignatureError),
+    /// The payload of a device does not match the checksum in the manifest.
+    #[error("the firmware of {} failed checksum verification", _0)]
+    Checksum(Box<str>),
+    /// The payload of a device could not be installed.
+    #[error("failed to install the firmware of {}", _0)]
+    Install(Box<str>, #[source] Box<FirmwareError>),
+    /// No backend installs the firmware of the device.
+    #[error("no firmware backend installs the firmware of {}", _0)]
+    Unhandled(Box<str>),
+    /// The system is running on battery, which the battery policy forbids updating on.
+    #[error("the system must be connected to power to install the bundle")]
+    OnBattery,
+    /// The export was cancelled.
+    #[error("the export of the bundle was cancelled")]
+    Cancelled,
+}
+
+/// Describes the firmware in a bundle.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    firmware: Vec<BundledFirmware>,
+    #[serde(default)]
+    system76: Vec<BundledArchive>,
+}
+
+/// A payload in a bundle, and the devices that it is for.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct BundledFirmware {
+    /// The name of the device that the payload was exported for.
+    name: Box<str>,
+    /// The GUIDs of the device, by which the devices that the payload is for are found.
+    guids: Vec<Box<str>>,
+    /// The version of the firmware in the payload.
+    version: Box<str>,
+    /// The SHA256 checksum of the payload, which is also its name in the bundle.
+    checksum: Box<str>,
+}
+
+/// A signed System76 firmware archive in a bundle, and the firmware that it updates.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct BundledArchive {
+    /// The name of the firmware that the archive was exported for.
+    name: Box<str>,
+    /// The kind of firmware in the archive.
+    kind: ArchiveKind,
+    /// The version of the firmware in the archive.
+    version: Box<str>,
+    /// The digest which identifies the archive, which is also its name in the bundle.
+    digest: Box<str>,
+}
+
+/// The kinds of System76 firmware which may be bundled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ArchiveKind {
+    /// System firmware, which is scheduled to be installed on reboot.
+    System,
+    /// The firmware of every Thelio I/O board, which are flashed at once.
+    ThelioIo,
+}
+
+impl BundledArchive {
+    /// The path of the archive in the `bundle`, if its digest is a hexadecimal digest.
+    fn archive(&self, bundle: &Path) -> Option<PathBuf> {
+        is_hex_digest(&self.digest, &[64, 96, 128]).then(|| bundle.join(&*self.digest))
+    }
+}
+
+impl BundledFirmware {
+    /// The path of the payload in the `bundle`, if its checksum is a SHA256 digest.
+    fn payload(&self, bundle: &Path) -> Option<PathBuf> {
+        let name = [&*self.checksum, ".cab"].concat();
+        is_hex_digest(&self.checksum, &[64]).then(|| bundle.join(name))
+    }
+
+    /// Whether the payload updates the device to a newer version.
+    fn updates(&self, device: &FwupdDevice) -> bool {
+        device.guid.iter().any(|guid| self.guids.contains(guid))
+            && fwupd::is_newer(&device.version, &self.version)
+    }
+}
+
+/// Whether the `digest` is hexadecimal, and has one of the given `lengths`, so that it may name
+/// a file of a bundle.
+fn is_hex_digest(digest: &str, lengths: &[usize]) -> bool {
+    lengths.contains(&digest.len()) && digest.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Downloads the payload of every pending fwupd and System76 update into the `bundle` directory,
+/// and describes them in its manifest, which is then signed, returning the number of payloads
+/// that were exported.
+///
+/// Updates which the firmware policy of the system does not approve are not exported.
+pub(crate) fn export(bundle: &Path, cancellable: &CancellationToken) -> Result<usize, BundleError> {
+    if !Path::new(BUNDLE_SIGNING_KEY).exists() {
+        return Err(BundleError::SigningKey);
+    }
+
+    let client = FwupdClient::new()?;
+    fs::create_dir_all(bundle).map_err(|why| BundleError::Write(bundle.to_owned(), why))?;
+
+    let mut manifest = Manifest::default();
+
+    for device in client.devices()? {
+        let signal = match fwupd::fwupd_signal(&client, device) {
+            Some(signal) if signal.upgradeable && signal.info.policy.is_none() => signal,
+            _ => continue,
+        };
+
+        let release = match signal.releases.last() {
+            Some(release) => release,
+            None => continue,
+        };
+
+        let name = signal.info.name;
+        info!("exporting {} {} to {}", name, release.version, bundle.display());
+
+        let cached = download::fetch(&release.uri, &release.checksum, cancellable, |_| ())
+            .map_err(|why| match why {
+                DownloadError::Cancelled => BundleError::Cancelled,
+                why => BundleError::Download(name.clone(), why),
+            })?;
+
+        let verified = checksum::preferred(&release.checksum)
+            .map_or(false, |expected| checksum::verify(&cached, expected).unwrap_or(false));
+        if !verified {
+            return Err(BundleError::Checksum(name));
+        }
+
+        let sha256 =
+            checksum::sha256(&cached).map_err(|why| BundleError::Read(cached.clone(), why))?;
+        let firmware = BundledFirmware {
+            name,
+            guids: signal.device.guid.to_vec(),
+            version: release.version.clone(),
+            checksum: sha256.into(),
+        };
+
+        if let Some(payload) = firmware.payload(bundle) {
+            fs::copy(&cached, &payload).map_err(|why| BundleError::Write(payload, why))?;
+            manifest.firmware.push(firmware);
+        }
+    }
+
+    // System76 archives are only bundled once they were verified, which needs trusted keys.
+    if system76::s76_firmware_is_active() {
+        if signature::is_enabled() {
+            export_system76(bundle, cancellable, &mut manifest)?;
+        } else {
+            info!("not bundling System76 firmware, as signature verification is not enabled");
+        }
+    }
+
+    let path = bundle.join(MANIFEST);
+    let contents = toml::to_string(&manifest).map_err(BundleError::Serialize)?;
+    fs::write(&path, contents).map_err(|why| BundleError::Write(path, why))?;
+    sign(bundle)?;
+
+    Ok(manifest.firmware.len() + manifest.system76.len())
+}
+
+/// Downloads the signed archive of each pending System76 update into the `bundle` directory,
+/// with its signature, and describes them in the `manifest`.
+fn export_system76(
+    bundle: &Path,
+    cancellable: &CancellationToken,
+    manifest: &mut Manifest,
+) -> Result<(), BundleError> {
+    let (sender, receiver) = mpsc::channel();
+    system76::s76_scan(&System76Client::new()?, sender);
+
+    for signal in receiver {
+        let (kind, info, digest) = match signal {
+            FirmwareSignal::S76System(info, Some((digest, _))) => {
+                (ArchiveKind::System, info, digest)
+            }
+            FirmwareSignal::ThelioIo(_, info, Some(digest)) => {
+                (ArchiveKind::ThelioIo, info, digest)
+            }
+            _ => continue,
+        };
+
+        let version = match info.latest {
+            Some(latest) if latest != info.current && info.policy.is_none() => latest,
+            _ => continue,
+        };
+
+        // Every Thelio I/O board is updated by the same archive.
+        if manifest.system76.iter().any(|archive| archive.digest == digest.0) {
+            continue;
+        }
+
+        if cancellable.is_cancelled() {
+            return Err(BundleError::Cancelled);
+        }
+
+        let name = match kind {
+            ArchiveKind::System => info.name,
+            ArchiveKind::ThelioIo => system76::THELIO_IO.into(),
+        };
+
+        let archive = BundledArchive { name, kind, version, digest: digest.0.clone() };
+        let path = match archive.archive(bundle) {
+            Some(path) => path,
+            None => continue,
+        };
+
+        info!("exporting {} {} to {}", archive.name, archive.version, bundle.display());
+        let (contents, signature) = signature::fetch_archive(&archive.digest)?;
+        fs::write(&path, contents).map_err(|why| BundleError::Write(path.clone(), why))?;
+
+        let signature_path = minisig_path(&path);
+        fs::write(&signature_path, signature)
+            .map_err(|why| BundleError::Write(signature_path, why))?;
+
+        manifest.system76.push(archive);
+    }
+
+    Ok(())
+}
+
+/// Signs the manifest of the `bundle` with the [`BUNDLE_SIGNING_KEY`].
+fn sign(bundle: &Path) -> Result<(), BundleError> {
+    let output = Command::new("minisign")
+        .args(["-S", "-s", BUNDLE_SIGNING_KEY, "-m"])
+        .arg(bundle.join(MANIFEST))
+        .arg("-x")
+        .arg(bundle.join(MANIFEST_SIGNATURE))
+        .stdin(Stdio::null())
+        .output()
+        .map_err(BundleError::Sign)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+        return Err(BundleError::Sign(io::Error::new(io::ErrorKind::Other, reason.trim())));
+    }
+
+    Ok(())
+}
+
+/// The detached minisign signature beside a file of the bundle.
+fn minisig_path(path: &Path) -> PathBuf {
+    let mut signature = path.as_os_str().to_owned();
+    signature.push(".minisig");
+    PathBuf::from(signature)
+}
+
+/// Installs the payloads of the `bundle` on the devices of this system that they update, with the
+/// `backends` which update them, returning the names of the devices which were updated.
+///
+/// Payloads which fail to install are reported to `failed`, and the remaining payloads are still
+/// installed.
+pub(crate) fn import<F: FnMut(BundleError)>(
+    bundle: &Path,
+    backends: &Backends,
+    mut failed: F,
+) -> Result<Vec<Box<str>>, BundleError> {
+    let manifest = read_manifest(bundle)?;
+
+    if crate::config().battery_policy == BatteryPolicy::RequireAc && power::on_battery() {
+        return Err(BundleError::OnBattery);
+    }
+
+    let devices = FwupdClient::new()?.devices()?;
+    let cancellable = CancellationToken::default();
+
+    let mut updated = Vec::new();
+
+    for firmware in &manifest.firmware {
+        let payload = match firmware.payload(bundle) {
+            Some(payload) => payload,
+            None => {
+                failed(BundleError::Checksum(firmware.name.clone()));
+                continue;
+            }
+        };
+
+        if !checksum::verify(&payload, &firmware.checksum).unwrap_or(false) {
+            failed(BundleError::Checksum(firmware.name.clone()));
+            continue;
+        }
+
+        // The payload is installed from the firmware cache, as if it had been downloaded.
+        let staged = match download::stage(&payload, &firmware.checksum) {
+            Ok(staged) => staged,
+            Err(why) => {
+                failed(BundleError::Download(firmware.name.clone(), why));
+                continue;
+            }
+        };
+
+        let release = Arc::new(FwupdRelease {
+            version: firmware.version.clone(),
+            checksum: firmware.checksum.clone(),
+            uri: payload.to_string_lossy().into(),
+            size: fs::metadata(&staged).map_or(0, |metadata| metadata.len()),
+            ..FwupdRelease::default()
+        });
+
+        for device in devices.iter().filter(|device| firmware.updates(device)) {
+            let name = fwupd::device_name(device);
+            let device = Arc::new(device.clone());
+            let event = FirmwareEvent::Fwupd(
+                Entity::default(),
+                device,
+                release.clone(),
+                cancellable.clone(),
+            );
+            match install(backends, &name, &event) {
+                Ok(()) => updated.push(name.into()),
+                Err(why) => failed(why),
+            }
+        }
+    }
+
+    if !manifest.system76.is_empty() && system76::s76_firmware_is_active() {
+        import_system76(bundle, backends, &manifest.system76, &cancellable, &mut updated, failed)?;
+    }
+
+    Ok(updated)
+}
+
+/// Installs the signed System76 archives of the `bundle` on the firmware of this system that they
+/// update, adding the names of the firmware which was updated to `updated`.
+fn import_system76<F: FnMut(BundleError)>(
+    bundle: &Path,
+    backends: &Backends,
+    archives: &[BundledArchive],
+    cancellable: &CancellationToken,
+    updated: &mut Vec<Box<str>>,
+    mut failed: F,
+) -> Result<(), BundleError> {
+    let (sender, receiver) = mpsc::channel();
+    system76::s76_scan(&System76Client::new()?, sender);
+
+    let (mut system, mut boards) = (None, Vec::new());
+    for signal in receiver {
+        match signal {
+            FirmwareSignal::S76System(info, _) => system = Some(info),
+            FirmwareSignal::ThelioIo(board, info, _) => boards.push((board, info)),
+            _ => (),
+        }
+    }
+
+    for archive in archives {
+        let digest = System76Digest(archive.digest.clone());
+        let (info, event, policy) = match archive.kind {
+            ArchiveKind::System => match system.as_ref() {
+                Some(info) => {
+                    let policy = crate::check_policy(&info.name, &[], &archive.version);
+                    (info, FirmwareEvent::S76System(Entity::default(), digest), policy)
+                }
+                None => continue,
+            },
+            ArchiveKind::ThelioIo => {
+                match boards.iter().find(|(_, info)| info.current != archive.version) {
+                    Some((board, info)) => {
+                        let ids = [&*info.name, &**board];
+                        let policy =
+                            crate::check_policy(system76::THELIO_IO, &ids, &archive.version);
+                        let event = FirmwareEvent::ThelioIo(
+                            Entity::default(),
+                            board.clone(),
+                            digest,
+                            cancellable.clone(),
+                        );
+                        (info, event, policy)
+                    }
+                    None => continue,
+                }
+            }
+        };
+
+        if info.current == archive.version {
+            continue;
+        }
+
+        if let Err(why) = policy {
+            let why = FirmwareError::Policy(archive.version.clone(), why);
+            failed(BundleError::Install(info.name.clone(), Box::new(why)));
+            continue;
+        }
+
+        if let Err(why) = stage_archive(bundle, archive) {
+            failed(why);
+            continue;
+        }
+
+        match install(backends, &info.name, &event) {
+            Ok(()) => updated.push(info.name.clone()),
+            Err(why) => failed(why),
+        }
+    }
+
+    Ok(())
+}
+
+/// Verifies the signed archive of the `bundle`, and keeps it to be verified again when it is
+/// installed.
+fn stage_archive(bundle: &Path, archive: &BundledArchive) -> Result<(), BundleError> {
+    // Without trusted keys, the archive is neither verified, nor needed to verify its update.
+    if !signature::is_enabled() {
+        return Ok(());
+    }
+
+    let path = archive.archive(bundle).ok_or_else(|| BundleError::Checksum(archive.name.clone()))?;
+    let signature_path = minisig_path(&path);
+
+    let contents = fs::read(&path).map_err(|why| BundleError::Read(path, why))?;
+    let signature = fs::read_to_string(&signature_path)
+        .map_err(|why| BundleError::Read(signature_path, why))?;
+
+    signature::stage_archive(&archive.digest, &contents, &signature)?;
+    Ok(())
+}
+
+/// Performs an update of the bundle with the backend which handles it, so that it is approved,
+/// checked, and sequenced as an update requested by a frontend would be.
+///
+/// The update does not belong to an entity of a frontend, so the signals which the backend sends
+/// while updating are discarded, and only its outcome is reported.
+fn install(backends: &Backends, name: &str, event: &FirmwareEvent) -> Result<(), BundleError> {
+    let (sender, _receiver) = mpsc::channel();
+    match backends.update(event, &sender) {
+        Some(FirmwareSignal::Error(_, why)) => {
+            Err(BundleError::Install(name.into(), Box::new(why)))
+        }
+        Some(FirmwareSignal::VerificationFailed(_, why)) => Err(BundleError::Signature(why)),
+        Some(_) => Ok(()),
+        None => Err(BundleError::Unhandled(name.into())),
+    }
+}
+
+/// Reads the manifest of the `bundle`, once its signature has been verified.
+fn read_manifest(bundle: &Path) -> Result<Manifest, BundleError> {
+    let read = |name: &str| {
+        let path = bundle.join(name);
+        fs::read(&path).map_err(|why| BundleError::Read(path, why))
+    };
+
+    let contents = read(MANIFEST)?;
+    let signature = String::from_utf8_lossy(&read(MANIFEST_SIGNATURE)?).into_owned();
+    signature::verify_file(Path::new(BUNDLE_KEYS), MANIFEST, &contents, &signature)?;
+
+    toml::from_str(&String::from_utf8_lossy(&contents)).map_err(BundleError::Parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchiveKind, BundledArchive, BundledFirmware, Manifest};
+    use std::path::Path;
+
+    const MANIFEST: &str = r#"[[firmware]]
+name = "Thunderbolt Dock"
+guids = ["9d4c9d2c-0e72-5c6b-8a1b-6e8f2d1c3b4a"]
+version = "40.1"
+checksum = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+
+[[system76]]
+name = "Thelio I/O"
+kind = "thelio-io"
+version = "1.0.1"
+digest = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+"#;
+
+    #[test]
+    fn manifest() {
+        let manifest: Manifest = toml::from_str(MANIFEST).unwrap();
+        let firmware = &manifest.firmware[0];
+        assert_eq!(&*firmware.version, "40.1");
+        assert_eq!(manifest.system76[0].kind, ArchiveKind::ThelioIo);
+
+        let serialized = toml::to_string(&manifest).unwrap();
+        assert_eq!(toml::from_str::<Manifest>(&serialized).unwrap(), manifest);
+        assert_eq!(toml::from_str::<Manifest>("").unwrap(), Manifest::default());
+    }
+
+    #[test]
+    fn payload() {
+        let mut firmware = BundledFirmware {
+            name: "Thunderbolt Dock".into(),
+            guids: Vec::new(),
+            version: "40.1".into(),
+            checksum: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".into(),
+        };
+
+        let expected = Path::new("/media/bundle/")
+            .join("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08.cab");
+        assert_eq!(firmware.payload(Path::new("/media/bundle")), Some(expected));
+
+        firmware.checksum = "../../etc/passwd".into();
+        assert_eq!(firmware.payload(Path::new("/media/bundle")), None);
+    }
+
+    #[test]
+    fn archive() {
+        let mut archive = BundledArchive {
+            name: "Thelio I/O".into(),
+            kind: ArchiveKind::ThelioIo,
+            version: "1.0.1".into(),
+            digest: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".into(),
+        };
+
+        let expected = Path::new("/media/bundle/")
+            .join("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08");
+        assert_eq!(archive.archive(Path::new("/media/bundle")), Some(expected));
+
+        archive.digest = "../../etc/passwd".into();
+        assert_eq!(archive.archive(Path::new("/media/bundle")), None);
+    }
+}
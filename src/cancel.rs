@@ -0,0 +1,116 @@
+use crate::Entity;
+use slotmap::SparseSecondaryMap;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, MutexGuard, PoisonError,
+};
+
+/// A token which a frontend may use to cancel an update that is in progress.
+///
+/// Clones of a token share the same state, so a frontend keeps a clone of the token that it sent
+/// with an update request, and cancels it when the user requests for the update to stop.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<TokenState>);
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+}
+
+impl CancellationToken {
+    /// Requests for the associated update to be cancelled.
+    pub fn cancel(&self) { self.0.cancelled.store(true, Ordering::SeqCst); }
+
+    /// Requests for the associated update to be cancelled, keeping what was downloaded of its
+    /// firmware so that the download resumes from there when the update is requested again.
+    ///
+    /// The update is reported with [`FirmwareSignal::Paused`], which returns the request.
+    ///
+    /// [`FirmwareSignal::Paused`]: crate::FirmwareSignal::Paused
+    pub fn pause(&self) {
+        self.0.paused.store(true, Ordering::SeqCst);
+        self.cancel();
+    }
+
+    /// Clears a pause, so that the paused update may be requested again with this token.
+    pub fn resume(&self) {
+        self.0.paused.store(false, Ordering::SeqCst);
+        self.0.cancelled.store(false, Ordering::SeqCst);
+    }
+
+    /// Checks if cancellation has been requested.
+    pub fn is_cancelled(&self) -> bool { self.0.cancelled.load(Ordering::SeqCst) }
+
+    /// Checks if the update was paused, rather than cancelled outright.
+    pub fn is_paused(&self) -> bool { self.0.paused.load(Ordering::SeqCst) }
+}
+
+/// The tokens of the updates which are in progress, which are cancelled when the event loop stops.
+#[derive(Default)]
+pub(crate) struct InProgress(Mutex<SparseSecondaryMap<Entity, CancellationToken>>);
+
+impl InProgress {
+    /// Tracks the update of an entity until it has finished.
+    pub fn insert(&self, entity: Entity, token: CancellationToken) {
+        self.tokens().insert(entity, token);
+    }
+
+    /// Stops tracking the update of an entity, once it has finished.
+    pub fn remove(&self, entity: Entity) { self.tokens().remove(entity); }
+
+    /// Cancels every update in progress, returning how many there were.
+    ///
+    /// Downloads stop at once, but devices which have begun flashing are left to finish, as
+    /// interrupting them may leave the device unusable.
+    pub fn cancel_all(&self) -> usize {
+        let tokens = self.tokens();
+        tokens.values().for_each(CancellationToken::cancel);
+        tokens.len()
+    }
+
+    fn tokens(&self) -> MutexGuard<'_, SparseSecondaryMap<Entity, CancellationToken>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CancellationToken, InProgress};
+    use crate::Entities;
+
+    #[test]
+    fn cancel_clones() {
+        let token = CancellationToken::default();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        token.cancel();
+        assert!(clone.is_cancelled());
+        assert!(!clone.is_paused());
+
+        let paused = CancellationToken::default();
+        paused.clone().pause();
+        assert!(paused.is_cancelled());
+        assert!(paused.is_paused());
+
+        paused.resume();
+        assert!(!paused.is_cancelled());
+        assert!(!paused.is_paused());
+    }
+
+    #[test]
+    fn cancel_in_progress() {
+        let mut entities = Entities::default();
+        let (first, second) = (entities.create(), entities.create());
+        let (a, b) = (CancellationToken::default(), CancellationToken::default());
+
+        let in_progress = InProgress::default();
+        in_progress.insert(first, a.clone());
+        in_progress.insert(second, b.clone());
+        in_progress.remove(second);
+
+        assert_eq!(in_progress.cancel_all(), 1);
+        assert!(a.is_cancelled());
+        assert!(!b.is_cancelled());
+    }
+}
@@ -0,0 +1,359 @@
+//! A backend which stages UEFI capsules on the EFI System Partition without fwupd.
+//!
+//! Minimal systems may not run the fwupd daemon, but their firmware may still accept capsule
+//! updates which are delivered on disk. The devices that the firmware can update are listed in
+//! the EFI System Resource Table (ESRT), and vendors provide capsules for them in [`CAPSULES_DIR`]
+//! as `<fw_class>/<version>.cap`, where the version is the ESRT firmware version that the capsule
+//! installs.
+//!
+//! To install a capsule, it is copied to `EFI/UpdateCapsule` on the EFI System Partition, and the
+//! firmware is asked to process it on the next boot by setting the file capsule delivery bit of
+//! the `OsIndications` variable. Both require root, so the backend is only registered when the
+//! firmware manager runs as root.
+
+use crate::{
+    history, space, DeviceId, DeviceSignal, FirmwareBackend, FirmwareError, FirmwareEvent,
+    FirmwareInfo, FirmwareSignal, RebootPolicy,
+};
+use std::{
+    ffi::OsStr,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+};
+
+/// The name of the capsule backend, which identifies its devices.
+pub(crate) const BACKEND: &str = "capsule";
+
+/// The directory where vendors install capsules, by the ESRT firmware class that they update.
+pub const CAPSULES_DIR: &str = "/usr/lib/firmware-manager/capsules";
+
+/// Where the entries of the EFI System Resource Table are described.
+pub(crate) const ESRT_ENTRIES: &str = "/sys/firmware/efi/esrt/entries";
+
+/// Where EFI variables are exposed by efivarfs.
+const EFIVARS: &str = "/sys/firmware/efi/efivars";
+
+/// The vendor GUID of the variables defined by the UEFI specification.
+const EFI_GLOBAL_VARIABLE: &str = "8be4df61-93ca-11d2-aa0d-00e098032b8c";
+
+/// The `OsIndications` bit which asks the firmware to process capsules on the ESP.
+const FILE_CAPSULE_DELIVERY: u64 = 0x4;
+
+/// The attributes of `OsIndications`: non-volatile, and accessible at boot and runtime.
+const OS_INDICATIONS_ATTRIBUTES: u32 = 0x7;
+
+/// The inode flag which efivarfs sets on variables to protect them from accidental deletion.
+const FS_IMMUTABLE_FL: libc::c_int = 0x10;
+
+/// The ESRT firmware type of system firmware.
+const ESRT_SYSTEM_FIRMWARE: u32 = 1;
+
+/// An error that may occur when staging a capsule.
+#[derive(Debug, Error)]
+pub enum CapsuleError {
+    /// The firmware does not process capsules which are delivered on disk.
+    #[error("the firmware does not support capsule updates from the EFI System Partition")]
+    Unsupported,
+    /// No capsule newer than the installed firmware was provided for the device.
+    #[error("no newer capsule is available for {}", _0)]
+    NotFound(Box<str>),
+    /// The capsule could not be copied to the EFI System Partition.
+    #[error("failed to copy the capsule to the EFI System Partition")]
+    Copy(#[source] io::Error),
+    /// The `OsIndications` variable could not be set.
+    #[error("failed to set the OsIndications EFI variable")]
+    Variable(#[source] io::Error),
+}
+
+/// A device which the firmware can update, as described by the ESRT.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Resource {
+    /// The GUID of the firmware class.
+    pub class: Box<str>,
+    /// The type of the firmware, such as system firmware.
+    pub kind: u32,
+    /// The version of the installed firmware.
+    pub version: u32,
+}
+
+impl Resource {
+    pub fn name(&self) -> String {
+        if self.kind == ESRT_SYSTEM_FIRMWARE {
+            "UEFI System Firmware".into()
+        } else {
+            format!("UEFI Device Firmware ({})", &self.class[..self.class.len().min(8)])
+        }
+    }
+}
+
+/// A firmware backend which stages UEFI capsules, for systems without fwupd.
+#[derive(Default)]
+pub struct CapsuleBackend;
+
+impl CapsuleBackend {
+    /// Checks if the firmware describes any devices that capsules may update.
+    pub fn is_supported() -> bool { Path::new(ESRT_ENTRIES).is_dir() }
+
+    /// Checks if the device was discovered by the capsule backend.
+    ///
+    /// Firmware which enforces Secure Boot only installs capsules which are signed by the vendor
+    /// of the system, so frontends should warn the user before updating these devices while
+    /// [`secure_boot`](crate::secure_boot) is enabled.
+    pub fn manages(device: &DeviceId) -> bool { &*device.backend == BACKEND }
+}
+
+impl FirmwareBackend for CapsuleBackend {
+    fn name(&self) -> &str { BACKEND }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        for resource in resources(Path::new(ESRT_ENTRIES)) {
+            let latest = latest_capsule(Path::new(CAPSULES_DIR), &resource.class)
+                .filter(|(version, _)| *version > resource.version)
+                .map(|(version, _)| version.to_string().into());
+
+            let _res = sender.send(FirmwareSignal::Device(DeviceSignal {
+                device: DeviceId { backend: BACKEND.into(), id: resource.class.clone() },
+                info: FirmwareInfo {
+                    name: resource.name().into(),
+                    current: resource.version.to_string().into(),
+                    latest,
+                    install_duration: 1,
+                    policy: None,
+                },
+                read_only: false,
+            }));
+        }
+    }
+
+    fn update(&self, event: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+        let (entity, id, cancellable) = match event {
+            FirmwareEvent::Device(entity, id, cancellable) if &*id.backend == BACKEND => {
+                (*entity, id, cancellable)
+            }
+            _ => return None,
+        };
+
+        if cancellable.is_cancelled() {
+            return Some(FirmwareSignal::Cancelled(entity));
+        }
+
+        let resource = resources(Path::new(ESRT_ENTRIES))
+            .into_iter()
+            .find(|resource| resource.class == id.id)?;
+
+        let name = resource.name();
+        let current = resource.version.to_string();
+        let error = |why: FirmwareError| Some(FirmwareSignal::Error(Some(entity), why));
+
+        // The event may be stale, so the capsule is checked again, as it is when scanning.
+        let latest = latest_capsule(Path::new(CAPSULES_DIR), &resource.class)
+            .filter(|(version, _)| *version > resource.version);
+
+        let (version, capsule) = match latest {
+            Some(latest) => latest,
+            None => {
+                let why = CapsuleError::NotFound(name.into());
+                return error(FirmwareError::Backend(BACKEND.into(), Box::new(why)));
+            }
+        };
+
+        let latest = version.to_string();
+
+        if let Err(why) = crate::check_policy(&name, &[&id.id], &latest) {
+            return error(FirmwareError::Policy(latest.into(), why));
+        }
+
+        let result = info_span!("stage", device_id = &*id.id).in_scope(|| stage(&capsule, &id.id));
+
+        let signal = match result {
+            Ok(()) if crate::config().reboot_policy == RebootPolicy::NextBoot => {
+                FirmwareSignal::Staged(entity)
+            }
+            Ok(()) => FirmwareSignal::SystemScheduled,
+            Err(why) => FirmwareSignal::Error(Some(entity), why),
+        };
+
+        history::record(&name, &current, &latest, &signal);
+        Some(signal)
+    }
+}
+
+/// Checks if capsules should be staged directly, which is only when the firmware describes
+/// devices that capsules may update, and the firmware manager runs as root, as staging writes to
+/// the EFI System Partition and to EFI variables.
+pub(crate) fn is_needed() -> bool {
+    if !CapsuleBackend::is_supported() {
+        return false;
+    }
+
+    // Safety: geteuid has no preconditions, and can not fail.
+    if unsafe { libc::geteuid() } != 0 {
+        info!("not staging capsules, as the firmware manager is not running as root");
+        return false;
+    }
+
+    true
+}
+
+/// Copies the capsule to the EFI System Partition, and asks the firmware to process it.
+fn stage(capsule: &Path, class: &str) -> Result<(), FirmwareError> {
+    let backend = |why: CapsuleError| FirmwareError::Backend(BACKEND.into(), Box::new(why));
+
+    if read_u64_variable("OsIndicationsSupported").unwrap_or(0) & FILE_CAPSULE_DELIVERY == 0 {
+        return Err(backend(CapsuleError::Unsupported));
+    }
+
+    let size = fs::metadata(capsule).map_err(|why| backend(CapsuleError::Copy(why)))?.len();
+    let esp = space::ensure_esp(size)?;
+
+    let directory = esp.join("EFI/UpdateCapsule");
+    let target = directory.join([class, ".cap"].concat());
+
+    fs::create_dir_all(&directory)
+        .and_then(|_| fs::copy(capsule, &target))
+        .and_then(|_| File::open(&target)?.sync_all())
+        .map_err(|why| backend(CapsuleError::Copy(why)))?;
+
+    set_os_indications(FILE_CAPSULE_DELIVERY).map_err(|why| backend(CapsuleError::Variable(why)))
+}
+
+/// The devices described by the entries of the ESRT.
+pub(crate) fn resources(entries: &Path) -> Vec<Resource> {
+    let entries = match fs::read_dir(entries) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut resources = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let attribute = |name: &str| -> Option<String> {
+                Some(fs::read_to_string(path.join(name)).ok()?.trim().to_owned())
+            };
+
+            Some(Resource {
+                class: attribute("fw_class")?.into(),
+                kind: attribute("fw_type")?.parse().ok()?,
+                version: attribute("fw_version")?.parse().ok()?,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    resources.sort_by(|a, b| a.class.cmp(&b.class));
+    resources
+}
+
+/// The capsule with the highest version that was provided for the firmware class.
+fn latest_capsule(capsules: &Path, class: &str) -> Option<(u32, PathBuf)> {
+    fs::read_dir(capsules.join(class))
+        .ok()?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let version = capsule_version(&path)?;
+            Some((version, path))
+        })
+        .max_by_key(|(version, _)| *version)
+}
+
+/// The version of a capsule, from its file name.
+fn capsule_version(path: &Path) -> Option<u32> {
+    if path.extension() != Some(OsStr::new("cap")) {
+        return None;
+    }
+
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+/// The path of a global EFI variable in efivarfs.
+pub(crate) fn efivar(name: &str) -> PathBuf {
+    Path::new(EFIVARS).join([name, "-", EFI_GLOBAL_VARIABLE].concat())
+}
+
+/// Reads a global EFI variable which holds a 64-bit integer.
+fn read_u64_variable(name: &str) -> io::Result<u64> {
+    let contents = fs::read(efivar(name))?;
+    parse_u64_variable(&contents)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed EFI variable"))
+}
+
+/// Parses the contents of an efivarfs file, which are the attributes of the variable followed by
+/// its value.
+fn parse_u64_variable(contents: &[u8]) -> Option<u64> {
+    let value = contents.get(4..12)?;
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(value);
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// Sets the given bits of the `OsIndications` variable.
+fn set_os_indications(bits: u64) -> io::Result<()> {
+    let current = match read_u64_variable("OsIndications") {
+        Ok(current) => current,
+        Err(why) if why.kind() == io::ErrorKind::NotFound => 0,
+        Err(why) => return Err(why),
+    };
+
+    if current & bits == bits {
+        return Ok(());
+    }
+
+    let path = efivar("OsIndications");
+    if path.exists() {
+        set_mutable(&path)?;
+    }
+
+    let mut contents = OS_INDICATIONS_ATTRIBUTES.to_le_bytes().to_vec();
+    contents.extend_from_slice(&(current | bits).to_le_bytes());
+
+    // efivarfs replaces the whole variable with each write, which must hold both the attributes
+    // and the value, so the file is not truncated beforehand.
+    OpenOptions::new().write(true).create(true).truncate(false).open(&path)?.write_all(&contents)
+}
+
+/// Clears the immutable flag which efivarfs sets on existing variables.
+fn set_mutable(path: &Path) -> io::Result<()> {
+    let file = File::open(path)?;
+    let mut flags: libc::c_int = 0;
+
+    // Safety: the ioctls read and write an int, which outlives the calls.
+    unsafe {
+        if libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if flags & FS_IMMUTABLE_FL == 0 {
+            return Ok(());
+        }
+
+        flags &= !FS_IMMUTABLE_FL;
+        if libc::ioctl(file.as_raw_fd(), libc::FS_IOC_SETFLAGS, &flags) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    #[test]
+    fn capsule_version() {
+        assert_eq!(super::capsule_version(Path::new("/capsules/guid/65586.cap")), Some(65586));
+        assert_eq!(super::capsule_version(Path::new("/capsules/guid/65586.bin")), None);
+        assert_eq!(super::capsule_version(Path::new("/capsules/guid/latest.cap")), None);
+    }
+
+    #[test]
+    fn parse_u64_variable() {
+        let contents = [7, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(super::parse_u64_variable(&contents), Some(4));
+        assert_eq!(super::parse_u64_variable(&contents[..8]), None);
+    }
+}
@@ -0,0 +1,81 @@
+//! Verification of downloaded firmware against the checksums published alongside it.
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Picks the strongest supported checksum from a comma-separated list of hex digests.
+pub(crate) fn preferred(checksums: &str) -> Option<&str> {
+    checksums.split(',').map(str::trim).filter(|sum| is_supported(sum)).max_by_key(|sum| sum.len())
+}
+
+/// Checks if the file at `path` matches the `expected` checksum.
+///
+/// The hashing algorithm is determined by the length of the expected checksum.
+pub(crate) fn verify(path: &Path, expected: &str) -> io::Result<bool> {
+    let file = File::open(path)?;
+
+    let actual = match expected.len() {
+        40 => digest::<Sha1, _>(file)?,
+        64 => digest::<Sha256, _>(file)?,
+        128 => digest::<Sha512, _>(file)?,
+        _ => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "unsupported checksum type"))
+        }
+    };
+
+    Ok(actual.eq_ignore_ascii_case(expected))
+}
+
+/// The SHA256 digest of the file at `path`, as a hex string.
+pub(crate) fn sha256(path: &Path) -> io::Result<String> { digest::<Sha256, _>(File::open(path)?) }
+
+/// Checks if the checksum is a SHA1, SHA256, or SHA512 hex digest.
+fn is_supported(checksum: &str) -> bool {
+    matches!(checksum.len(), 40 | 64 | 128) && checksum.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Hashes everything that is read from the `reader`, returning the digest as a hex string.
+fn digest<D: Digest, R: Read>(mut reader: R) -> io::Result<String> {
+    let mut hasher = D::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use sha1::Sha1;
+    use sha2::Sha256;
+
+    const SHA1: &str = "a9993e364706816aba3e25717850c26c9cd0d89d";
+    const SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn digest() {
+        assert_eq!(super::digest::<Sha1, _>(&b"abc"[..]).unwrap(), SHA1);
+        assert_eq!(super::digest::<Sha256, _>(&b"abc"[..]).unwrap(), SHA256);
+    }
+
+    #[test]
+    fn preferred() {
+        let checksums = [SHA1, ",", SHA256].concat();
+        assert_eq!(super::preferred(&checksums), Some(SHA256));
+        assert_eq!(super::preferred(SHA1), Some(SHA1));
+        assert_eq!(super::preferred("not-a-checksum"), None);
+        assert_eq!(super::preferred(""), None);
+    }
+}
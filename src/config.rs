@@ -0,0 +1,645 @@
+//! User configuration, which is shared by every frontend of the firmware manager.
+//!
+//! The configuration is read from `$XDG_CONFIG_HOME/firmware-manager/config.toml`:
+//!
+//! ```toml
+//! check_interval = 86400
+//! release_channel = "stable"
+//! channels = { fwupd = "testing" }
+//! battery_policy = "require_ac"
+//! reboot_policy = "next_boot"
+//! proxy = "http://proxy:3128"
+//! no_proxy = [".internal"]
+//! bandwidth_limit = 512
+//! ignored_devices = ["Thelio I/O"]
+//! upload_reports = true
+//! flashrom = false
+//! lvfs_fallback = true
+//! auto_refresh = 30
+//! sort = "upgradeable_first"
+//! snoozed = { "System Firmware" = { version = "2023-05-04_21c7d33", until = 1700000000 } }
+//!
+//! [[mirrors]]
+//! origin = "https://fwupd.org/downloads/"
+//! mirrors = ["https://cdn.fwupd.org/downloads/", "https://mirror.internal/lvfs/"]
+//! ```
+
+use crate::{download, mirror, timestamp, DownloadConfig, FirmwareSignal, Mirror};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fs, io,
+    path::PathBuf,
+    sync::{Mutex, RwLock},
+    time::Duration,
+};
+
+static CONFIG: Lazy<RwLock<Config>> = Lazy::new(|| {
+    let config = Config::load_or_default();
+    config.apply();
+    RwLock::new(config)
+});
+
+/// The download settings which the configuration last applied, which are replaced when it is
+/// reloaded. Settings which were set explicitly are kept.
+static APPLIED: Lazy<Mutex<DownloadConfig>> = Lazy::new(Mutex::default);
+
+/// An error that may occur when loading the configuration file.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// The location of the configuration file could not be determined.
+    #[error("failed to get XDG base directory")]
+    BaseDirectory(#[from] xdg::BaseDirectoriesError),
+    /// The configuration file could not be read.
+    #[error("failed to read the configuration file")]
+    Read(#[source] io::Error),
+    /// The configuration file is not valid.
+    #[error("failed to parse the configuration file")]
+    Parse(#[source] toml::de::Error),
+    /// The directory of the configuration file could not be created.
+    #[error("failed to create the configuration directory")]
+    Place(#[source] io::Error),
+    /// The modified configuration could not be serialized.
+    #[error("failed to serialize the configuration file")]
+    Serialize(#[source] toml::ser::Error),
+    /// The configuration file could not be written.
+    #[error("failed to write the configuration file")]
+    Write(#[source] io::Error),
+}
+
+/// The releases which updates are offered from.
+///
+/// Channels are ordered from the most to the least stable, and each channel includes the releases
+/// of the channels which are more stable than it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseChannel {
+    /// Only stable releases.
+    #[default]
+    Stable,
+    /// Candidates for the next stable release, in addition to stable releases.
+    Beta,
+    /// Releases which are still being tested, in addition to beta and stable releases.
+    Testing,
+}
+
+impl ReleaseChannel {
+    /// The name of the channel in the configuration file.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReleaseChannel::Stable => "stable",
+            ReleaseChannel::Beta => "beta",
+            ReleaseChannel::Testing => "testing",
+        }
+    }
+}
+
+/// How firmware updates are handled while the system is running on battery.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatteryPolicy {
+    /// Warn that the system should be connected to power before updating.
+    #[default]
+    Warn,
+    /// Refuse to update until the system is connected to power.
+    RequireAc,
+    /// Update without regard to the battery.
+    Ignore,
+}
+
+/// When system firmware is installed, once it has been staged to be installed on reboot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RebootPolicy {
+    /// Reboot to install the firmware as soon as it has been staged.
+    #[default]
+    Immediate,
+    /// Install the firmware the next time that the user reboots the system.
+    NextBoot,
+}
+
+/// The order which frontends list devices in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceSort {
+    /// By the names of the devices.
+    #[default]
+    Name,
+    /// By the vendors of the devices, and then by their names.
+    Vendor,
+    /// Devices with updates available before those without, and then by their names.
+    UpgradeableFirst,
+}
+
+/// What a device is sorted by.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SortKey {
+    /// The name of the device.
+    pub name: Box<str>,
+    /// The vendor of the device, which is empty if it is not known.
+    pub vendor: Box<str>,
+    /// Whether an update is available for the device.
+    pub upgradeable: bool,
+    /// Whether the update is critical, which sorts the device before the others in every order.
+    pub critical: bool,
+}
+
+impl DeviceSort {
+    /// Every order, as it is offered to the user.
+    pub const ALL: [DeviceSort; 3] =
+        [DeviceSort::Name, DeviceSort::Vendor, DeviceSort::UpgradeableFirst];
+
+    /// The name of the order in the configuration file.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeviceSort::Name => "name",
+            DeviceSort::Vendor => "vendor",
+            DeviceSort::UpgradeableFirst => "upgradeable_first",
+        }
+    }
+
+    /// Parses the name of an order in the configuration file.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|sort| sort.as_str() == name)
+    }
+
+    /// Compares two devices in this order. Names and vendors are compared without regard to case,
+    /// and devices whose vendors are not known are sorted after those whose vendors are.
+    pub fn compare(self, a: &SortKey, b: &SortKey) -> Ordering {
+        let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+
+        let by_order = || match self {
+            DeviceSort::Name => by_name(),
+            DeviceSort::Vendor => (a.vendor.is_empty(), a.vendor.to_lowercase())
+                .cmp(&(b.vendor.is_empty(), b.vendor.to_lowercase()))
+                .then_with(by_name),
+            DeviceSort::UpgradeableFirst => b.upgradeable.cmp(&a.upgradeable).then_with(by_name),
+        };
+
+        b.critical.cmp(&a.critical).then_with(by_order)
+    }
+}
+
+/// An update which the user asked to be reminded of later.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Snooze {
+    /// The version of the update. Snoozing an update does not snooze any newer update.
+    pub version: Box<str>,
+    /// When the snooze expires, in seconds since the UNIX epoch.
+    pub until: u64,
+}
+
+/// Options for the firmware manager, which are read from the configuration file.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Seconds to wait between refreshes of the firmware metadata.
+    pub check_interval: u64,
+    /// The releases which updates are offered from, which are stable releases if unset.
+    pub release_channel: Option<ReleaseChannel>,
+    /// Release channels of specific backends, by the name of the backend, which take precedence
+    /// over the `release_channel`.
+    pub channels: BTreeMap<Box<str>, ReleaseChannel>,
+    /// How updates are handled while the system is running on battery.
+    pub battery_policy: BatteryPolicy,
+    /// When system firmware is installed.
+    pub reboot_policy: RebootPolicy,
+    /// The proxy to download firmware through, if not set by the proxy environment variables.
+    pub proxy: Option<Box<str>>,
+    /// Hosts which firmware is downloaded from directly, rather than through the proxy.
+    pub no_proxy: Vec<Box<str>>,
+    /// The maximum rate to download firmware at, in KiB per second, shared by all downloads.
+    pub bandwidth_limit: Option<u64>,
+    /// Names, IDs, or GUIDs of devices which should never be offered updates.
+    pub ignored_devices: Vec<Box<str>>,
+    /// Whether reports of fwupd updates are uploaded to the LVFS. The user is asked if unset.
+    pub upload_reports: Option<bool>,
+    /// Whether the SPI flash is inspected with flashrom, for boards which no other backend
+    /// supports. It is only inspected as root, and is meant for experts.
+    pub flashrom: bool,
+    /// Whether the LVFS metadata is checked directly for updates when fwupd is not running.
+    pub lvfs_fallback: bool,
+    /// Minutes between automatic rescans of the devices while a frontend is open. Devices are
+    /// only rescanned when requested if unset.
+    pub auto_refresh: Option<u32>,
+    /// The order which devices are listed in.
+    pub sort: DeviceSort,
+    /// Updates which are not notified of until their snoozes expire, by the name of the device.
+    pub snoozed: BTreeMap<Box<str>, Snooze>,
+    /// Mirrors to fail over to when a file cannot be fetched from its origin, which replace the
+    /// [`default_mirrors`](crate::default_mirrors) if set.
+    pub mirrors: Vec<Mirror>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            check_interval: 60 * 60 * 24,
+            release_channel: None,
+            channels: BTreeMap::new(),
+            battery_policy: BatteryPolicy::default(),
+            reboot_policy: RebootPolicy::default(),
+            proxy: None,
+            no_proxy: Vec::new(),
+            bandwidth_limit: None,
+            ignored_devices: Vec::new(),
+            upload_reports: None,
+            flashrom: false,
+            lvfs_fallback: false,
+            auto_refresh: None,
+            sort: DeviceSort::default(),
+            snoozed: BTreeMap::new(),
+            mirrors: mirror::default_mirrors(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration file, or the default configuration if it does not exist.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = match config_path()? {
+            Some(path) => path,
+            None => return Ok(Self::default()),
+        };
+
+        info!("loading configuration from {}", path.display());
+
+        match fs::read_to_string(&path) {
+            Ok(config) => Self::parse(&config),
+            Err(why) if why.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(why) => Err(ConfigError::Read(why)),
+        }
+    }
+
+    /// Loads the configuration file, falling back to the default configuration on error.
+    pub fn load_or_default() -> Self {
+        Self::load().unwrap_or_else(|why| {
+            error!("invalid configuration: {}", crate::format_error(why));
+            Self::default()
+        })
+    }
+
+    /// Parses the contents of a configuration file.
+    pub fn parse(config: &str) -> Result<Self, ConfigError> {
+        toml::from_str(config).map_err(ConfigError::Parse)
+    }
+
+    /// The release channel of the backend with the given name.
+    pub fn release_channel_of(&self, backend: &str) -> ReleaseChannel {
+        self.configured_channel_of(backend).unwrap_or_default()
+    }
+
+    /// The release channel of the backend with the given name, if one was configured, either for
+    /// the backend or for every backend. Backends leave their sources as they are otherwise.
+    pub fn configured_channel_of(&self, backend: &str) -> Option<ReleaseChannel> {
+        self.channels.get(backend).copied().or(self.release_channel)
+    }
+
+    /// Checks if the device with the given name, or any of the given IDs or GUIDs, should never
+    /// be offered updates.
+    pub fn is_ignored(&self, name: &str, ids: &[&str]) -> bool {
+        self.ignored_devices.iter().any(|ignored| {
+            ignored.eq_ignore_ascii_case(name)
+                || ids.iter().any(|id| id.eq_ignore_ascii_case(ignored))
+        })
+    }
+
+    /// Checks if the device described by a discovery signal should never be offered updates.
+    pub fn ignores(&self, signal: &FirmwareSignal) -> bool {
+        match signal {
+            FirmwareSignal::Fwupd(signal) | FirmwareSignal::FwupdChanged(signal) => {
+                self.is_ignored(&signal.info.name, &crate::fwupd::device_ids(&signal.device))
+            }
+            FirmwareSignal::Device(signal) => {
+                self.is_ignored(&signal.info.name, &[&signal.device.id])
+            }
+            FirmwareSignal::S76System(info, _)
+            | FirmwareSignal::S76Ec(info)
+            | FirmwareSignal::ManagementEngine(info)
+            | FirmwareSignal::Microcode(info) => self.is_ignored(&info.name, &[]),
+            // Ignoring Thelio I/O ignores every board, rather than only the first.
+            FirmwareSignal::ThelioIo(board, info, _) => {
+                self.is_ignored(&info.name, &[board, crate::system76::THELIO_IO])
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks if the update of the device with the given name to the given version was snoozed,
+    /// and the snooze has not expired by `now`.
+    pub fn is_snoozed(&self, name: &str, version: &str, now: u64) -> bool {
+        self.snoozed
+            .get(name)
+            .map_or(false, |snooze| &*snooze.version == version && snooze.until > now)
+    }
+
+    /// Checks if the update which a discovery signal describes should not be notified of yet.
+    pub fn snoozes(&self, signal: &FirmwareSignal) -> bool {
+        let info = match signal {
+            FirmwareSignal::Fwupd(signal) | FirmwareSignal::FwupdChanged(signal) => &signal.info,
+            FirmwareSignal::Device(signal) => &signal.info,
+            FirmwareSignal::S76System(info, _) | FirmwareSignal::ThelioIo(_, info, _) => info,
+            _ => return false,
+        };
+
+        info.latest
+            .as_deref()
+            .map_or(false, |latest| self.is_snoozed(&info.name, latest, timestamp::current()))
+    }
+
+    /// Sends downloads through the configured proxy and mirrors, and limits their bandwidth,
+    /// unless these were set explicitly.
+    fn apply(&self) {
+        let mut applied = APPLIED.lock().unwrap_or_else(|why| why.into_inner());
+        let mut download = download::download_config();
+        self.apply_to(&mut download, &mut applied);
+        download::set_download_config(download);
+    }
+
+    /// Replaces the settings of the `download` configuration which are unset, or which were
+    /// `applied` by a previous configuration, recording those which this configuration applied.
+    fn apply_to(&self, download: &mut DownloadConfig, applied: &mut DownloadConfig) {
+        let bandwidth_limit = self.bandwidth_limit.map(|kib| kib * 1024);
+
+        if download.proxy == applied.proxy {
+            download.proxy = self.proxy.clone();
+        }
+
+        if download.bandwidth_limit == applied.bandwidth_limit {
+            download.bandwidth_limit = bandwidth_limit;
+        }
+
+        if download.mirrors == applied.mirrors {
+            download.mirrors = self.mirrors.clone();
+        }
+
+        // Only the hosts which the configuration added are removed, as others may have been
+        // added explicitly.
+        download.no_proxy.retain(|host| !applied.no_proxy.contains(host));
+        let added: Vec<Box<str>> = self
+            .no_proxy
+            .iter()
+            .filter(|host| !download.no_proxy.contains(host))
+            .cloned()
+            .collect();
+        download.no_proxy.extend(added.iter().cloned());
+
+        *applied = DownloadConfig {
+            proxy: self.proxy.clone(),
+            no_proxy: added,
+            mirrors: self.mirrors.clone(),
+            bandwidth_limit,
+        };
+    }
+}
+
+/// Fetches the configuration, which is loaded from the configuration file on first use.
+pub fn config() -> Config { CONFIG.read().unwrap_or_else(|why| why.into_inner()).clone() }
+
+/// Reloads the configuration from the configuration file, such as after it has been edited.
+pub fn reload_config() -> Result<(), ConfigError> {
+    let config = Config::load()?;
+    config.apply();
+    *CONFIG.write().unwrap_or_else(|why| why.into_inner()) = config;
+    Ok(())
+}
+
+/// Sets the release channel of a backend, such as `fwupd` or `system76`.
+///
+/// This does not modify the configuration file, so the channel will be reset to the configured
+/// channel when the configuration is next reloaded. The channel applies from the next scan.
+pub fn set_release_channel(backend: &str, channel: ReleaseChannel) {
+    let mut config = CONFIG.write().unwrap_or_else(|why| why.into_inner());
+    config.channels.insert(backend.into(), channel);
+}
+
+/// Adds a device to the `ignored_devices` of the configuration file, by its name, ID, or GUID,
+/// or removes it from them. Ignored devices are omitted from the next scan.
+///
+/// The file is rewritten as with [`write_option`].
+pub fn set_device_ignored(device: &str, ignored: bool) -> Result<(), ConfigError> {
+    let mut devices = config().ignored_devices;
+    devices.retain(|ignored| !ignored.eq_ignore_ascii_case(device));
+
+    if ignored {
+        info!("ignoring {} from now on", device);
+        devices.push(device.into());
+    } else {
+        info!("no longer ignoring {}", device);
+    }
+
+    let values = devices.iter().map(|device| toml::Value::from(&**device)).collect();
+    write_option("ignored_devices", toml::Value::Array(values))?;
+
+    CONFIG.write().unwrap_or_else(|why| why.into_inner()).ignored_devices = devices;
+    Ok(())
+}
+
+/// Snoozes the update of a device to the given version, so that it is not notified of until the
+/// `duration` has passed, and saves it to the configuration file. Expired snoozes are removed.
+///
+/// The file is rewritten as with [`write_option`].
+pub fn snooze_update(device: &str, version: &str, duration: Duration) -> Result<(), ConfigError> {
+    let now = timestamp::current();
+    let mut snoozed = config().snoozed;
+    snoozed.retain(|_, snooze| snooze.until > now);
+    let snooze = Snooze { version: version.into(), until: now + duration.as_secs() };
+    snoozed.insert(device.into(), snooze);
+
+    info!("snoozing the update of {} to {} for {:?}", device, version, duration);
+
+    let values = snoozed
+        .iter()
+        .map(|(device, snooze)| {
+            let mut value = toml::Table::new();
+            value.insert("version".into(), toml::Value::from(&*snooze.version));
+            value.insert("until".into(), toml::Value::Integer(snooze.until as i64));
+            (String::from(&**device), toml::Value::Table(value))
+        })
+        .collect();
+
+    write_option("snoozed", toml::Value::Table(values))?;
+
+    CONFIG.write().unwrap_or_else(|why| why.into_inner()).snoozed = snoozed;
+    Ok(())
+}
+
+/// Sets the order which devices are listed in, and saves it to the configuration file.
+///
+/// The file is rewritten as with [`write_option`].
+pub fn set_device_sort(sort: DeviceSort) -> Result<(), ConfigError> {
+    write_option("sort", toml::Value::from(sort.as_str()))?;
+    CONFIG.write().unwrap_or_else(|why| why.into_inner()).sort = sort;
+    Ok(())
+}
+
+/// Sets an option of the configuration file, creating the file if it does not exist.
+///
+/// Other options in the configuration file are preserved, but comments are not.
+fn write_option(key: &str, value: toml::Value) -> Result<(), ConfigError> {
+    let path = xdg::BaseDirectories::with_prefix("firmware-manager")?
+        .place_config_file("config.toml")
+        .map_err(ConfigError::Place)?;
+
+    let mut table = match fs::read_to_string(&path) {
+        Ok(config) => toml::from_str::<toml::Table>(&config).map_err(ConfigError::Parse)?,
+        Err(why) if why.kind() == io::ErrorKind::NotFound => toml::Table::new(),
+        Err(why) => return Err(ConfigError::Read(why)),
+    };
+
+    table.insert(key.into(), value);
+
+    let contents = toml::to_string(&table).map_err(ConfigError::Serialize)?;
+    fs::write(&path, contents).map_err(ConfigError::Write)
+}
+
+/// The location of the configuration file, if it exists.
+fn config_path() -> Result<Option<PathBuf>, ConfigError> {
+    let base = xdg::BaseDirectories::with_prefix("firmware-manager")?;
+    Ok(base.find_config_file("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+
+        let config = Config::parse(
+            "check_interval = 3600\n\
+             release_channel = \"beta\"\n\
+             channels = { fwupd = \"testing\" }\n\
+             battery_policy = \"require_ac\"\n\
+             reboot_policy = \"next_boot\"\n\
+             proxy = \"http://proxy:3128\"\n\
+             bandwidth_limit = 512\n\
+             ignored_devices = [\"Thelio I/O\"]\n\
+             upload_reports = false\n\
+             flashrom = true\n\
+             auto_refresh = 30\n\
+             sort = \"upgradeable_first\"\n\
+             snoozed = { Dock = { version = \"1.2\", until = 1700000000 } }\n\
+             [[mirrors]]\n\
+             origin = \"https://origin.example/\"\n\
+             mirrors = [\"https://mirror.example/\"]\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.check_interval, 3600);
+        assert_eq!(config.release_channel, Some(ReleaseChannel::Beta));
+        assert_eq!(config.release_channel_of("fwupd"), ReleaseChannel::Testing);
+        assert_eq!(config.release_channel_of("system76"), ReleaseChannel::Beta);
+        assert_eq!(Config::default().configured_channel_of("fwupd"), None);
+        assert_eq!(config.battery_policy, BatteryPolicy::RequireAc);
+        assert_eq!(config.reboot_policy, RebootPolicy::NextBoot);
+        assert_eq!(config.proxy.as_deref(), Some("http://proxy:3128"));
+        assert!(config.no_proxy.is_empty());
+        assert_eq!(config.bandwidth_limit, Some(512));
+        assert_eq!(config.upload_reports, Some(false));
+        assert!(config.flashrom);
+        assert_eq!(config.auto_refresh, Some(30));
+        assert_eq!(config.sort, DeviceSort::UpgradeableFirst);
+        assert_eq!(config.snoozed["Dock"], Snooze { version: "1.2".into(), until: 1700000000 });
+        assert_eq!(config.mirrors, [Mirror {
+            origin: "https://origin.example/".into(),
+            mirrors: vec!["https://mirror.example/".into()],
+        }]);
+
+        assert!(Config::parse("battery_policy = \"sometimes\"").is_err());
+    }
+
+    #[test]
+    fn apply_to() {
+        let config = |proxy: &str, no_proxy: &[&str], bandwidth_limit| Config {
+            proxy: Some(proxy.into()),
+            no_proxy: no_proxy.iter().map(|&host| host.into()).collect(),
+            bandwidth_limit,
+            ..Config::default()
+        };
+
+        let mut download =
+            DownloadConfig { no_proxy: vec![".explicit".into()], ..DownloadConfig::default() };
+        let mut applied = DownloadConfig::default();
+
+        config("http://a:3128", &[".internal", ".explicit"], Some(1))
+            .apply_to(&mut download, &mut applied);
+        assert_eq!(download.proxy.as_deref(), Some("http://a:3128"));
+        assert_eq!(download.bandwidth_limit, Some(1024));
+        assert_eq!(download.no_proxy, [".explicit".into(), ".internal".into()]);
+
+        // Reloading replaces what the configuration set, without duplicating hosts.
+        config("http://b:3128", &[".internal"], None).apply_to(&mut download, &mut applied);
+        assert_eq!(download.proxy.as_deref(), Some("http://b:3128"));
+        assert_eq!(download.bandwidth_limit, None);
+        assert_eq!(download.no_proxy, [".explicit".into(), ".internal".into()]);
+
+        // Settings which were changed explicitly are kept.
+        download.proxy = Some("http://explicit:3128".into());
+        config("http://c:3128", &[], Some(2)).apply_to(&mut download, &mut applied);
+        assert_eq!(download.proxy.as_deref(), Some("http://explicit:3128"));
+        assert_eq!(download.bandwidth_limit, Some(2048));
+        assert_eq!(download.no_proxy, [Box::from(".explicit")]);
+    }
+
+    #[test]
+    fn is_ignored() {
+        let config = Config {
+            ignored_devices: vec!["thelio i/o".into(), "6a3b2c1d".into()],
+            ..Config::default()
+        };
+
+        assert!(config.is_ignored("Thelio I/O", &[]));
+        assert!(config.is_ignored("Fingerprint Reader", &["7b4c3d2e", "6A3B2C1D"]));
+        assert!(!config.is_ignored("Fingerprint Reader", &["7b4c3d2e"]));
+    }
+
+    #[test]
+    fn is_snoozed() {
+        let snooze = Snooze { version: "1.2".into(), until: 1000 };
+        let snoozed = [("Dock".into(), snooze)].into_iter().collect();
+        let config = Config { snoozed, ..Config::default() };
+
+        assert!(config.is_snoozed("Dock", "1.2", 999));
+        assert!(!config.is_snoozed("Dock", "1.2", 1000));
+        assert!(!config.is_snoozed("Dock", "1.3", 999));
+        assert!(!config.is_snoozed("Mouse", "1.2", 999));
+    }
+
+    #[test]
+    fn device_sort() {
+        let key = |name: &str, vendor: &str, upgradeable| SortKey {
+            name: name.into(),
+            vendor: vendor.into(),
+            upgradeable,
+            critical: false,
+        };
+
+        let dock = key("Thunderbolt Dock", "Dell", false);
+        let mouse = key("mouse", "Logitech", true);
+        let reader = key("Fingerprint Reader", "", true);
+        let system = SortKey { critical: true, ..key("System Firmware", "System76", true) };
+
+        let sorted = |sort: DeviceSort| {
+            let mut devices = vec![&dock, &mouse, &reader];
+            devices.sort_by(|a, b| sort.compare(a, b));
+            devices.iter().map(|device| &*device.name).collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(DeviceSort::Name), ["Fingerprint Reader", "mouse", "Thunderbolt Dock"]);
+        assert_eq!(sorted(DeviceSort::Vendor), ["Thunderbolt Dock", "mouse", "Fingerprint Reader"]);
+        assert_eq!(
+            sorted(DeviceSort::UpgradeableFirst),
+            ["Fingerprint Reader", "mouse", "Thunderbolt Dock"]
+        );
+
+        for sort in DeviceSort::ALL {
+            assert_eq!(DeviceSort::from_name(sort.as_str()), Some(sort));
+            assert_eq!(sort.compare(&system, &dock), Ordering::Less);
+            assert_eq!(sort.compare(&reader, &system), Ordering::Greater);
+        }
+    }
+}
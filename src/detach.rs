@@ -0,0 +1,127 @@
+//! Tracks devices which disappear from fwupd while they are being updated.
+//!
+//! Some devices, such as fingerprint readers, reset into their bootloader to be flashed, and reset
+//! again into the new firmware afterwards. fwupd reports each reset as the device being removed,
+//! and then added again. Reporting these to frontends would remove the device, along with the
+//! progress of its update, so they are withheld until the device returns, and the return is
+//! reported as a change to the device.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// What is known of a device which may disappear during its update.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Presence {
+    /// The device is being updated.
+    updating: bool,
+    /// fwupd has reported the device as removed, and it has not yet returned.
+    detached: bool,
+}
+
+/// How a hotplug event from fwupd should be reported to frontends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotplug {
+    /// The event is reported as it is.
+    Report,
+    /// The device reset during its update, and is not reported as removed.
+    Withhold,
+    /// The device returned after resetting, and is reported as changed rather than added.
+    Returned,
+}
+
+/// The devices which are expected to disappear during their updates, by their fwupd device IDs.
+#[derive(Debug, Default)]
+pub struct Detaching(Mutex<HashMap<Box<str>, Presence>>);
+
+impl Detaching {
+    /// Expects the device to disappear until the returned guard is dropped, after its update.
+    pub fn expect<'a>(&'a self, device_id: &str) -> DetachGuard<'a> {
+        self.devices().entry(device_id.into()).or_default().updating = true;
+        DetachGuard { detaching: self, device_id: device_id.into() }
+    }
+
+    /// fwupd reported that the device was removed.
+    pub fn removed(&self, device_id: &str) -> Hotplug {
+        match self.devices().get_mut(device_id) {
+            Some(presence) => {
+                info!("{} reset during its update", device_id);
+                presence.detached = true;
+                Hotplug::Withhold
+            }
+            None => Hotplug::Report,
+        }
+    }
+
+    /// fwupd reported that the device was added.
+    pub fn added(&self, device_id: &str) -> Hotplug {
+        let mut devices = self.devices();
+        let presence = match devices.get_mut(device_id) {
+            Some(presence) if presence.detached => presence,
+            _ => return Hotplug::Report,
+        };
+
+        presence.detached = false;
+        if !presence.updating {
+            devices.remove(device_id);
+        }
+
+        Hotplug::Returned
+    }
+
+    fn devices(&self) -> MutexGuard<'_, HashMap<Box<str>, Presence>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Ends the update of a device which may disappear during it, when dropped.
+///
+/// A device which has not yet returned after resetting is still expected to return.
+pub struct DetachGuard<'a> {
+    detaching: &'a Detaching,
+    device_id: Box<str>,
+}
+
+impl<'a> Drop for DetachGuard<'a> {
+    fn drop(&mut self) {
+        let mut devices = self.detaching.devices();
+        match devices.get_mut(&self.device_id) {
+            Some(presence) if presence.detached => presence.updating = false,
+            _ => {
+                devices.remove(&self.device_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Detaching, Hotplug};
+
+    #[test]
+    fn reset_during_update() {
+        let detaching = Detaching::default();
+        assert_eq!(detaching.removed("reader"), Hotplug::Report);
+
+        let guard = detaching.expect("reader");
+        assert_eq!(detaching.removed("other"), Hotplug::Report);
+        assert_eq!(detaching.removed("reader"), Hotplug::Withhold);
+        assert_eq!(detaching.added("reader"), Hotplug::Returned);
+        assert_eq!(detaching.removed("reader"), Hotplug::Withhold);
+        drop(guard);
+
+        // The device returns after its update has finished.
+        assert_eq!(detaching.added("reader"), Hotplug::Returned);
+        assert_eq!(detaching.removed("reader"), Hotplug::Report);
+        assert_eq!(detaching.added("reader"), Hotplug::Report);
+    }
+
+    #[test]
+    fn no_reset() {
+        let detaching = Detaching::default();
+        drop(detaching.expect("reader"));
+        assert_eq!(detaching.added("reader"), Hotplug::Report);
+        assert_eq!(detaching.removed("reader"), Hotplug::Report);
+    }
+}
@@ -0,0 +1,206 @@
+//! An expert backend which inspects the SPI flash of boards that no other backend supports.
+//!
+//! Boards without fwupd or System76 support may still have their firmware inspected with
+//! flashrom, which reads the flash chip through the internal programmer. The backend reports the
+//! flash chip and the firmware ID in the `RO_FRID` region of its flash map, which coreboot
+//! firmware has, rather than reading the whole flash. It never writes to the flash.
+//!
+//! Reading the flash requires root, so the backend is only registered when `flashrom = true` is
+//! set in the configuration file, the firmware manager runs as root, and no other backend
+//! manages the system firmware.
+
+use crate::{
+    capsule, fwupd, Backends, DeviceId, DeviceSignal, FirmwareBackend, FirmwareError,
+    FirmwareEvent, FirmwareInfo, FirmwareSignal,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fs, io,
+    path::Path,
+    process::Command,
+    sync::mpsc::Sender,
+};
+
+/// The name of the flashrom backend, which identifies its devices.
+const BACKEND: &str = "flashrom";
+
+/// The flashrom programmer which accesses the flash of the running system.
+const PROGRAMMER: &str = "internal";
+
+/// The region of the flash map which holds the ID of the read-only firmware.
+const VERSION_REGION: &str = "RO_FRID";
+
+/// An error that may occur when inspecting the flash with flashrom.
+#[derive(Debug, Error)]
+pub enum FlashromError {
+    /// flashrom could not be executed, such as when it is not installed.
+    #[error("failed to execute flashrom")]
+    Spawn(#[source] io::Error),
+    /// flashrom exited with an error.
+    #[error("flashrom failed: {}", _0)]
+    Failed(Box<str>),
+    /// flashrom did not report the flash chip.
+    #[error("flashrom did not identify the flash chip")]
+    Chip,
+    /// The location to read the image to could not be determined.
+    #[error("failed to get XDG base directory")]
+    BaseDirectory(#[from] xdg::BaseDirectoriesError),
+    /// The directory to read the image to could not be created.
+    #[error("failed to create the cache directory")]
+    Place(#[source] io::Error),
+    /// The version region which was read could not be opened.
+    #[error("failed to read the firmware version region")]
+    Read(#[source] io::Error),
+    /// The version region does not hold a firmware ID.
+    #[error("the firmware version region is empty")]
+    Version,
+}
+
+/// A flash chip, as identified by flashrom.
+#[derive(Debug, PartialEq, Eq)]
+struct Chip<'a> {
+    vendor: &'a str,
+    name: &'a str,
+}
+
+/// A firmware backend which reports the SPI flash through flashrom.
+#[derive(Default)]
+pub struct FlashromBackend;
+
+impl FirmwareBackend for FlashromBackend {
+    fn name(&self) -> &str { BACKEND }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        info!("inspecting the SPI flash with flashrom");
+
+        let signal = match inspect() {
+            Ok(info) => FirmwareSignal::Device(DeviceSignal {
+                device: DeviceId { backend: BACKEND.into(), id: PROGRAMMER.into() },
+                info,
+                read_only: true,
+            }),
+            Err(why) => {
+                FirmwareSignal::Error(None, FirmwareError::Backend(BACKEND.into(), Box::new(why)))
+            }
+        };
+
+        let _res = sender.send(signal);
+    }
+
+    /// The flash is only inspected, and never written.
+    fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+        None
+    }
+}
+
+/// Checks if the flash should be inspected, which is only when the firmware manager runs as
+/// root, and none of the `backends` manages the system firmware.
+pub(crate) fn is_needed(backends: &Backends) -> bool {
+    // Safety: geteuid has no preconditions, and can not fail.
+    if unsafe { libc::geteuid() } != 0 {
+        info!("not inspecting the SPI flash, as the firmware manager is not running as root");
+        return false;
+    }
+
+    if backends.contains("system76") || backends.contains(capsule::BACKEND) {
+        return false;
+    }
+
+    // fwupd manages system firmware, which is installed on reboot, when it reports any.
+    fwupd::reported_devices(|device| device.needs_reboot()).map_or(true, |system| system.is_empty())
+}
+
+/// Identifies the flash chip, and reads the firmware ID from its version region.
+fn inspect() -> Result<FirmwareInfo, FlashromError> {
+    let output = flashrom(&["--flash-name"])?;
+    let chip = parse_chip(&output).ok_or(FlashromError::Chip)?;
+    let name = format!("SPI Flash ({} {})", chip.vendor, chip.name);
+
+    let region = xdg::BaseDirectories::with_prefix("com.system76.FirmwareManager")?
+        .place_cache_file("flashrom-frid.bin")
+        .map_err(FlashromError::Place)?;
+
+    let contents =
+        read_region(&region).and_then(|_| fs::read(&region).map_err(FlashromError::Read));
+
+    // The region is only needed for the version that it holds.
+    let _res = fs::remove_file(&region);
+
+    let version = parse_version(&contents?).ok_or(FlashromError::Version)?;
+
+    Ok(FirmwareInfo {
+        name: name.into(),
+        current: version.into(),
+        latest: None,
+        install_duration: 0,
+        policy: None,
+    })
+}
+
+/// Reads only the version region of the flash to the file at `region`.
+fn read_region(region: &Path) -> Result<(), FlashromError> {
+    let _span = info_span!("flashrom_read").entered();
+    let mut include = OsString::from([VERSION_REGION, ":"].concat());
+    include.push(region);
+    flashrom(&[OsStr::new("--fmap"), OsStr::new("-i"), &include, OsStr::new("-r")]).map(|_| ())
+}
+
+/// The firmware ID in a version region, which is padded with nul or erased bytes.
+fn parse_version(region: &[u8]) -> Option<String> {
+    let end = region.iter().position(|&byte| byte == 0 || byte == 0xff).unwrap_or(region.len());
+    let version = String::from_utf8_lossy(&region[..end]).trim().to_owned();
+    (!version.is_empty()).then_some(version)
+}
+
+/// Runs flashrom with the internal programmer, returning its standard output.
+fn flashrom<S: AsRef<OsStr>>(args: &[S]) -> Result<String, FlashromError> {
+    let output = Command::new("flashrom")
+        .args(["-p", PROGRAMMER])
+        .args(args)
+        .output()
+        .map_err(FlashromError::Spawn)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or_default();
+        return Err(FlashromError::Failed(reason.trim().into()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parses the `vendor="..." name="..."` line that flashrom prints with `--flash-name`.
+fn parse_chip(output: &str) -> Option<Chip<'_>> {
+    output.lines().find_map(|line| {
+        let quoted = |key: &str| -> Option<&str> {
+            let start = line.find(key)? + key.len();
+            let end = line[start..].find('"')?;
+            Some(&line[start..start + end])
+        };
+
+        Some(Chip { vendor: quoted("vendor=\"")?, name: quoted("name=\"")? })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Chip;
+
+    #[test]
+    fn parse_chip() {
+        let output = "flashrom v1.3.0 on Linux 6.5.0\n\
+                      Found chipset \"Intel Tiger Lake U Premium\".\n\
+                      vendor=\"Winbond\" name=\"W25Q256JV_M\"\n";
+
+        let chip = Chip { vendor: "Winbond", name: "W25Q256JV_M" };
+        assert_eq!(super::parse_chip(output), Some(chip));
+        assert_eq!(super::parse_chip("No EEPROM/flash device found.\n"), None);
+    }
+
+    #[test]
+    fn parse_version() {
+        let region = b"Google_Volteer.13672.0.0\0\0\0";
+        assert_eq!(super::parse_version(region).as_deref(), Some("Google_Volteer.13672.0.0"));
+        assert_eq!(super::parse_version(&[0xff; 8]), None);
+    }
+}
@@ -1,8 +1,126 @@
 //! Functions specific to working with fwupd firmware.
 
-use crate::{FirmwareInfo, FirmwareSignal};
-use fwupd_dbus::{Client as FwupdClient, Device as FwupdDevice, Release as FwupdRelease};
-use std::{cmp::Ordering, sync::mpsc::Sender};
+use crate::{
+    attestation::{self, Attestation},
+    checksum,
+    detach::{Detaching, Hotplug},
+    download::{self, DownloadEvent},
+    history, hsi, inhibit, journal, report,
+    retry::retry,
+    secure_boot::{secure_boot, SecureBoot},
+    space,
+    topology::{Sequencer, Ticket, Topology},
+    CancellationToken, DeviceCategory, DownloadError, Entity, FirmwareBackend, FirmwareError,
+    FirmwareEvent, FirmwareInfo, FirmwareSignal, PolicyReason, RebootPolicy, ReleaseChannel,
+};
+use dbus::{
+    arg::{prop_cast, PropMap},
+    blocking::Connection,
+};
+use fwupd_dbus::{
+    Client as FwupdClient, Device as FwupdDevice, DeviceFlags, InstallFlags,
+    Release as FwupdRelease,
+};
+use std::{
+    cmp::Ordering,
+    fs::{self, File},
+    path::Path,
+    sync::{atomic::AtomicBool, mpsc::Sender, Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+/// The name of the fwupd backend, by which its release channel is configured.
+pub(crate) const BACKEND: &str = "fwupd";
+
+/// The well-known name of the fwupd daemon on the system bus.
+pub(crate) const FWUPD_DEST: &str = "org.freedesktop.fwupd";
+
+/// The fwupd remote which provides releases from the testing channel of the LVFS.
+const LVFS_TESTING: &str = "lvfs-testing";
+
+/// The name of the branch which releases belong to if their vendor did not name one.
+pub const DEFAULT_BRANCH: &str = "default";
+
+/// The fwupd plugin which manages Thunderbolt controllers and docks.
+const THUNDERBOLT_PLUGIN: &str = "thunderbolt";
+
+/// The fwupd plugins which manage storage devices.
+const STORAGE_PLUGINS: &[&str] = &["ata", "emmc", "nvme", "scsi"];
+
+/// The fwupd plugin which manages Dell docks.
+const DELL_DOCK_PLUGIN: &str = "dell_dock";
+
+/// The icons which fwupd gives to input devices.
+const INPUT_ICONS: &[&str] =
+    &["input-gaming", "input-keyboard", "input-mouse", "input-tablet", "input-touchpad"];
+
+/// The icons which fwupd gives to storage devices.
+const STORAGE_ICONS: &[&str] = &["drive-harddisk", "drive-harddisk-solidstate", "media-flash"];
+
+/// The icons which fwupd gives to docks.
+const DOCK_ICONS: &[&str] = &["dock", "dock-usb", "thunderbolt"];
+
+/// The fwupd plugins which manage fingerprint readers.
+const FINGERPRINT_PLUGINS: &[&str] =
+    &["elanfp", "focalfp", "fpc", "goodixmoc", "synaptics_prometheus"];
+
+/// How urgently the vendor of a release advises that it be installed.
+///
+/// fwupd does not rate the severity of each issue that a release fixes, so the urgency of the
+/// release is also the severity of its [`ReleaseIssues`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReleaseUrgency {
+    /// The vendor did not state the urgency of the release.
+    #[default]
+    Unknown,
+    /// The release may be installed whenever is convenient.
+    Low,
+    /// The release should be installed soon.
+    Medium,
+    /// The release should be installed as soon as possible.
+    High,
+    /// The release fixes an issue which is actively exploited, or which risks data loss.
+    Critical,
+}
+
+impl ReleaseUrgency {
+    fn from_fwupd(urgency: u32) -> Self {
+        match urgency {
+            1 => ReleaseUrgency::Low,
+            2 => ReleaseUrgency::Medium,
+            3 => ReleaseUrgency::High,
+            4 => ReleaseUrgency::Critical,
+            _ => ReleaseUrgency::Unknown,
+        }
+    }
+}
+
+/// The security issues which a release fixes, as its metadata describes them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReleaseIssues {
+    /// The identifiers of the issues, which are usually CVEs, such as `CVE-2023-20569`.
+    pub ids: Vec<Box<str>>,
+    /// How urgently the vendor advises that the release be installed.
+    pub urgency: ReleaseUrgency,
+}
+
+/// The releases which an update from the installed version would include.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PendingReleases {
+    /// The number of releases which are newer than the installed version.
+    pub releases: usize,
+    /// The number of security issues which those releases fix.
+    pub fixes: usize,
+}
+
+/// The metadata of a release which the fwupd client does not describe.
+#[derive(Debug, PartialEq, Eq)]
+struct ReleaseMetadata {
+    version: Box<str>,
+    /// The branch that the release was published to.
+    branch: Box<str>,
+    issues: ReleaseIssues,
+}
 
 /// A signal sent when a fwupd-compatible device has been discovered.
 #[derive(Debug)]
@@ -13,8 +131,504 @@ pub struct FwupdSignal {
     pub device: FwupdDevice,
     /// Tracks whether the firmware is upgradeable or not.
     pub upgradeable: bool,
-    /// All releases that were found for the firmware.
+    /// All releases that were found for the firmware, from the channels which are enabled.
     pub releases: Vec<FwupdRelease>,
+    /// The channel of each release, in the same order as the `releases`.
+    pub channels: Vec<ReleaseChannel>,
+    /// The security issues which each release fixes, in the same order as the `releases`.
+    pub issues: Vec<ReleaseIssues>,
+    /// The reason that each release may not be installed, if the firmware policy of the system
+    /// does not approve it, in the same order as the `releases`.
+    pub policies: Vec<Option<PolicyReason>>,
+    /// The branch of the installed firmware, such as [`DEFAULT_BRANCH`] or `coreboot`.
+    ///
+    /// Only releases from this branch are included in the `releases`.
+    pub branch: Box<str>,
+    /// Other branches which the device may be switched to with [`FirmwareEvent::SwitchBranch`].
+    pub branches: Vec<Box<str>>,
+    /// New firmware was installed, but the device must be unplugged and plugged in again before
+    /// it takes effect. See [`needs_replug`].
+    pub replug_pending: bool,
+    /// Whether the measurements of system firmware match those expected of the installed release,
+    /// once an update of it has been installed by a reboot. See [`Attestation`].
+    pub attestation: Option<Attestation>,
+}
+
+/// A firmware backend for devices managed by the fwupd daemon.
+pub struct FwupdBackend {
+    client: Mutex<FwupdClient>,
+    sequencer: Sequencer,
+    detaching: Arc<Detaching>,
+}
+
+impl FwupdBackend {
+    /// Connects to the fwupd daemon, and listens for device requests and hotplug events in a
+    /// background thread.
+    ///
+    /// The listener will stop when the `cancellable` is set to `false`.
+    pub fn connect(sender: Sender<FirmwareSignal>, cancellable: Arc<AtomicBool>) -> Option<Self> {
+        let detaching = Arc::new(Detaching::default());
+
+        // Use Ping() to wake up fwupd, and to check if it exists.
+        let fwupd_connect = || {
+            let client = FwupdClient::new()?;
+            client.ping()?;
+
+            let _res = client.set_feature_flags(fwupd_dbus::FeatureFlags::REQUESTS);
+
+            let detaching = detaching.clone();
+            std::thread::spawn(move || {
+                if let Ok(client) = FwupdClient::new() {
+                    if let Ok(signals) = client.listen_signals(cancellable) {
+                        for signal in signals {
+                            use fwupd_dbus::Signal;
+                            let message = match signal {
+                                Signal::DeviceAdded(device) => {
+                                    let hotplug = detaching.added(&device.device_id);
+                                    match fwupd_signal(&client, device) {
+                                        Some(signal) if hotplug == Hotplug::Returned => {
+                                            FirmwareSignal::FwupdChanged(signal)
+                                        }
+                                        Some(signal) => FirmwareSignal::Fwupd(signal),
+                                        None => continue,
+                                    }
+                                }
+                                Signal::DeviceChanged(device) => {
+                                    match fwupd_signal(&client, device) {
+                                        Some(signal) => FirmwareSignal::FwupdChanged(signal),
+                                        None => continue,
+                                    }
+                                }
+                                Signal::DeviceRemoved(device) => {
+                                    match detaching.removed(&device.device_id) {
+                                        Hotplug::Withhold => continue,
+                                        _ => FirmwareSignal::FwupdRemoved(device.device_id),
+                                    }
+                                }
+                                Signal::DeviceRequest(request) => {
+                                    FirmwareSignal::DeviceRequest(request.update_message)
+                                }
+                                _ => continue,
+                            };
+
+                            if crate::config().ignores(&message) {
+                                continue;
+                            }
+
+                            if sender.send(message).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            });
+
+            Ok(client)
+        };
+
+        let client = crate::get_client::<_, _, fwupd_dbus::Error>("fwupd", || true, fwupd_connect)?;
+        Some(Self { client: Mutex::new(client), sequencer: Sequencer::default(), detaching })
+    }
+
+    /// Locks the client, so that requests to the daemon are made one at a time.
+    fn client(&self) -> MutexGuard<'_, FwupdClient> {
+        self.client.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl FirmwareBackend for FwupdBackend {
+    fn name(&self) -> &str { BACKEND }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        let client = self.client();
+        apply_release_channel(&client);
+
+        if let Err(why) = fwupd_updates(&client) {
+            let _res = sender.send(FirmwareSignal::Error(None, why.into()));
+        }
+
+        fwupd_scan(&client, sender.clone());
+    }
+
+    fn update(
+        &self,
+        event: &FirmwareEvent,
+        sender: &Sender<FirmwareSignal>,
+    ) -> Option<FirmwareSignal> {
+        let (entity, device, release, flags, cancellable) = match event {
+            FirmwareEvent::FwupdRemotes => return Some(self.modify_remotes(|_| Ok(()))),
+            FirmwareEvent::FwupdRemoteEnabled(remote_id, enable) => {
+                let modify = |client: &_| set_remote_enabled(client, remote_id, *enable);
+                return Some(self.modify_remotes(modify));
+            }
+            FirmwareEvent::FwupdRemoteRefresh(remote_id) => {
+                return Some(self.modify_remotes(|client| refresh_remote(client, remote_id)));
+            }
+            FirmwareEvent::HostSecurity => {
+                return Some(match hsi::security_attributes() {
+                    Ok(attributes) => FirmwareSignal::HostSecurity(attributes),
+                    Err(why) => FirmwareSignal::Error(None, FirmwareError::HostSecurity(why)),
+                });
+            }
+            FirmwareEvent::Fwupd(entity, device, release, cancellable) => {
+                (*entity, device, release.clone(), InstallFlags::empty(), cancellable)
+            }
+            FirmwareEvent::Downgrade(entity, device, version, cancellable) => {
+                let release = match self.release(device, version) {
+                    Some(release) => Arc::new(release),
+                    None => {
+                        let why = FirmwareError::Release(version.clone());
+                        return Some(FirmwareSignal::Error(Some(*entity), why));
+                    }
+                };
+
+                warn!("downgrading {} from {} to {}", device.name, device.version, version);
+                (*entity, device, release, InstallFlags::ALLOW_OLDER, cancellable)
+            }
+            FirmwareEvent::SwitchBranch(entity, device, branch, cancellable) => {
+                let release = match self.branch_release(device, branch) {
+                    Some(release) => Arc::new(release),
+                    None => {
+                        let why = FirmwareError::Branch(branch.clone());
+                        return Some(FirmwareSignal::Error(Some(*entity), why));
+                    }
+                };
+
+                warn!("switching {} to the {} branch", device.name, branch);
+                let flags = InstallFlags::ALLOW_BRANCH_SWITCH
+                    | InstallFlags::ALLOW_OLDER
+                    | InstallFlags::ALLOW_REINSTALL;
+                (*entity, device, release, flags, cancellable)
+            }
+            _ => return None,
+        };
+
+        let _span = info_span!("fwupd", device_id = %device.device_id, version = %release.version)
+            .entered();
+
+        let signal = self.update_device(entity, device, &release, flags, cancellable, sender);
+        history::record(&device_name(device), &device.version, &release.version, &signal);
+        Some(signal)
+    }
+}
+
+/// Risks that a user should be warned of before the firmware of a device is downgraded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DowngradeWarning {
+    /// Older firmware may be missing security fixes of the installed firmware.
+    Security,
+    /// The device is system firmware, which is installed on reboot. If the older firmware is
+    /// faulty, the system may be unable to boot.
+    SystemFirmware,
+}
+
+/// The risks of downgrading the firmware of a device, which a frontend should present before it
+/// requests a [`FirmwareEvent::Downgrade`].
+pub fn downgrade_warnings(device: &FwupdDevice) -> Vec<DowngradeWarning> {
+    let mut warnings = vec![DowngradeWarning::Security];
+
+    if device.needs_reboot() {
+        warnings.push(DowngradeWarning::SystemFirmware);
+    }
+
+    warnings
+}
+
+/// Whether the device is a Thunderbolt controller or dock.
+pub fn is_thunderbolt(device: &FwupdDevice) -> bool { &*device.plugin == THUNDERBOLT_PLUGIN }
+
+/// Whether the device is a fingerprint reader.
+///
+/// Fingerprint readers reset into their bootloader to be flashed, and so they disappear from
+/// fwupd while they are being updated.
+pub fn is_fingerprint_reader(device: &FwupdDevice) -> bool {
+    FINGERPRINT_PLUGINS.contains(&&*device.plugin)
+}
+
+/// Whether new firmware only takes effect once the device has been unplugged and plugged in
+/// again, as with Thunderbolt docks.
+///
+/// The firmware of internal Thunderbolt controllers is instead activated when the system is
+/// rebooted.
+pub fn needs_replug(device: &FwupdDevice) -> bool {
+    is_thunderbolt(device) && !device.flags.contains(DeviceFlags::INTERNAL)
+}
+
+/// The kind of the device, by the plugin which manages it and the icons which fwupd gives it.
+///
+/// System firmware is installed on reboot, and Thunderbolt docks are those whose firmware takes
+/// effect once they have been plugged in again.
+pub fn device_category(device: &FwupdDevice) -> DeviceCategory {
+    if device.needs_reboot() {
+        DeviceCategory::System
+    } else if needs_replug(device) {
+        DeviceCategory::Dock
+    } else {
+        category(&device.plugin, &device.icon)
+    }
+}
+
+fn category(plugin: &str, icons: &[Box<str>]) -> DeviceCategory {
+    let has_icon = |kind: &[&str]| icons.iter().any(|icon| kind.contains(&&**icon));
+
+    if plugin == DELL_DOCK_PLUGIN || has_icon(DOCK_ICONS) {
+        DeviceCategory::Dock
+    } else if STORAGE_PLUGINS.contains(&plugin) || has_icon(STORAGE_ICONS) {
+        DeviceCategory::Storage
+    } else if has_icon(INPUT_ICONS) {
+        DeviceCategory::Input
+    } else {
+        DeviceCategory::Other
+    }
+}
+
+/// The flags of a device, with the names which fwupd gives them.
+const FLAG_NAMES: &[(DeviceFlags, &str)] = &[
+    (DeviceFlags::INTERNAL, "internal"),
+    (DeviceFlags::UPDATABLE, "updatable"),
+    (DeviceFlags::ONLY_OFFLINE, "only-offline"),
+    (DeviceFlags::REQUIRE_AC, "require-ac"),
+    (DeviceFlags::LOCKED, "locked"),
+    (DeviceFlags::SUPPORTED, "supported"),
+    (DeviceFlags::NEEDS_BOOTLOADER, "needs-bootloader"),
+    (DeviceFlags::REGISTERED, "registered"),
+    (DeviceFlags::NEEDS_REBOOT, "needs-reboot"),
+    (DeviceFlags::REPORTED, "reported"),
+    (DeviceFlags::NOTIFIED, "notified"),
+    (DeviceFlags::USE_RUNTIME_VERSION, "use-runtime-version"),
+    (DeviceFlags::INSTALL_PARENT_FIRST, "install-parent-first"),
+    (DeviceFlags::IS_BOOTLOADER, "is-bootloader"),
+    (DeviceFlags::WAIT_FOR_REPLUG, "wait-for-replug"),
+    (DeviceFlags::IGNORE_VALIDATION, "ignore-validation"),
+    (DeviceFlags::TRUSTED, "trusted"),
+    (DeviceFlags::NEEDS_SHUTDOWN, "needs-shutdown"),
+    (DeviceFlags::ANOTHER_WRITE_REQUIRED, "another-write-required"),
+    (DeviceFlags::NO_AUTO_INSTANCE_IDS, "no-auto-instance-ids"),
+    (DeviceFlags::NEEDS_ACTIVATION, "needs-activation"),
+    (DeviceFlags::ENSURE_SEMVER, "ensure-semver"),
+    (DeviceFlags::HISTORICAL, "historical"),
+];
+
+/// The names of the flags of a device, as fwupd names them, such as `needs-reboot`.
+pub fn flag_names(flags: DeviceFlags) -> Vec<&'static str> {
+    FLAG_NAMES.iter().filter(|(flag, _)| flags.contains(*flag)).map(|&(_, name)| name).collect()
+}
+
+/// Risks that a user should be warned of before the firmware of a device is switched to another
+/// branch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BranchWarning {
+    /// Firmware on another branch may be provided by someone other than the vendor of the device,
+    /// who may not support the device while it runs that firmware.
+    Unsupported,
+    /// The device is system firmware, which is installed on reboot. Switching back to the original
+    /// branch may not be possible if the firmware of the other branch is faulty.
+    SystemFirmware,
+    /// The system firmware enforces Secure Boot, which firmware on another branch may not
+    /// support, or whose keys it may not trust.
+    SecureBoot,
+}
+
+/// The risks of switching the firmware of a device to another branch, which a frontend must have
+/// the user confirm before it requests a [`FirmwareEvent::SwitchBranch`].
+pub fn branch_warnings(device: &FwupdDevice) -> Vec<BranchWarning> {
+    let mut warnings = vec![BranchWarning::Unsupported];
+
+    if device.needs_reboot() {
+        warnings.push(BranchWarning::SystemFirmware);
+
+        if secure_boot() == Some(SecureBoot::Enabled) {
+            warnings.push(BranchWarning::SecureBoot);
+        }
+    }
+
+    warnings
+}
+
+impl FwupdBackend {
+    /// Applies a change to the remotes, and then lists the remotes as they are afterwards.
+    fn modify_remotes<F>(&self, modify: F) -> FirmwareSignal
+    where
+        F: FnOnce(&FwupdClient) -> Result<(), FirmwareError>,
+    {
+        let client = self.client();
+        match modify(&client).and_then(|()| remotes(&client)) {
+            Ok(remotes) => FirmwareSignal::FwupdRemotes(remotes),
+            Err(why) => FirmwareSignal::Error(None, why),
+        }
+    }
+
+    /// Downloads the release, and then installs it on the device.
+    fn update_device(
+        &self,
+        entity: Entity,
+        device: &FwupdDevice,
+        release: &FwupdRelease,
+        flags: InstallFlags,
+        cancellable: &CancellationToken,
+        sender: &Sender<FirmwareSignal>,
+    ) -> FirmwareSignal {
+        // Scheduled before downloading, so that the order of related devices which are updated
+        // together does not depend on which of their downloads completes first.
+        let mut ticket = self.schedule(device);
+
+        if let Err(why) = policy(device, release) {
+            return FirmwareSignal::Error(Some(entity), why);
+        }
+
+        if let Err(why) = preflight(device, release) {
+            return FirmwareSignal::Error(Some(entity), why);
+        }
+
+        let progress = |event| {
+            let _res = sender.send(match event {
+                DownloadEvent::Begin(size) => FirmwareSignal::DownloadBegin(entity, size),
+                DownloadEvent::Progress(progress) => {
+                    FirmwareSignal::DownloadUpdate(entity, progress)
+                }
+                DownloadEvent::Complete => FirmwareSignal::DownloadComplete(entity),
+            });
+        };
+
+        let download = info_span!("download")
+            .in_scope(|| download::fetch(&release.uri, &release.checksum, cancellable, progress));
+
+        let path = match download {
+            Ok(path) => path,
+            Err(DownloadError::Cancelled) => return FirmwareSignal::Cancelled(entity),
+            Err(why) => return FirmwareSignal::Error(Some(entity), why.into()),
+        };
+
+        if let Err(why) = verify(entity, &path, &release.checksum, sender) {
+            return FirmwareSignal::Error(Some(entity), why);
+        }
+
+        ticket.wait();
+        if cancellable.is_cancelled() {
+            return FirmwareSignal::Cancelled(entity);
+        }
+
+        // System firmware is staged by fwupd's offline update mechanism, rather than by the
+        // plugin, when the user chose to install it on their next reboot.
+        let staged =
+            device.needs_reboot() && crate::config().reboot_policy == RebootPolicy::NextBoot;
+
+        let flags = if staged { flags | InstallFlags::OFFLINE } else { flags };
+
+        let name = device_name(device);
+        let _inhibitor = inhibit::flashing(&name);
+        let _flashing = journal::begin(&name, &device.version, &release.version);
+        let _detaching =
+            is_fingerprint_reader(device).then(|| self.detaching.expect(&device.device_id));
+        let _res = sender.send(FirmwareSignal::DeviceFlashing(entity));
+
+        let result = info_span!("flash").in_scope(|| self.install(device, &path, flags));
+        report::offer(device, release, result.as_ref().err(), sender);
+
+        match result {
+            Ok(()) if staged => FirmwareSignal::Staged(entity),
+            Ok(()) if needs_replug(device) => FirmwareSignal::ReplugRequired(entity),
+            Ok(()) => FirmwareSignal::DeviceUpdated(entity),
+            Err(why) => FirmwareSignal::Error(Some(entity), why),
+        }
+    }
+
+    /// Schedules the flashing of a device after the other devices of its composite device, if it
+    /// is part of one, which must be flashed before it.
+    fn schedule(&self, device: &FwupdDevice) -> Ticket<'_> {
+        let devices = self.client().devices().unwrap_or_default();
+        self.sequencer.schedule(&Topology::from_devices(&devices), &device.device_id)
+    }
+
+    /// The published release of the device with the given version, if there is one.
+    fn release(&self, device: &FwupdDevice, version: &str) -> Option<FwupdRelease> {
+        let releases = self.client().releases(device).ok()?;
+        releases.into_iter().find(|release| &*release.version == version)
+    }
+
+    /// The latest release of the device from the given branch, if there is one.
+    fn branch_release(&self, device: &FwupdDevice, branch: &str) -> Option<FwupdRelease> {
+        let branches = release_metadata(&device.device_id)
+            .map_err(|why| error!("failed to get branches of {}: {}", device.name, why))
+            .ok()?;
+
+        let mut releases = self.client().releases(device).ok()?;
+        releases.retain(|release| branch_of(&branches, &release.version) == branch);
+        crate::sort_versions(&mut releases);
+        releases.pop()
+    }
+
+    /// Installs downloaded firmware with the fwupd daemon.
+    ///
+    /// The download is performed by the backend, rather than by the fwupd client, so that it may
+    /// be cancelled before the device begins flashing.
+    fn install(
+        &self,
+        device: &FwupdDevice,
+        path: &Path,
+        flags: InstallFlags,
+    ) -> Result<(), FirmwareError> {
+        let file = File::open(path).map_err(FirmwareError::Open)?;
+        let filename = path.to_string_lossy();
+        self.client()
+            .install(device, "(null)", &filename, file, flags)
+            .map_err(FirmwareError::from_daemon)
+    }
+}
+
+/// Hashes the downloaded firmware, and compares it to the checksum published with the release.
+///
+/// Firmware which does not match is removed from the cache, so that it is downloaded again by the
+/// next attempt.
+fn verify(
+    entity: Entity,
+    path: &Path,
+    checksums: &str,
+    sender: &Sender<FirmwareSignal>,
+) -> Result<(), FirmwareError> {
+    let checksum = match checksum::preferred(checksums) {
+        Some(checksum) => checksum,
+        None => {
+            warn!("release does not have a supported checksum: skipping verification");
+            return Ok(());
+        }
+    };
+
+    let _res = sender.send(FirmwareSignal::Verifying(entity));
+
+    match info_span!("verify").in_scope(|| checksum::verify(path, checksum)) {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            error!("{} does not match its checksum: {}", path.display(), checksum);
+            let _res = fs::remove_file(path);
+            Err(FirmwareError::Verification(path.to_path_buf()))
+        }
+        Err(why) => Err(FirmwareError::Open(why)),
+    }
+}
+
+/// Checks if the firmware policy of the system approves the release for the device.
+fn policy(device: &FwupdDevice, release: &FwupdRelease) -> Result<(), FirmwareError> {
+    crate::check_policy(&device_name(device), &device_ids(device), &release.version)
+        .map_err(|why| FirmwareError::Policy(release.version.clone(), why))
+}
+
+/// Ensures that there is space to download the release, and that the EFI System Partition may be
+/// used to stage it if it is a capsule.
+fn preflight(device: &FwupdDevice, release: &FwupdRelease) -> Result<(), FirmwareError> {
+    if device.needs_reboot() {
+        space::ensure_esp(release.size)?;
+    }
+
+    if release.size == 0 {
+        return Ok(());
+    }
+
+    if let Ok(cache) = crate::cache::cache("firmware") {
+        space::ensure_available(&cache, release.size)?;
+    }
+
+    Ok(())
 }
 
 /// Scan for supported devices from the fwupd DBus daemon.
@@ -30,53 +644,305 @@ pub fn fwupd_scan(fwupd: &FwupdClient, sender: Sender<FirmwareSignal>) {
     };
 
     for device in devices {
-        if device.is_supported() {
-            let releases = match fwupd.releases(&device) {
-                Ok(mut releases) => {
-                    crate::sort_versions(&mut releases);
-                    releases
-                }
-                Err(why) => {
-                    error!(
-                        "failure to get fwupd releases for {}: {}",
-                        device.name,
-                        super::format_error(why)
-                    );
+        if let Some(signal) = fwupd_signal(fwupd, device) {
+            let _res = sender.send(FirmwareSignal::Fwupd(signal));
+        }
+    }
 
-                    Vec::new()
-                }
-            };
+    info!("fwupd scanning complete");
+}
 
-            let latest = releases.iter().last();
-            let upgradeable = latest.map_or(false, |latest| {
-                is_newer(&device.version, &latest.version)
-            });
-            let install_duration = latest.map_or(0, |latest| {
-                latest.install_duration
+/// Fetches the releases of a supported device, to describe its firmware to a frontend.
+///
+/// Returns `None` if the device does not support being updated by fwupd.
+pub(crate) fn fwupd_signal(fwupd: &FwupdClient, device: FwupdDevice) -> Option<FwupdSignal> {
+    if !device.is_supported() {
+        return None;
+    }
+
+    let channel = crate::config().release_channel_of(BACKEND);
+
+    let branches = release_metadata(&device.device_id).unwrap_or_else(|why| {
+        warn!("failed to get branches of {}: {}", device.name, why);
+        Vec::new()
+    });
+
+    let branch: Box<str> = branch_of(&branches, &device.version).into();
+
+    let releases = match fwupd.releases(&device) {
+        Ok(mut releases) => {
+            // Releases from other branches are only installed by switching branches explicitly.
+            releases.retain(|release| {
+                release_channel(release) <= channel
+                    && branch_of(&branches, &release.version) == &*branch
             });
 
-            let _res = sender.send(FirmwareSignal::Fwupd(FwupdSignal {
-                info: FirmwareInfo {
-                    name: [&device.vendor, " ", &device.name].concat().into(),
-                    current: device.version.clone(),
-                    latest: latest.map(|latest| latest.version.clone()),
-                    install_duration,
-                },
-                device,
-                upgradeable,
-                releases,
-            }));
+            crate::sort_versions(&mut releases);
+            releases
+        }
+        Err(why) => {
+            error!(
+                "failure to get fwupd releases for {}: {}",
+                device.name,
+                super::format_error(why)
+            );
+
+            Vec::new()
+        }
+    };
+
+    let mut other_branches = Vec::<Box<str>>::new();
+    for ReleaseMetadata { branch: name, .. } in &branches {
+        if *name != branch && !other_branches.contains(name) {
+            other_branches.push(name.clone());
         }
     }
 
-    info!("fwupd scanning complete");
+    let name = device_name(&device);
+    let ids = device_ids(&device);
+    let policies = releases
+        .iter()
+        .map(|release| crate::check_policy(&name, &ids, &release.version).err())
+        .collect::<Vec<_>>();
+
+    let latest = releases.iter().last();
+    let upgradeable = latest.map_or(false, |latest| is_newer(&device.version, &latest.version));
+    let install_duration = latest.map_or(0, |latest| latest.install_duration);
+    let replug_pending =
+        needs_replug(&device) && device.flags.contains(DeviceFlags::NEEDS_ACTIVATION);
+    let attestation = attestation::attest(&device, &name);
+
+    Some(FwupdSignal {
+        info: FirmwareInfo {
+            name: name.into(),
+            current: device.version.clone(),
+            latest: latest.map(|latest| latest.version.clone()),
+            install_duration,
+            policy: policies.last().copied().flatten(),
+        },
+        device,
+        upgradeable,
+        channels: releases.iter().map(release_channel).collect(),
+        issues: releases.iter().map(|release| issues_of(&branches, &release.version)).collect(),
+        policies,
+        releases,
+        branch,
+        branches: other_branches,
+        replug_pending,
+        attestation,
+    })
+}
+
+/// The branch and the issues of each release of a device.
+///
+/// The fwupd client does not describe branches or issues, so the releases are requested from the
+/// daemon directly.
+fn release_metadata(device_id: &str) -> Result<Vec<ReleaseMetadata>, dbus::Error> {
+    let connection = Connection::new_system()?;
+    let proxy = connection.with_proxy(FWUPD_DEST, "/", Duration::from_secs(30));
+    let (releases,): (Vec<PropMap>,) =
+        proxy.method_call(FWUPD_DEST, "GetReleases", (device_id,))?;
+
+    Ok(releases.iter().filter_map(parse_release_metadata).collect())
+}
+
+/// Parses the properties of a release, where releases which do not name a branch belong to the
+/// [`DEFAULT_BRANCH`].
+fn parse_release_metadata(release: &PropMap) -> Option<ReleaseMetadata> {
+    let version = prop_cast::<String>(release, "Version")?;
+    let branch = prop_cast::<String>(release, "Branch").map_or(DEFAULT_BRANCH, String::as_str);
+
+    // Arrays are not cast to vectors when they are read from messages, so they are iterated.
+    let ids = release
+        .get("Issues")
+        .and_then(|issues| issues.0.as_iter())
+        .map(|issues| issues.filter_map(|id| id.as_str().map(Box::from)).collect())
+        .unwrap_or_default();
+
+    let urgency = prop_cast::<u32>(release, "Urgency").copied().unwrap_or(0);
+
+    Some(ReleaseMetadata {
+        version: version.as_str().into(),
+        branch: branch.into(),
+        issues: ReleaseIssues { ids, urgency: ReleaseUrgency::from_fwupd(urgency) },
+    })
+}
+
+/// The branch of the release with the given version, which is assumed to be the
+/// [`DEFAULT_BRANCH`] if the version was not published.
+fn branch_of<'a>(releases: &'a [ReleaseMetadata], version: &str) -> &'a str {
+    releases
+        .iter()
+        .find(|release| &*release.version == version)
+        .map_or(DEFAULT_BRANCH, |release| &release.branch)
+}
+
+/// How urgently a device should be updated, which is the greatest urgency of the releases that
+/// are newer than its `current` firmware.
+///
+/// The `issues` of each release are in the same order as the `releases`, as they are given by a
+/// [`FwupdSignal`].
+pub fn update_urgency(
+    current: &str,
+    releases: &[FwupdRelease],
+    issues: &[ReleaseIssues],
+) -> ReleaseUrgency {
+    let urgencies = issues.iter().map(|issues| issues.urgency);
+    newest_urgency(current, releases.iter().map(|release| &*release.version).zip(urgencies))
+}
+
+fn newest_urgency<'a>(
+    current: &str,
+    releases: impl Iterator<Item = (&'a str, ReleaseUrgency)>,
+) -> ReleaseUrgency {
+    releases
+        .filter(|(version, _)| is_newer(current, version))
+        .map(|(_, urgency)| urgency)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Summarizes the releases which are newer than the `current` version, given the `releases` and
+/// `issues` of a [`FwupdSignal`].
+pub fn pending_releases(
+    current: &str,
+    releases: &[FwupdRelease],
+    issues: &[ReleaseIssues],
+) -> PendingReleases {
+    let fixes = issues.iter().map(|issues| issues.ids.len());
+    count_pending(current, releases.iter().map(|release| &*release.version).zip(fixes))
+}
+
+fn count_pending<'a>(
+    current: &str,
+    releases: impl Iterator<Item = (&'a str, usize)>,
+) -> PendingReleases {
+    releases.filter(|(version, _)| is_newer(current, version)).fold(
+        PendingReleases::default(),
+        |pending, (_, fixes)| PendingReleases {
+            releases: pending.releases + 1,
+            fixes: pending.fixes + fixes,
+        },
+    )
+}
+
+/// The issues which the release with the given version fixes.
+fn issues_of(releases: &[ReleaseMetadata], version: &str) -> ReleaseIssues {
+    releases
+        .iter()
+        .find(|release| &*release.version == version)
+        .map_or_else(ReleaseIssues::default, |release| release.issues.clone())
+}
+
+/// The channel that a release was published to, as determined by the remote that provides it.
+fn release_channel(release: &FwupdRelease) -> ReleaseChannel { remote_channel(&release.remote_id) }
+
+/// The channel that a remote publishes releases to, as indicated by its ID.
+fn remote_channel(remote_id: &str) -> ReleaseChannel {
+    if remote_id.contains("testing") {
+        ReleaseChannel::Testing
+    } else if remote_id.contains("beta") {
+        ReleaseChannel::Beta
+    } else {
+        ReleaseChannel::Stable
+    }
+}
+
+/// Enables the LVFS testing remote if the fwupd backend follows the testing channel, and disables
+/// it otherwise. The remotes are left as they are if no channel was configured.
+fn apply_release_channel(client: &FwupdClient) {
+    let enable = match crate::config().configured_channel_of(BACKEND) {
+        Some(channel) => channel == ReleaseChannel::Testing,
+        None => return,
+    };
+
+    match set_remote_enabled(client, LVFS_TESTING, enable) {
+        Ok(()) | Err(FirmwareError::UnknownRemote(_)) => (),
+        Err(why) => warn!("failed to apply the release channel: {}", super::format_error(why)),
+    }
+}
+
+/// A source of firmware metadata which fwupd may fetch releases from.
+#[derive(Clone, Debug)]
+pub struct FwupdRemote {
+    /// Identifies the remote, such as `lvfs` or `lvfs-testing`.
+    pub id: Box<str>,
+    /// Whether releases from this remote are offered.
+    pub enabled: bool,
+    /// Whether the metadata of this remote is downloaded, rather than installed locally.
+    pub download: bool,
+    /// The release channel which this remote publishes to.
+    pub channel: ReleaseChannel,
+}
+
+/// Lists the remotes which are known to fwupd.
+fn remotes(client: &FwupdClient) -> Result<Vec<FwupdRemote>, FirmwareError> {
+    let remotes = client.remotes()?.into_iter().map(|remote| FwupdRemote {
+        channel: remote_channel(&remote.remote_id),
+        download: matches!(remote.kind, fwupd_dbus::RemoteKind::Download),
+        enabled: remote.enabled,
+        id: remote.remote_id,
+    });
+
+    Ok(remotes.collect())
+}
+
+/// Finds the remote with the given ID.
+fn find_remote(client: &FwupdClient, remote_id: &str) -> Result<fwupd_dbus::Remote, FirmwareError> {
+    client
+        .remotes()?
+        .into_iter()
+        .find(|remote| &*remote.remote_id == remote_id)
+        .ok_or_else(|| FirmwareError::UnknownRemote(remote_id.into()))
+}
+
+/// Enables or disables a remote, and fetches its metadata if it was enabled.
+fn set_remote_enabled(
+    client: &FwupdClient,
+    remote_id: &str,
+    enable: bool,
+) -> Result<(), FirmwareError> {
+    let remote = find_remote(client, remote_id)?;
+    if remote.enabled == enable {
+        return Ok(());
+    }
+
+    info!("{} the {} remote", if enable { "enabling" } else { "disabling" }, remote_id);
+
+    let enabled = if enable { "true" } else { "false" };
+    modify_remote(remote_id, "Enabled", enabled).map_err(|why| {
+        FirmwareError::from_daemon(FirmwareError::Remote(remote_id.into(), why))
+    })?;
+
+    if enable {
+        update_metadata(client, &remote)?;
+    }
+
+    Ok(())
+}
+
+/// Fetches the latest metadata of the remote with the given ID.
+fn refresh_remote(client: &FwupdClient, remote_id: &str) -> Result<(), FirmwareError> {
+    update_metadata(client, &find_remote(client, remote_id)?)
+}
+
+/// Fetches the latest metadata of a remote, retrying if the request fails.
+fn update_metadata(client: &FwupdClient, remote: &fwupd_dbus::Remote) -> Result<(), FirmwareError> {
+    info!("updating {} metadata", remote.remote_id);
+    let update = || remote.update_metadata(client);
+    retry("update remote metadata", None, |_| true, update).map_err(FirmwareError::from_daemon)
+}
+
+/// Sets a property of a fwupd remote.
+fn modify_remote(remote_id: &str, key: &str, value: &str) -> Result<(), dbus::Error> {
+    let connection = Connection::new_system()?;
+    let proxy = connection.with_proxy(FWUPD_DEST, "/", Duration::from_secs(30));
+    proxy.method_call(FWUPD_DEST, "ModifyRemote", (remote_id, key, value))
 }
 
 /// Update the fwupd remotes
 pub fn fwupd_updates(client: &FwupdClient) -> Result<(), fwupd_dbus::Error> {
-    const SECONDS_IN_DAY: u64 = 60 * 60 * 24;
-
-    if crate::timestamp::exceeded(SECONDS_IN_DAY).ok().unwrap_or(true) {
+    if crate::timestamp::exceeded(crate::config().check_interval).ok().unwrap_or(true) {
         info!("refreshing remotes");
 
         if let Err(why) = crate::timestamp::refresh() {
@@ -92,7 +958,8 @@ pub fn fwupd_updates(client: &FwupdClient) -> Result<(), fwupd_dbus::Error> {
 
             if let fwupd_dbus::RemoteKind::Download = remote.kind {
                 info!("Updating {:?} metadata from {:?}", remote.remote_id, remote.uri);
-                if let Err(why) = remote.update_metadata(client) {
+                let update = || remote.update_metadata(client);
+                if let Err(why) = retry("update remote metadata", None, |_| true, update) {
                     error!(
                         "failed to fetch updates from {}: {:?}",
                         remote.filename_cache,
@@ -106,13 +973,137 @@ pub fn fwupd_updates(client: &FwupdClient) -> Result<(), fwupd_dbus::Error> {
     Ok(())
 }
 
-// Returns `true` if the `latest` string is a newer version than the `current` string.
-fn is_newer(current: &str, latest: &str) -> bool {
+/// The name of a device, as it is displayed to the user.
+pub(crate) fn device_name(device: &FwupdDevice) -> String {
+    [&device.vendor, " ", &device.name].concat()
+}
+
+/// The serial numbers of the devices that fwupd reports which match the `filter`, or `None` if
+/// fwupd is not available.
+///
+/// Other backends use this to avoid listing devices which the fwupd backend already lists.
+pub(crate) fn reported_serials<F>(filter: F) -> Option<Vec<Option<Box<str>>>>
+where
+    F: Fn(&FwupdDevice) -> bool,
+{
+    Some(reported_devices(filter)?.into_iter().map(|device| device.serial).collect())
+}
+
+/// The devices which fwupd reports, of those which match the `filter`, or `None` if fwupd is not
+/// available.
+pub(crate) fn reported_devices<F>(filter: F) -> Option<Vec<FwupdDevice>>
+where
+    F: Fn(&FwupdDevice) -> bool,
+{
+    let devices = FwupdClient::new().and_then(|client| client.devices()).ok()?;
+    Some(devices.into_iter().filter(filter).collect())
+}
+
+/// The ID and GUIDs of a device, by which it may be ignored or approved in the configuration.
+pub(crate) fn device_ids(device: &FwupdDevice) -> Vec<&str> {
+    std::iter::once(&device.device_id).chain(device.guid.iter()).map(AsRef::as_ref).collect()
+}
+
+/// Returns `true` if the `latest` string is a newer version than the `current` string.
+pub fn is_newer(current: &str, latest: &str) -> bool {
     human_sort::compare(current, latest) == Ordering::Less
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn metadata(version: &str, branch: &str) -> ReleaseMetadata {
+        ReleaseMetadata {
+            version: version.into(),
+            branch: branch.into(),
+            issues: ReleaseIssues::default(),
+        }
+    }
+
+    #[test]
+    fn branch_of() {
+        let branches = [metadata("1.2", "default"), metadata("4.11", "coreboot")];
+
+        assert_eq!(super::branch_of(&branches, "1.2"), DEFAULT_BRANCH);
+        assert_eq!(super::branch_of(&branches, "4.11"), "coreboot");
+        assert_eq!(super::branch_of(&branches, "1.1"), DEFAULT_BRANCH);
+    }
+
+    #[test]
+    fn parse_release_metadata() {
+        use dbus::arg::{RefArg, Variant};
+
+        let mut properties = PropMap::new();
+        let mut insert = |key: &str, value: Box<dyn RefArg>| {
+            properties.insert(key.into(), Variant(value));
+        };
+
+        insert("Version", Box::new(String::from("1.2")));
+        insert("Issues", Box::new(vec![String::from("CVE-2023-20569")]));
+        insert("Urgency", Box::new(3u32));
+
+        let release = super::parse_release_metadata(&properties).unwrap();
+        assert_eq!(&*release.branch, DEFAULT_BRANCH);
+        assert_eq!(release.issues.ids, [Box::from("CVE-2023-20569")]);
+        assert_eq!(release.issues.urgency, ReleaseUrgency::High);
+
+        let releases = [release];
+        assert_eq!(super::issues_of(&releases, "1.2").urgency, ReleaseUrgency::High);
+        assert_eq!(super::issues_of(&releases, "1.1"), ReleaseIssues::default());
+    }
+
+    #[test]
+    fn newest_urgency() {
+        let releases = [
+            ("1.1", ReleaseUrgency::Critical),
+            ("1.2", ReleaseUrgency::Low),
+            ("1.3", ReleaseUrgency::High),
+        ];
+
+        let urgency = |current| super::newest_urgency(current, releases.iter().copied());
+        assert_eq!(urgency("1.0"), ReleaseUrgency::Critical);
+        assert_eq!(urgency("1.1"), ReleaseUrgency::High);
+        assert_eq!(urgency("1.3"), ReleaseUrgency::Unknown);
+    }
+
+    #[test]
+    fn count_pending() {
+        let releases = [("1.1", 2), ("1.2", 0), ("1.3", 5)];
+
+        let pending = |current| super::count_pending(current, releases.iter().copied());
+        assert_eq!(pending("1.0"), PendingReleases { releases: 3, fixes: 7 });
+        assert_eq!(pending("1.1"), PendingReleases { releases: 2, fixes: 5 });
+        assert_eq!(pending("1.3"), PendingReleases::default());
+    }
+
+    #[test]
+    fn category() {
+        let icons = |icons: &[&str]| icons.iter().map(|&icon| Box::from(icon)).collect::<Vec<_>>();
+
+        assert_eq!(super::category("nvme", &[]), DeviceCategory::Storage);
+        assert_eq!(super::category("dell_dock", &icons(&["audio-card"])), DeviceCategory::Dock);
+        assert_eq!(super::category("hidpp", &icons(&["input-mouse"])), DeviceCategory::Input);
+        assert_eq!(super::category("vli", &icons(&["dock-usb"])), DeviceCategory::Dock);
+        assert_eq!(super::category("uefi_capsule", &icons(&["computer"])), DeviceCategory::Other);
+    }
+
+    #[test]
+    fn flag_names() {
+        assert!(super::flag_names(DeviceFlags::empty()).is_empty());
+        assert_eq!(super::flag_names(DeviceFlags::UPDATABLE), ["updatable"]);
+
+        let names = super::flag_names(DeviceFlags::NEEDS_REBOOT | DeviceFlags::INTERNAL);
+        assert_eq!(names, ["internal", "needs-reboot"]);
+    }
+
+    #[test]
+    fn remote_channel() {
+        assert_eq!(super::remote_channel("lvfs"), ReleaseChannel::Stable);
+        assert_eq!(super::remote_channel("lvfs-testing"), ReleaseChannel::Testing);
+        assert_eq!(super::remote_channel("vendor-beta"), ReleaseChannel::Beta);
+    }
+
     #[test]
     pub fn is_newer() {
         assert!(super::is_newer("0.2.8", "0.2.11"));
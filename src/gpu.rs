@@ -0,0 +1,175 @@
+//! A backend which reports the video BIOS versions of graphics cards.
+//!
+//! The video BIOS of a graphics card is updated by its vendor's tools, if at all, so it is only
+//! reported for the inventory of the system to be complete. The amdgpu driver exposes the version
+//! in sysfs, while the NVIDIA driver describes each of its GPUs in procfs.
+
+use crate::{DeviceId, DeviceSignal, FirmwareBackend, FirmwareEvent, FirmwareInfo, FirmwareSignal};
+use std::{fs, path::Path, sync::mpsc::Sender};
+
+/// The name of the GPU backend, which identifies its devices.
+const BACKEND: &str = "gpu";
+
+/// Where the DRM devices of the system are described.
+const DRM_DEVICES: &str = "/sys/class/drm";
+
+/// Where the NVIDIA driver describes the GPUs that it drives.
+const NVIDIA_GPUS: &str = "/proc/driver/nvidia/gpus";
+
+/// The name of an AMD GPU which does not report its product name.
+const AMD_NAME: &str = "AMD Radeon Graphics";
+
+/// A graphics card, and the version of its video BIOS.
+#[derive(Debug, PartialEq, Eq)]
+struct Gpu {
+    /// The PCI slot of the card, such as `0000:03:00.0`.
+    slot: Box<str>,
+    /// The model of the card.
+    model: Box<str>,
+    /// The version of the video BIOS.
+    vbios: Box<str>,
+}
+
+/// A firmware backend which reports the video BIOS versions of graphics cards.
+#[derive(Default)]
+pub struct GpuBackend;
+
+impl FirmwareBackend for GpuBackend {
+    fn name(&self) -> &str { BACKEND }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        let mut gpus = amd_gpus(Path::new(DRM_DEVICES));
+        gpus.extend(nvidia_gpus(Path::new(NVIDIA_GPUS)));
+
+        for gpu in gpus {
+            let _res = sender.send(FirmwareSignal::Device(DeviceSignal {
+                device: DeviceId { backend: BACKEND.into(), id: gpu.slot },
+                info: FirmwareInfo {
+                    name: gpu.model,
+                    current: gpu.vbios,
+                    latest: None,
+                    install_duration: 0,
+                    policy: None,
+                },
+                read_only: true,
+            }));
+        }
+    }
+
+    /// Video BIOSes are only updated with the tools of their vendors.
+    fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+        None
+    }
+}
+
+/// The GPUs whose DRM devices expose the version of their video BIOS.
+fn amd_gpus(drm: &Path) -> Vec<Gpu> {
+    let entries = match fs::read_dir(drm) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut gpus = entries
+        .filter_map(Result::ok)
+        // Connectors, such as `card0-DP-1`, belong to the card that they are named after.
+        .filter(|entry| is_card(&entry.file_name().to_string_lossy()))
+        .filter_map(|entry| {
+            let device = entry.path().join("device");
+            let attribute = |name: &str| -> Option<Box<str>> {
+                let value = fs::read_to_string(device.join(name)).ok()?;
+                let value = value.trim();
+                Some(value.into()).filter(|_| !value.is_empty())
+            };
+
+            let vbios = attribute("vbios_version")?;
+            let slot = fs::canonicalize(&device).ok()?.file_name()?.to_string_lossy().into();
+            let model = attribute("product_name").unwrap_or_else(|| AMD_NAME.into());
+
+            Some(Gpu { slot, model, vbios })
+        })
+        .collect::<Vec<_>>();
+
+    gpus.sort_by(|a, b| a.slot.cmp(&b.slot));
+    gpus.dedup_by(|a, b| a.slot == b.slot);
+    gpus
+}
+
+/// The GPUs which are driven by the NVIDIA driver.
+fn nvidia_gpus(gpus: &Path) -> Vec<Gpu> {
+    let entries = match fs::read_dir(gpus) {
+        Ok(entries) => entries,
+        // The directory only exists when the NVIDIA driver is loaded.
+        Err(_) => return Vec::new(),
+    };
+
+    let mut gpus = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let information = fs::read_to_string(entry.path().join("information")).ok()?;
+            let slot = entry.file_name().to_string_lossy().into();
+            parse_nvidia_information(slot, &information)
+        })
+        .collect::<Vec<_>>();
+
+    gpus.sort_by(|a, b| a.slot.cmp(&b.slot));
+    gpus
+}
+
+/// Checks if a DRM device is a card, such as `card0`, rather than one of its connectors.
+fn is_card(name: &str) -> bool {
+    match name.strip_prefix("card") {
+        Some(index) => !index.is_empty() && index.bytes().all(|byte| byte.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// Parses the `information` file which the NVIDIA driver writes for each of its GPUs.
+///
+/// Each line is a `Key: value` pair, of which the model and video BIOS version are needed.
+fn parse_nvidia_information(slot: Box<str>, information: &str) -> Option<Gpu> {
+    let value = |key: &str| -> Option<Box<str>> {
+        information.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            let value = value.trim();
+            Some(value.into()).filter(|_| name.trim() == key && !value.is_empty())
+        })
+    };
+
+    Some(Gpu { slot, model: value("Model")?, vbios: value("Video BIOS")? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Gpu;
+
+    #[test]
+    fn parse_nvidia_information() {
+        let information = "Model: \t\t NVIDIA GeForce RTX 3060 Laptop GPU\n\
+                           IRQ:   \t\t 155\n\
+                           GPU UUID: \t GPU-1b2c3d4e-5f60-7182-93a4-b5c6d7e8f901\n\
+                           Video BIOS: \t 94.06.19.00.4b\n\
+                           Bus Type: \t PCIe\n\
+                           Bus Location: \t 0000:01:00.0\n";
+
+        let expected = Gpu {
+            slot: "0000:01:00.0".into(),
+            model: "NVIDIA GeForce RTX 3060 Laptop GPU".into(),
+            vbios: "94.06.19.00.4b".into(),
+        };
+
+        let parsed = super::parse_nvidia_information("0000:01:00.0".into(), information);
+        assert_eq!(parsed, Some(expected));
+
+        let missing = super::parse_nvidia_information("0000:01:00.0".into(), "Video BIOS: ??\n");
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn is_card() {
+        assert!(super::is_card("card0"));
+        assert!(super::is_card("card12"));
+        assert!(!super::is_card("card0-DP-1"));
+        assert!(!super::is_card("renderD128"));
+        assert!(!super::is_card("card"));
+    }
+}
@@ -0,0 +1,204 @@
+//! A persistent record of every firmware update that was attempted.
+
+use crate::{timestamp, FirmwareSignal};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// An error that may occur when reading or writing the update history.
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    /// The location of the history file could not be determined.
+    #[error("failed to get XDG base directory")]
+    BaseDirectory(#[from] xdg::BaseDirectoriesError),
+    /// The directory of the history file could not be created.
+    #[error("failed to create the update history directory")]
+    Place(#[source] io::Error),
+    /// The history file could not be read.
+    #[error("failed to read the update history")]
+    Read(#[source] io::Error),
+    /// An entry could not be serialized.
+    #[error("failed to serialize update history entry")]
+    Serialize(#[source] serde_json::Error),
+    /// The history file could not be written to.
+    #[error("failed to write to the update history")]
+    Write(#[source] io::Error),
+}
+
+/// The outcome of an attempted update.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateResult {
+    /// The device was updated.
+    Success,
+    /// The firmware was scheduled to be installed on the next reboot, or when the device is next
+    /// reconnected.
+    Scheduled,
+    /// The update was cancelled before the device began flashing.
+    Cancelled,
+    /// The update failed with the given error.
+    Failed(Box<str>),
+    /// The firmware manager stopped unexpectedly while the device was flashing, so the outcome of
+    /// the update is not known.
+    Interrupted,
+}
+
+impl UpdateResult {
+    /// The outcome of an update, given the signal that the backend returned for it.
+    pub(crate) fn from_signal(signal: &FirmwareSignal) -> Option<Self> {
+        let result = match signal {
+            FirmwareSignal::DeviceUpdated(_) => UpdateResult::Success,
+            FirmwareSignal::SystemScheduled
+            | FirmwareSignal::Staged(_)
+            | FirmwareSignal::ReplugRequired(_) => UpdateResult::Scheduled,
+            FirmwareSignal::Cancelled(_) => UpdateResult::Cancelled,
+            FirmwareSignal::Error(_, why) => UpdateResult::Failed(crate::format_error(why).into()),
+            FirmwareSignal::VerificationFailed(_, why) => {
+                UpdateResult::Failed(crate::format_error(why).into())
+            }
+            _ => return None,
+        };
+
+        Some(result)
+    }
+}
+
+/// A record of an attempted update.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// The name of the device.
+    pub device: Box<str>,
+    /// The version of the firmware before the update.
+    pub from: Box<str>,
+    /// The version of the firmware that was installed.
+    pub to: Box<str>,
+    /// When the update was attempted, in seconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// The outcome of the update.
+    pub result: UpdateResult,
+}
+
+/// Fetches every recorded update, from oldest to newest.
+pub fn update_history() -> Result<Vec<HistoryEntry>, HistoryError> {
+    match fs::read_to_string(history_path()?) {
+        Ok(history) => Ok(parse(&history)),
+        Err(why) if why.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(why) => Err(HistoryError::Read(why)),
+    }
+}
+
+/// Fetches the most recent successful update of the named device.
+pub fn last_update(device: &str) -> Result<Option<HistoryEntry>, HistoryError> {
+    update_history().map(|history| last_success(history, device))
+}
+
+/// Records the outcome of an update, if the signal describes one.
+///
+/// A failure to record the update is logged, as it should not affect the update itself.
+pub(crate) fn record(device: &str, from: &str, to: &str, signal: &FirmwareSignal) {
+    if let Some(result) = UpdateResult::from_signal(signal) {
+        record_result(device, from, to, result);
+    }
+}
+
+/// Records the outcome of an update.
+pub(crate) fn record_result(device: &str, from: &str, to: &str, result: UpdateResult) {
+    let entry = HistoryEntry {
+        device: device.into(),
+        from: from.into(),
+        to: to.into(),
+        timestamp: timestamp::current(),
+        result,
+    };
+
+    if let Err(why) = append(&entry) {
+        warn!("{}", crate::format_error(why));
+    }
+}
+
+/// Appends an entry to the history file, as a line of JSON.
+fn append(entry: &HistoryEntry) -> Result<(), HistoryError> {
+    let mut line = serde_json::to_string(entry).map_err(HistoryError::Serialize)?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path()?)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(HistoryError::Write)
+}
+
+/// Parses each line of the history file, skipping those which are malformed.
+fn parse(history: &str) -> Vec<HistoryEntry> {
+    history
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(why) => {
+                warn!("skipping malformed update history entry: {}", why);
+                None
+            }
+        })
+        .collect()
+}
+
+/// The last entry in the history which successfully updated the `device`.
+fn last_success(history: Vec<HistoryEntry>, device: &str) -> Option<HistoryEntry> {
+    history.into_iter().rev().find(|entry| {
+        &*entry.device == device
+            && matches!(entry.result, UpdateResult::Success | UpdateResult::Scheduled)
+    })
+}
+
+/// The location of the history file, in the XDG data directory.
+fn history_path() -> Result<PathBuf, HistoryError> {
+    xdg::BaseDirectories::with_prefix("com.system76.FirmwareManager")?
+        .place_data_file("history.jsonl")
+        .map_err(HistoryError::Place)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HistoryEntry, UpdateResult};
+
+    fn entry(device: &str, to: &str, timestamp: u64, result: UpdateResult) -> HistoryEntry {
+        HistoryEntry { device: device.into(), from: "1.0".into(), to: to.into(), timestamp, result }
+    }
+
+    #[test]
+    fn parse() {
+        let history = [
+            entry("Dock", "1.1", 100, UpdateResult::Success),
+            entry("Dock", "1.2", 200, UpdateResult::Failed("device busy".into())),
+        ];
+
+        let mut file = String::new();
+        for entry in &history {
+            file.push_str(&serde_json::to_string(entry).unwrap());
+            file.push('\n');
+        }
+
+        file.push_str("{ not json\n");
+
+        assert_eq!(super::parse(&file), history);
+    }
+
+    #[test]
+    fn last_success() {
+        let history = vec![
+            entry("Dock", "1.1", 100, UpdateResult::Success),
+            entry("Mouse", "2.0", 150, UpdateResult::Success),
+            entry("Dock", "1.2", 200, UpdateResult::Failed("device busy".into())),
+            entry("Dock", "1.2", 300, UpdateResult::Cancelled),
+        ];
+
+        let last = super::last_success(history.clone(), "Dock");
+        assert_eq!(last.map(|entry| entry.timestamp), Some(100));
+        assert_eq!(super::last_success(history, "Keyboard"), None);
+    }
+}
@@ -0,0 +1,156 @@
+//! The Host Security ID attributes which fwupd reports for the system.
+//!
+//! fwupd checks which protections the firmware of the system enables, such as Intel BootGuard or
+//! the IOMMU, and assigns each check a level of the Host Security ID specification. The HSI level
+//! of the system is the highest level whose checks, and those of every level below it, pass.
+
+use crate::fwupd::FWUPD_DEST;
+use dbus::{
+    arg::{prop_cast, PropMap},
+    blocking::Connection,
+};
+use std::time::Duration;
+
+/// The attribute is met by the system.
+const FLAG_SUCCESS: u64 = 1 << 0;
+
+/// The attribute was replaced by another attribute, and is not counted.
+const FLAG_OBSOLETED: u64 = 1 << 1;
+
+/// A security attribute of the system, as checked by fwupd.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecurityAttribute {
+    /// Identifies the attribute, such as `org.fwupd.hsi.Iommu`.
+    pub id: Box<str>,
+    /// The name of the attribute, as shown to the user.
+    pub name: Box<str>,
+    /// The HSI level that the attribute counts towards, or zero for runtime attributes which
+    /// only lower the level by a suffix.
+    pub level: u32,
+    /// The outcome of the check, such as `enabled` or `not-locked`, if fwupd reported one.
+    pub result: Option<Box<str>>,
+    /// Whether the system meets the attribute.
+    pub success: bool,
+}
+
+/// The HSI level of the system, which is the highest level whose attributes, and those of every
+/// level below it, are met.
+pub fn hsi_level(attributes: &[SecurityAttribute]) -> u32 {
+    let highest = attributes.iter().map(|attribute| attribute.level).max().unwrap_or(0);
+
+    (1..=highest)
+        .take_while(|&level| {
+            let mut attributes = attributes.iter().filter(|attribute| attribute.level == level);
+            attributes.all(|attribute| attribute.success)
+        })
+        .last()
+        .unwrap_or(0)
+}
+
+/// Fetches the security attributes of the system from fwupd.
+pub(crate) fn security_attributes() -> Result<Vec<SecurityAttribute>, dbus::Error> {
+    let connection = Connection::new_system()?;
+    let proxy = connection.with_proxy(FWUPD_DEST, "/", Duration::from_secs(30));
+    let (attributes,): (Vec<PropMap>,) =
+        proxy.method_call(FWUPD_DEST, "GetHostSecurityAttrs", ())?;
+
+    Ok(attributes.iter().filter_map(attribute).collect())
+}
+
+/// Parses an attribute, skipping those which have been obsoleted.
+fn attribute(properties: &PropMap) -> Option<SecurityAttribute> {
+    let flags = prop_cast::<u64>(properties, "Flags").copied().unwrap_or(0);
+    if flags & FLAG_OBSOLETED != 0 {
+        return None;
+    }
+
+    let id = prop_cast::<String>(properties, "AppstreamId")?;
+
+    // Newer versions of fwupd give attributes a title, in addition to their name.
+    let name = prop_cast::<String>(properties, "Title")
+        .or_else(|| prop_cast::<String>(properties, "Name"))
+        .unwrap_or(id);
+
+    Some(SecurityAttribute {
+        id: id.as_str().into(),
+        name: name.as_str().into(),
+        level: prop_cast::<u32>(properties, "HsiLevel").copied().unwrap_or(0),
+        result: prop_cast::<u32>(properties, "HsiResult").and_then(|&result| result_name(result)),
+        success: flags & FLAG_SUCCESS != 0,
+    })
+}
+
+/// The name of a result of fwupd's `FwupdSecurityAttrResult`.
+fn result_name(result: u32) -> Option<Box<str>> {
+    let name = match result {
+        1 => "enabled",
+        2 => "not-enabled",
+        3 => "valid",
+        4 => "not-valid",
+        5 => "locked",
+        6 => "not-locked",
+        7 => "encrypted",
+        8 => "not-encrypted",
+        9 => "tainted",
+        10 => "not-tainted",
+        11 => "found",
+        12 => "not-found",
+        13 => "supported",
+        14 => "not-supported",
+        _ => return None,
+    };
+
+    Some(name.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecurityAttribute;
+    use dbus::arg::{PropMap, RefArg, Variant};
+
+    fn attribute(level: u32, success: bool) -> SecurityAttribute {
+        SecurityAttribute {
+            id: "org.fwupd.hsi.Test".into(),
+            name: "Test".into(),
+            level,
+            result: None,
+            success,
+        }
+    }
+
+    #[test]
+    fn hsi_level() {
+        assert_eq!(super::hsi_level(&[]), 0);
+        assert_eq!(super::hsi_level(&[attribute(1, true), attribute(2, true)]), 2);
+        assert_eq!(super::hsi_level(&[attribute(1, true), attribute(2, false)]), 1);
+        assert_eq!(super::hsi_level(&[attribute(1, false), attribute(2, true)]), 0);
+        assert_eq!(super::hsi_level(&[attribute(0, false), attribute(1, true)]), 1);
+    }
+
+    #[test]
+    fn parse_attribute() {
+        let mut properties = PropMap::new();
+        let mut insert = |key: &str, value: Box<dyn RefArg>| {
+            properties.insert(key.into(), Variant(value));
+        };
+
+        insert("AppstreamId", Box::new(String::from("org.fwupd.hsi.Iommu")));
+        insert("Name", Box::new(String::from("IOMMU")));
+        insert("HsiLevel", Box::new(2u32));
+        insert("HsiResult", Box::new(1u32));
+        insert("Flags", Box::new(1u64));
+
+        let expected = SecurityAttribute {
+            id: "org.fwupd.hsi.Iommu".into(),
+            name: "IOMMU".into(),
+            level: 2,
+            result: Some("enabled".into()),
+            success: true,
+        };
+
+        assert_eq!(super::attribute(&properties), Some(expected));
+
+        properties.insert("Flags".into(), Variant(Box::new(2u64)));
+        assert_eq!(super::attribute(&properties), None);
+    }
+}
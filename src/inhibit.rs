@@ -0,0 +1,42 @@
+//! Prevents the system from sleeping or shutting down while a device is flashing.
+//!
+//! An interrupted flash may leave a device unusable, so a lock is taken from systemd-logind
+//! which blocks the system from being suspended or powered off, such as when the lid of a laptop
+//! is closed. The lock is held until the file descriptor that logind returns is closed.
+
+use dbus::{arg::OwnedFd, blocking::Connection};
+use std::time::Duration;
+
+const LOGIND_DEST: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_MANAGER: &str = "org.freedesktop.login1.Manager";
+
+/// Blocks sleep and shutdown until it is dropped.
+pub(crate) struct Inhibitor {
+    _fd: OwnedFd,
+}
+
+/// Takes an inhibitor lock from logind while the named device is flashed.
+///
+/// A failure to take the lock is logged, as it should not prevent the update.
+pub(crate) fn flashing(device: &str) -> Option<Inhibitor> {
+    let why = ["Updating the firmware of ", device].concat();
+
+    match inhibit("sleep:shutdown", &why) {
+        Ok(fd) => Some(Inhibitor { _fd: fd }),
+        Err(why) => {
+            warn!("failed to inhibit sleep and shutdown while flashing {}: {}", device, why);
+            None
+        }
+    }
+}
+
+/// Requests a blocking inhibitor lock of the given kinds.
+fn inhibit(what: &str, why: &str) -> Result<OwnedFd, dbus::Error> {
+    let connection = Connection::new_system()?;
+    let proxy = connection.with_proxy(LOGIND_DEST, LOGIND_PATH, Duration::from_secs(5));
+    let (fd,): (OwnedFd,) =
+        proxy.method_call(LOGIND_MANAGER, "Inhibit", (what, "Firmware Manager", why, "block"))?;
+
+    Ok(fd)
+}
@@ -0,0 +1,274 @@
+//! A journal of the devices which are being flashed.
+//!
+//! An entry is written before a device begins flashing, and removed once the flash has finished,
+//! whether it succeeded or not. Any entry which remains when the firmware manager is next launched
+//! belongs to an update that was interrupted by a crash or power loss, and so the version reported
+//! for that device may not be what is actually installed. Such entries are kept until a frontend
+//! has told the user of them, or until the device is flashed again.
+//!
+//! The journal is shared by every process of the firmware manager, so each entry records the
+//! process which is flashing the device, and the boot that it was running in. Entries whose
+//! process is still running belong to a flash which is in progress, and are not interrupted.
+
+use crate::{history, timestamp, UpdateResult};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{Mutex, PoisonError},
+    thread,
+};
+
+/// Serializes modifications of the journal, as devices may be flashed concurrently.
+static JOURNAL: Lazy<Mutex<()>> = Lazy::new(Mutex::default);
+
+/// An error that may occur when reading or writing the flash journal.
+#[derive(Debug, Error)]
+pub enum JournalError {
+    /// The location of the journal could not be determined.
+    #[error("failed to get XDG base directory")]
+    BaseDirectory(#[from] xdg::BaseDirectoriesError),
+    /// The directory of the journal could not be created.
+    #[error("failed to create the flash journal directory")]
+    Place(#[source] io::Error),
+    /// The journal could not be read.
+    #[error("failed to read the flash journal")]
+    Read(#[source] io::Error),
+    /// The journal could not be serialized.
+    #[error("failed to serialize the flash journal")]
+    Serialize(#[source] serde_json::Error),
+    /// The journal could not be written.
+    #[error("failed to write the flash journal")]
+    Write(#[source] io::Error),
+}
+
+/// An update which was in progress when the firmware manager last stopped unexpectedly.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterruptedUpdate {
+    /// The name of the device.
+    pub device: Box<str>,
+    /// The version of the firmware before the update.
+    pub from: Box<str>,
+    /// The version of the firmware that was being installed.
+    pub to: Box<str>,
+    /// When the device began flashing, in seconds since the UNIX epoch.
+    pub timestamp: u64,
+}
+
+/// An update in the journal, with the process which is flashing the device.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    #[serde(flatten)]
+    update: InterruptedUpdate,
+    /// The ID of the process which is flashing the device, which is 0 if it is not known.
+    #[serde(default)]
+    pid: u32,
+    /// The boot that the process was running in, as process IDs are reused after a reboot.
+    #[serde(default)]
+    boot_id: Box<str>,
+}
+
+impl Entry {
+    /// Whether the process which is flashing the device is still running, in which case the
+    /// flash is in progress, rather than interrupted.
+    fn is_in_progress(&self, boot_id: &str) -> bool {
+        self.pid != 0
+            && &*self.boot_id == boot_id
+            && Path::new("/proc").join(self.pid.to_string()).exists()
+    }
+}
+
+/// Marks a device as being flashed until it is dropped.
+///
+/// The entry is kept if the thread panics while flashing, as the flash may not have finished.
+pub(crate) struct Flashing {
+    device: Box<str>,
+}
+
+impl Drop for Flashing {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            return;
+        }
+
+        let pid = process::id();
+        let result = modify(|entries| {
+            entries.retain(|entry| entry.pid != pid || entry.update.device != self.device)
+        });
+        if let Err(why) = result {
+            warn!("{}", crate::format_error(why));
+        }
+    }
+}
+
+/// Journals that the device is about to be flashed from one version to another.
+///
+/// A failure to write the journal is logged, as it should not prevent the update.
+pub(crate) fn begin(device: &str, from: &str, to: &str) -> Flashing {
+    let entry = Entry {
+        update: InterruptedUpdate {
+            device: device.into(),
+            from: from.into(),
+            to: to.into(),
+            timestamp: timestamp::current(),
+        },
+        pid: process::id(),
+        boot_id: boot_id().into(),
+    };
+
+    let result = modify(|entries| {
+        entries.retain(|entry| &*entry.update.device != device);
+        entries.push(entry);
+    });
+
+    if let Err(why) = result {
+        warn!("{}", crate::format_error(why));
+    }
+
+    Flashing { device: device.into() }
+}
+
+/// The updates which were interrupted since the journal was last cleared. Updates which another
+/// process is still flashing are in progress, rather than interrupted.
+pub(crate) fn interrupted() -> Vec<InterruptedUpdate> {
+    let boot_id = boot_id();
+    let mut interrupted = Vec::new();
+
+    let result = modify(|entries| {
+        interrupted = entries
+            .iter()
+            .filter(|entry| !entry.is_in_progress(&boot_id))
+            .map(|entry| entry.update.clone())
+            .collect();
+    });
+
+    if let Err(why) = result {
+        warn!("{}", crate::format_error(why));
+    }
+
+    interrupted
+}
+
+/// Clears the interrupted updates from the journal once the user has been told of them,
+/// recording each of them in the update history. Updates which are in progress are kept.
+pub(crate) fn clear() {
+    let boot_id = boot_id();
+    let mut interrupted = Vec::new();
+
+    let result = modify(|entries| {
+        let (in_progress, cleared) =
+            std::mem::take(entries).into_iter().partition(|entry| entry.is_in_progress(&boot_id));
+        *entries = in_progress;
+        interrupted = cleared;
+    });
+
+    if let Err(why) = result {
+        warn!("{}", crate::format_error(why));
+        return;
+    }
+
+    for Entry { update, .. } in interrupted {
+        history::record_result(&update.device, &update.from, &update.to, UpdateResult::Interrupted);
+    }
+}
+
+/// Applies a change to the entries of the journal, and then writes it.
+///
+/// The journal is replaced by renaming a synced file over it, so that it is never left partially
+/// written by a power loss.
+fn modify<F: FnOnce(&mut Vec<Entry>)>(func: F) -> Result<(), JournalError> {
+    let _lock = JOURNAL.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let path = journal_path()?;
+    let mut entries = match fs::read_to_string(&path) {
+        Ok(journal) => parse(&journal),
+        Err(why) if why.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(why) => return Err(JournalError::Read(why)),
+    };
+
+    let previous = entries.clone();
+    func(&mut entries);
+
+    if entries == previous {
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        return fs::remove_file(&path).map_err(JournalError::Write);
+    }
+
+    let journal = serde_json::to_vec(&entries).map_err(JournalError::Serialize)?;
+    let temporary = path.with_extension("json.tmp");
+
+    File::create(&temporary)
+        .and_then(|mut file| {
+            file.write_all(&journal)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temporary, &path))
+        .map_err(JournalError::Write)
+}
+
+/// Parses the journal, which is discarded if it is malformed.
+fn parse(journal: &str) -> Vec<Entry> {
+    serde_json::from_str(journal).unwrap_or_else(|why| {
+        warn!("discarding malformed flash journal: {}", why);
+        Vec::new()
+    })
+}
+
+/// Identifies the current boot, which is empty if it is not known.
+fn boot_id() -> String {
+    fs::read_to_string("/proc/sys/kernel/random/boot_id")
+        .map(|id| id.trim().to_owned())
+        .unwrap_or_default()
+}
+
+/// The location of the journal, in the XDG data directory.
+fn journal_path() -> Result<PathBuf, JournalError> {
+    xdg::BaseDirectories::with_prefix("com.system76.FirmwareManager")?
+        .place_data_file("flashing.json")
+        .map_err(JournalError::Place)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, InterruptedUpdate};
+
+    fn entry(pid: u32, boot_id: &str) -> Entry {
+        Entry {
+            update: InterruptedUpdate {
+                device: "Thelio I/O".into(),
+                from: "1.0".into(),
+                to: "1.1".into(),
+                timestamp: 100,
+            },
+            pid,
+            boot_id: boot_id.into(),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let entries = vec![entry(1, "boot")];
+
+        let journal = serde_json::to_string(&entries).unwrap();
+        assert_eq!(super::parse(&journal), entries);
+        assert_eq!(super::parse(&journal[..journal.len() / 2]), Vec::new());
+
+        // Journals which were written before the owning process was recorded are interrupted.
+        let journal = r#"[{"device":"Thelio I/O","from":"1.0","to":"1.1","timestamp":100}]"#;
+        assert_eq!(super::parse(journal), [entry(0, "")]);
+    }
+
+    #[test]
+    fn in_progress() {
+        let pid = std::process::id();
+        assert!(entry(pid, "boot").is_in_progress("boot"));
+        assert!(!entry(pid, "boot").is_in_progress("another boot"));
+        assert!(!entry(0, "boot").is_in_progress("boot"));
+    }
+}
@@ -0,0 +1,224 @@
+//! A backend which detects System76 Launch keyboards.
+//!
+//! The firmware of Launch keyboards is published to the LVFS, and so keyboards which fwupd
+//! supports are listed and updated by the fwupd backend, with the same download and flashing
+//! progress as any other fwupd device. This backend lists the keyboards which fwupd does not
+//! report, such as when its plugin for them is not installed, with the latest firmware that the
+//! LVFS publishes for them, so that the user is told of updates which fwupd would install.
+
+use crate::{
+    lvfs, DeviceId, DeviceSignal, FirmwareBackend, FirmwareEvent, FirmwareInfo, FirmwareSignal,
+    FwupdDevice,
+};
+use std::{fs, path::Path, sync::mpsc::Sender};
+
+/// The name of the Launch backend, which identifies its devices.
+const BACKEND: &str = "launch";
+
+/// The USB vendor ID of System76.
+const SYSTEM76_VID: &str = "3384";
+
+/// The vendor ID of System76 USB devices, as fwupd reports it.
+const FWUPD_VENDOR_ID: &str = "USB:0x3384";
+
+/// Where the USB devices of the system are described.
+const USB_DEVICES: &str = "/sys/bus/usb/devices";
+
+/// The models of Launch keyboards, by USB product ID.
+const MODELS: &[(&str, &str)] = &[
+    ("0001", "System76 Launch"),
+    ("0005", "System76 Launch Lite"),
+    ("0006", "System76 Launch 2"),
+    ("0007", "System76 Launch Heavy"),
+];
+
+/// A Launch keyboard which is connected to the system.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Keyboard {
+    /// The name of the keyboard's model.
+    model: &'static str,
+    /// The USB product ID of the keyboard, such as `0001`.
+    product: Box<str>,
+    /// The serial number of the keyboard, if it reports one.
+    serial: Option<Box<str>>,
+    /// The USB device release number of the keyboard, which is its firmware version.
+    version: Box<str>,
+    /// The name of the keyboard in sysfs, such as `3-2`.
+    port: Box<str>,
+}
+
+/// A firmware backend for System76 Launch keyboards.
+#[derive(Default)]
+pub struct LaunchBackend;
+
+impl FirmwareBackend for LaunchBackend {
+    fn name(&self) -> &str { BACKEND }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        let keyboards = keyboards(Path::new(USB_DEVICES));
+        if keyboards.is_empty() {
+            return;
+        }
+
+        let fwupd = crate::fwupd::reported_devices(|device| &*device.vendor_id == FWUPD_VENDOR_ID);
+
+        let keyboards = keyboards
+            .into_iter()
+            .filter(|keyboard| {
+                let reported = reported_by_fwupd(keyboard, fwupd.as_deref());
+                if reported {
+                    info!("{} is managed by fwupd", keyboard.model);
+                }
+
+                !reported
+            })
+            .collect::<Vec<_>>();
+
+        if keyboards.is_empty() {
+            return;
+        }
+
+        let components = lvfs::components().unwrap_or_else(|why| {
+            warn!("{}", crate::format_error(why));
+            Vec::new()
+        });
+
+        for keyboard in keyboards {
+            let guid = keyboard.guid();
+            let latest = components
+                .iter()
+                .find(|component| component.provides(&guid))
+                .and_then(|component| component.update_from(&keyboard.version));
+
+            let _res = sender.send(FirmwareSignal::Device(DeviceSignal {
+                device: DeviceId { backend: BACKEND.into(), id: keyboard.port },
+                info: FirmwareInfo {
+                    name: keyboard.model.into(),
+                    current: keyboard.version,
+                    latest,
+                    install_duration: 0,
+                    policy: None,
+                },
+                read_only: true,
+            }));
+        }
+    }
+
+    /// Launch keyboards are only updated through fwupd.
+    fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+        None
+    }
+}
+
+impl Keyboard {
+    /// The GUID which fwupd identifies the model of the keyboard by, and which its firmware is
+    /// published for.
+    fn guid(&self) -> String {
+        lvfs::instance_guid(&["USB\\VID_", SYSTEM76_VID, "&PID_", &self.product].concat())
+    }
+}
+
+/// The Launch keyboards which are described in the USB devices directory.
+fn keyboards(devices: &Path) -> Vec<Keyboard> {
+    let entries = match fs::read_dir(devices) {
+        Ok(entries) => entries,
+        Err(why) => {
+            warn!("failed to read {}: {}", devices.display(), why);
+            return Vec::new();
+        }
+    };
+
+    entries.filter_map(Result::ok).filter_map(|entry| keyboard(&entry.path())).collect()
+}
+
+/// Reads the description of a USB device, if it is a Launch keyboard.
+fn keyboard(device: &Path) -> Option<Keyboard> {
+    let attribute = |name: &str| -> Option<String> {
+        let mut value = fs::read_to_string(device.join(name)).ok()?;
+        value.truncate(value.trim_end().len());
+        Some(value)
+    };
+
+    if attribute("idVendor")? != SYSTEM76_VID {
+        return None;
+    }
+
+    let product = attribute("idProduct")?;
+    let model = MODELS.iter().find(|(id, _)| *id == product).map(|(_, model)| *model)?;
+
+    Some(Keyboard {
+        model,
+        product: product.to_uppercase().into(),
+        serial: attribute("serial").map(Box::from),
+        version: release_version(&attribute("bcdDevice")?).into(),
+        port: device.file_name()?.to_string_lossy().into(),
+    })
+}
+
+/// Formats a binary-coded USB device release number, such as `0102`, as a version, such as `1.2`.
+fn release_version(bcd: &str) -> String {
+    let (major, minor) = bcd.split_at(bcd.len().saturating_sub(2));
+    let digits = |digits: &str| digits.parse::<u8>().unwrap_or_default();
+    format!("{}.{}", digits(major), digits(minor))
+}
+
+/// Checks if fwupd reports the keyboard, in which case the fwupd backend lists it.
+///
+/// Keyboards are matched by the GUID of their model, and then by their serial numbers. If either
+/// does not report a serial number, any device of the same model is assumed to be the keyboard.
+fn reported_by_fwupd(keyboard: &Keyboard, fwupd: Option<&[FwupdDevice]>) -> bool {
+    let devices = match fwupd {
+        Some(devices) => devices,
+        None => return false,
+    };
+
+    let guid = keyboard.guid();
+    devices
+        .iter()
+        .filter(|device| device.guid.iter().any(|id| id.eq_ignore_ascii_case(&guid)))
+        .any(|device| match (&device.serial, &keyboard.serial) {
+            (Some(serial), Some(keyboard)) => serial == keyboard,
+            _ => true,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Keyboard;
+    use crate::FwupdDevice;
+
+    #[test]
+    fn release_version() {
+        assert_eq!(super::release_version("0102"), "1.2");
+        assert_eq!(super::release_version("1210"), "12.10");
+        assert_eq!(super::release_version("7"), "0.7");
+    }
+
+    #[test]
+    fn reported_by_fwupd() {
+        let keyboard = Keyboard {
+            model: "System76 Launch",
+            product: "0001".into(),
+            serial: Some("a1b2".into()),
+            version: "1.0".into(),
+            port: "3-2".into(),
+        };
+
+        let device = |guid: &str, serial: Option<&str>| FwupdDevice {
+            guid: vec![guid.into()].into(),
+            serial: serial.map(Box::from),
+            ..FwupdDevice::default()
+        };
+
+        let launch = keyboard.guid();
+        let launch_2 = Keyboard { product: "0006".into(), ..keyboard.clone() }.guid();
+        let reported = |devices: &[FwupdDevice]| super::reported_by_fwupd(&keyboard, Some(devices));
+
+        assert!(!super::reported_by_fwupd(&keyboard, None));
+        assert!(!reported(&[]));
+        assert!(!reported(&[device(&launch, Some("c3d4"))]));
+        assert!(reported(&[device(&launch, Some("a1b2"))]));
+        assert!(reported(&[device(&launch, None)]));
+        assert!(!reported(&[device(&launch_2, None)]));
+    }
+}
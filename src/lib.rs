@@ -4,19 +4,68 @@
 //!
 //! The firmware manager core manages all firmware tasks in an event loop, and provides a handful of
 //! useful capabilities that are useful to frontends of the firmware manager.
+//!
+//! Frontends may either run the [`event_loop`] in a thread of their own, communicating with it
+//! through channels, or spawn it with [`FirmwareManager::spawn`] and consume its signals as a
+//! [`SignalStream`].
 
 #[macro_use]
 extern crate thiserror;
 #[macro_use]
-extern crate log;
-#[macro_use]
 extern crate shrinkwraprs;
+#[macro_use]
+extern crate tracing;
 
+#[macro_use]
+mod localize;
+
+mod attestation;
+mod backend;
+mod bundle;
 mod cache;
+mod cancel;
+mod capsule;
+mod checksum;
+mod config;
+mod detach;
+mod download;
+mod flashrom;
+mod gpu;
+mod history;
+mod hsi;
+mod inhibit;
+mod journal;
+mod launch;
+mod lvfs;
+mod mei;
+mod microcode;
+mod mirror;
+#[cfg(feature = "mock")]
+mod mock;
+mod network;
+#[cfg(feature = "nvme")]
+mod nvme;
+mod open_firmware;
+mod plugin;
+mod policy;
+mod power;
+mod queue;
+mod recovery;
+mod release_notes;
+mod report;
+mod retry;
+mod schedule;
+mod secure_boot;
+mod signature;
+mod smbios;
+mod space;
+mod stream;
 mod timestamp;
+mod topology;
 mod udev;
 mod users;
 mod version_sorting;
+mod wireless;
 
 mod fwupd;
 mod system76;
@@ -32,35 +81,306 @@ pub use system76_firmware_daemon::{
     SystemInfo as S76SystemInfo, ThelioIoInfo,
 };
 
-use self::version_sorting::sort_versions;
-pub use self::{fwupd::*, system76::*, udev::usb_hotplug_event_loop};
+use self::{cancel::InProgress, queue::UpdateQueue, version_sorting::sort_versions};
+pub use self::{
+    attestation::Attestation,
+    backend::{Backends, DeviceCategory, DeviceId, DeviceSignal, FirmwareBackend, ScanResult},
+    bundle::{BundleError, BUNDLE_KEYS, BUNDLE_SIGNING_KEY},
+    cancel::CancellationToken,
+    capsule::{CapsuleBackend, CapsuleError, CAPSULES_DIR},
+    config::{
+        config, reload_config, set_device_ignored, set_device_sort, set_release_channel,
+        snooze_update, BatteryPolicy, Config, ConfigError, DeviceSort, RebootPolicy,
+        ReleaseChannel, Snooze, SortKey,
+    },
+    download::{
+        download_config, prune_firmware_cache, set_download_config, DownloadConfig, DownloadError,
+        DownloadProgress, DEFAULT_FIRMWARE_CACHE_LIMIT,
+    },
+    flashrom::{FlashromBackend, FlashromError},
+    fwupd::*,
+    gpu::GpuBackend,
+    history::{last_update, update_history, HistoryEntry, HistoryError, UpdateResult},
+    hsi::{hsi_level, SecurityAttribute},
+    journal::InterruptedUpdate,
+    launch::LaunchBackend,
+    localize::localizer,
+    lvfs::{lvfs_scan, LvfsError},
+    mei::MeiBackend,
+    microcode::MicrocodeBackend,
+    mirror::{default_mirrors, Mirror},
+    network::{network_state, Connectivity, DeferReason, MeteredPolicy, NetworkState},
+    open_firmware::{open_firmware_transition, TransitionBlocker, OPEN_FIRMWARE_BRANCH},
+    plugin::{
+        Plugin, PluginBackend, PluginDevice, PluginError, PluginFound, PLUGINS_DIR,
+        PLUGIN_ABI_VERSION,
+    },
+    policy::{check_policy, firmware_policy, Approval, FirmwarePolicy, PolicyError, PolicyReason},
+    power::PowerBackend,
+    queue::{UpdateBatch, UpdateRequest},
+    recovery::{usb_drives, RecoveryError, RecoveryProgress, UsbDrive},
+    release_notes::{nvd_url, parse_release_notes, NoteBlock, NoteSpan},
+    report::{remember_report_consent, report_consent, ReportError, UpdateReport},
+    retry::{retry_policy, set_retry_policy, RetryPolicy},
+    schedule::{
+        last_check, next_check, record_check, refresh_check_timer, register_check_timer,
+        set_check_interval, CheckTimer, ScheduleError, CHECK_TIMER,
+    },
+    secure_boot::{secure_boot, SecureBoot},
+    signature::{SignatureError, SIGNATURE_LOCATION, TRUSTED_KEYS},
+    smbios::{SmbiosBackend, SystemSummary},
+    space::{EspError, InsufficientSpace},
+    stream::{FirmwareManager, SignalStream},
+    system76::*,
+    topology::Topology,
+    udev::usb_hotplug_event_loop,
+    wireless::WirelessBackend,
+};
+#[cfg(feature = "mock")]
+pub use self::mock::{MockBackend, MockDevice, MockFailure};
+#[cfg(feature = "nvme")]
+pub use self::nvme::NvmeBackend;
 pub use slotmap::DefaultKey as Entity;
 use slotmap::{SlotMap, SparseSecondaryMap};
 use std::{
     io,
+    path::{Path, PathBuf},
     process::Command,
     sync::{mpsc::{Receiver, Sender}, Arc, atomic::{AtomicBool, Ordering}},
 };
 pub use system76_firmware_daemon::Client as System76Client;
 
 /// Errors that may occur in the firmware manager core.
+///
+/// Frontends may match on the variants of this error to describe a failure to the user in their
+/// own words, or to decide how to recover from it.
 #[derive(Debug, Error)]
-pub enum Error {
-    /// Errors specific to fwupd devices.
+pub enum FirmwareError {
+    /// A D-Bus request to the fwupd daemon failed.
     #[error("error in fwupd client")]
     Fwupd(#[from] fwupd_dbus::Error),
-    /// Errors specific to system76 devices.
+    /// An error which occurred in a backend other than fwupd or System76.
+    #[error("error in {} backend", _0)]
+    Backend(Box<str>, #[source] Box<dyn std::error::Error + Send + Sync>),
+    /// The fwupd daemon could not be started.
+    #[error("failed to start the fwupd daemon")]
+    FwupdStart(#[source] io::Error),
+    /// A D-Bus request to the system76-firmware daemon failed.
     #[error("error in system76-firmware client")]
     System76(#[from] System76Error),
+    /// Errors that occurred while downloading firmware.
+    #[error("failed to download firmware")]
+    Download(#[from] DownloadError),
+    /// The downloaded firmware does not match the checksum of its release.
+    #[error("firmware at {} failed checksum verification", _0.display())]
+    Verification(PathBuf),
+    /// The system76-firmware daemon could not schedule system firmware to be installed.
+    #[error("failed to schedule system firmware update")]
+    Schedule(#[source] System76Error),
+    /// The firmware daemon refused the request, as the user is not authorized to make it.
+    #[error("not authorized to update firmware")]
+    Permission(#[source] Box<FirmwareError>),
+    /// There is not enough disk space to download or stage the firmware.
+    #[error("insufficient disk space for firmware update")]
+    Space(#[from] InsufficientSpace),
+    /// The EFI System Partition can not be used to stage a capsule update.
+    #[error("failed to stage the update on the EFI System Partition")]
+    Esp(#[from] EspError),
+    /// The firmware file could not be opened for flashing.
+    #[error("failed to open firmware file for flashing")]
+    Open(#[source] io::Error),
+    /// A Thelio I/O board was flashed, but it does not report the new revision.
+    #[error("{} was not updated, and reports revision {}", _0, _1)]
+    ThelioIoBoard(Box<str>, Box<str>),
+    /// No release of the requested version was published for the device.
+    #[error("firmware version {} is not available for this device", _0)]
+    Release(Box<str>),
+    /// The firmware policy of the system does not approve the requested version.
+    #[error("firmware version {} may not be installed", _0)]
+    Policy(Box<str>, #[source] PolicyReason),
+    /// No release of the requested branch was published for the device.
+    #[error("no firmware is published on the {} branch for this device", _0)]
+    Branch(Box<str>),
+    /// A fwupd remote could not be modified.
+    #[error("failed to modify the {} fwupd remote", _0)]
+    Remote(Box<str>, #[source] dbus::Error),
+    /// The release channel which was chosen for a backend is not published by it.
+    #[error("the {} backend does not publish {} releases", _0, _1.as_str())]
+    Channel(Box<str>, ReleaseChannel),
+    /// No fwupd remote has the requested ID.
+    #[error("there is no fwupd remote named {}", _0)]
+    UnknownRemote(Box<str>),
+    /// The security attributes of the system could not be fetched from fwupd.
+    #[error("failed to get the host security attributes from fwupd")]
+    HostSecurity(#[source] dbus::Error),
+    /// An offline bundle could not be exported or imported, or one of its payloads could not be
+    /// installed.
+    #[error("failed to process the offline bundle")]
+    Bundle(#[from] BundleError),
+    /// A recovery drive could not be created.
+    #[error("failed to create a recovery drive")]
+    Recovery(#[from] RecoveryError),
+}
+
+impl FirmwareError {
+    /// Converts an error returned by a firmware daemon, detecting when the daemon refused the
+    /// request due to its authorization policy.
+    pub(crate) fn from_daemon<E: Into<Self>>(why: E) -> Self {
+        let why = why.into();
+        if is_permission_denied(&format_error(&why)) {
+            FirmwareError::Permission(Box::new(why))
+        } else {
+            why
+        }
+    }
+
+    /// Describes the error in the language of the user, followed by the errors which caused it.
+    ///
+    /// Only the errors of this crate are translated, as the errors of the firmware daemons and of
+    /// the operating system describe themselves in English.
+    pub fn localized(&self) -> String {
+        let mut message = self.localized_message();
+        let mut cause = std::error::Error::source(self);
+        while let Some(error) = cause {
+            message.push_str(": ");
+            match error.downcast_ref::<FirmwareError>() {
+                Some(error) => message.push_str(&error.localized_message()),
+                None => message.push_str(&error.to_string()),
+            }
+
+            cause = error.source();
+        }
+
+        message
+    }
+
+    /// The translated message of this error, without the errors which caused it.
+    fn localized_message(&self) -> String {
+        match self {
+            FirmwareError::Fwupd(_) => fl!("error-fwupd-client"),
+            FirmwareError::FwupdStart(_) => fl!("error-fwupd-start"),
+            FirmwareError::Backend(backend, _) => fl!("error-backend", backend = &**backend),
+            FirmwareError::System76(_) => fl!("error-system76-client"),
+            FirmwareError::Download(_) => fl!("error-download"),
+            FirmwareError::Verification(path) => {
+                fl!("error-verification", path = path.display().to_string())
+            }
+            FirmwareError::Schedule(_) => fl!("error-schedule"),
+            FirmwareError::Permission(_) => fl!("error-permission"),
+            FirmwareError::Space(_) => fl!("error-space"),
+            FirmwareError::Esp(_) => fl!("error-esp"),
+            FirmwareError::Open(_) => fl!("error-open"),
+            FirmwareError::ThelioIoBoard(board, revision) => {
+                fl!("error-thelio-io-board", board = &**board, revision = &**revision)
+            }
+            FirmwareError::Release(version) => fl!("error-release", version = &**version),
+            FirmwareError::Policy(version, _) => fl!("error-policy", version = &**version),
+            FirmwareError::Branch(branch) => fl!("error-branch", branch = &**branch),
+            FirmwareError::Remote(remote, _) => fl!("error-remote", remote = &**remote),
+            FirmwareError::Channel(backend, channel) => {
+                fl!("error-channel", backend = &**backend, channel = channel.as_str())
+            }
+            FirmwareError::UnknownRemote(remote) => {
+                fl!("error-unknown-remote", remote = &**remote)
+            }
+            FirmwareError::HostSecurity(_) => fl!("error-host-security"),
+            FirmwareError::Bundle(_) => fl!("error-bundle"),
+            FirmwareError::Recovery(_) => fl!("error-recovery"),
+        }
+    }
+}
+
+/// Checks if an error message contains a D-Bus or polkit authorization failure.
+fn is_permission_denied(message: &str) -> bool {
+    const DENIALS: &[&str] = &[
+        "org.freedesktop.DBus.Error.AccessDenied",
+        "org.freedesktop.fwupd.AuthFailed",
+        "org.freedesktop.fwupd.PermissionDenied",
+        "org.freedesktop.PolicyKit1.Error.NotAuthorized",
+    ];
+
+    DENIALS.iter().any(|denial| message.contains(denial))
 }
 
 /// A request for the background event loop to perform.
 #[derive(Debug)]
 pub enum FirmwareEvent {
+    /// Clear the updates of a [`FirmwareSignal::Interrupted`] once the user has been told of them,
+    /// recording them in the update history.
+    ClearInterrupted,
+
+    /// Upgrade the firmware of a device which was discovered by another backend.
+    Device(Entity, DeviceId, CancellationToken),
+
+    /// Download the payload of every pending fwupd update into the given directory, as an offline
+    /// bundle which may be carried to a system without internet access. The number of payloads
+    /// is sent with [`FirmwareSignal::BundleExported`].
+    ///
+    /// The manifest of the bundle is signed with the [`BUNDLE_SIGNING_KEY`], which must be
+    /// installed.
+    ExportBundle(PathBuf, CancellationToken),
+
+    /// Queue updates to be performed one at a time, in the given order.
+    ///
+    /// Each update waits for the previous update to finish, whether it succeeded or not. Their
+    /// positions in the queue are sent with [`FirmwareSignal::Queued`].
+    Enqueue(Vec<FirmwareEvent>),
+
+    /// Flash a previously-published release of a fwupd-compatible device, by its version.
+    ///
+    /// Frontends should present the [`downgrade_warnings`] of the device before sending this.
+    Downgrade(Entity, Arc<FwupdDevice>, Box<str>, CancellationToken),
+
+    /// Perform an update which was deferred with [`FirmwareSignal::UpdateDeferred`], downloading
+    /// its firmware even though the connection is metered.
+    ///
+    /// Unlike [`FirmwareEvent::MeteredPolicy`], this only applies to the download of this update.
+    DownloadAnyway(Box<FirmwareEvent>),
+
     /// Upgrade the firmware of a fwupd-compatible device.
-    Fwupd(Entity, Arc<FwupdDevice>, Arc<FwupdRelease>),
+    Fwupd(Entity, Arc<FwupdDevice>, Arc<FwupdRelease>, CancellationToken),
+
+    /// Fetch the Host Security ID attributes of the system from fwupd, which are sent with
+    /// [`FirmwareSignal::HostSecurity`].
+    HostSecurity,
+
+    /// List the fwupd remotes, which are sent with [`FirmwareSignal::FwupdRemotes`].
+    FwupdRemotes,
+
+    /// Enable or disable the fwupd remote with the given ID, fetching its metadata if it was
+    /// enabled. The remotes are listed afterwards.
+    FwupdRemoteEnabled(Box<str>, bool),
+
+    /// Fetch the latest metadata of the fwupd remote with the given ID. The remotes are listed
+    /// afterwards.
+    FwupdRemoteRefresh(Box<str>),
+
+    /// Install the payloads of the offline bundle in the given directory on the devices that they
+    /// update, once the signature of its manifest has been verified, with the backends which
+    /// update them. The devices which were updated are sent with
+    /// [`FirmwareSignal::BundleImported`].
+    ///
+    /// The manifest must be signed with one of the [`BUNDLE_KEYS`].
+    ImportBundle(PathBuf),
+
+    /// Sets how downloads are handled on metered connections.
+    MeteredPolicy(MeteredPolicy),
+
+    /// Download the recovery image of this model, and write it to the drive, erasing everything
+    /// on it. Progress is sent with [`FirmwareSignal::Recovery`].
+    ///
+    /// Frontends must have the user confirm that the drive may be erased before sending this.
+    RecoveryDrive(UsbDrive, CancellationToken),
+
+    /// The answer of the user to a [`FirmwareSignal::ReportAvailable`], which is remembered for
+    /// later reports. The report is uploaded if the user consented to it.
+    ReportConsent(Box<UpdateReport>, bool),
 
     /// Stop processing events.
+    ///
+    /// Queued updates are discarded, and downloads in progress are cancelled. The event loop
+    /// returns once every device which had begun flashing has finished, so frontends should wait
+    /// for it to return before they exit.
     Stop,
 
     /// Upgrade system firmware for System76 systems.
@@ -69,12 +389,79 @@ pub enum FirmwareEvent {
     /// Search for available firmware devices.
     Scan,
 
-    /// Upgrade the firmware of Thelio I/O boarods.
-    ThelioIo(Entity, System76Digest),
+    /// Start the fwupd daemon, when a [`ScanResult`] reports that it is unavailable, and register
+    /// its backend in place of the capsule backend. Firmware is scanned again once it has started.
+    StartFwupd,
+
+    /// Switch the firmware of a fwupd-compatible device to the latest release of another branch.
+    ///
+    /// Frontends must have the user confirm the [`branch_warnings`] of the device before sending
+    /// this.
+    SwitchBranch(Entity, Arc<FwupdDevice>, Box<str>, CancellationToken),
+
+    /// Upgrade the firmware of a Thelio I/O board, by the ID of the board.
+    ThelioIo(Entity, Box<str>, System76Digest, CancellationToken),
+
+    /// List the USB drives that a recovery drive may be created on, which are sent with
+    /// [`FirmwareSignal::UsbDrives`].
+    UsbDrives,
+
+    /// Queue the updates of a batch, as with [`FirmwareEvent::Enqueue`], in the order that the
+    /// batch decided.
+    UpdateAll(UpdateBatch),
+}
+
+impl FirmwareEvent {
+    /// The entity that this event is associated with, if any.
+    pub fn entity(&self) -> Option<Entity> {
+        match self {
+            FirmwareEvent::Device(entity, ..)
+            | FirmwareEvent::Downgrade(entity, ..)
+            | FirmwareEvent::Fwupd(entity, ..)
+            | FirmwareEvent::S76System(entity, ..)
+            | FirmwareEvent::SwitchBranch(entity, ..)
+            | FirmwareEvent::ThelioIo(entity, ..) => Some(*entity),
+            _ => None,
+        }
+    }
+
+    /// The token by which the update of this event may be cancelled, if it may be.
+    pub fn cancellable(&self) -> Option<&CancellationToken> {
+        match self {
+            FirmwareEvent::Device(.., cancellable)
+            | FirmwareEvent::Downgrade(.., cancellable)
+            | FirmwareEvent::Fwupd(.., cancellable)
+            | FirmwareEvent::SwitchBranch(.., cancellable)
+            | FirmwareEvent::ThelioIo(.., cancellable) => Some(cancellable),
+            _ => None,
+        }
+    }
+
+    /// Whether this event updates system firmware, which is installed on the next reboot.
+    pub fn is_system_firmware(&self) -> bool {
+        match self {
+            FirmwareEvent::S76System(..) => true,
+            FirmwareEvent::Device(_, device, _) => device.category() == DeviceCategory::System,
+            FirmwareEvent::Downgrade(_, device, ..)
+            | FirmwareEvent::Fwupd(_, device, ..)
+            | FirmwareEvent::SwitchBranch(_, device, ..) => device.needs_reboot(),
+            _ => false,
+        }
+    }
+
+    /// Whether the backend will need to download firmware to perform this event.
+    pub fn requires_download(&self) -> bool {
+        matches!(
+            self,
+            FirmwareEvent::Downgrade(..)
+                | FirmwareEvent::Fwupd(..)
+                | FirmwareEvent::SwitchBranch(..)
+        )
+    }
 }
 
 /// Information about a device and its current and latest firmware.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct FirmwareInfo {
     /// The name of this device.
     pub name: Box<str>,
@@ -87,6 +474,10 @@ pub struct FirmwareInfo {
 
     /// The time required for this firmware to be flashed, in seconds.
     pub install_duration: u32,
+
+    /// The reason that the latest version may not be installed, if the firmware policy of the
+    /// system does not approve it.
+    pub policy: Option<PolicyReason>,
 }
 
 /// A collection of all firmware device entities that a frontend is managing.
@@ -122,6 +513,12 @@ impl Entities {
         self.entities.insert(())
     }
 
+    /// Remove a device entity from the world
+    pub fn remove(&mut self, entity: Entity) {
+        self.entities.remove(entity);
+        self.system.remove(entity);
+    }
+
     /// Check if an entity is a system device
     pub fn is_system(&self, entity: Entity) -> bool {
         self.system.contains_key(entity)
@@ -136,6 +533,20 @@ impl Entities {
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum FirmwareSignal {
+    /// An offline bundle was exported to the directory, with the number of payloads in it.
+    BundleExported(PathBuf, usize),
+
+    /// An offline bundle was imported, with the names of the devices that it updated. Frontends
+    /// should scan again to show their new versions.
+    BundleImported(Vec<Box<str>>),
+
+    /// An update was cancelled before the device began flashing. Updates which were paused are
+    /// reported with [`FirmwareSignal::Paused`] instead.
+    Cancelled(Entity),
+
+    /// A device was discovered by a backend other than fwupd or System76.
+    Device(DeviceSignal),
+
     /// A device has initiated the flashing process.
     DeviceFlashing(Entity),
 
@@ -155,147 +566,469 @@ pub enum FirmwareSignal {
     DownloadUpdate(Entity, usize),
 
     /// An error occurred
-    Error(Option<Entity>, Error),
+    Error(Option<Entity>, FirmwareError),
+
+    /// The request of an update which failed, which follows its [`FirmwareSignal::Error`].
+    ///
+    /// The failed request is returned, so that the frontend may send it again if the user retries
+    /// it, after [`CancellationToken::resume`].
+    Failed(Entity, Box<FirmwareEvent>),
 
     /// Fwupd firmware was discovered.
     Fwupd(FwupdSignal),
 
+    /// The details of a fwupd device have changed.
+    FwupdChanged(FwupdSignal),
+
+    /// A fwupd device with the given device ID was removed.
+    ///
+    /// Fingerprint readers which reset while they are being updated are not reported as removed.
+    /// Their return is reported with [`FirmwareSignal::FwupdChanged`].
+    FwupdRemoved(Box<str>),
+
+    /// The remotes which fwupd may fetch releases from.
+    FwupdRemotes(Vec<FwupdRemote>),
+
+    /// The Host Security ID attributes of the system, which describe the protections that its
+    /// firmware enables. See [`hsi_level`].
+    HostSecurity(Vec<SecurityAttribute>),
+
+    /// Devices were flashing when the firmware manager last stopped unexpectedly, such as from a
+    /// crash or a loss of power. This is sent once, when the event loop starts.
+    ///
+    /// The versions reported by the next scan are those the devices report after the interrupted
+    /// update, so frontends should warn the user, and offer to verify the devices with a scan or
+    /// to retry their updates. The updates are reported again on the next launch unless the
+    /// frontend answers with a [`FirmwareEvent::ClearInterrupted`].
+    Interrupted(Vec<InterruptedUpdate>),
+
+    /// The Intel Management Engine was discovered.
+    ///
+    /// Its firmware is installed with the system firmware, so it is reported only so that users
+    /// may confirm which version is running. It can not be updated on its own.
+    ManagementEngine(FirmwareInfo),
+
+    /// The microcode of the processor was discovered.
+    ///
+    /// Microcode is loaded by the kernel when the system boots, from the packages of the
+    /// distribution, so it can not be updated by the firmware manager. The latest version is a
+    /// newer revision which the distribution ships, and which is loaded on the next boot.
+    Microcode(FirmwareInfo),
+
+    /// The outcome of a fwupd update may be reported to the LVFS, but the user has not been asked
+    /// whether reports should be uploaded.
+    ///
+    /// Frontends should ask the user, and answer with a [`FirmwareEvent::ReportConsent`].
+    ReportAvailable(Box<UpdateReport>),
+
+    /// The progress of a [`FirmwareEvent::RecoveryDrive`].
+    Recovery(RecoveryProgress),
+
+    /// The download of an update was paused with [`CancellationToken::pause`].
+    ///
+    /// The paused request is returned, so that the frontend may send it again once the user
+    /// resumes it, after [`CancellationToken::resume`]. The download resumes from what was kept.
+    Paused(Entity, Box<FirmwareEvent>),
+
+    /// An update is waiting in the queue, at the given position.
+    ///
+    /// The update at position zero is performed next, once the update in progress has finished.
+    Queued(Entity, usize),
+
+    /// New firmware was installed on a device, such as a Thunderbolt dock, which must be unplugged
+    /// and plugged in again for it to take effect. This is sent in place of
+    /// [`FirmwareSignal::DeviceUpdated`].
+    ///
+    /// Frontends should instruct the user to reconnect the device. The device is reported again
+    /// with its new version once it has been reconnected.
+    ReplugRequired(Entity),
+
     /// Devices are being scanned
     Scanning,
 
+    /// System firmware was staged, and will be installed the next time that the user reboots the
+    /// system. This is sent in place of [`FirmwareSignal::DeviceUpdated`] and
+    /// [`FirmwareSignal::SystemScheduled`] when the [`RebootPolicy`] is `NextBoot`.
+    Staged(Entity),
+
+    /// An update was deferred due to the state of the network.
+    ///
+    /// The deferred request is returned, so that the frontend may send it again later, or with
+    /// [`FirmwareEvent::DownloadAnyway`] if the user agrees to download on a metered connection.
+    UpdateDeferred(Entity, DeferReason, Box<FirmwareEvent>),
+
+    /// The USB drives that a recovery drive may be created on.
+    UsbDrives(Vec<UsbDrive>),
+
+    /// The downloaded firmware of an entity is being checked against its published checksum.
+    Verifying(Entity),
+
+    /// The firmware was not flashed, as it was not signed by a trusted key.
+    ///
+    /// This may indicate that the firmware was tampered with, so frontends should present it
+    /// more prominently than other errors.
+    VerificationFailed(Entity, SignatureError),
+
     /// Signals when scanning has completed.
     ScanningComplete,
 
+    /// The outcome of a scan, which is sent before [`FirmwareSignal::ScanningComplete`].
+    ScanResult(ScanResult),
+
     /// System firmware was scheduled for installation.
     SystemScheduled,
 
+    /// The system was described from its SMBIOS tables.
+    ///
+    /// This is not firmware, but frontends may show it above the system firmware.
+    SystemSummary(SystemSummary),
+
+    /// The embedded controller of a System76 system was discovered.
+    ///
+    /// Its firmware is bundled with the system firmware, and so it can not be updated on its own.
+    /// The latest version is the one installed by the latest system firmware, if that differs
+    /// from the current version. See [`bundled_ec`].
+    S76Ec(FirmwareInfo),
+
     /// System76 system firmware was discovered.
     S76System(FirmwareInfo, Option<(System76Digest, System76Changelog)>),
 
-    /// Thelio I/O firmware was discovered.
-    ThelioIo(FirmwareInfo, Option<System76Digest>),
+    /// A Thelio I/O board was discovered, with the ID of the board.
+    ///
+    /// Systems with several boards report each of them, so that they may be updated separately.
+    ThelioIo(Box<str>, FirmwareInfo, Option<System76Digest>),
+
+    /// Another Thelio I/O board, by its ID, began flashing along with the board whose update was
+    /// requested, as the daemon flashes every board which is out of date at once.
+    ///
+    /// Frontends should show the board as though its own update had begun flashing.
+    ThelioIoFlashing(Box<str>),
+
+    /// Another Thelio I/O board, by its ID, finished flashing, with its error if it was not
+    /// updated. This follows [`FirmwareSignal::ThelioIoFlashing`].
+    ThelioIoFlashed(Box<str>, Option<FirmwareError>),
 }
 
 /// An event loop that should be run in the background, as this function will block until
 /// the stop signal is received.
+///
+/// Requests are processed concurrently on a tokio runtime, so a long-running download will not
+/// prevent a scan from being performed in the meantime.
 pub fn event_loop(receiver: Receiver<FirmwareEvent>, sender: Sender<FirmwareSignal>) {
+    event_loop_with_backends(receiver, sender, Vec::new());
+}
+
+/// An event loop which manages third-party backends in addition to the built-in backends.
+///
+/// The additional backends are scanned alongside the System76, fwupd, SMBIOS, Launch, MEI,
+/// microcode, GPU, wireless, and power backends, and the NVMe backend when the `nvme` feature is
+/// enabled. UEFI capsules from [`CAPSULES_DIR`] are staged directly when fwupd is not available,
+/// and the event loop runs as root. The flashrom backend is only scanned when it is enabled in the
+/// configuration, and no other backend manages the system firmware. Plugins installed in
+/// [`PLUGINS_DIR`] are registered before the additional backends.
+pub fn event_loop_with_backends(
+    receiver: Receiver<FirmwareEvent>,
+    sender: Sender<FirmwareSignal>,
+    additional: Vec<Box<dyn FirmwareBackend>>,
+) {
     let cancellable = Arc::new(AtomicBool::new(true));
 
-    let s76 = get_client("system76", s76_firmware_is_active, System76Client::new);
+    let mut backends = Backends::default();
 
-    let fwupd = {
-        // Use Ping() to wake up fwupd, and to check if it exists.
-        let fwupd_connect = || {
-            let client = FwupdClient::new()?;
-            client.ping()?;
+    if let Some(backend) = System76Backend::connect() {
+        backends.register(backend);
+    }
 
-            let _res = client.set_feature_flags(fwupd_dbus::FeatureFlags::REQUESTS);
+    match FwupdBackend::connect(sender.clone(), cancellable.clone()) {
+        Some(backend) => backends.register(backend),
+        // Capsules are staged directly when fwupd is not available to stage them.
+        None if capsule::is_needed() => backends.register(CapsuleBackend),
+        None => (),
+    }
 
-            std::thread::spawn({
-                let sender = sender.clone();
-                let cancellable = cancellable.clone();
-                move || {
-                    if let Ok(client) = FwupdClient::new() {
-                        if let Ok(signals) = client.listen_signals(cancellable) {
-                            for signal in signals {
-                                if let fwupd_dbus::Signal::DeviceRequest(request) = dbg!(signal) {
-                                    let message = FirmwareSignal::DeviceRequest(request.update_message);
-                                    let _res = sender.send(message);
-                                }
-                            }
-                        }
-                    }
-                }
-            });
+    backends.register(SmbiosBackend);
+    backends.register(LaunchBackend);
+    backends.register(MeiBackend);
+    backends.register(MicrocodeBackend);
+    backends.register(GpuBackend);
+    backends.register(WirelessBackend);
+    backends.register(PowerBackend);
+
+    if config().flashrom && flashrom::is_needed(&backends) {
+        backends.register(FlashromBackend);
+    }
+
+    #[cfg(feature = "nvme")]
+    backends.register(NvmeBackend);
+
+    for backend in plugin::load_all(Path::new(PLUGINS_DIR)) {
+        backends.register(backend);
+    }
+
+    for backend in additional {
+        backends.register_boxed(backend);
+    }
+
+    event_loop_for(receiver, sender, backends);
+    cancellable.store(false, Ordering::SeqCst);
+}
+
+/// An event loop which manages only the given backends.
+///
+/// This may be used to run the firmware manager without the built-in backends, such as with the
+/// mock backend when demonstrating or testing a frontend. The fwupd backend is only added if it is
+/// requested with [`FirmwareEvent::StartFwupd`].
+pub fn event_loop_for(
+    receiver: Receiver<FirmwareEvent>,
+    sender: Sender<FirmwareSignal>,
+    backends: Backends,
+) {
+    let backends = Arc::new(backends);
+    let scanning = Arc::new(AtomicBool::new(false));
+    let starting = Arc::new(AtomicBool::new(false));
+    let queue = Arc::new(UpdateQueue::default());
+    let in_progress = Arc::new(InProgress::default());
+    let mut metered_policy = MeteredPolicy::default();
+
+    // Stops the listener of a fwupd backend which was registered by a `StartFwupd` event.
+    let cancellable = Arc::new(AtomicBool::new(true));
 
-            Ok(client)
-        };
+    // Backends communicate with their daemons through blocking DBus calls, so each request is
+    // handed to the runtime's blocking thread pool, allowing requests to run concurrently.
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .thread_name("firmware-worker")
+        .build()
+        .expect("failed to create the firmware worker runtime");
+
+    // Queued updates are performed one at a time by a dedicated worker.
+    runtime.spawn_blocking({
+        let backends = backends.clone();
+        let queue = queue.clone();
+        let in_progress = in_progress.clone();
+        let sender = sender.clone();
+        move || {
+            while let Some((event, metered_policy)) = queue.pop(&sender) {
+                perform(&backends, &in_progress, event, metered_policy, &sender);
+            }
+        }
+    });
 
-        get_client::<_, _, fwupd_dbus::Error>("fwupd", || true, fwupd_connect)
-    };
+    let interrupted = journal::interrupted();
+    if !interrupted.is_empty() {
+        let _res = sender.send(FirmwareSignal::Interrupted(interrupted));
+    }
 
     while let Ok(event) = receiver.recv() {
         trace!("event loop received firmware event: {:?}", event);
         match event {
             FirmwareEvent::Scan => {
-                let sender = &sender;
-                let _res = sender.send(FirmwareSignal::Scanning);
-
-                if let Some(ref client) = s76 {
-                    s76_scan(client, sender.clone());
+                if scanning.swap(true, Ordering::SeqCst) {
+                    info!("a scan is already in progress");
+                    continue;
                 }
 
-                if let Some(ref client) = fwupd {
-                    if let Err(why) = fwupd_updates(client) {
-                        eprintln!("failed to update fwupd remotes: {}", why);
-                    }
-
-                    fwupd_scan(client, sender.clone());
-                }
+                // Mirrors which do not respond are found before the updates which use them.
+                runtime.spawn_blocking(|| mirror::check_health(&download_config().mirrors));
 
-                let _ = sender.send(FirmwareSignal::ScanningComplete);
+                let backends = backends.clone();
+                let scanning = scanning.clone();
+                let sender = sender.clone();
+                runtime.spawn_blocking(move || scan(&backends, &scanning, &sender));
             }
-            FirmwareEvent::Fwupd(entity, device, release) => {
-                let flags = fwupd_dbus::InstallFlags::empty();
-                let event = match fwupd.as_ref().map(|fwupd| {
-                    fwupd.update_device_with_release(
-                        &device,
-                        &release,
-                        flags,
-                        Some(|download_event| {
-                            use fwupd_dbus::FlashEvent::*;
-                            let event = match download_event {
-                                DownloadUpdate(progress) => {
-                                    FirmwareSignal::DownloadUpdate(entity, progress)
-                                }
-                                DownloadInitiate(size) => {
-                                    FirmwareSignal::DownloadBegin(entity, size)
-                                }
-                                DownloadComplete => FirmwareSignal::DownloadComplete(entity),
-                                FlashInProgress => FirmwareSignal::DeviceFlashing(entity),
-                                VerifyingChecksum => return,
-                            };
-
-                            let _res = sender.send(event);
-                        }),
-                    )
-                }) {
-                    Some(Ok(_)) => FirmwareSignal::DeviceUpdated(entity),
-                    Some(Err(why)) => FirmwareSignal::Error(Some(entity), why.into()),
-                    None => panic!("fwupd event assigned to non-fwupd button"),
-                };
-
-                let _res = sender.send(event);
+            FirmwareEvent::Enqueue(events) => queue.push(events, metered_policy, &sender),
+            FirmwareEvent::UpdateAll(batch) => {
+                queue.push(batch.into_events(), metered_policy, &sender);
             }
-            FirmwareEvent::S76System(entity, digest) => {
-                let signal = match s76.as_ref().map(|client| client.schedule(&digest)) {
-                    Some(Ok(_)) => FirmwareSignal::SystemScheduled,
-                    Some(Err(why)) => FirmwareSignal::Error(Some(entity), why.into()),
-                    None => panic!("thelio event assigned to non-thelio button"),
-                };
-
-                let _res = sender.send(signal);
+            FirmwareEvent::MeteredPolicy(policy) => {
+                info!("metered connection policy set to {:?}", policy);
+                metered_policy = policy;
+            }
+            FirmwareEvent::DownloadAnyway(event) => {
+                let backends = backends.clone();
+                let in_progress = in_progress.clone();
+                let sender = sender.clone();
+                runtime.spawn_blocking(move || {
+                    perform(&backends, &in_progress, *event, MeteredPolicy::Always, &sender);
+                });
+            }
+            FirmwareEvent::ReportConsent(report, upload) => {
+                runtime.spawn_blocking(move || report::answer(&report, upload));
             }
-            FirmwareEvent::ThelioIo(entity, digest) => {
-                let _res = sender.send(FirmwareSignal::DeviceFlashing(entity));
-                let event = match s76.as_ref().map(|client| client.thelio_io_update(&digest)) {
-                    Some(Ok(_)) => FirmwareSignal::DeviceUpdated(entity),
-                    Some(Err(why)) => FirmwareSignal::Error(Some(entity), why.into()),
-                    None => panic!("thelio event assigned to non-thelio button"),
-                };
-
-                let _res = sender.send(event);
+            FirmwareEvent::ClearInterrupted => {
+                runtime.spawn_blocking(journal::clear);
+            }
+            FirmwareEvent::ExportBundle(directory, cancellable) => {
+                let sender = sender.clone();
+                runtime.spawn_blocking(move || {
+                    let _span = info_span!("export").entered();
+                    let signal = match bundle::export(&directory, &cancellable) {
+                        Ok(exported) => FirmwareSignal::BundleExported(directory, exported),
+                        Err(BundleError::Cancelled) => return,
+                        Err(why) => FirmwareSignal::Error(None, why.into()),
+                    };
+
+                    let _res = sender.send(signal);
+                });
+            }
+            FirmwareEvent::ImportBundle(directory) => {
+                let backends = backends.clone();
+                let sender = sender.clone();
+                runtime.spawn_blocking(move || {
+                    let _span = info_span!("import").entered();
+                    let failed = |why: BundleError| {
+                        let _res = sender.send(FirmwareSignal::Error(None, why.into()));
+                    };
+
+                    let signal = match bundle::import(&directory, &backends, failed) {
+                        Ok(updated) => FirmwareSignal::BundleImported(updated),
+                        Err(why) => FirmwareSignal::Error(None, why.into()),
+                    };
+
+                    let _res = sender.send(signal);
+                });
+            }
+            FirmwareEvent::UsbDrives => {
+                let _res = sender.send(FirmwareSignal::UsbDrives(usb_drives()));
+            }
+            FirmwareEvent::RecoveryDrive(drive, cancellable) => {
+                let sender = sender.clone();
+                runtime.spawn_blocking(move || {
+                    let _span = info_span!("recovery").entered();
+                    let progress = |progress| {
+                        let _res = sender.send(FirmwareSignal::Recovery(progress));
+                    };
+
+                    match recovery::create(&drive, &cancellable, progress) {
+                        Ok(()) => (),
+                        Err(RecoveryError::Cancelled) => info!("recovery drive was cancelled"),
+                        Err(why) => {
+                            let _res = sender.send(FirmwareSignal::Error(None, why.into()));
+                        }
+                    }
+                });
+            }
+            // Starting the daemon may take a while, after which its devices are scanned.
+            FirmwareEvent::StartFwupd => {
+                if starting.swap(true, Ordering::SeqCst) {
+                    info!("the fwupd daemon is already being started");
+                    continue;
+                }
+
+                let backends = backends.clone();
+                let cancellable = cancellable.clone();
+                let scanning = scanning.clone();
+                let starting = starting.clone();
+                let sender = sender.clone();
+                runtime.spawn_blocking(move || {
+                    let result = start_fwupd(&backends, &sender, &cancellable);
+                    starting.store(false, Ordering::SeqCst);
+
+                    match result {
+                        Ok(()) if !scanning.swap(true, Ordering::SeqCst) => {
+                            scan(&backends, &scanning, &sender);
+                        }
+                        Ok(()) => info!("a scan is already in progress"),
+                        Err(why) => {
+                            let _res = sender.send(FirmwareSignal::Error(None, why));
+                        }
+                    }
+                });
             }
             FirmwareEvent::Stop => {
                 trace!("received quit signal");
                 break;
             }
+            event => {
+                let backends = backends.clone();
+                let in_progress = in_progress.clone();
+                let sender = sender.clone();
+                runtime.spawn_blocking(move || {
+                    perform(&backends, &in_progress, event, metered_policy, &sender);
+                });
+            }
         }
     }
 
+    // Queued updates which have not begun are discarded, and updates in progress are cancelled.
+    // Those which were downloading stop at once, whereas devices which are flashing are left to
+    // finish, as dropping the runtime waits for in-progress requests to complete.
+    queue.stop();
+    let cancelled = in_progress.cancel_all();
+    if cancelled != 0 {
+        info!("waiting for {} updates in progress to stop", cancelled);
+    }
+
+    drop(runtime);
     cancellable.store(false, Ordering::SeqCst);
 }
 
+/// Scans every backend for firmware, and reports the outcome. The `scanning` flag must have been
+/// set by the caller, and is cleared once the backends have been scanned.
+fn scan(backends: &Backends, scanning: &AtomicBool, sender: &Sender<FirmwareSignal>) {
+    let _span = info_span!("scan").entered();
+    let _res = sender.send(FirmwareSignal::Scanning);
+    let result = backends.scan(sender);
+    scanning.store(false, Ordering::SeqCst);
+
+    if !result.failed() {
+        if let Err(why) = record_check() {
+            warn!("failed to record the time of the check: {}", format_error(why));
+        }
+    }
+
+    let _res = sender.send(FirmwareSignal::ScanResult(result));
+    let _res = sender.send(FirmwareSignal::ScanningComplete);
+}
+
+/// Performs an update with the backend that handles it, unless the download must be deferred.
+fn perform(
+    backends: &Backends,
+    in_progress: &InProgress,
+    event: FirmwareEvent,
+    metered_policy: MeteredPolicy,
+    sender: &Sender<FirmwareSignal>,
+) {
+    let _span = info_span!("update", entity = ?event.entity()).entered();
+    if event.requires_download() {
+        if let Some(reason) = network::defer_download(metered_policy) {
+            info!("deferring {:?}: {:?}", event, reason);
+            if let Some(entity) = event.entity() {
+                let signal = FirmwareSignal::UpdateDeferred(entity, reason, Box::new(event));
+                let _res = sender.send(signal);
+            }
+
+            return;
+        }
+    }
+
+    let tracked = event.entity().zip(event.cancellable().cloned());
+    if let Some((entity, cancellable)) = tracked {
+        in_progress.insert(entity, cancellable);
+    }
+
+    let signal = backends.update(&event, sender);
+
+    if let Some(entity) = event.entity() {
+        in_progress.remove(entity);
+    }
+
+    match signal {
+        Some(FirmwareSignal::Cancelled(entity))
+            if event.cancellable().map_or(false, CancellationToken::is_paused) =>
+        {
+            let _res = sender.send(FirmwareSignal::Paused(entity, Box::new(event)));
+        }
+        Some(FirmwareSignal::Error(Some(entity), why)) => {
+            let _res = sender.send(FirmwareSignal::Error(Some(entity), why));
+            let _res = sender.send(FirmwareSignal::Failed(entity, Box::new(event)));
+        }
+        Some(signal) => {
+            let _res = sender.send(signal);
+        }
+        None => error!("{:?} was not handled by any firmware backend", event),
+    }
+}
+
 /// Function for getting a timmed string from a file.
 fn read_trimmed(path: &str) -> io::Result<String> {
     let mut vendor = std::fs::read_to_string(path)?;
@@ -313,6 +1046,12 @@ fn product_version() -> io::Result<String> {
     read_trimmed("/sys/class/dmi/id/product_version")
 }
 
+/// Convenience function for reading the embedded controller firmware release from the DMI ID
+/// information.
+pub(crate) fn ec_firmware_release() -> io::Result<String> {
+    read_trimmed("/sys/class/dmi/id/ec_firmware_release")
+}
+
 /// Convenience function for reading the system vendor from the DMI ID information.
 fn sys_vendor() -> io::Result<String> {
     read_trimmed("/sys/class/dmi/id/sys_vendor")
@@ -336,6 +1075,37 @@ where
     }
 }
 
+/// Starts the fwupd daemon, and registers its backend in place of the capsule backend, which
+/// stages capsules only while fwupd is not available to stage them.
+fn start_fwupd(
+    backends: &Backends,
+    sender: &Sender<FirmwareSignal>,
+    cancellable: &Arc<AtomicBool>,
+) -> Result<(), FirmwareError> {
+    if backends.contains(fwupd::BACKEND) {
+        return Ok(());
+    }
+
+    info!("starting the fwupd daemon");
+    let status = Command::new("systemctl")
+        .args(["start", "fwupd.service"])
+        .status()
+        .map_err(FirmwareError::FwupdStart)?;
+
+    if !status.success() {
+        let why = io::Error::new(io::ErrorKind::Other, format!("systemctl {}", status));
+        return Err(FirmwareError::FwupdStart(why));
+    }
+
+    let backend = FwupdBackend::connect(sender.clone(), cancellable.clone()).ok_or_else(|| {
+        let why = io::Error::new(io::ErrorKind::NotConnected, "fwupd did not respond");
+        FirmwareError::FwupdStart(why)
+    })?;
+
+    backends.replace(capsule::BACKEND, Box::new(backend));
+    Ok(())
+}
+
 /// Checks if a systemd service is active.
 fn systemd_service_is_active(name: &str) -> bool {
     Command::new("systemctl")
@@ -346,23 +1116,6 @@ fn systemd_service_is_active(name: &str) -> bool {
         .map_or(false, |status| status.success())
 }
 
-/// Finds the lowest revision from anything that is or may become an `Iterator` of strings.
-fn lowest_revision<'a, I: IntoIterator<Item = &'a str>>(list: I) -> &'a str {
-    use std::cmp::Ordering;
-    let mut list = list.into_iter();
-    match list.next() {
-        Some(mut lowest_revision) => {
-            for rev in list {
-                if human_sort::compare(lowest_revision, rev) == Ordering::Greater {
-                    lowest_revision = rev;
-                }
-            }
-            lowest_revision
-        }
-        None => "",
-    }
-}
-
 /// Helper for formatting errors for logs.
 fn format_error<E: std::error::Error>(why: E) -> String {
     let mut error_message = format!("{}", why);
@@ -378,17 +1131,23 @@ fn format_error<E: std::error::Error>(why: E) -> String {
 #[cfg(test)]
 mod tests {
     #[test]
-    fn lowest_revision() {
-        let input = vec!["", "F10", "F5"];
-        let rev = super::lowest_revision(input.iter().cloned());
-        assert_eq!(rev, "");
-
-        let input = vec!["F3", "F10", "F5"];
-        let rev = super::lowest_revision(input.iter().cloned());
-        assert_eq!(rev, "F3");
-
-        let input = vec!["F10", "F3", "F5"];
-        let rev = super::lowest_revision(input.iter().cloned());
-        assert_eq!(rev, "F3");
+    fn is_permission_denied() {
+        let message = "error in fwupd client: org.freedesktop.fwupd.AuthFailed: denied";
+        assert!(super::is_permission_denied(message));
+        assert!(!super::is_permission_denied("error in fwupd client: device busy"));
+    }
+
+    #[test]
+    fn localized_error() {
+        use super::FirmwareError;
+        use std::io;
+
+        let why = io::Error::new(io::ErrorKind::Other, "busy");
+        let error = FirmwareError::Permission(Box::new(FirmwareError::Open(why)));
+
+        assert_eq!(
+            error.localized(),
+            "not authorized to update firmware: failed to open firmware file for flashing: busy"
+        );
     }
 }
@@ -0,0 +1,367 @@
+//! Checks the LVFS metadata for updates directly, for when the fwupd daemon is not running.
+//!
+//! Without fwupd, the only devices whose firmware GUIDs are known are those of the EFI System
+//! Resource Table, which are matched against the firmware that the LVFS publishes for them. The
+//! devices are reported so that frontends may tell the user that updates exist, but installing
+//! them is deferred until fwupd is running. This is only done when `lvfs_fallback = true` is set
+//! in the configuration file.
+//!
+//! The metadata is cached, and only downloaded again once the LVFS has published a new copy. It
+//! is parsed only after its PKCS#7 signature, from the Jcat file that the LVFS publishes beside
+//! it, was verified with `openssl` against the certificate authority that fwupd installs.
+
+use crate::{
+    cache, capsule, download, DeviceId, DeviceSignal, DownloadError, FirmwareInfo,
+    FirmwareSignal,
+};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::{
+    cmp::Ordering,
+    fs,
+    io::{self, Read},
+    path::Path,
+    process::Command,
+    sync::mpsc::Sender,
+};
+
+/// The name of the LVFS fallback, which identifies its devices.
+const BACKEND: &str = "lvfs";
+
+/// The metadata of every firmware release that the LVFS publishes.
+const LVFS_METADATA: &str = "https://cdn.fwupd.org/downloads/firmware.xml.zst";
+
+/// The name of the metadata, by which the Jcat file identifies its signatures.
+const METADATA_NAME: &str = "firmware.xml.zst";
+
+/// The certificate authority which signs the LVFS metadata, as installed by fwupd.
+const METADATA_CA: &str = "/etc/pki/fwupd-metadata/LVFS-CA.pem";
+
+/// The kind of the blobs of a Jcat file which hold PKCS#7 signatures.
+const JCAT_PKCS7: u32 = 3;
+
+/// The flag of the blobs of a Jcat file whose data is text, rather than Base64.
+const JCAT_UTF8: u32 = 1;
+
+/// An error that may occur when checking the LVFS metadata.
+#[derive(Debug, Error)]
+pub enum LvfsError {
+    /// The metadata could not be downloaded.
+    #[error("failed to download the LVFS metadata")]
+    Download(#[from] DownloadError),
+    /// The metadata could not be decompressed.
+    #[error("failed to decompress the LVFS metadata")]
+    Decompress(#[source] io::Error),
+    /// The metadata is not valid XML.
+    #[error("failed to parse the LVFS metadata")]
+    Parse(#[from] roxmltree::Error),
+    /// The Jcat file which signs the metadata could not be read.
+    #[error("failed to read the signatures of the LVFS metadata")]
+    Jcat(#[source] io::Error),
+    /// The Jcat file has no PKCS#7 signature of the metadata.
+    #[error("the LVFS metadata is not signed")]
+    Unsigned,
+    /// openssl could not be executed to verify the signature.
+    #[error("failed to execute openssl to verify the LVFS metadata")]
+    Openssl(#[source] io::Error),
+    /// The signature of the metadata is not valid.
+    #[error("the signature of the LVFS metadata is not valid: {}", _0)]
+    Invalid(Box<str>),
+}
+
+/// The namespace of the GUIDs which fwupd derives from instance IDs, such as
+/// `USB\VID_3384&PID_0001`.
+const INSTANCE_ID_NAMESPACE: [u8; 16] = [
+    0x6b, 0xa7, 0xb8, 0x10, 0x9d, 0xad, 0x11, 0xd1, 0x80, 0xb4, 0x00, 0xc0, 0x4f, 0xd4, 0x30, 0xc8,
+];
+
+/// Firmware which the LVFS publishes, and its latest release.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Component {
+    /// The name of the firmware.
+    pub name: Box<str>,
+    /// The GUIDs of the devices that the firmware is flashed to.
+    pub guids: Vec<Box<str>>,
+    /// The version of the latest release.
+    pub latest: Box<str>,
+    /// How the integer versions of the devices are displayed, such as `triplet`.
+    pub version_format: Box<str>,
+}
+
+impl Component {
+    /// Whether the firmware is flashed to the device with the given GUID.
+    pub fn provides(&self, guid: &str) -> bool {
+        self.guids.iter().any(|provided| provided.eq_ignore_ascii_case(guid))
+    }
+
+    /// The latest release, if it is newer than the `current` version.
+    pub fn update_from(&self, current: &str) -> Option<Box<str>> {
+        match human_sort::compare(&self.latest, current) {
+            Ordering::Greater => Some(self.latest.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Reports the devices of the ESRT, with the latest firmware that the LVFS publishes for them.
+///
+/// This is meant for when the fwupd daemon is not available. The devices can not be updated
+/// until it is, so they are only reported for the user to be told that updates exist.
+pub fn lvfs_scan(sender: &Sender<FirmwareSignal>) -> Result<(), LvfsError> {
+    let resources = capsule::resources(Path::new(capsule::ESRT_ENTRIES));
+    if resources.is_empty() {
+        return Ok(());
+    }
+
+    let components = components()?;
+
+    for resource in resources {
+        let provides = |component: &&Component| component.provides(&resource.class);
+        let component = match components.iter().find(provides) {
+            Some(component) => component,
+            None => continue,
+        };
+
+        let current = format_version(resource.version, &component.version_format);
+        let latest = component.update_from(&current);
+
+        let _res = sender.send(FirmwareSignal::Device(DeviceSignal {
+            device: DeviceId { backend: BACKEND.into(), id: resource.class },
+            info: FirmwareInfo {
+                name: component.name.clone(),
+                current: current.into(),
+                latest,
+                install_duration: 0,
+                policy: None,
+            },
+            read_only: true,
+        }));
+    }
+
+    Ok(())
+}
+
+/// Fetches the firmware components which the LVFS publishes.
+pub(crate) fn components() -> Result<Vec<Component>, LvfsError> {
+    info!("checking the LVFS metadata for updates");
+
+    let cached = cache::cache(METADATA_NAME).map_err(DownloadError::Cache)?;
+    let compressed = download::fetch_cached(LVFS_METADATA, &cached)?;
+
+    // A copy which fails verification is downloaded again in full, rather than trusted later.
+    if let Err(why) = verify_metadata(&cached) {
+        download::forget_cached(&cached);
+        return Err(why);
+    }
+
+    let metadata = zstd::decode_all(&*compressed).map_err(LvfsError::Decompress)?;
+    Ok(parse_metadata(&String::from_utf8_lossy(&metadata))?)
+}
+
+/// A Jcat file, which holds the signatures of the files that the LVFS publishes.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Jcat {
+    items: Vec<JcatItem>,
+}
+
+/// The signatures of a file, which is identified by its name.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JcatItem {
+    id: Box<str>,
+    blobs: Vec<JcatBlob>,
+}
+
+/// A signature or checksum of a file.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JcatBlob {
+    kind: u32,
+    #[serde(default)]
+    flags: u32,
+    data: Box<str>,
+}
+
+/// Verifies the `metadata` file against the PKCS#7 signature of the LVFS.
+fn verify_metadata(metadata: &Path) -> Result<(), LvfsError> {
+    let compressed = download::fetch_bytes(&[LVFS_METADATA, ".jcat"].concat())?;
+
+    let mut json = String::new();
+    GzDecoder::new(&*compressed).read_to_string(&mut json).map_err(LvfsError::Jcat)?;
+
+    let signature = pkcs7_signature(&json, METADATA_NAME).ok_or(LvfsError::Unsigned)?;
+
+    let signature_path =
+        cache::cache([METADATA_NAME, ".p7b"].concat()).map_err(DownloadError::Cache)?;
+    fs::write(&signature_path, &*signature).map_err(LvfsError::Jcat)?;
+
+    let output = Command::new("openssl")
+        .args(["cms", "-verify", "-binary", "-inform", "PEM", "-purpose", "any"])
+        .arg("-in")
+        .arg(&signature_path)
+        .arg("-content")
+        .arg(metadata)
+        .args(["-CAfile", METADATA_CA, "-out", "/dev/null"])
+        .output();
+
+    let _res = fs::remove_file(&signature_path);
+    let output = output.map_err(LvfsError::Openssl)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+        return Err(LvfsError::Invalid(reason.trim().into()));
+    }
+
+    Ok(())
+}
+
+/// The PKCS#7 signature of the file with the given `id` in a Jcat file, in PEM form.
+fn pkcs7_signature(json: &str, id: &str) -> Option<Box<str>> {
+    let jcat = serde_json::from_str::<Jcat>(json).ok()?;
+    let item = jcat.items.into_iter().find(|item| &*item.id == id)?;
+
+    item.blobs
+        .into_iter()
+        .find(|blob| blob.kind == JCAT_PKCS7 && blob.flags & JCAT_UTF8 != 0)
+        .map(|blob| blob.data)
+}
+
+/// The GUID which fwupd derives from an instance ID, as a name-based UUID.
+pub(crate) fn instance_guid(instance_id: &str) -> String {
+    let digest =
+        Sha1::new().chain_update(INSTANCE_ID_NAMESPACE).chain_update(instance_id).finalize();
+
+    let mut uuid = [0u8; 16];
+    uuid.copy_from_slice(&digest[..16]);
+    uuid[6] = 0x50 | (uuid[6] & 0x0f);
+    uuid[8] = 0x80 | (uuid[8] & 0x3f);
+
+    let hex = uuid.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    [&hex[..8], "-", &hex[8..12], "-", &hex[12..16], "-", &hex[16..20], "-", &hex[20..]].concat()
+}
+
+/// Parses the firmware components of the LVFS metadata.
+fn parse_metadata(xml: &str) -> Result<Vec<Component>, roxmltree::Error> {
+    let document = roxmltree::Document::parse(xml)?;
+
+    let components = document
+        .descendants()
+        .filter(|node| node.has_tag_name("component") && node.attribute("type") == Some("firmware"))
+        .filter_map(|node| {
+            let children =
+                |tag: &'static str| node.descendants().filter(move |child| child.has_tag_name(tag));
+
+            let name = children("name").next()?.text()?.trim().into();
+
+            let guids = children("firmware")
+                .filter(|firmware| firmware.attribute("type") == Some("flashed"))
+                .filter_map(|firmware| firmware.text())
+                .map(|guid| guid.trim().into())
+                .collect::<Vec<Box<str>>>();
+
+            let latest = children("release")
+                .filter_map(|release| release.attribute("version"))
+                .max_by(|a, b| human_sort::compare(a, b))?
+                .into();
+
+            let version_format = children("value")
+                .find(|value| value.attribute("key") == Some("LVFS::VersionFormat"))
+                .and_then(|value| value.text())
+                .unwrap_or("plain")
+                .into();
+
+            Some(Component { name, guids, latest, version_format })
+        })
+        .filter(|component| !component.guids.is_empty())
+        .collect();
+
+    Ok(components)
+}
+
+/// Formats the integer version of an ESRT entry as the LVFS displays it.
+fn format_version(version: u32, format: &str) -> String {
+    let [a, b, c, d] = version.to_be_bytes();
+    match format {
+        "pair" => format!("{}.{}", version >> 16, version & 0xffff),
+        "triplet" => format!("{}.{}.{}", a, b, version & 0xffff),
+        "quad" => format!("{}.{}.{}.{}", a, b, c, d),
+        _ => version.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Component;
+
+    #[test]
+    fn pkcs7_signature() {
+        let json = r#"{
+            "JcatVersionMajor": 0,
+            "JcatVersionMinor": 1,
+            "Items": [
+              {
+                "Id": "firmware.xml.zst",
+                "Blobs": [
+                  { "Kind": 1, "Flags": 1, "Timestamp": 1700000000, "Data": "0a1b2c" },
+                  { "Kind": 3, "Flags": 1, "Data": "-----BEGIN PKCS7-----" }
+                ]
+              }
+            ]
+        }"#;
+
+        let signature = super::pkcs7_signature(json, "firmware.xml.zst");
+        assert_eq!(signature.as_deref(), Some("-----BEGIN PKCS7-----"));
+        assert_eq!(super::pkcs7_signature(json, "firmware.xml.gz"), None);
+        assert_eq!(super::pkcs7_signature("{}", "firmware.xml.zst"), None);
+    }
+
+    #[test]
+    fn parse_metadata() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+            <components origin="lvfs" version="0.9">
+              <component type="firmware">
+                <name>ThinkPad System Firmware</name>
+                <provides>
+                  <firmware type="flashed">a0b1c2d3-e4f5-4a6b-8c7d-9e0f1a2b3c4d</firmware>
+                </provides>
+                <releases>
+                  <release version="0.1.9" />
+                  <release version="0.1.10" />
+                </releases>
+                <custom>
+                  <value key="LVFS::VersionFormat">triplet</value>
+                </custom>
+              </component>
+              <component type="desktop-application">
+                <name>Not Firmware</name>
+              </component>
+            </components>"#;
+
+        let expected = vec![Component {
+            name: "ThinkPad System Firmware".into(),
+            guids: vec!["a0b1c2d3-e4f5-4a6b-8c7d-9e0f1a2b3c4d".into()],
+            latest: "0.1.10".into(),
+            version_format: "triplet".into(),
+        }];
+
+        assert_eq!(super::parse_metadata(xml).unwrap(), expected);
+        assert!(super::parse_metadata("<components>").is_err());
+    }
+
+    #[test]
+    fn instance_guid() {
+        // A name-based UUID of the DNS namespace, as in RFC 4122.
+        assert_eq!(super::instance_guid("www.example.com"), "2ed6657d-e927-568b-95e1-2665a8aea6a2");
+    }
+
+    #[test]
+    fn format_version() {
+        assert_eq!(super::format_version(0x0001_000a, "triplet"), "0.1.10");
+        assert_eq!(super::format_version(0x0001_000a, "pair"), "1.10");
+        assert_eq!(super::format_version(0x0102_0304, "quad"), "1.2.3.4");
+        assert_eq!(super::format_version(65546, "plain"), "65546");
+    }
+}
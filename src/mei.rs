@@ -0,0 +1,75 @@
+//! A backend which reports the version of the Intel Management Engine.
+//!
+//! The Management Engine (also known as the CSME) is updated along with the system firmware, so
+//! it is only reported for the user to confirm which version is running after a firmware update.
+//! The kernel's MEI driver exposes the version which the engine reported when it was probed.
+
+use crate::{FirmwareBackend, FirmwareEvent, FirmwareInfo, FirmwareSignal};
+use std::{fs, path::Path, sync::mpsc::Sender};
+
+/// Where the MEI devices of the system are described.
+const MEI_DEVICES: &str = "/sys/class/mei";
+
+/// The name of the device, as shown to the user.
+const ME_NAME: &str = "Intel Management Engine";
+
+/// A firmware backend which reports the version of the Intel Management Engine.
+#[derive(Default)]
+pub struct MeiBackend;
+
+impl FirmwareBackend for MeiBackend {
+    fn name(&self) -> &str { "mei" }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        if let Some(current) = me_version(Path::new(MEI_DEVICES)) {
+            let _res = sender.send(FirmwareSignal::ManagementEngine(FirmwareInfo {
+                name: ME_NAME.into(),
+                current: current.into(),
+                latest: None,
+                install_duration: 0,
+                policy: None,
+            }));
+        }
+    }
+
+    /// The Management Engine is only updated with the system firmware.
+    fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+        None
+    }
+}
+
+/// The version reported by the first MEI device which has one.
+fn me_version(devices: &Path) -> Option<String> {
+    let mut entries = fs::read_dir(devices)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path().join("fw_ver"))
+        .collect::<Vec<_>>();
+
+    entries.sort();
+
+    entries
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .find_map(|fw_ver| parse_fw_ver(&fw_ver).map(String::from))
+}
+
+/// Parses the version of the running firmware from the `fw_ver` attribute of an MEI device.
+///
+/// Each line describes a firmware component as `platform:major.minor.hotfix.build`, with the
+/// running firmware on the first line.
+fn parse_fw_ver(fw_ver: &str) -> Option<&str> {
+    let (_platform, version) = fw_ver.lines().next()?.split_once(':')?;
+    Some(version.trim()).filter(|version| !version.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_fw_ver() {
+        let fw_ver = "0:16.1.25.2049\n0:16.1.25.2049\n0:16.0.15.1620\n";
+        assert_eq!(super::parse_fw_ver(fw_ver), Some("16.1.25.2049"));
+        assert_eq!(super::parse_fw_ver(""), None);
+        assert_eq!(super::parse_fw_ver("0:\n"), None);
+    }
+}
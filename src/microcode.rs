@@ -0,0 +1,317 @@
+//! A backend which reports the microcode revision of the processor.
+//!
+//! Microcode is loaded by the kernel each time that the system boots, from the packages of the
+//! distribution, so it is only reported. The revision which is loaded is read from
+//! `/proc/cpuinfo`, and is compared to the newest revision for the processor that the
+//! distribution ships in `/lib/firmware`. A newer revision is loaded on the next boot, once the
+//! initramfs has been regenerated with it.
+
+use crate::{FirmwareBackend, FirmwareEvent, FirmwareInfo, FirmwareSignal};
+use std::{fs, path::Path, sync::mpsc::Sender};
+
+/// Describes the processors of the system, and the microcode that they run.
+const CPUINFO: &str = "/proc/cpuinfo";
+
+/// Where the distribution ships the microcode of Intel processors.
+const INTEL_UCODE: &str = "/lib/firmware/intel-ucode";
+
+/// Where the distribution ships the microcode of AMD processors.
+const AMD_UCODE: &str = "/lib/firmware/amd-ucode";
+
+/// The size of the header of an Intel microcode update.
+const INTEL_HEADER_LEN: usize = 48;
+
+/// The total size of an Intel microcode update whose header does not specify it.
+const INTEL_DEFAULT_TOTAL_LEN: usize = 2048;
+
+/// Identifies containers of AMD microcode patches.
+const AMD_CONTAINER_MAGIC: u32 = 0x0041_4d44;
+
+/// The section of an AMD container which maps processor signatures to equivalence IDs.
+const AMD_EQUIVALENCE_TABLE: u32 = 0;
+
+/// The section of an AMD container which holds a microcode patch.
+const AMD_PATCH: u32 = 1;
+
+/// The processor of the system, as described by `/proc/cpuinfo`.
+#[derive(Debug, PartialEq, Eq)]
+struct Processor {
+    vendor: Vendor,
+    family: u32,
+    model: u32,
+    stepping: u32,
+    /// The revision of the microcode which is loaded.
+    microcode: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Vendor {
+    Intel,
+    Amd,
+}
+
+/// A firmware backend which reports the microcode revision of the processor.
+#[derive(Default)]
+pub struct MicrocodeBackend;
+
+impl FirmwareBackend for MicrocodeBackend {
+    fn name(&self) -> &str { "microcode" }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        let processor = match fs::read_to_string(CPUINFO).ok().and_then(|info| processor(&info)) {
+            Some(processor) => processor,
+            None => return,
+        };
+
+        let shipped = match processor.vendor {
+            Vendor::Intel => intel_shipped(&processor),
+            Vendor::Amd => amd_shipped(&processor),
+        };
+
+        let latest = shipped
+            .filter(|&revision| revision > processor.microcode)
+            .map(|revision| format!("{:#x}", revision).into());
+
+        let name = match processor.vendor {
+            Vendor::Intel => "Intel Processor Microcode",
+            Vendor::Amd => "AMD Processor Microcode",
+        };
+
+        let _res = sender.send(FirmwareSignal::Microcode(FirmwareInfo {
+            name: name.into(),
+            current: format!("{:#x}", processor.microcode).into(),
+            latest,
+            install_duration: 0,
+            policy: None,
+        }));
+    }
+
+    /// Microcode is loaded by the kernel from the packages of the distribution.
+    fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+        None
+    }
+}
+
+impl Processor {
+    /// The signature of the processor, as reported in EAX by CPUID leaf 1.
+    fn signature(&self) -> u32 {
+        let (family, extended_family) =
+            if self.family >= 0xf { (0xf, self.family - 0xf) } else { (self.family, 0) };
+
+        let (model, extended_model) = if self.family == 6 || self.family >= 0xf {
+            (self.model & 0xf, self.model >> 4)
+        } else {
+            (self.model, 0)
+        };
+
+        (extended_family << 20)
+            | (extended_model << 16)
+            | (family << 8)
+            | (model << 4)
+            | self.stepping
+    }
+}
+
+/// Parses the first processor of `/proc/cpuinfo`.
+fn processor(cpuinfo: &str) -> Option<Processor> {
+    let first = cpuinfo.split("\n\n").next()?;
+
+    let field = |key: &str| {
+        first.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some(value.trim()).filter(|_| name.trim() == key)
+        })
+    };
+
+    let number = |key: &str| -> Option<u32> {
+        let value = field(key)?;
+        match value.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        }
+    };
+
+    let vendor = match field("vendor_id")? {
+        "GenuineIntel" => Vendor::Intel,
+        "AuthenticAMD" => Vendor::Amd,
+        _ => return None,
+    };
+
+    Some(Processor {
+        vendor,
+        family: number("cpu family")?,
+        model: number("model")?,
+        stepping: number("stepping")?,
+        microcode: number("microcode")?,
+    })
+}
+
+/// The newest revision for the processor among the Intel microcode that is shipped.
+fn intel_shipped(processor: &Processor) -> Option<u32> {
+    let Processor { family, model, stepping, .. } = processor;
+    let file = format!("{:02x}-{:02x}-{:02x}", family, model, stepping);
+    let data = fs::read(Path::new(INTEL_UCODE).join(file)).ok()?;
+    intel_revision(&data, processor.signature())
+}
+
+/// The newest revision among the updates of an Intel microcode file for the given signature.
+fn intel_revision(data: &[u8], signature: u32) -> Option<u32> {
+    let mut revision = None;
+    let mut offset = 0;
+
+    while let Some(header) = data.get(offset..offset + INTEL_HEADER_LEN) {
+        let total = match le_u32(header, 32)? as usize {
+            0 => INTEL_DEFAULT_TOTAL_LEN,
+            total if total < INTEL_HEADER_LEN => return revision,
+            total => total,
+        };
+
+        if le_u32(header, 12)? == signature {
+            revision = revision.max(Some(le_u32(header, 4)?));
+        }
+
+        offset += total;
+    }
+
+    revision
+}
+
+/// The newest revision for the processor among the AMD microcode that is shipped.
+fn amd_shipped(processor: &Processor) -> Option<u32> {
+    let file = if processor.family >= 0x15 {
+        format!("microcode_amd_fam{:02x}h.bin", processor.family)
+    } else {
+        "microcode_amd.bin".into()
+    };
+
+    let data = fs::read(Path::new(AMD_UCODE).join(file)).ok()?;
+    amd_revision(&data, processor.signature())
+}
+
+/// The newest revision among the patches of AMD microcode containers for the given signature.
+fn amd_revision(data: &[u8], signature: u32) -> Option<u32> {
+    let mut revision = None;
+    let mut equivalence_id = None;
+    let mut offset = 0;
+
+    while let Some(magic) = le_u32(data, offset) {
+        if magic != AMD_CONTAINER_MAGIC {
+            return revision;
+        }
+
+        offset += 4;
+
+        // Each container begins with its equivalence table, which is followed by its patches.
+        while let (Some(kind), Some(len)) = (le_u32(data, offset), le_u32(data, offset + 4)) {
+            let section = match data.get(offset + 8..offset + 8 + len as usize) {
+                Some(section) => section,
+                None => return revision,
+            };
+
+            match kind {
+                AMD_EQUIVALENCE_TABLE => {
+                    equivalence_id = section
+                        .chunks_exact(16)
+                        .find(|entry| le_u32(entry, 0) == Some(signature))
+                        .and_then(|entry| le_u16(entry, 12));
+                }
+                AMD_PATCH => {
+                    let patch = le_u32(section, 4)?;
+                    if equivalence_id.is_some() && le_u16(section, 24) == equivalence_id {
+                        revision = revision.max(Some(patch));
+                    }
+                }
+                _ => break,
+            }
+
+            offset += 8 + len as usize;
+        }
+    }
+
+    revision
+}
+
+fn le_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn le_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Processor, Vendor};
+
+    #[test]
+    fn processor() {
+        let cpuinfo = "processor\t: 0\n\
+                       vendor_id\t: GenuineIntel\n\
+                       cpu family\t: 6\n\
+                       model\t\t: 140\n\
+                       model name\t: 11th Gen Intel(R) Core(TM) i7-1165G7 @ 2.80GHz\n\
+                       stepping\t: 1\n\
+                       microcode\t: 0xb4\n\
+                       \n\
+                       processor\t: 1\n";
+
+        let expected = Processor {
+            vendor: Vendor::Intel,
+            family: 6,
+            model: 140,
+            stepping: 1,
+            microcode: 0xb4,
+        };
+
+        let processor = super::processor(cpuinfo).unwrap();
+        assert_eq!(processor.signature(), 0x806c1);
+        assert_eq!(processor, expected);
+    }
+
+    #[test]
+    fn intel_revision() {
+        let update = |signature: u32, revision: u32| {
+            let mut update = vec![0; 64];
+            update[4..8].copy_from_slice(&revision.to_le_bytes());
+            update[12..16].copy_from_slice(&signature.to_le_bytes());
+            update[32..36].copy_from_slice(&64u32.to_le_bytes());
+            update
+        };
+
+        let data = [update(0x806c1, 0xb4), update(0x806c1, 0xb8), update(0x806c2, 0xc0)].concat();
+        assert_eq!(super::intel_revision(&data, 0x806c1), Some(0xb8));
+        assert_eq!(super::intel_revision(&data, 0x906a3), None);
+    }
+
+    #[test]
+    fn amd_revision() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&super::AMD_CONTAINER_MAGIC.to_le_bytes());
+
+        // An equivalence table which maps the signature to ID 0x8310.
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&16u32.to_le_bytes());
+        let mut entry = [0; 16];
+        entry[0..4].copy_from_slice(&0x0083_0f10u32.to_le_bytes());
+        entry[12..14].copy_from_slice(&0x8310u16.to_le_bytes());
+        data.extend_from_slice(&entry);
+
+        let mut patch = |revision: u32, equivalence_id: u16| {
+            let mut section = [0; 32];
+            section[4..8].copy_from_slice(&revision.to_le_bytes());
+            section[24..26].copy_from_slice(&equivalence_id.to_le_bytes());
+            data.extend_from_slice(&1u32.to_le_bytes());
+            data.extend_from_slice(&32u32.to_le_bytes());
+            data.extend_from_slice(&section);
+        };
+
+        patch(0x0830_107a, 0x8310);
+        patch(0x0830_107c, 0x8310);
+        patch(0x0860_1019, 0x8601);
+
+        assert_eq!(super::amd_revision(&data, 0x0083_0f10), Some(0x0830_107c));
+        assert_eq!(super::amd_revision(&data, 0x0086_0f01), None);
+    }
+}
@@ -0,0 +1,165 @@
+//! Failover between mirrors which host identical copies of firmware payloads.
+//!
+//! The LVFS is mirrored by its CDN by default. System76 firmware is flashed by the
+//! system76-firmware daemon, which downloads it without failing over, but the files that the
+//! firmware manager fetches from System76 to verify it fail over to any mirrors configured for
+//! its origin.
+
+use crate::download::{self, url_host};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+/// How long a mirror that failed is tried only after every other mirror.
+const COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+/// The time of the last failure of each host which has failed since its last success.
+static FAILURES: Lazy<Mutex<HashMap<String, SystemTime>>> = Lazy::new(Mutex::default);
+
+/// Servers which host identical copies of the files found at an origin.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Mirror {
+    /// The URL prefix of the files that are mirrored, such as `https://example.com/firmware/`.
+    pub origin: Box<str>,
+    /// URL prefixes which replace the origin to fetch a file from a mirror, in order of
+    /// preference.
+    pub mirrors: Vec<Box<str>>,
+}
+
+/// The mirrors which are used unless others are configured: the CDN of the LVFS, which the
+/// payloads of fwupd releases are published to.
+pub fn default_mirrors() -> Vec<Mirror> {
+    vec![Mirror {
+        origin: "https://fwupd.org/downloads/".into(),
+        mirrors: vec!["https://cdn.fwupd.org/downloads/".into()],
+    }]
+}
+
+/// Checks that the origin and mirrors of each of the `mirrors` respond, so that those which do
+/// not are tried last, rather than after every request to them has timed out.
+pub(crate) fn check_health(mirrors: &[Mirror]) {
+    let prefixes =
+        mirrors.iter().flat_map(|mirror| std::iter::once(&mirror.origin).chain(&mirror.mirrors));
+
+    for prefix in prefixes {
+        match download::probe(prefix) {
+            Ok(()) => record_success(prefix),
+            Err(why) => {
+                warn!("mirror is unavailable: {}", crate::format_error(why));
+                record_failure(prefix);
+            }
+        }
+    }
+}
+
+/// The URLs to try fetching `url` from, in the order that they should be tried.
+///
+/// Hosts which have failed within the last ten minutes are moved behind those which have not.
+pub(crate) fn candidates(url: &str, mirrors: &[Mirror]) -> Vec<String> {
+    let candidates = rewrite(url, mirrors);
+    let failures = FAILURES.lock().unwrap_or_else(|why| why.into_inner());
+    order(candidates, &failures, SystemTime::now())
+}
+
+/// Records that a fetch from the host of `url` has failed.
+pub(crate) fn record_failure(url: &str) {
+    if let Some(host) = url_host(url) {
+        let mut failures = FAILURES.lock().unwrap_or_else(|why| why.into_inner());
+        failures.insert(host.to_owned(), SystemTime::now());
+    }
+}
+
+/// Records that a fetch from the host of `url` has succeeded.
+pub(crate) fn record_success(url: &str) {
+    if let Some(host) = url_host(url) {
+        FAILURES.lock().unwrap_or_else(|why| why.into_inner()).remove(host);
+    }
+}
+
+/// The `url`, followed by the same file at each of the mirrors of its origin.
+fn rewrite(url: &str, mirrors: &[Mirror]) -> Vec<String> {
+    let mut candidates = vec![url.to_owned()];
+
+    for mirror in mirrors {
+        if let Some(path) = url.strip_prefix(&*mirror.origin) {
+            candidates.extend(mirror.mirrors.iter().map(|prefix| [prefix, path].concat()));
+        }
+    }
+
+    candidates.dedup();
+    candidates
+}
+
+/// Moves the candidates whose hosts have failed within the cooldown behind the rest, with those
+/// that failed the longest ago first.
+fn order(
+    mut candidates: Vec<String>,
+    failures: &HashMap<String, SystemTime>,
+    now: SystemTime,
+) -> Vec<String> {
+    let failed_at = |url: &str| {
+        let failed_at = *failures.get(url_host(url)?)?;
+        let elapsed = now.duration_since(failed_at).unwrap_or_default();
+        Some(failed_at).filter(|_| elapsed < COOLDOWN)
+    };
+
+    // The sort is stable, so healthy candidates remain in order of preference.
+    candidates.sort_by_key(|url| failed_at(url));
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mirror;
+    use std::{
+        collections::HashMap,
+        time::{Duration, SystemTime},
+    };
+
+    fn mirrors() -> Vec<Mirror> {
+        vec![Mirror {
+            origin: "https://origin.example/firmware/".into(),
+            mirrors: vec!["https://a.example/fw/".into(), "https://b.example/".into()],
+        }]
+    }
+
+    #[test]
+    fn rewrite() {
+        let candidates = super::rewrite("https://origin.example/firmware/abc.tar.xz", &mirrors());
+        assert_eq!(candidates, vec![
+            "https://origin.example/firmware/abc.tar.xz",
+            "https://a.example/fw/abc.tar.xz",
+            "https://b.example/abc.tar.xz",
+        ]);
+
+        let candidates = super::rewrite("https://other.example/abc.tar.xz", &mirrors());
+        assert_eq!(candidates, vec!["https://other.example/abc.tar.xz"]);
+    }
+
+    #[test]
+    fn order() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100_000);
+        let candidates = super::rewrite("https://origin.example/firmware/abc", &mirrors());
+
+        let mut failures = HashMap::new();
+        let ago = |secs| now - Duration::from_secs(secs);
+        failures.insert("origin.example".to_owned(), ago(10));
+        failures.insert("a.example".to_owned(), ago(60));
+
+        let ordered = super::order(candidates.clone(), &failures, now);
+        assert_eq!(ordered, vec![
+            "https://b.example/abc",
+            "https://a.example/fw/abc",
+            "https://origin.example/firmware/abc",
+        ]);
+
+        // Failures beyond the cooldown no longer affect the order.
+        failures.insert("origin.example".to_owned(), ago(60 * 60));
+        failures.remove("a.example");
+        assert_eq!(super::order(candidates.clone(), &failures, now), candidates);
+    }
+}
@@ -0,0 +1,253 @@
+//! A backend of synthetic devices, for demonstrating and testing frontends without real hardware.
+
+use crate::{
+    DeviceId, DeviceSignal, DownloadError, FirmwareBackend, FirmwareError, FirmwareEvent,
+    FirmwareInfo, FirmwareSignal,
+};
+use std::{
+    io,
+    path::PathBuf,
+    sync::{mpsc::Sender, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::Duration,
+};
+
+/// The name of the mock backend, which identifies its devices.
+const BACKEND: &str = "mock";
+
+/// The number of progress updates that a simulated download is divided into.
+const DOWNLOAD_STEPS: u64 = 30;
+
+/// A stage of an update that a mock device may be configured to fail at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MockFailure {
+    /// The download fails partway through with a network error.
+    Download,
+    /// The device fails while it is being flashed.
+    Flash,
+    /// The firmware daemon refuses to update the device.
+    Permission,
+    /// The downloaded firmware does not match its checksum.
+    Verification,
+}
+
+/// A synthetic device that the mock backend reports.
+#[derive(Clone, Debug)]
+pub struct MockDevice {
+    /// An identifier of the device, which is unique within the mock backend.
+    pub id: Box<str>,
+    /// The name of the device.
+    pub name: Box<str>,
+    /// The installed version of the firmware.
+    pub current: Box<str>,
+    /// The version of the firmware that is available, if any.
+    pub latest: Option<Box<str>>,
+    /// The size of the firmware to simulate downloading, in bytes.
+    pub size: u64,
+    /// The stage at which updates of this device should fail, if any.
+    pub failure: Option<MockFailure>,
+}
+
+impl MockDevice {
+    /// A device with the given firmware versions, which updates successfully.
+    pub fn new(id: &str, name: &str, current: &str, latest: Option<&str>) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            current: current.into(),
+            latest: latest.map(Box::from),
+            size: 4 * 1024 * 1024,
+            failure: None,
+        }
+    }
+
+    /// Causes updates of this device to fail at the given stage.
+    pub fn fail_at(mut self, failure: MockFailure) -> Self {
+        self.failure = Some(failure);
+        self
+    }
+}
+
+/// A firmware backend which reports synthetic devices and simulates their updates.
+///
+/// Updates of mock devices simulate download progress and flashing, and then either succeed or
+/// fail as the device was configured to. A successful update is reflected in the next scan.
+pub struct MockBackend {
+    devices: Mutex<Vec<MockDevice>>,
+    step: Duration,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new(vec![
+            MockDevice::new("dock", "Mock Dock", "1.0.2", Some("1.1.0")),
+            MockDevice::new("keyboard", "Mock Keyboard", "0.9.1", Some("1.0.0")),
+            MockDevice::new("mouse", "Mock Mouse", "2.3.0", Some("2.3.0")),
+            MockDevice::new("ssd", "Mock SSD", "4B2QGXA7", Some("4B2QGXB8"))
+                .fail_at(MockFailure::Download),
+            MockDevice::new("webcam", "Mock Webcam", "1.2", Some("1.3"))
+                .fail_at(MockFailure::Flash),
+        ])
+    }
+}
+
+impl MockBackend {
+    /// A backend which reports the given devices.
+    pub fn new(devices: Vec<MockDevice>) -> Self {
+        Self { devices: Mutex::new(devices), step: Duration::from_millis(100) }
+    }
+
+    /// Sets the delay between each step of a simulated update.
+    pub fn step(mut self, step: Duration) -> Self {
+        self.step = step;
+        self
+    }
+
+    fn devices(&self) -> MutexGuard<'_, Vec<MockDevice>> {
+        self.devices.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl FirmwareBackend for MockBackend {
+    fn name(&self) -> &str { BACKEND }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        for device in self.devices().iter() {
+            let _res = sender.send(FirmwareSignal::Device(DeviceSignal {
+                device: DeviceId { backend: BACKEND.into(), id: device.id.clone() },
+                info: FirmwareInfo {
+                    name: device.name.clone(),
+                    current: device.current.clone(),
+                    latest: device.latest.clone(),
+                    install_duration: 3,
+                    policy: None,
+                },
+                read_only: false,
+            }));
+        }
+    }
+
+    fn update(
+        &self,
+        event: &FirmwareEvent,
+        sender: &Sender<FirmwareSignal>,
+    ) -> Option<FirmwareSignal> {
+        let (entity, id, cancellable) = match event {
+            FirmwareEvent::Device(entity, id, cancellable) if &*id.backend == BACKEND => {
+                (*entity, id, cancellable)
+            }
+            _ => return None,
+        };
+
+        let device = self.devices().iter().find(|device| device.id == id.id).cloned()?;
+        let error = |why: FirmwareError| Some(FirmwareSignal::Error(Some(entity), why));
+        let simulated = |message: &str| io::Error::new(io::ErrorKind::Other, message.to_owned());
+
+        if let Some(latest) = &device.latest {
+            if let Err(why) = crate::check_policy(&device.name, &[&device.id], latest) {
+                return error(FirmwareError::Policy(latest.clone(), why));
+            }
+        }
+
+        let _res = sender.send(FirmwareSignal::DownloadBegin(entity, device.size));
+
+        let step = device.size / DOWNLOAD_STEPS;
+        for progress in 0..DOWNLOAD_STEPS {
+            if cancellable.is_cancelled() {
+                return Some(FirmwareSignal::Cancelled(entity));
+            }
+
+            if device.failure == Some(MockFailure::Download) && progress == DOWNLOAD_STEPS / 2 {
+                let why = DownloadError::Read(simulated("simulated network failure"));
+                return error(why.into());
+            }
+
+            thread::sleep(self.step);
+            let _res = sender.send(FirmwareSignal::DownloadUpdate(entity, step as usize));
+        }
+
+        let _res = sender.send(FirmwareSignal::DownloadComplete(entity));
+
+        let _res = sender.send(FirmwareSignal::Verifying(entity));
+        thread::sleep(self.step);
+
+        if device.failure == Some(MockFailure::Verification) {
+            let path = PathBuf::from(["/tmp/mock/", &*device.id, ".cab"].concat());
+            return error(FirmwareError::Verification(path));
+        }
+
+        if device.failure == Some(MockFailure::Permission) {
+            let why = FirmwareError::Backend(BACKEND.into(), Box::new(simulated("access denied")));
+            return error(FirmwareError::Permission(Box::new(why)));
+        }
+
+        let _res = sender.send(FirmwareSignal::DeviceFlashing(entity));
+        thread::sleep(self.step * 10);
+
+        if device.failure == Some(MockFailure::Flash) {
+            let why = simulated("simulated flashing failure");
+            return error(FirmwareError::Backend(BACKEND.into(), Box::new(why)));
+        }
+
+        if let Some(device) = self.devices().iter_mut().find(|device| device.id == id.id) {
+            if let Some(ref latest) = device.latest {
+                device.current = latest.clone();
+            }
+        }
+
+        Some(FirmwareSignal::DeviceUpdated(entity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockBackend, MockDevice, MockFailure};
+    use crate::{
+        CancellationToken, DeviceId, Entities, FirmwareBackend, FirmwareError, FirmwareEvent,
+        FirmwareSignal,
+    };
+    use std::{sync::mpsc, time::Duration};
+
+    fn update(backend: &MockBackend, id: &str) -> Option<FirmwareSignal> {
+        let entity = Entities::default().create();
+        let id = DeviceId { backend: "mock".into(), id: id.into() };
+        let event = FirmwareEvent::Device(entity, id, CancellationToken::default());
+        let (tx, _rx) = mpsc::channel();
+        backend.update(&event, &tx)
+    }
+
+    #[test]
+    fn mock_backend() {
+        let backend = MockBackend::new(vec![
+            MockDevice::new("dock", "Dock", "1.0", Some("1.1")),
+            MockDevice::new("ssd", "SSD", "1.0", Some("1.1")).fail_at(MockFailure::Permission),
+            MockDevice::new("hub", "Hub", "1.0", Some("1.1")).fail_at(MockFailure::Verification),
+        ])
+        .step(Duration::from_millis(0));
+
+        assert!(matches!(update(&backend, "dock"), Some(FirmwareSignal::DeviceUpdated(_))));
+        assert!(matches!(
+            update(&backend, "ssd"),
+            Some(FirmwareSignal::Error(_, FirmwareError::Permission(_)))
+        ));
+        assert!(matches!(
+            update(&backend, "hub"),
+            Some(FirmwareSignal::Error(_, FirmwareError::Verification(_)))
+        ));
+        assert!(update(&backend, "unknown").is_none());
+
+        let (tx, rx) = mpsc::channel();
+        backend.scan(&tx);
+        drop(tx);
+
+        let current = rx
+            .iter()
+            .filter_map(|signal| match signal {
+                FirmwareSignal::Device(signal) => Some(signal.info.current),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(current, vec![Box::from("1.1"), Box::from("1.0"), Box::from("1.0")]);
+    }
+}
@@ -0,0 +1,138 @@
+//! Detection of the network state through NetworkManager, so that downloads may be deferred when
+//! the system is offline, or when the connection is metered.
+
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use std::time::Duration;
+
+const NM_DEST: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+
+/// How firmware downloads are handled when the connection is metered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeteredPolicy {
+    /// Always download firmware, regardless of the connection.
+    Always,
+    /// Defer the download until the user has agreed to download on a metered connection.
+    Ask,
+    /// Never download firmware on a metered connection.
+    Never,
+}
+
+impl Default for MeteredPolicy {
+    fn default() -> Self { MeteredPolicy::Ask }
+}
+
+/// The reason that an update was deferred.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeferReason {
+    /// The system is not connected to the internet.
+    Offline,
+    /// The connection is metered, and the policy forbids downloading on metered connections.
+    Metered,
+    /// The connection is metered, and the user should be asked before the download proceeds.
+    MeteredAsk,
+}
+
+/// The connectivity of the system, as reported by NetworkManager.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connectivity {
+    /// The connectivity could not be determined.
+    Unknown,
+    /// The system is not connected to any network.
+    None,
+    /// The connection is behind a captive portal.
+    Portal,
+    /// The system is connected to a network, but it does not have access to the internet.
+    Limited,
+    /// The system is connected to the internet.
+    Full,
+}
+
+impl From<u32> for Connectivity {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Connectivity::None,
+            2 => Connectivity::Portal,
+            3 => Connectivity::Limited,
+            4 => Connectivity::Full,
+            _ => Connectivity::Unknown,
+        }
+    }
+}
+
+/// The state of the system's network connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetworkState {
+    /// Whether the system has access to the internet.
+    pub connectivity: Connectivity,
+    /// Whether the primary connection is known, or guessed, to be metered.
+    pub metered: bool,
+}
+
+/// Fetches the state of the network from NetworkManager.
+///
+/// Returns `None` if NetworkManager is not available.
+pub fn network_state() -> Option<NetworkState> {
+    let connection = Connection::new_system().ok()?;
+    let proxy = connection.with_proxy(NM_DEST, NM_PATH, Duration::from_secs(5));
+
+    let connectivity = proxy
+        .get::<u32>(NM_DEST, "Connectivity")
+        .map_err(|why| warn!("failed to get network connectivity: {}", why))
+        .ok()?;
+
+    // NM_METERED_YES and NM_METERED_GUESS_YES
+    let metered = proxy.get::<u32>(NM_DEST, "Metered").map_or(false, |metered| {
+        metered == 1 || metered == 3
+    });
+
+    Some(NetworkState { connectivity: Connectivity::from(connectivity), metered })
+}
+
+/// Determines if a download should be deferred, according to the network state and policy.
+pub(crate) fn defer_download(policy: MeteredPolicy) -> Option<DeferReason> {
+    network_state().and_then(|state| defer_reason(state, policy))
+}
+
+fn defer_reason(state: NetworkState, policy: MeteredPolicy) -> Option<DeferReason> {
+    match state.connectivity {
+        Connectivity::None | Connectivity::Portal | Connectivity::Limited => {
+            return Some(DeferReason::Offline)
+        }
+        Connectivity::Unknown | Connectivity::Full => (),
+    }
+
+    if !state.metered {
+        return None;
+    }
+
+    match policy {
+        MeteredPolicy::Always => None,
+        MeteredPolicy::Ask => Some(DeferReason::MeteredAsk),
+        MeteredPolicy::Never => Some(DeferReason::Metered),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defer_reason() {
+        let state = |connectivity, metered| NetworkState { connectivity, metered };
+
+        let offline = state(Connectivity::None, false);
+        assert_eq!(super::defer_reason(offline, MeteredPolicy::Always), Some(DeferReason::Offline));
+
+        let unknown = state(Connectivity::Unknown, false);
+        assert_eq!(super::defer_reason(unknown, MeteredPolicy::Never), None);
+
+        let metered = state(Connectivity::Full, true);
+        assert_eq!(super::defer_reason(metered, MeteredPolicy::Always), None);
+        assert_eq!(super::defer_reason(metered, MeteredPolicy::Ask), Some(DeferReason::MeteredAsk));
+        assert_eq!(super::defer_reason(metered, MeteredPolicy::Never), Some(DeferReason::Metered));
+
+        let unmetered = state(Connectivity::Full, false);
+        assert_eq!(super::defer_reason(unmetered, MeteredPolicy::Never), None);
+    }
+}
@@ -0,0 +1,131 @@
+//! A backend which detects NVMe drives.
+//!
+//! The firmware revision of each NVMe controller is read from sysfs, where the kernel exposes the
+//! identify data of the controller. Drives whose vendors publish firmware to the LVFS are listed
+//! and updated by the fwupd backend, so this backend only lists the drives which fwupd does not
+//! report, as version-only entries.
+
+use crate::{DeviceId, DeviceSignal, FirmwareBackend, FirmwareEvent, FirmwareInfo, FirmwareSignal};
+use std::{fs, path::Path, sync::mpsc::Sender};
+
+/// The name of the NVMe backend, which identifies its devices.
+const BACKEND: &str = "nvme";
+
+/// The name of the fwupd plugin which updates NVMe drives.
+const FWUPD_PLUGIN: &str = "nvme";
+
+/// Where the NVMe controllers of the system are described.
+const NVME_CONTROLLERS: &str = "/sys/class/nvme";
+
+/// An NVMe controller which is attached to the system.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Drive {
+    /// The model of the drive.
+    model: Box<str>,
+    /// The serial number of the drive.
+    serial: Box<str>,
+    /// The revision of the firmware which is active on the drive.
+    revision: Box<str>,
+    /// The name of the controller in sysfs, such as `nvme0`.
+    controller: Box<str>,
+}
+
+/// A firmware backend for NVMe drives.
+#[derive(Default)]
+pub struct NvmeBackend;
+
+impl FirmwareBackend for NvmeBackend {
+    fn name(&self) -> &str { BACKEND }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        let drives = drives(Path::new(NVME_CONTROLLERS));
+        if drives.is_empty() {
+            return;
+        }
+
+        let fwupd = crate::fwupd::reported_serials(|device| &*device.plugin == FWUPD_PLUGIN);
+
+        for drive in drives {
+            if reported_by_fwupd(&drive, fwupd.as_deref()) {
+                info!("{} is managed by fwupd", drive.model);
+                continue;
+            }
+
+            let _res = sender.send(FirmwareSignal::Device(DeviceSignal {
+                device: DeviceId { backend: BACKEND.into(), id: drive.controller },
+                info: FirmwareInfo {
+                    name: drive.model,
+                    current: drive.revision,
+                    latest: None,
+                    install_duration: 0,
+                    policy: None,
+                },
+                read_only: true,
+            }));
+        }
+    }
+
+    /// NVMe drives are only updated through fwupd.
+    fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+        None
+    }
+}
+
+/// The NVMe drives which are described in the controllers directory.
+fn drives(controllers: &Path) -> Vec<Drive> {
+    let entries = match fs::read_dir(controllers) {
+        Ok(entries) => entries,
+        // Systems without NVMe drives may not have the class at all.
+        Err(_) => return Vec::new(),
+    };
+
+    entries.filter_map(Result::ok).filter_map(|entry| drive(&entry.path())).collect()
+}
+
+/// Reads the identify data of an NVMe controller.
+fn drive(controller: &Path) -> Option<Drive> {
+    let attribute = |name: &str| -> Option<Box<str>> {
+        let value = fs::read_to_string(controller.join(name)).ok()?;
+        Some(identify_string(&value).into())
+    };
+
+    Some(Drive {
+        model: attribute("model")?,
+        serial: attribute("serial")?,
+        revision: attribute("firmware_rev")?,
+        controller: controller.file_name()?.to_string_lossy().into(),
+    })
+}
+
+/// Identify data strings are padded with spaces to their fixed width, which sysfs keeps.
+fn identify_string(value: &str) -> &str { value.trim() }
+
+/// Checks if fwupd reports the drive, in which case the fwupd backend lists it.
+fn reported_by_fwupd(drive: &Drive, fwupd: Option<&[Option<Box<str>>]>) -> bool {
+    let serials = match fwupd {
+        Some(serials) => serials,
+        None => return false,
+    };
+
+    serials.iter().flatten().any(|serial| identify_string(serial) == &*drive.serial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Drive;
+
+    #[test]
+    fn reported_by_fwupd() {
+        let drive = Drive {
+            model: "Samsung SSD 980 PRO 1TB".into(),
+            serial: "S5GXNX0R123456".into(),
+            revision: "5B2QGXA7".into(),
+            controller: "nvme0".into(),
+        };
+
+        assert!(!super::reported_by_fwupd(&drive, None));
+        assert!(!super::reported_by_fwupd(&drive, Some(&[None])));
+        assert!(!super::reported_by_fwupd(&drive, Some(&[Some("S5GXNX0R654321".into())])));
+        assert!(super::reported_by_fwupd(&drive, Some(&[Some("S5GXNX0R123456  ".into())])));
+    }
+}
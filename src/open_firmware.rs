@@ -0,0 +1,218 @@
+//! System76 Open Firmware, and the transition to it from proprietary firmware.
+//!
+//! Systems which run System76 Open Firmware are based on coreboot, and are updated by the
+//! system76-firmware daemon as systems with proprietary firmware are. The changelog which the
+//! daemon provides only summarizes each release, so the notes of open firmware releases are taken
+//! from the changelog that is published with its source, which lists each change that a release
+//! made.
+//!
+//! Models which shipped with proprietary firmware may be moved to open firmware where it is
+//! published to the LVFS, by switching their system firmware to the [`OPEN_FIRMWARE_BRANCH`].
+
+use crate::{download, System76Changelog};
+use fwupd_dbus::Device as FwupdDevice;
+use std::fs;
+
+/// The fwupd branch which System76 Open Firmware is published to.
+pub const OPEN_FIRMWARE_BRANCH: &str = "coreboot";
+
+/// Where the kernel exposes the vendor of the system firmware.
+const BIOS_VENDOR: &str = "/sys/class/dmi/id/bios_vendor";
+
+/// The vendor which System76 Open Firmware reports.
+const COREBOOT: &str = "coreboot";
+
+/// The changelog of System76 Open Firmware, in Markdown.
+const OPEN_FIRMWARE_CHANGELOG: &str =
+    "https://raw.githubusercontent.com/system76/firmware-open/master/CHANGELOG.md";
+
+/// Why system firmware can not be moved from proprietary firmware to open firmware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransitionBlocker {
+    /// The system already runs open firmware.
+    AlreadyOpen,
+    /// Open firmware is not published for the model, or the device is not system firmware.
+    Unsupported,
+}
+
+/// A release of open firmware, as described by its changelog.
+#[derive(Debug, PartialEq, Eq)]
+struct Release {
+    /// The date of the release, which begins the versions of its firmware.
+    date: Box<str>,
+    /// Each change that the release made.
+    changes: Vec<Box<str>>,
+}
+
+/// Checks if the system runs System76 Open Firmware.
+pub(crate) fn is_open_firmware() -> bool {
+    fs::read_to_string(BIOS_VENDOR).map_or(false, |vendor| vendor.trim() == COREBOOT)
+}
+
+/// Checks if the system firmware may be moved to open firmware, given the other branches that
+/// it may be switched to.
+///
+/// Frontends should only offer the transition if it may be made, and must have the user confirm
+/// the [`branch_warnings`](crate::branch_warnings) of the device before switching it to the
+/// [`OPEN_FIRMWARE_BRANCH`].
+pub fn open_firmware_transition(
+    device: &FwupdDevice,
+    branches: &[Box<str>],
+) -> Result<(), TransitionBlocker> {
+    transition(device.needs_reboot(), is_open_firmware(), branches)
+}
+
+fn transition(
+    system_firmware: bool,
+    open_firmware: bool,
+    branches: &[Box<str>],
+) -> Result<(), TransitionBlocker> {
+    if open_firmware {
+        Err(TransitionBlocker::AlreadyOpen)
+    } else if !system_firmware || !branches.iter().any(|branch| &**branch == OPEN_FIRMWARE_BRANCH) {
+        Err(TransitionBlocker::Unsupported)
+    } else {
+        Ok(())
+    }
+}
+
+/// Replaces the summary of each release in the changelog from the daemon with the changes that
+/// the changelog of open firmware lists for it.
+///
+/// The summaries are kept if the changelog of open firmware can not be fetched.
+pub(crate) fn add_release_notes(changelog: &mut System76Changelog) {
+    match download::fetch_text(OPEN_FIRMWARE_CHANGELOG) {
+        Ok(notes) => merge(changelog, &parse(&notes)),
+        Err(why) => {
+            warn!("failed to fetch open firmware changelog: {}", crate::format_error(why))
+        }
+    }
+}
+
+/// Describes each version of the changelog with the changes of the release that it belongs to.
+fn merge(changelog: &mut System76Changelog, releases: &[Release]) {
+    for version in &mut changelog.versions {
+        if let Some(notes) = release_notes(&version.bios, releases) {
+            version.description = notes.into();
+        }
+    }
+}
+
+/// The changes of the release which a version of open firmware belongs to, rendered as the HTML
+/// which changelogs are described with.
+///
+/// Versions of open firmware begin with the date of their release, as in `2023-08-17_6b3c1ba`.
+fn release_notes(version: &str, releases: &[Release]) -> Option<String> {
+    let date = version.split('_').next()?;
+    let release = releases.iter().find(|release| &*release.date == date)?;
+    Some(render(&release.changes)).filter(|_| !release.changes.is_empty())
+}
+
+/// Parses the releases of a changelog, whose headings name releases by their dates, and whose
+/// list items are the changes of each release.
+fn parse(changelog: &str) -> Vec<Release> {
+    let mut releases = Vec::<Release>::new();
+
+    for line in changelog.lines() {
+        let line = line.trim();
+
+        if let Some(heading) = line.strip_prefix("## ") {
+            let date = heading.trim().trim_start_matches('[').trim_end_matches(']');
+            releases.push(Release { date: date.into(), changes: Vec::new() });
+            continue;
+        }
+
+        let release = match releases.last_mut() {
+            Some(release) => release,
+            None => continue,
+        };
+
+        if let Some(change) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+            release.changes.push(change.trim().into());
+        } else if !line.is_empty() {
+            // Changes which are wrapped across several lines continue the last change.
+            if let Some(last) = release.changes.last_mut() {
+                *last = [last, " ", line].concat().into();
+            }
+        }
+    }
+
+    releases
+}
+
+/// Renders the changes of a release as the HTML list which changelogs are described with.
+fn render(changes: &[Box<str>]) -> String {
+    let mut html = String::from("<ul>");
+    for change in changes {
+        html.push_str("<li>");
+        html.push_str(&escape(change));
+        html.push_str("</li>");
+    }
+
+    html.push_str("</ul>");
+    html
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Release, TransitionBlocker};
+
+    const CHANGELOG: &str = "# Changelog\n\
+                             \n\
+                             ## unreleased\n\
+                             \n\
+                             ## 2023-08-17\n\
+                             \n\
+                             - Updated coreboot to 4.20\n\
+                             - Fixed the keyboard backlight <after>\n  \
+                               resuming from suspend\n\
+                             \n\
+                             ## 2023-03-01\n\
+                             \n\
+                             * Added support for darp9\n";
+
+    #[test]
+    fn parse() {
+        let expected = [
+            Release { date: "unreleased".into(), changes: Vec::new() },
+            Release {
+                date: "2023-08-17".into(),
+                changes: vec![
+                    "Updated coreboot to 4.20".into(),
+                    "Fixed the keyboard backlight <after> resuming from suspend".into(),
+                ],
+            },
+            Release { date: "2023-03-01".into(), changes: vec!["Added support for darp9".into()] },
+        ];
+
+        assert_eq!(super::parse(CHANGELOG), expected);
+    }
+
+    #[test]
+    fn release_notes() {
+        let releases = super::parse(CHANGELOG);
+
+        let expected = "<ul>\
+                        <li>Updated coreboot to 4.20</li>\
+                        <li>Fixed the keyboard backlight &lt;after&gt; resuming from suspend</li>\
+                        </ul>";
+        let notes = super::release_notes("2023-08-17_6b3c1ba", &releases);
+        assert_eq!(notes.as_deref(), Some(expected));
+
+        assert_eq!(super::release_notes("unreleased", &releases), None);
+        assert_eq!(super::release_notes("2022-11-30_5ed7f9c", &releases), None);
+    }
+
+    #[test]
+    fn transition() {
+        let branches = ["coreboot".into()];
+        assert_eq!(super::transition(true, false, &branches), Ok(()));
+        assert_eq!(super::transition(true, true, &[]), Err(TransitionBlocker::AlreadyOpen));
+        assert_eq!(super::transition(true, false, &[]), Err(TransitionBlocker::Unsupported));
+        assert_eq!(super::transition(false, false, &branches), Err(TransitionBlocker::Unsupported));
+    }
+}
@@ -0,0 +1,421 @@
+//! Backends which are loaded from shared objects, so that vendors may add support for their
+//! devices without recompiling the firmware manager.
+//!
+//! Every `.so` file in [`PLUGINS_DIR`] is opened when the event loop starts. A plugin exports a
+//! `firmware_manager_plugin` function, which returns a pointer to a static [`Plugin`] describing
+//! the plugin. Plugins which were built for another [`PLUGIN_ABI_VERSION`] are not loaded. In C,
+//! the interface of version 1 is:
+//!
+//! ```c
+//! struct fm_device {
+//!     const char *id;           // unique within the plugin
+//!     const char *name;
+//!     const char *current;
+//!     const char *latest;       // NULL if no update is available
+//!     uint32_t install_duration; // in seconds
+//! };
+//!
+//! struct fm_plugin {
+//!     uint32_t abi_version;     // 1
+//!     const char *name;
+//!     void (*scan)(void (*found)(void *context, const struct fm_device *), void *context);
+//!     // Returns zero on success, or writes a message to `error` and returns an error code.
+//!     int32_t (*update)(const char *id, char *error, size_t error_len);
+//! };
+//!
+//! const struct fm_plugin *firmware_manager_plugin(void);
+//! ```
+//!
+//! The strings of a device only need to live until the `found` callback returns. Both functions
+//! may be called from several threads at once. Plugins run within the firmware manager, so they
+//! are only loaded when they are owned by root and can not be modified by other users.
+//!
+//! Devices are only updated after the plugin found them in the last scan, and their updates are
+//! subject to the firmware policy, journal, and update history as those of built-in backends.
+//! Plugins may not take the name of a built-in backend, as the devices of each backend are
+//! identified by its name.
+
+use crate::{
+    backend, DeviceId, DeviceSignal, Entity, FirmwareBackend, FirmwareError, FirmwareEvent,
+    FirmwareInfo, FirmwareSignal,
+};
+use libloading::{Library, Symbol};
+use std::{
+    collections::BTreeMap,
+    ffi::{c_char, c_void, CStr, CString, OsStr},
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{mpsc::Sender, Mutex, MutexGuard, PoisonError},
+};
+
+/// The version of the plugin interface which this build of the firmware manager implements.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// The directory where plugins are installed.
+pub const PLUGINS_DIR: &str = "/usr/lib/firmware-manager/plugins";
+
+/// The function which every plugin exports.
+const PLUGIN_ENTRY: &[u8] = b"firmware_manager_plugin\0";
+
+/// The size of the buffer that a plugin may write the reason for a failed update to.
+const ERROR_LEN: usize = 512;
+
+/// The names of the built-in backends, which plugins may not take.
+const RESERVED_NAMES: &[&str] = &[
+    "capsule",
+    "flashrom",
+    "fwupd",
+    "gpu",
+    "launch",
+    "lvfs",
+    "mei",
+    "microcode",
+    "mock",
+    "nvme",
+    "power",
+    "smbios",
+    "system76",
+    "wireless",
+];
+
+/// An error that may occur when loading or using a plugin.
+#[derive(Debug, Error)]
+pub enum PluginError {
+    /// The shared object could not be opened.
+    #[error("failed to load plugin at {}", _0.display())]
+    Load(PathBuf, #[source] libloading::Error),
+    /// The shared object does not export the plugin entry point.
+    #[error("{} is not a firmware manager plugin", _0.display())]
+    Entry(PathBuf, #[source] libloading::Error),
+    /// The plugin implements a different version of the plugin interface.
+    #[error("{} implements plugin ABI {}, rather than {}", _0.display(), _1, PLUGIN_ABI_VERSION)]
+    Abi(PathBuf, u32),
+    /// The plugin did not describe itself.
+    #[error("{} did not provide a plugin description", _0.display())]
+    Invalid(PathBuf),
+    /// The plugin took the name of a built-in backend.
+    #[error("{} takes the name of the built-in {} backend", _0.display(), _1)]
+    Reserved(PathBuf, Box<str>),
+    /// The plugin may be modified by users other than root.
+    #[error("{} is not owned by root, or is writable by other users", _0.display())]
+    Untrusted(PathBuf),
+    /// An update was requested for a device which the plugin did not find.
+    #[error("plugin did not find a device with the ID {}", _0)]
+    NotFound(Box<str>),
+    /// The plugin failed to update a device.
+    #[error("plugin failed with error code {}: {}", _0, _1)]
+    Update(i32, Box<str>),
+}
+
+/// A device which a plugin found, as described by the plugin.
+#[repr(C)]
+pub struct PluginDevice {
+    /// An identifier of the device, which is unique within the plugin.
+    pub id: *const c_char,
+    /// The name of the device.
+    pub name: *const c_char,
+    /// The version of the installed firmware.
+    pub current: *const c_char,
+    /// The version of the latest firmware, or null if there is no update.
+    pub latest: *const c_char,
+    /// The estimated time to install the update, in seconds.
+    pub install_duration: u32,
+}
+
+/// The callback which a plugin calls for each device that it finds while scanning.
+pub type PluginFound = extern "C" fn(context: *mut c_void, device: *const PluginDevice);
+
+/// Describes a plugin, and the functions which it implements.
+#[repr(C)]
+pub struct Plugin {
+    /// The version of the plugin interface which the plugin implements.
+    pub abi_version: u32,
+    /// The name of the plugin, which identifies its devices.
+    pub name: *const c_char,
+    /// Scans for devices, calling `found` with the `context` for each device.
+    pub scan: extern "C" fn(found: PluginFound, context: *mut c_void),
+    /// Updates the device with the given ID to its latest firmware.
+    ///
+    /// Zero is returned on success. Otherwise, a nul-terminated reason is written to the error
+    /// buffer of the given length.
+    pub update: extern "C" fn(id: *const c_char, error: *mut c_char, error_len: usize) -> i32,
+}
+
+/// A firmware backend which is implemented by a plugin.
+pub struct PluginBackend {
+    name: Box<str>,
+    plugin: *const Plugin,
+    /// The devices found by the last scan, by their IDs.
+    scanned: Mutex<BTreeMap<Box<str>, FirmwareInfo>>,
+    // The library must outlive the plugin, which points into it.
+    _library: Library,
+}
+
+// Plugins are required to be callable from several threads at once.
+unsafe impl Send for PluginBackend {}
+unsafe impl Sync for PluginBackend {}
+
+impl PluginBackend {
+    /// Loads the plugin from the shared object at `path`.
+    pub fn load(path: &Path) -> Result<Self, PluginError> {
+        let trusted = match fs::metadata(path) {
+            Ok(metadata) => trusted(metadata.uid(), metadata.mode()),
+            Err(_) => false,
+        };
+
+        if !trusted {
+            return Err(PluginError::Untrusted(path.into()));
+        }
+
+        // Safety: the plugin is trusted as much as the firmware manager, as only root may
+        // install it, and it is described by the versioned ABI of this module.
+        unsafe {
+            let library = Library::new(path).map_err(|why| PluginError::Load(path.into(), why))?;
+
+            let entry: Symbol<extern "C" fn() -> *const Plugin> =
+                library.get(PLUGIN_ENTRY).map_err(|why| PluginError::Entry(path.into(), why))?;
+
+            let plugin = entry();
+            let name = describe(plugin.as_ref(), path)?;
+
+            Ok(Self { name, plugin, scanned: Mutex::default(), _library: library })
+        }
+    }
+
+    fn plugin(&self) -> &Plugin {
+        // Safety: the pointer was checked when loading, and the library is kept loaded.
+        unsafe { &*self.plugin }
+    }
+
+    fn scanned(&self) -> MutexGuard<'_, BTreeMap<Box<str>, FirmwareInfo>> {
+        self.scanned.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl FirmwareBackend for PluginBackend {
+    fn name(&self) -> &str { &self.name }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        let mut found = Found { backend: &self.name, devices: Vec::new() };
+        (self.plugin().scan)(found_device, &mut found as *mut Found as *mut c_void);
+
+        let mut scanned = BTreeMap::new();
+        for signal in found.devices {
+            scanned.insert(signal.device.id.clone(), signal.info.clone());
+            let _res = sender.send(FirmwareSignal::Device(signal));
+        }
+
+        *self.scanned() = scanned;
+    }
+
+    fn update(
+        &self,
+        event: &FirmwareEvent,
+        sender: &Sender<FirmwareSignal>,
+    ) -> Option<FirmwareSignal> {
+        let (entity, id, cancellable) = match event {
+            FirmwareEvent::Device(entity, id, cancellable) if id.backend == self.name => {
+                (*entity, id, cancellable)
+            }
+            _ => return None,
+        };
+
+        if cancellable.is_cancelled() {
+            return Some(FirmwareSignal::Cancelled(entity));
+        }
+
+        let error = |why: Box<dyn std::error::Error + Send + Sync>| {
+            let why = FirmwareError::Backend(self.name.clone(), why);
+            Some(FirmwareSignal::Error(Some(entity), why))
+        };
+
+        let info = match self.scanned().get(&id.id) {
+            Some(info) => info.clone(),
+            None => return error(Box::new(PluginError::NotFound(id.id.clone()))),
+        };
+
+        let c_id = match CString::new(&*id.id) {
+            Ok(id) => id,
+            Err(why) => return error(Box::new(why)),
+        };
+
+        Some(backend::flash_device(entity, &info, &[&id.id], sender, || self.flash(entity, &c_id)))
+    }
+}
+
+impl PluginBackend {
+    /// Asks the plugin to flash the device with the given ID.
+    fn flash(&self, entity: Entity, id: &CStr) -> FirmwareSignal {
+        let mut error = vec![0 as c_char; ERROR_LEN];
+        let span = info_span!("flash", plugin = &*self.name, device_id = %id.to_string_lossy());
+        let code =
+            span.in_scope(|| (self.plugin().update)(id.as_ptr(), error.as_mut_ptr(), ERROR_LEN));
+
+        if code == 0 {
+            return FirmwareSignal::DeviceUpdated(entity);
+        }
+
+        // The reason is terminated at the end of the buffer, in case the plugin did not.
+        error[ERROR_LEN - 1] = 0;
+        // Safety: the buffer is nul-terminated.
+        let reason = unsafe { CStr::from_ptr(error.as_ptr()) }.to_string_lossy();
+        let why = PluginError::Update(code, reason.into());
+        let why = FirmwareError::Backend(self.name.clone(), Box::new(why));
+        FirmwareSignal::Error(Some(entity), why)
+    }
+}
+
+/// The devices which a plugin found during a scan.
+struct Found<'a> {
+    backend: &'a str,
+    devices: Vec<DeviceSignal>,
+}
+
+/// Collects a device which a plugin found, ignoring devices which are missing required fields.
+extern "C" fn found_device(context: *mut c_void, device: *const PluginDevice) {
+    // Safety: the context is the `Found` passed to the scan, and the device is valid until the
+    // callback returns.
+    let (found, device) = unsafe {
+        match (context.cast::<Found>().as_mut(), device.as_ref()) {
+            (Some(found), Some(device)) => (found, device),
+            _ => return,
+        }
+    };
+
+    let string = |ptr: *const c_char| -> Option<Box<str>> {
+        // Safety: the plugin ABI requires strings to be nul-terminated or null.
+        (!ptr.is_null()).then(|| unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into())
+    };
+
+    let (id, name, current) = match (string(device.id), string(device.name), string(device.current))
+    {
+        (Some(id), Some(name), Some(current)) => (id, name, current),
+        _ => {
+            warn!("{} plugin found a device without an ID, name, or version", found.backend);
+            return;
+        }
+    };
+
+    found.devices.push(DeviceSignal {
+        device: DeviceId { backend: found.backend.into(), id },
+        info: FirmwareInfo {
+            name,
+            current,
+            latest: string(device.latest),
+            install_duration: device.install_duration,
+            policy: None,
+        },
+        read_only: false,
+    });
+}
+
+/// Checks the description of a plugin, returning its name.
+fn describe(plugin: Option<&Plugin>, path: &Path) -> Result<Box<str>, PluginError> {
+    let plugin = plugin.ok_or_else(|| PluginError::Invalid(path.into()))?;
+
+    if plugin.abi_version != PLUGIN_ABI_VERSION {
+        return Err(PluginError::Abi(path.into(), plugin.abi_version));
+    }
+
+    if plugin.name.is_null() {
+        return Err(PluginError::Invalid(path.into()));
+    }
+
+    // Safety: the plugin ABI requires the name to be a nul-terminated string.
+    let name: Box<str> = unsafe { CStr::from_ptr(plugin.name) }.to_string_lossy().into();
+
+    if RESERVED_NAMES.contains(&&*name) {
+        return Err(PluginError::Reserved(path.into(), name));
+    }
+
+    Ok(name)
+}
+
+/// Plugins must be owned by root, and must not be writable by its group or by other users.
+fn trusted(uid: u32, mode: u32) -> bool { uid == 0 && mode & 0o022 == 0 }
+
+/// Loads every plugin in the plugins directory, logging those which could not be loaded.
+pub(crate) fn load_all(directory: &Path) -> Vec<PluginBackend> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        // The directory only exists when plugins have been installed.
+        Err(_) => return Vec::new(),
+    };
+
+    let mut paths = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension() == Some(OsStr::new("so")))
+        .collect::<Vec<_>>();
+
+    // Plugins are registered in a consistent order.
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| match PluginBackend::load(&path) {
+            Ok(backend) => Some(backend),
+            Err(why) => {
+                warn!("{}", crate::format_error(why));
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn scan(found: PluginFound, context: *mut c_void) {
+        let device = PluginDevice {
+            id: b"dev0\0".as_ptr().cast(),
+            name: b"Widget\0".as_ptr().cast(),
+            current: b"1.0\0".as_ptr().cast(),
+            latest: std::ptr::null(),
+            install_duration: 5,
+        };
+
+        found(context, &device);
+        found(context, &PluginDevice { id: std::ptr::null(), ..device });
+    }
+
+    extern "C" fn update(_: *const c_char, _: *mut c_char, _: usize) -> i32 { 0 }
+
+    #[test]
+    fn describe() {
+        let path = Path::new("/usr/lib/firmware-manager/plugins/widget.so");
+        let name = b"widget\0".as_ptr().cast();
+        let mut plugin = Plugin { abi_version: PLUGIN_ABI_VERSION, name, scan, update };
+
+        assert_eq!(super::describe(Some(&plugin), path).unwrap(), "widget".into());
+        assert!(super::describe(None, path).is_err());
+
+        plugin.name = b"fwupd\0".as_ptr().cast();
+        assert!(matches!(super::describe(Some(&plugin), path), Err(PluginError::Reserved(_, _))));
+
+        plugin.name = name;
+        plugin.abi_version = PLUGIN_ABI_VERSION + 1;
+        assert!(matches!(super::describe(Some(&plugin), path), Err(PluginError::Abi(_, _))));
+    }
+
+    #[test]
+    fn found_device() {
+        let mut found = Found { backend: "widget", devices: Vec::new() };
+        scan(super::found_device, &mut found as *mut Found as *mut c_void);
+
+        assert_eq!(found.devices.len(), 1);
+        let id = DeviceId { backend: "widget".into(), id: "dev0".into() };
+        assert_eq!(found.devices[0].device, id);
+        assert_eq!(found.devices[0].info.latest, None);
+    }
+
+    #[test]
+    fn trusted() {
+        assert!(super::trusted(0, 0o100755));
+        assert!(!super::trusted(1000, 0o100755));
+        assert!(!super::trusted(0, 0o100775));
+        assert!(!super::trusted(0, 0o100757));
+    }
+}
@@ -0,0 +1,152 @@
+//! An allowlist of firmware which an administrator has approved for installation.
+//!
+//! When `/etc/firmware-manager/approved.toml` exists, only the firmware versions that it lists
+//! may be installed:
+//!
+//! ```toml
+//! [[approved]]
+//! device = "Thelio I/O"
+//! versions = ["1.2.3"]
+//! ```
+//!
+//! Devices are matched by name, ID, or GUID, as with the `ignored_devices` of the configuration.
+//! Releases which are not approved are still reported, along with the reason that they may not be
+//! installed, so that frontends may explain why an update is unavailable.
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::{fs, io, path::Path};
+
+/// The location of the policy file, which is managed by the administrator.
+const POLICY_PATH: &str = "/etc/firmware-manager/approved.toml";
+
+static POLICY: Lazy<Option<FirmwarePolicy>> = Lazy::new(|| {
+    FirmwarePolicy::load(Path::new(POLICY_PATH)).unwrap_or_else(|why| {
+        // An invalid policy approves nothing, rather than everything.
+        error!("invalid firmware policy: {}", crate::format_error(why));
+        Some(FirmwarePolicy::default())
+    })
+});
+
+/// An error that may occur when loading the policy file.
+#[derive(Debug, Error)]
+pub enum PolicyError {
+    /// The policy file could not be read.
+    #[error("failed to read the firmware policy")]
+    Read(#[source] io::Error),
+    /// The policy file is not valid.
+    #[error("failed to parse the firmware policy")]
+    Parse(#[source] toml::de::Error),
+}
+
+/// The reason that the firmware policy forbids a release from being installed.
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+pub enum PolicyReason {
+    /// No firmware is approved for the device.
+    #[error("no firmware is approved for this device by the administrator")]
+    DeviceNotApproved,
+    /// Other versions of the firmware are approved for the device, but not this one.
+    #[error("this firmware version is not approved by the administrator")]
+    VersionNotApproved,
+}
+
+/// The firmware which an administrator has approved for installation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct FirmwarePolicy {
+    /// Every device which may be updated, with the versions that it may be updated to.
+    pub approved: Vec<Approval>,
+}
+
+/// The versions of firmware which a device may be updated to.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Approval {
+    /// The name, ID, or GUID of the device.
+    pub device: Box<str>,
+    /// The versions which may be installed.
+    pub versions: Vec<Box<str>>,
+}
+
+impl FirmwarePolicy {
+    /// Loads the policy file, or `None` if it does not exist.
+    pub fn load(path: &Path) -> Result<Option<Self>, PolicyError> {
+        match fs::read_to_string(path) {
+            Ok(policy) => {
+                info!("loading firmware policy from {}", path.display());
+                Self::parse(&policy).map(Some)
+            }
+            Err(why) if why.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(why) => Err(PolicyError::Read(why)),
+        }
+    }
+
+    /// Parses the contents of a policy file.
+    pub fn parse(policy: &str) -> Result<Self, PolicyError> {
+        toml::from_str(policy).map_err(PolicyError::Parse)
+    }
+
+    /// Checks if the device with the given name, or any of the given IDs or GUIDs, may be updated
+    /// to the `version`.
+    pub fn check(&self, name: &str, ids: &[&str], version: &str) -> Result<(), PolicyReason> {
+        let mut approvals = self
+            .approved
+            .iter()
+            .filter(|approval| {
+                approval.device.eq_ignore_ascii_case(name)
+                    || ids.iter().any(|id| id.eq_ignore_ascii_case(&approval.device))
+            })
+            .peekable();
+
+        if approvals.peek().is_none() {
+            return Err(PolicyReason::DeviceNotApproved);
+        }
+
+        if approvals.any(|approval| approval.versions.iter().any(|approved| &**approved == version))
+        {
+            Ok(())
+        } else {
+            Err(PolicyReason::VersionNotApproved)
+        }
+    }
+}
+
+/// The firmware policy of the system, if the administrator has set one.
+pub fn firmware_policy() -> Option<&'static FirmwarePolicy> { POLICY.as_ref() }
+
+/// Checks if the firmware policy of the system allows the device with the given name, or any of
+/// the given IDs or GUIDs, to be updated to the `version`.
+///
+/// Every update is allowed if the administrator has not set a policy. Backends other than fwupd
+/// and System76 should use this to refuse updates which are not approved.
+pub fn check_policy(name: &str, ids: &[&str], version: &str) -> Result<(), PolicyReason> {
+    firmware_policy().map_or(Ok(()), |policy| policy.check(name, ids, version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check() {
+        let policy = FirmwarePolicy::parse(
+            "[[approved]]\n\
+             device = \"Thelio I/O\"\n\
+             versions = [\"1.2.3\"]\n\
+             \n\
+             [[approved]]\n\
+             device = \"6a3b2c1d\"\n\
+             versions = [\"0.9\", \"1.0\"]\n",
+        )
+        .unwrap();
+
+        assert_eq!(policy.check("thelio i/o", &[], "1.2.3"), Ok(()));
+        assert_eq!(policy.check("Thelio I/O", &[], "1.2.4"), Err(PolicyReason::VersionNotApproved));
+        assert_eq!(policy.check("Fingerprint Reader", &["6A3B2C1D"], "1.0"), Ok(()));
+        assert_eq!(
+            policy.check("Fingerprint Reader", &["7b4c3d2e"], "1.0"),
+            Err(PolicyReason::DeviceNotApproved)
+        );
+
+        assert!(FirmwarePolicy::parse("[[approved]]\ndevice = \"Thelio I/O\"\n").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! A backend which reports the firmware of batteries and USB-C power delivery controllers.
+//!
+//! Faults in charging are often caused by the firmware of the battery or of the power delivery
+//! controller, so their versions are reported alongside the rest of the system's firmware, to be
+//! included when users report them. fwupd describes these devices even when it can not update
+//! them, in which case the fwupd backend does not report them. The embedded controller of
+//! Chromebooks describes its power delivery controller in sysfs.
+
+use crate::{DeviceId, DeviceSignal, FirmwareBackend, FirmwareEvent, FirmwareInfo, FirmwareSignal};
+use fwupd_dbus::{Client as FwupdClient, Device as FwupdDevice};
+use std::{fs, sync::mpsc::Sender};
+
+/// The name of the power backend, which identifies its devices.
+const BACKEND: &str = "power";
+
+/// Where the embedded controller of Chromebooks describes the firmware of its power delivery
+/// controller.
+const CROS_PD_VERSION: &str = "/sys/class/chromeos/cros_pd/version";
+
+/// The fwupd plugins which manage USB-C power delivery controllers.
+const PD_PLUGINS: &[&str] = &["ccgx", "ccgx_dmc", "ti_tps6598x"];
+
+/// The icon which fwupd gives to batteries.
+const BATTERY_ICON: &str = "battery";
+
+/// Where the kernel describes the power supplies of the system.
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+/// A firmware backend which reports the firmware of batteries and power delivery controllers.
+#[derive(Default)]
+pub struct PowerBackend;
+
+impl FirmwareBackend for PowerBackend {
+    fn name(&self) -> &str { BACKEND }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        let mut devices = fwupd_power_devices();
+
+        if let Some(version) = fs::read_to_string(CROS_PD_VERSION).ok().and_then(cros_pd_version) {
+            devices.push(("cros-pd".into(), "USB-C Power Delivery Controller".into(), version));
+        }
+
+        for (id, name, version) in devices {
+            let _res = sender.send(FirmwareSignal::Device(DeviceSignal {
+                device: DeviceId { backend: BACKEND.into(), id },
+                info: FirmwareInfo {
+                    name,
+                    current: version,
+                    latest: None,
+                    install_duration: 0,
+                    policy: None,
+                },
+                read_only: true,
+            }));
+        }
+    }
+
+    /// Firmware which fwupd is able to update is reported by the fwupd backend instead.
+    fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+        None
+    }
+}
+
+/// The batteries and power delivery controllers which fwupd describes but can not update.
+fn fwupd_power_devices() -> Vec<(Box<str>, Box<str>, Box<str>)> {
+    let devices = match FwupdClient::new().and_then(|client| client.devices()) {
+        Ok(devices) => devices,
+        Err(why) => {
+            debug!("fwupd is not available to describe power devices: {}", why);
+            return Vec::new();
+        }
+    };
+
+    devices
+        .into_iter()
+        .filter(|device| !device.is_supported() && is_power_device(device))
+        .filter(|device| !device.version.is_empty())
+        .map(|device| (device.device_id, device.name, device.version))
+        .collect()
+}
+
+/// Whether fwupd describes the device as a battery or a power delivery controller.
+/// Whether the system is running on battery, as when it has a battery, and no external power
+/// supply is online. Without any power supplies, it is assumed that it is not.
+pub(crate) fn on_battery() -> bool {
+    let entries = match fs::read_dir(POWER_SUPPLY) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    let (mut battery, mut external) = (false, false);
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let attribute = |name: &str| {
+            let value = fs::read_to_string(path.join(name)).unwrap_or_default();
+            value.trim().to_owned()
+        };
+
+        match &*attribute("type") {
+            "Battery" => battery = true,
+            "Mains" | "USB" => external |= attribute("online") == "1",
+            _ => (),
+        }
+    }
+
+    battery && !external
+}
+
+fn is_power_device(device: &FwupdDevice) -> bool {
+    PD_PLUGINS.contains(&&*device.plugin) || device.icon.iter().any(|icon| &**icon == BATTERY_ICON)
+}
+
+/// Parses the version of the firmware copy which the power delivery controller is running.
+fn cros_pd_version(version: String) -> Option<Box<str>> {
+    let field = |key: &str| {
+        version.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some(value.trim()).filter(|value| name.trim() == key && !value.is_empty())
+        })
+    };
+
+    let copy = field("Firmware copy").unwrap_or("RW");
+    field(&[copy, " version"].concat()).map(Box::from)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn cros_pd_version() {
+        let version = "RO version:    samus_pd_v1.1.2017-a4b8a42\n\
+                       RW version:    samus_pd_v1.1.2144-3c1bb16\n\
+                       Firmware copy: RW\n\
+                       Build info:    samus_pd_v1.1.2144-3c1bb16 2014-10-14\n";
+
+        let expected = Some("samus_pd_v1.1.2144-3c1bb16".into());
+        assert_eq!(super::cros_pd_version(version.into()), expected);
+
+        let version = version.replace("Firmware copy: RW", "Firmware copy: RO");
+        assert_eq!(super::cros_pd_version(version), Some("samus_pd_v1.1.2017-a4b8a42".into()));
+
+        assert_eq!(super::cros_pd_version("Build info: x\n".into()), None);
+    }
+}
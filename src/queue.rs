@@ -0,0 +1,239 @@
+//! A queue of updates which are performed one at a time.
+//!
+//! Updates which are sent to the event loop directly are performed concurrently. Updates which
+//! are queued with [`FirmwareEvent::Enqueue`] are instead performed in the order that they were
+//! queued, each after the previous update has finished, whether it succeeded or not.
+//!
+//! [`FirmwareEvent::UpdateAll`] queues an [`UpdateBatch`], in which system firmware is last.
+
+use crate::{
+    CancellationToken, DeviceId, Entity, FirmwareEvent, FirmwareSignal, FwupdDevice, FwupdRelease,
+    MeteredPolicy, System76Digest,
+};
+use std::{
+    collections::VecDeque,
+    sync::{mpsc::Sender, Arc, Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+/// An update which is waiting in the queue, with the metered policy at the time it was queued.
+type Queued = (Entity, FirmwareEvent, MeteredPolicy);
+
+/// Updates which are waiting to be performed by the queue worker.
+#[derive(Default)]
+pub(crate) struct UpdateQueue {
+    state: Mutex<QueueState>,
+    condvar: Condvar,
+}
+
+#[derive(Default)]
+struct QueueState {
+    pending: VecDeque<Queued>,
+    stopped: bool,
+}
+
+impl UpdateQueue {
+    /// Adds updates to the back of the queue, and signals their positions in it.
+    ///
+    /// Events which do not update a device are not queued.
+    pub fn push(
+        &self,
+        events: Vec<FirmwareEvent>,
+        policy: MeteredPolicy,
+        sender: &Sender<FirmwareSignal>,
+    ) {
+        let mut state = self.state();
+
+        for event in events {
+            match event.entity() {
+                Some(entity) => {
+                    info!("queueing {:?}", event);
+                    let position = state.pending.len();
+                    state.pending.push_back((entity, event, policy));
+                    let _res = sender.send(FirmwareSignal::Queued(entity, position));
+                }
+                None => error!("{:?} is not an update, and can not be queued", event),
+            }
+        }
+
+        self.condvar.notify_one();
+    }
+
+    /// Blocks until an update is waiting, and takes it from the front of the queue. The updates
+    /// which remain are notified of their new positions.
+    ///
+    /// Returns `None` once the queue has been stopped.
+    pub fn pop(&self, sender: &Sender<FirmwareSignal>) -> Option<(FirmwareEvent, MeteredPolicy)> {
+        let mut state = self.state();
+
+        loop {
+            if state.stopped {
+                return None;
+            }
+
+            if let Some((_, event, policy)) = state.pending.pop_front() {
+                for (position, (entity, ..)) in state.pending.iter().enumerate() {
+                    let _res = sender.send(FirmwareSignal::Queued(*entity, position));
+                }
+
+                return Some((event, policy));
+            }
+
+            state = self.condvar.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Stops the queue worker once the update that it is performing has finished. Updates which
+    /// are still waiting are discarded.
+    pub fn stop(&self) {
+        let mut state = self.state();
+        if !state.pending.is_empty() {
+            info!("discarding {} queued updates", state.pending.len());
+        }
+
+        state.stopped = true;
+        state.pending.clear();
+        self.condvar.notify_all();
+    }
+
+    fn state(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The update which was found for a device, from which the requests to update it are built.
+#[derive(Clone, Debug)]
+pub enum UpdateRequest {
+    /// A device which was discovered by another backend.
+    Device(DeviceId),
+    /// A fwupd device, with the release that it is updated to.
+    Fwupd(Arc<FwupdDevice>, Arc<FwupdRelease>),
+    /// System76 system firmware, by the digest of its latest firmware.
+    S76System(System76Digest),
+    /// A Thelio I/O board, by its ID and the digest of its latest firmware.
+    ThelioIo(Box<str>, System76Digest),
+}
+
+impl UpdateRequest {
+    /// The request to update the device of the `entity`, which is cancelled with `cancellable`.
+    pub fn event(&self, entity: Entity, cancellable: CancellationToken) -> FirmwareEvent {
+        match self {
+            UpdateRequest::Device(device) => {
+                FirmwareEvent::Device(entity, device.clone(), cancellable)
+            }
+            UpdateRequest::Fwupd(device, release) => {
+                FirmwareEvent::Fwupd(entity, device.clone(), release.clone(), cancellable)
+            }
+            UpdateRequest::S76System(digest) => FirmwareEvent::S76System(entity, digest.clone()),
+            UpdateRequest::ThelioIo(board, digest) => {
+                FirmwareEvent::ThelioIo(entity, board.clone(), digest.clone(), cancellable)
+            }
+        }
+    }
+}
+
+/// The updates which are queued together by [`FirmwareEvent::UpdateAll`].
+///
+/// Frontends choose which devices are updated, and the batch decides which of them are included,
+/// and the order that they are updated in: system firmware is updated last, so that the other
+/// devices are updated before the system reboots to install it, or not at all if it was not
+/// requested.
+#[derive(Debug)]
+pub struct UpdateBatch {
+    system: bool,
+    events: Vec<FirmwareEvent>,
+    system_events: Vec<FirmwareEvent>,
+}
+
+impl UpdateBatch {
+    /// Creates an empty batch, which includes system firmware if `system` is set.
+    pub fn new(system: bool) -> Self {
+        Self { system, events: Vec::new(), system_events: Vec::new() }
+    }
+
+    /// Adds the update of the device of the `entity` to the batch, returning the token which
+    /// cancels it. Returns `None` if the update is not included in the batch.
+    pub fn push(&mut self, entity: Entity, request: &UpdateRequest) -> Option<CancellationToken> {
+        let cancellable = CancellationToken::default();
+        let event = request.event(entity, cancellable.clone());
+
+        if !event.is_system_firmware() {
+            self.events.push(event);
+        } else if self.system {
+            self.system_events.push(event);
+        } else {
+            info!("not including system firmware in the batch: {:?}", event);
+            return None;
+        }
+
+        Some(cancellable)
+    }
+
+    /// Whether no updates were added to the batch.
+    pub fn is_empty(&self) -> bool { self.events.is_empty() && self.system_events.is_empty() }
+
+    /// The updates of the batch, in the order that they are performed.
+    pub(crate) fn into_events(self) -> Vec<FirmwareEvent> {
+        let mut events = self.events;
+        events.extend(self.system_events);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CancellationToken, DeviceId, System76Digest};
+    use slotmap::SlotMap;
+    use std::sync::mpsc;
+
+    #[test]
+    fn update_queue() {
+        let mut entities = SlotMap::<Entity, ()>::new();
+        let (first, second) = (entities.insert(()), entities.insert(()));
+        let update = |entity| {
+            let id = DeviceId { backend: "mock".into(), id: "device".into() };
+            FirmwareEvent::Device(entity, id, CancellationToken::default())
+        };
+
+        let queue = UpdateQueue::default();
+        let (tx, rx) = mpsc::channel();
+
+        let events = vec![update(first), FirmwareEvent::Scan, update(second)];
+        queue.push(events, MeteredPolicy::default(), &tx);
+
+        let positions = rx.try_iter().collect::<Vec<_>>();
+        assert!(matches!(positions[..], [
+            FirmwareSignal::Queued(a, 0),
+            FirmwareSignal::Queued(b, 1),
+        ] if a == first && b == second));
+
+        let (event, _) = queue.pop(&tx).unwrap();
+        assert_eq!(event.entity(), Some(first));
+        assert!(matches!(rx.try_recv(), Ok(FirmwareSignal::Queued(entity, 0)) if entity == second));
+
+        queue.stop();
+        assert!(queue.pop(&tx).is_none());
+    }
+
+    #[test]
+    fn update_batch_system_last() {
+        let mut entities = SlotMap::<Entity, ()>::new();
+        let (system, device) = (entities.insert(()), entities.insert(()));
+        let batch = |include_system| {
+            let id = DeviceId { backend: "mock".into(), id: "device".into() };
+            let mut batch = UpdateBatch::new(include_system);
+            let system = batch.push(system, &UpdateRequest::S76System(System76Digest("d".into())));
+            assert_eq!(system.is_some(), include_system);
+            assert!(batch.push(device, &UpdateRequest::Device(id)).is_some());
+            batch.into_events().iter().filter_map(FirmwareEvent::entity).collect::<Vec<_>>()
+        };
+
+        assert_eq!(batch(true), [device, system]);
+        assert_eq!(batch(false), [device]);
+
+        let capsule = DeviceId { backend: "capsule".into(), id: "class".into() };
+        let mut batch = UpdateBatch::new(false);
+        assert!(batch.push(system, &UpdateRequest::Device(capsule)).is_none());
+        assert!(batch.is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+//! Creation of recovery drives, from which the system firmware may be restored.
+//!
+//! If system firmware is left unbootable by a failed flash, it may only be restored by booting a
+//! USB drive which reflashes it. The drive must therefore be prepared in advance, on the system
+//! that it is meant to recover, as the image is chosen by the model which this system reports.
+//!
+//! Recovery images are published alongside the signed System76 firmware archives, at the location
+//! configured by the distribution's package (see [`crate::signature`]). Each image has a SHA256
+//! checksum with a detached minisign signature, which must be made by a trusted key, and whose
+//! trusted comment must name the image, as `file:recovery/<model>.img`, so that the checksum of
+//! another model's image is refused. The image of this model is downloaded into the firmware
+//! cache, verified against the signed checksum, and then written to the selected drive.
+
+use crate::{
+    checksum,
+    download::{self, DownloadEvent},
+    signature::{self, SignatureError},
+    CancellationToken, DownloadError,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+/// The directory of recovery images, relative to the location of signed System76 firmware.
+const RECOVERY_IMAGES: &str = "recovery";
+
+/// Where the kernel describes the block devices of the system.
+const SYS_BLOCK: &str = "/sys/block";
+
+/// The vendor of systems which recovery images are published for.
+const SYSTEM76: &str = "System76";
+
+/// The size of the sectors that sysfs reports the sizes of block devices in.
+const SECTOR_SIZE: u64 = 512;
+
+/// The number of bytes which are written to the drive at a time.
+const WRITE_SIZE: usize = 4 * 1024 * 1024;
+
+/// A removable USB drive which a recovery image may be written to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsbDrive {
+    /// The path of the block device of the drive, such as `/dev/sdb`.
+    pub path: PathBuf,
+    /// The vendor and model of the drive.
+    pub name: Box<str>,
+    /// The capacity of the drive, in bytes.
+    pub size: u64,
+}
+
+/// The progress of the creation of a recovery drive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecoveryProgress {
+    /// The recovery image is being downloaded, with the bytes downloaded, and the total size.
+    Downloading(u64, u64),
+    /// The recovery image is being checked against its checksum.
+    Verifying,
+    /// The recovery image is being written, with the bytes written, and the total size.
+    Writing(u64, u64),
+    /// The recovery image was written to the drive, which may now be removed.
+    Created(UsbDrive),
+}
+
+/// Errors that may occur while creating a recovery drive.
+#[derive(Debug, Error)]
+pub enum RecoveryError {
+    /// The model of the system could not be identified.
+    #[error("failed to identify the model of the system")]
+    Model(#[source] io::Error),
+    /// Recovery images are not published for the model of the system.
+    #[error("recovery images are not published for {}", _0)]
+    Unsupported(Box<str>),
+    /// The recovery image or its checksum could not be downloaded.
+    #[error("failed to download the recovery image")]
+    Download(#[from] DownloadError),
+    /// The checksum of the recovery image was not signed by a trusted key.
+    #[error("the checksum of the recovery image is not trusted")]
+    Signature(#[from] SignatureError),
+    /// The checksum published for the recovery image is not a SHA256 digest.
+    #[error("the checksum of the recovery image is invalid")]
+    Checksum,
+    /// The downloaded recovery image does not match its checksum.
+    #[error("the recovery image at {} failed checksum verification", _0.display())]
+    Verification(PathBuf),
+    /// The recovery image is larger than the drive.
+    #[error("the recovery image needs {} bytes, but the drive only has {}", _0, _1)]
+    TooSmall(u64, u64),
+    /// The drive could not be opened, which fails if any of its partitions are mounted.
+    #[error("failed to open {} for writing", _0.display())]
+    Open(PathBuf, #[source] io::Error),
+    /// The recovery image could not be written to the drive.
+    #[error("failed to write the recovery image to {}", _0.display())]
+    Write(PathBuf, #[source] io::Error),
+    /// The creation of the recovery drive was cancelled.
+    #[error("the creation of the recovery drive was cancelled")]
+    Cancelled,
+}
+
+/// Lists the removable USB drives which are attached to the system.
+pub fn usb_drives() -> Vec<UsbDrive> {
+    let entries = match fs::read_dir(SYS_BLOCK) {
+        Ok(entries) => entries,
+        Err(why) => {
+            warn!("failed to list block devices: {}", why);
+            return Vec::new();
+        }
+    };
+
+    let mut drives: Vec<UsbDrive> =
+        entries.filter_map(Result::ok).filter_map(|entry| usb_drive(&entry.path())).collect();
+
+    drives.sort_by(|a, b| a.path.cmp(&b.path));
+    drives
+}
+
+/// Describes the block device at `sysfs`, if it is a removable USB drive with media in it.
+fn usb_drive(sysfs: &Path) -> Option<UsbDrive> {
+    let attribute =
+        |name: &str| fs::read_to_string(sysfs.join(name)).ok().map(|value| value.trim().to_owned());
+
+    let device = fs::canonicalize(sysfs.join("device")).ok()?;
+    if attribute("removable")? != "1" || !is_usb(&device) {
+        return None;
+    }
+
+    let size = attribute("size")?.parse::<u64>().ok()? * SECTOR_SIZE;
+    if size == 0 {
+        return None;
+    }
+
+    let name = [attribute("device/vendor"), attribute("device/model")]
+        .iter()
+        .flatten()
+        .filter(|field| !field.is_empty())
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Some(UsbDrive { path: Path::new("/dev").join(sysfs.file_name()?), name: name.into(), size })
+}
+
+/// Whether the device is attached through a USB controller.
+fn is_usb(device: &Path) -> bool {
+    device.components().any(|component| {
+        component.as_os_str().to_str().map_or(false, |name| name.starts_with("usb"))
+    })
+}
+
+/// Downloads the recovery image of this model, and writes it to the `drive`.
+///
+/// Everything on the drive is overwritten, so frontends must have the user confirm that the drive
+/// may be erased before this is called.
+pub(crate) fn create<F: FnMut(RecoveryProgress)>(
+    drive: &UsbDrive,
+    cancellable: &CancellationToken,
+    mut progress: F,
+) -> Result<(), RecoveryError> {
+    let model = model()?;
+    let image_name = image_name(&model);
+    let url = signature::published_url(&image_name)?;
+
+    // The checksum is only trusted once its signature is verified, and names the image of this
+    // model, as it verifies the image.
+    let checksum_url = [&url, ".sha256"].concat();
+    let checksum = download::fetch_text(&checksum_url)?;
+    let checksum_signature = download::fetch_text(&[&checksum_url, ".minisig"].concat())?;
+    let keys = Path::new(signature::TRUSTED_KEYS);
+    signature::verify_file(keys, &image_name, checksum.as_bytes(), &checksum_signature)?;
+    let checksum = published_checksum(&checksum).ok_or(RecoveryError::Checksum)?;
+
+    let (mut downloaded, mut total) = (0, 0);
+    let result = download::fetch(&url, checksum, cancellable, |event| match event {
+        DownloadEvent::Begin(size) => total = size,
+        DownloadEvent::Progress(bytes) => {
+            downloaded += bytes as u64;
+            progress(RecoveryProgress::Downloading(downloaded, total));
+        }
+        DownloadEvent::Complete => (),
+    });
+
+    let image = match result {
+        Ok(image) => image,
+        Err(DownloadError::Cancelled) => return Err(RecoveryError::Cancelled),
+        Err(why) => return Err(why.into()),
+    };
+
+    progress(RecoveryProgress::Verifying);
+    if !checksum::verify(&image, checksum).unwrap_or(false) {
+        let _ = fs::remove_file(&image);
+        return Err(RecoveryError::Verification(image));
+    }
+
+    info!("writing the recovery image of {} to {}", model, drive.path.display());
+    write(&image, drive, cancellable, &mut progress)?;
+    progress(RecoveryProgress::Created(drive.clone()));
+    Ok(())
+}
+
+/// Writes the `image` to the `drive`, flushing it to the drive before returning.
+fn write<F: FnMut(RecoveryProgress)>(
+    image: &Path,
+    drive: &UsbDrive,
+    cancellable: &CancellationToken,
+    progress: &mut F,
+) -> Result<(), RecoveryError> {
+    let write_error = |why| RecoveryError::Write(drive.path.clone(), why);
+
+    let mut source = File::open(image).map_err(write_error)?;
+    let total = source.metadata().map_err(write_error)?.len();
+    if total > drive.size {
+        return Err(RecoveryError::TooSmall(total, drive.size));
+    }
+
+    // Block devices may only be opened exclusively while none of their partitions are mounted.
+    let mut target = OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_EXCL)
+        .open(&drive.path)
+        .map_err(|why| RecoveryError::Open(drive.path.clone(), why))?;
+
+    let mut buffer = vec![0u8; WRITE_SIZE];
+    let mut written = 0;
+    loop {
+        if cancellable.is_cancelled() {
+            return Err(RecoveryError::Cancelled);
+        }
+
+        let read = source.read(&mut buffer).map_err(write_error)?;
+        if read == 0 {
+            break;
+        }
+
+        target.write_all(&buffer[..read]).map_err(write_error)?;
+        written += read as u64;
+        progress(RecoveryProgress::Writing(written, total));
+    }
+
+    target.sync_all().map_err(write_error)
+}
+
+/// The model of this system, by which its recovery image is named.
+fn model() -> Result<Box<str>, RecoveryError> {
+    let vendor = crate::sys_vendor().map_err(RecoveryError::Model)?;
+    let model = crate::product_version().map_err(RecoveryError::Model)?;
+
+    if vendor != SYSTEM76 || !is_model_name(&model) {
+        return Err(RecoveryError::Unsupported([&*vendor, " ", &*model].concat().into()));
+    }
+
+    Ok(model.into())
+}
+
+/// Whether the product version is a model name which may be placed in a URL, such as `galp5`.
+fn is_model_name(model: &str) -> bool {
+    !model.is_empty() && model.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Where the recovery image of the `model` is published, relative to signed System76 firmware.
+fn image_name(model: &str) -> String { [RECOVERY_IMAGES, "/", model, ".img"].concat() }
+
+/// Parses a checksum file, in the format of `sha256sum`, which lists the digest before the name.
+fn published_checksum(contents: &str) -> Option<&str> {
+    let digest = contents.split_whitespace().next()?;
+    checksum::preferred(digest).filter(|digest| digest.len() == 64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    #[test]
+    fn is_usb() {
+        let usb = "/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host4/target4:0:0/4:0:0:0";
+        assert!(super::is_usb(Path::new(usb)));
+
+        let sata = "/sys/devices/pci0000:00/0000:00:17.0/ata1/host0/target0:0:0/0:0:0:0";
+        assert!(!super::is_usb(Path::new(sata)));
+    }
+
+    #[test]
+    fn is_model_name() {
+        assert!(super::is_model_name("galp5"));
+        assert!(super::is_model_name("thelio-mira-r2"));
+        assert!(!super::is_model_name(""));
+        assert!(!super::is_model_name("../galp5"));
+        assert!(!super::is_model_name("To Be Filled By O.E.M."));
+    }
+
+    #[test]
+    fn published_checksum() {
+        let digest = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let contents = [digest, "  galp5.img\n"].concat();
+        assert_eq!(super::published_checksum(&contents), Some(digest));
+        assert_eq!(super::published_checksum(digest), Some(digest));
+
+        let sha1 = "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3  galp5.img\n";
+        assert_eq!(super::published_checksum(sha1), None);
+        assert_eq!(super::published_checksum(""), None);
+    }
+}
@@ -0,0 +1,551 @@
+//! Parsing of the descriptions of releases, so that frontends may render their structure.
+//!
+//! The LVFS describes releases with the subset of HTML which AppStream permits, which is
+//! paragraphs, lists, and emphasis, while some vendors write their descriptions in Markdown
+//! instead. Both are parsed into the same [`NoteBlock`]s. Tags which AppStream does not permit
+//! are dropped, keeping their text.
+//!
+//! Neither permits links, so URLs and CVE identifiers are found in the text of each block, and
+//! parsed as [`NoteSpan::Link`]s. CVEs link to their entries in the National Vulnerability
+//! Database.
+
+/// Where the National Vulnerability Database describes each CVE.
+const NVD_URL: &str = "https://nvd.nist.gov/vuln/detail/";
+
+/// A block of the description of a release.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NoteBlock {
+    /// A paragraph of text.
+    Paragraph(Vec<NoteSpan>),
+    /// A list whose items are marked with bullets.
+    BulletList(Vec<Vec<NoteSpan>>),
+    /// A list whose items are numbered.
+    NumberedList(Vec<Vec<NoteSpan>>),
+}
+
+/// A run of text within a block, and how it is emphasized.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NoteSpan {
+    /// Text without emphasis.
+    Text(Box<str>),
+    /// Emphasized text, which is rendered in italics.
+    Emphasis(Box<str>),
+    /// Strongly emphasized text, which is rendered in bold.
+    Strong(Box<str>),
+    /// Code, such as a command or a file name, which is rendered in a monospace font.
+    Code(Box<str>),
+    /// A URL or CVE identifier, which links to a web page.
+    Link {
+        /// The URL or CVE identifier, as it was written.
+        text: Box<str>,
+        /// The URI of the page that it links to.
+        uri: Box<str>,
+    },
+}
+
+impl NoteBlock {
+    /// The links of the block, in the order that they appear.
+    pub fn links(&self) -> impl Iterator<Item = (&str, &str)> {
+        let spans: Box<dyn Iterator<Item = &NoteSpan>> = match self {
+            NoteBlock::Paragraph(spans) => Box::new(spans.iter()),
+            NoteBlock::BulletList(items) | NoteBlock::NumberedList(items) => {
+                Box::new(items.iter().flatten())
+            }
+        };
+
+        spans.filter_map(|span| match span {
+            NoteSpan::Link { text, uri } => Some((&**text, &**uri)),
+            _ => None,
+        })
+    }
+}
+
+/// Where the National Vulnerability Database describes the `cve`, such as `CVE-2023-1234`.
+pub fn nvd_url(cve: &str) -> String { [NVD_URL, cve].concat() }
+
+/// Parses the description of a release, whether it is written in AppStream markup or Markdown.
+pub fn parse_release_notes(description: &str) -> Vec<NoteBlock> {
+    let description = description.trim();
+    let mut blocks = if description.starts_with('<') {
+        parse_markup(description)
+    } else {
+        parse_markdown(description)
+    };
+
+    for block in &mut blocks {
+        match block {
+            NoteBlock::Paragraph(spans) => *spans = link_spans(spans),
+            NoteBlock::BulletList(items) | NoteBlock::NumberedList(items) => {
+                items.iter_mut().for_each(|spans| *spans = link_spans(spans))
+            }
+        }
+    }
+
+    blocks
+}
+
+/// How the text of AppStream markup is being emphasized.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Plain,
+    Emphasis,
+    Strong,
+    Code,
+}
+
+/// The blocks of AppStream markup, and the block which is being parsed.
+#[derive(Default)]
+struct MarkupParser {
+    blocks: Vec<NoteBlock>,
+    /// The spans of the paragraph or list item which is being parsed.
+    spans: Vec<NoteSpan>,
+    /// The items of the list which is being parsed, and whether it is numbered.
+    list: Option<(bool, Vec<Vec<NoteSpan>>)>,
+}
+
+impl MarkupParser {
+    /// Ends the paragraph or list item which is being parsed.
+    fn end_spans(&mut self) {
+        let mut spans = std::mem::take(&mut self.spans);
+        trim_spans(&mut spans);
+        if spans.is_empty() {
+            return;
+        }
+
+        match self.list.as_mut() {
+            Some((_, items)) => items.push(spans),
+            None => self.blocks.push(NoteBlock::Paragraph(spans)),
+        }
+    }
+
+    /// Ends the list which is being parsed.
+    fn end_list(&mut self) {
+        self.end_spans();
+        match self.list.take() {
+            Some((_, items)) if items.is_empty() => (),
+            Some((true, items)) => self.blocks.push(NoteBlock::NumberedList(items)),
+            Some((false, items)) => self.blocks.push(NoteBlock::BulletList(items)),
+            None => (),
+        }
+    }
+}
+
+fn parse_markup(markup: &str) -> Vec<NoteBlock> {
+    let mut parser = MarkupParser::default();
+    let mut style = Style::Plain;
+    let mut rest = markup;
+
+    while !rest.is_empty() {
+        let (text, tag) = match rest.find('<') {
+            Some(0) => match rest.find('>') {
+                Some(end) => ("", Some(&rest[1..end])),
+                None => (rest, None),
+            },
+            Some(start) => (&rest[..start], None),
+            None => (rest, None),
+        };
+
+        if let Some(tag) = tag {
+            rest = &rest[tag.len() + 2..];
+            let (closing, name) = match tag.strip_prefix('/') {
+                Some(name) => (true, name),
+                None => (false, tag),
+            };
+
+            let name = name.split_whitespace().next().unwrap_or("").trim_end_matches('/');
+            match (closing, &*name.to_ascii_lowercase()) {
+                (_, "p") | (_, "li") | (false, "br") => parser.end_spans(),
+                (false, "ul") => {
+                    parser.end_list();
+                    parser.list = Some((false, Vec::new()));
+                }
+                (false, "ol") => {
+                    parser.end_list();
+                    parser.list = Some((true, Vec::new()));
+                }
+                (true, "ul") | (true, "ol") => parser.end_list(),
+                (false, "em") | (false, "i") => style = Style::Emphasis,
+                (false, "strong") | (false, "b") => style = Style::Strong,
+                (false, "code") => style = Style::Code,
+                (true, "em") | (true, "i") | (true, "strong") | (true, "b") | (true, "code") => {
+                    style = Style::Plain
+                }
+                _ => (),
+            }
+
+            continue;
+        }
+
+        rest = &rest[text.len()..];
+        let text = collapse_whitespace(&decode_entities(text));
+        if !text.is_empty() {
+            push_span(&mut parser.spans, style, &text);
+        }
+    }
+
+    parser.end_list();
+    parser.blocks
+}
+
+fn parse_markdown(markdown: &str) -> Vec<NoteBlock> {
+    let mut blocks = Vec::new();
+    let mut paragraph = String::new();
+    let mut list: Option<(bool, Vec<String>)> = None;
+
+    let end_paragraph = |paragraph: &mut String, blocks: &mut Vec<NoteBlock>| {
+        if !paragraph.is_empty() {
+            blocks.push(NoteBlock::Paragraph(parse_inline(paragraph)));
+            paragraph.clear();
+        }
+    };
+
+    let end_list = |list: &mut Option<(bool, Vec<String>)>, blocks: &mut Vec<NoteBlock>| {
+        if let Some((numbered, items)) = list.take() {
+            let items = items.iter().map(|item| parse_inline(item)).collect();
+            blocks.push(if numbered {
+                NoteBlock::NumberedList(items)
+            } else {
+                NoteBlock::BulletList(items)
+            });
+        }
+    };
+
+    for line in markdown.lines() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            end_paragraph(&mut paragraph, &mut blocks);
+            end_list(&mut list, &mut blocks);
+            continue;
+        }
+
+        if let Some((numbered, item)) = list_item(line) {
+            end_paragraph(&mut paragraph, &mut blocks);
+            if list.as_ref().map_or(false, |(kind, _)| *kind != numbered) {
+                end_list(&mut list, &mut blocks);
+            }
+
+            list.get_or_insert_with(|| (numbered, Vec::new())).1.push(item.to_owned());
+            continue;
+        }
+
+        // Lines which are wrapped continue the last list item, or the paragraph.
+        let text = match list.as_mut().and_then(|(_, items)| items.last_mut()) {
+            Some(item) => item,
+            None => &mut paragraph,
+        };
+
+        if !text.is_empty() {
+            text.push(' ');
+        }
+
+        text.push_str(line.trim_start_matches('#').trim());
+    }
+
+    end_paragraph(&mut paragraph, &mut blocks);
+    end_list(&mut list, &mut blocks);
+    blocks
+}
+
+/// Parses an item of a Markdown list, and whether the list is numbered.
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    if let Some(item) = ["- ", "* ", "+ "].iter().find_map(|bullet| line.strip_prefix(bullet)) {
+        return Some((false, item.trim()));
+    }
+
+    let (number, item) = line.split_once(". ")?;
+    let numbered = !number.is_empty() && number.chars().all(|c| c.is_ascii_digit());
+    numbered.then(|| (true, item.trim()))
+}
+
+/// Parses the emphasis of Markdown text, which is `*emphasis*`, `**strong**`, or `` `code` ``.
+fn parse_inline(text: &str) -> Vec<NoteSpan> {
+    let mut spans = Vec::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let start = match rest.find(['*', '`', '_']) {
+            Some(start) => start,
+            None => break,
+        };
+
+        let marker = match &rest[start..] {
+            tail if tail.starts_with("**") => "**",
+            tail if tail.starts_with('`') => "`",
+            tail if tail.starts_with('_') => "_",
+            _ => "*",
+        };
+
+        let style = match marker {
+            "**" => Style::Strong,
+            "`" => Style::Code,
+            _ => Style::Emphasis,
+        };
+
+        let inner = &rest[start + marker.len()..];
+        // Underscores within words, as in `snake_case`, are not emphasis.
+        let within_word = marker == "_"
+            && rest[..start].chars().next_back().map_or(false, char::is_alphanumeric);
+
+        match inner.find(marker).filter(|&end| end != 0 && !within_word) {
+            Some(end) => {
+                push_span(&mut spans, Style::Plain, &rest[..start]);
+                push_span(&mut spans, style, &inner[..end]);
+                rest = &inner[end + marker.len()..];
+            }
+            None => {
+                push_span(&mut spans, Style::Plain, &rest[..start + marker.len()]);
+                rest = inner;
+            }
+        }
+    }
+
+    push_span(&mut spans, Style::Plain, rest);
+    spans
+}
+
+/// Appends text to the spans, joining it with the last span if it has the same style.
+fn push_span(spans: &mut Vec<NoteSpan>, style: Style, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+
+    if let (Style::Plain, Some(NoteSpan::Text(last))) = (style, spans.last_mut()) {
+        *last = [&**last, text].concat().into();
+        return;
+    }
+
+    spans.push(match style {
+        Style::Plain => NoteSpan::Text(text.into()),
+        Style::Emphasis => NoteSpan::Emphasis(text.into()),
+        Style::Strong => NoteSpan::Strong(text.into()),
+        Style::Code => NoteSpan::Code(text.into()),
+    });
+}
+
+/// Splits the URLs and CVE identifiers out of the unemphasized text of the spans.
+fn link_spans(spans: &[NoteSpan]) -> Vec<NoteSpan> {
+    let mut linked = Vec::with_capacity(spans.len());
+    for span in spans {
+        let mut rest = match span {
+            NoteSpan::Text(text) => &**text,
+            span => {
+                linked.push(span.clone());
+                continue;
+            }
+        };
+
+        while let Some((start, end, uri)) = find_link(rest) {
+            push_span(&mut linked, Style::Plain, &rest[..start]);
+            linked.push(NoteSpan::Link { text: rest[start..end].into(), uri: uri.into() });
+            rest = &rest[end..];
+        }
+
+        push_span(&mut linked, Style::Plain, rest);
+    }
+
+    linked
+}
+
+/// Finds the first URL or CVE identifier in the text, with where it starts and ends, and the URI
+/// that it links to.
+fn find_link(text: &str) -> Option<(usize, usize, String)> {
+    let mut search = 0;
+    while let Some(found) = text[search..].find(['h', 'C']) {
+        let start = search + found;
+        search = start + 1;
+
+        let preceded_by_word =
+            text[..start].chars().next_back().map_or(false, char::is_alphanumeric);
+        if preceded_by_word {
+            continue;
+        }
+
+        let candidate = &text[start..];
+        if candidate.starts_with("https://") || candidate.starts_with("http://") {
+            let length = url_length(candidate);
+            if !candidate[..length].ends_with("//") {
+                return Some((start, start + length, candidate[..length].to_owned()));
+            }
+        } else if let Some(length) = cve_length(candidate) {
+            return Some((start, start + length, nvd_url(&candidate[..length])));
+        }
+    }
+
+    None
+}
+
+/// The length of the URL at the start of the text, without the punctuation which follows it.
+fn url_length(text: &str) -> usize {
+    let end = text.find(|c: char| c.is_whitespace() || "<>\"".contains(c)).unwrap_or(text.len());
+    let mut url = &text[..end];
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'']);
+        // Parentheses which enclose a URL are not a part of it, unless it opens them itself.
+        let unbalanced = trimmed.matches(')').count() > trimmed.matches('(').count();
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(enclosed) if unbalanced => enclosed,
+            _ => trimmed,
+        };
+
+        if trimmed.len() == url.len() {
+            return url.len();
+        }
+
+        url = trimmed;
+    }
+}
+
+/// The length of the CVE identifier at the start of the text, such as `CVE-2023-1234`.
+fn cve_length(text: &str) -> Option<usize> {
+    let rest = text.strip_prefix("CVE-")?;
+    let year = rest.get(..4).filter(|year| year.chars().all(|c| c.is_ascii_digit()))?;
+    let rest = rest[year.len()..].strip_prefix('-')?;
+
+    let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let followed_by_word = rest[digits..].chars().next().map_or(false, char::is_alphanumeric);
+    (digits >= 4 && !followed_by_word).then(|| "CVE-".len() + year.len() + 1 + digits)
+}
+
+/// Removes the whitespace which the markup left at the start and end of a block.
+fn trim_spans(spans: &mut Vec<NoteSpan>) {
+    if let Some(NoteSpan::Text(first)) = spans.first_mut() {
+        *first = first.trim_start().into();
+    }
+
+    if let Some(NoteSpan::Text(last)) = spans.last_mut() {
+        *last = last.trim_end().into();
+    }
+
+    spans.retain(|span| !matches!(span, NoteSpan::Text(text) if text.is_empty()));
+}
+
+/// Collapses each run of whitespace into a single space, as markup is rendered.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+
+        if space {
+            collapsed.push(' ');
+            space = false;
+        }
+
+        collapsed.push(c);
+    }
+
+    if space {
+        collapsed.push(' ');
+    }
+
+    collapsed
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_release_notes, NoteBlock, NoteSpan};
+
+    fn text(text: &str) -> NoteSpan { NoteSpan::Text(text.into()) }
+
+    #[test]
+    fn markup() {
+        let description = "<p>This release fixes <em>several</em> issues &amp; adds:</p>\n\
+                           <ul>\n  <li>Support for <code>USB4</code></li>\n  \
+                           <li><strong>Faster</strong> charging</li>\n</ul>\
+                           <ol><li>Update</li><li>Reboot</li></ol>";
+
+        let expected = [
+            NoteBlock::Paragraph(vec![
+                text("This release fixes "),
+                NoteSpan::Emphasis("several".into()),
+                text(" issues & adds:"),
+            ]),
+            NoteBlock::BulletList(vec![
+                vec![text("Support for "), NoteSpan::Code("USB4".into())],
+                vec![NoteSpan::Strong("Faster".into()), text(" charging")],
+            ]),
+            NoteBlock::NumberedList(vec![vec![text("Update")], vec![text("Reboot")]]),
+        ];
+
+        assert_eq!(parse_release_notes(description), expected);
+    }
+
+    #[test]
+    fn markdown() {
+        let description = "This release fixes **several** issues in\n\
+                           the `snake_case` parser.\n\
+                           \n\
+                           - Support for *USB4*\n\
+                           - Faster charging, which was\n  \
+                             wrapped\n\
+                           1. Update\n\
+                           2. Reboot\n";
+
+        let expected = [
+            NoteBlock::Paragraph(vec![
+                text("This release fixes "),
+                NoteSpan::Strong("several".into()),
+                text(" issues in the "),
+                NoteSpan::Code("snake_case".into()),
+                text(" parser."),
+            ]),
+            NoteBlock::BulletList(vec![
+                vec![text("Support for "), NoteSpan::Emphasis("USB4".into())],
+                vec![text("Faster charging, which was wrapped")],
+            ]),
+            NoteBlock::NumberedList(vec![vec![text("Update")], vec![text("Reboot")]]),
+        ];
+
+        assert_eq!(parse_release_notes(description), expected);
+    }
+
+    #[test]
+    fn links() {
+        let description = "<p>Fixes CVE-2023-20569 and CVE-2022-40982, as described at \
+                           https://example.com/advisory?id=1 (or https://example.com/a_(b)).</p>\
+                           <p>CVE-2023-1 and XCVE-2023-1234 and https:// are not links.</p>";
+
+        let link = |text: &str, uri: &str| NoteSpan::Link { text: text.into(), uri: uri.into() };
+        let expected = [
+            NoteBlock::Paragraph(vec![
+                text("Fixes "),
+                link("CVE-2023-20569", "https://nvd.nist.gov/vuln/detail/CVE-2023-20569"),
+                text(" and "),
+                link("CVE-2022-40982", "https://nvd.nist.gov/vuln/detail/CVE-2022-40982"),
+                text(", as described at "),
+                link("https://example.com/advisory?id=1", "https://example.com/advisory?id=1"),
+                text(" (or "),
+                link("https://example.com/a_(b)", "https://example.com/a_(b)"),
+                text(")."),
+            ]),
+            NoteBlock::Paragraph(vec![text(
+                "CVE-2023-1 and XCVE-2023-1234 and https:// are not links.",
+            )]),
+        ];
+
+        let blocks = parse_release_notes(description);
+        assert_eq!(blocks, expected);
+        assert_eq!(blocks[0].links().count(), 4);
+        assert_eq!(blocks[1].links().count(), 0);
+    }
+
+    #[test]
+    fn unmatched_markers() {
+        let expected = [NoteBlock::Paragraph(vec![text("2 * 3 = 6, and a_b")])];
+        assert_eq!(parse_release_notes("2 * 3 = 6, and a_b"), expected);
+        assert!(parse_release_notes("").is_empty());
+    }
+}
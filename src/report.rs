@@ -0,0 +1,251 @@
+//! Reports of fwupd updates, which may be uploaded to the LVFS.
+//!
+//! Reports tell vendors whether their firmware was installed successfully, so that faulty
+//! releases may be found and pulled. They are only uploaded with the consent of the user, which
+//! is either set with `upload_reports` in the configuration file, or remembered from the answer
+//! to a [`FirmwareSignal::ReportAvailable`].
+//!
+//! fwupd records the outcome of every update in its history, and reports are uploaded from that
+//! history with `fwupdmgr report-history`. fwupd anonymizes them as it does when it asks to
+//! upload them itself, and marks them as reported, so that none are uploaded twice.
+//!
+//! The user only consents to the report of the update that they were asked about, so the other
+//! reports in the history are marked as reported while it is uploaded, and unmarked afterwards.
+
+use crate::{fwupd::FWUPD_DEST, FirmwareError, FirmwareSignal};
+use dbus::{
+    arg::{prop_cast, PropMap},
+    blocking::Connection,
+};
+use fwupd_dbus::{Device as FwupdDevice, DeviceFlags, Release as FwupdRelease};
+use std::{fs, io, path::PathBuf, process::Command, sync::mpsc::Sender, time::Duration};
+
+/// An error that may occur when remembering consent, or uploading a report.
+#[derive(Debug, Error)]
+pub enum ReportError {
+    /// The location of the consent file could not be determined.
+    #[error("failed to get XDG base directory")]
+    BaseDirectory(#[from] xdg::BaseDirectoriesError),
+    /// The directory of the consent file could not be created.
+    #[error("failed to create the report consent directory")]
+    Place(#[source] io::Error),
+    /// The consent of the user could not be written.
+    #[error("failed to remember consent to upload reports")]
+    Write(#[source] io::Error),
+    /// The update history could not be fetched from fwupd, or the other reports in it could not
+    /// be set aside.
+    #[error("failed to set aside the other reports in the fwupd history")]
+    History(#[source] dbus::Error),
+    /// fwupdmgr could not be run to upload the report.
+    #[error("failed to run fwupdmgr")]
+    Spawn(#[source] io::Error),
+    /// fwupdmgr failed to upload the report.
+    #[error("fwupdmgr failed to upload the report: {}", _0)]
+    Rejected(Box<str>),
+}
+
+/// The outcome of a fwupd update, which may be uploaded to the LVFS.
+#[derive(Clone, Debug)]
+pub struct UpdateReport {
+    device: Box<str>,
+    device_id: Box<str>,
+    succeeded: bool,
+}
+
+impl UpdateReport {
+    /// The name of the device that was updated.
+    pub fn device(&self) -> &str { &self.device }
+
+    /// Whether the device was updated successfully.
+    pub fn succeeded(&self) -> bool { self.succeeded }
+}
+
+/// Whether the user consented to uploading reports, if they have been asked.
+///
+/// The `upload_reports` option of the configuration file takes precedence over the answer that
+/// the user gave.
+pub fn report_consent() -> Option<bool> {
+    crate::config().upload_reports.or_else(|| {
+        let consent = fs::read_to_string(consent_path().ok()?).ok()?;
+        consent.trim().parse().ok()
+    })
+}
+
+/// Remembers whether the user consented to uploading reports, so that they are not asked again.
+pub fn remember_report_consent(upload: bool) -> Result<(), ReportError> {
+    info!("{} reports to the LVFS from now on", if upload { "uploading" } else { "not uploading" });
+    fs::write(consent_path()?, upload.to_string()).map_err(ReportError::Write)
+}
+
+/// Reports the outcome of flashing a device, given the `error` that it failed with, either by
+/// uploading the report or by asking the frontend for consent to upload it.
+///
+/// Only updates from the LVFS are reported, and failures which occurred before fwupd began to
+/// flash the device are not reported.
+pub(crate) fn offer(
+    device: &FwupdDevice,
+    release: &FwupdRelease,
+    error: Option<&FirmwareError>,
+    sender: &Sender<FirmwareSignal>,
+) {
+    if !release.remote_id.starts_with("lvfs") {
+        return;
+    }
+
+    if let Some(FirmwareError::Open(_) | FirmwareError::Permission(_)) = error {
+        return;
+    }
+
+    let report = UpdateReport {
+        device: crate::fwupd::device_name(device).into(),
+        device_id: device.device_id.clone(),
+        succeeded: error.is_none(),
+    };
+
+    match report_consent() {
+        Some(true) => upload_or_warn(&report),
+        Some(false) => (),
+        None => {
+            let _res = sender.send(FirmwareSignal::ReportAvailable(Box::new(report)));
+        }
+    }
+}
+
+/// Remembers the answer of the user, and uploads the report if they consented to it.
+pub(crate) fn answer(report: &UpdateReport, upload: bool) {
+    if let Err(why) = remember_report_consent(upload) {
+        warn!("{}", crate::format_error(why));
+    }
+
+    if upload {
+        upload_or_warn(report);
+    }
+}
+
+/// Uploads a report, logging a warning if it could not be uploaded.
+fn upload_or_warn(report: &UpdateReport) {
+    match info_span!("report").in_scope(|| upload(report)) {
+        Ok(()) => info!("uploaded the report of {} to the LVFS", report.device),
+        Err(why) => warn!("{}", crate::format_error(why)),
+    }
+}
+
+/// Uploads the report of an update from the fwupd history.
+///
+/// `fwupdmgr report-history` uploads every report in the history which has not been uploaded,
+/// so the others are marked as reported until it is done, and are then offered again later.
+fn upload(report: &UpdateReport) -> Result<(), ReportError> {
+    info!("uploading the report of {} with fwupdmgr", report.device);
+
+    let history = history().map_err(ReportError::History)?;
+    let others = unreported(&history, &report.device_id);
+    let mut set_aside = Vec::with_capacity(others.len());
+
+    let set_aside_all = others.iter().try_for_each(|&device_id| {
+        set_flag(device_id, "reported")?;
+        set_aside.push(device_id);
+        Ok(())
+    });
+
+    let result = match set_aside_all {
+        Ok(()) => report_history(),
+        Err(why) => Err(ReportError::History(why)),
+    };
+
+    for device_id in set_aside {
+        if let Err(why) = set_flag(device_id, "~reported") {
+            warn!("failed to restore the report of {} to the fwupd history: {}", device_id, why);
+        }
+    }
+
+    result
+}
+
+/// The devices in the fwupd history, as they were when they were last updated.
+fn history() -> Result<Vec<PropMap>, dbus::Error> {
+    let connection = Connection::new_system()?;
+    let proxy = connection.with_proxy(FWUPD_DEST, "/", Duration::from_secs(30));
+    let (history,) = proxy.method_call(FWUPD_DEST, "GetHistory", ())?;
+    Ok(history)
+}
+
+/// Sets or, when prefixed with `~`, clears a flag of a device in the fwupd history.
+fn set_flag(device_id: &str, flag: &str) -> Result<(), dbus::Error> {
+    let connection = Connection::new_system()?;
+    let proxy = connection.with_proxy(FWUPD_DEST, "/", Duration::from_secs(30));
+    proxy.method_call(FWUPD_DEST, "ModifyDevice", (device_id, "Flags", flag))
+}
+
+/// The IDs of the devices in the fwupd history, other than `device_id`, whose reports have not
+/// been uploaded.
+fn unreported<'a>(history: &'a [PropMap], device_id: &str) -> Vec<&'a str> {
+    history
+        .iter()
+        .filter(|device| {
+            let flags = prop_cast::<u64>(device, "Flags").copied().unwrap_or_default();
+            !DeviceFlags::from_bits_truncate(flags).contains(DeviceFlags::REPORTED)
+        })
+        .filter_map(|device| prop_cast::<String>(device, "DeviceId"))
+        .map(String::as_str)
+        .filter(|&id| id != device_id)
+        .collect()
+}
+
+/// Uploads the reports in the fwupd history which have not been uploaded yet.
+fn report_history() -> Result<(), ReportError> {
+    let output = Command::new("fwupdmgr")
+        .args(["report-history", "--assume-yes"])
+        .output()
+        .map_err(ReportError::Spawn)?;
+
+    if !output.status.success() {
+        return Err(ReportError::Rejected(failure_reason(&output.stderr)));
+    }
+
+    Ok(())
+}
+
+/// The last line that fwupdmgr printed before it failed, which describes why it failed.
+fn failure_reason(stderr: &[u8]) -> Box<str> {
+    let stderr = String::from_utf8_lossy(stderr);
+    let reason = stderr.lines().rev().find(|line| !line.trim().is_empty());
+    reason.unwrap_or("no reason was given").trim().into()
+}
+
+/// The location of the file which remembers the consent of the user.
+fn consent_path() -> Result<PathBuf, ReportError> {
+    xdg::BaseDirectories::with_prefix("com.system76.FirmwareManager")?
+        .place_data_file("report-consent")
+        .map_err(ReportError::Place)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn failure_reason() {
+        let stderr = b"Uploading firmware reports...\nFailed to upload report: 404\n\n";
+        assert_eq!(&*super::failure_reason(stderr), "Failed to upload report: 404");
+        assert_eq!(&*super::failure_reason(b""), "no reason was given");
+    }
+
+    #[test]
+    fn unreported() {
+        use dbus::arg::{PropMap, Variant};
+        use fwupd_dbus::DeviceFlags;
+
+        let device = |id: &str, flags: DeviceFlags| {
+            let mut properties = PropMap::new();
+            properties.insert("DeviceId".into(), Variant(Box::new(String::from(id)) as _));
+            properties.insert("Flags".into(), Variant(Box::new(flags.bits()) as _));
+            properties
+        };
+
+        let history = [
+            device("dock", DeviceFlags::UPDATABLE),
+            device("ssd", DeviceFlags::UPDATABLE | DeviceFlags::REPORTED),
+            device("system", DeviceFlags::INTERNAL),
+        ];
+
+        assert_eq!(super::unreported(&history, "system"), ["dock"]);
+    }
+}
@@ -0,0 +1,154 @@
+//! Retries of remote fetches which fail due to transient network errors.
+
+use crate::CancellationToken;
+use once_cell::sync::Lazy;
+use std::{
+    sync::RwLock,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+static POLICY: Lazy<RwLock<RetryPolicy>> = Lazy::new(|| RwLock::new(RetryPolicy::default()));
+
+/// Controls how many times, and how often, a failed remote fetch is retried.
+///
+/// The delay between attempts doubles with each attempt, up to the maximum delay, and is
+/// randomly reduced by up to half to avoid many clients retrying at the same moment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The total number of attempts to make, including the first.
+    pub attempts: u32,
+    /// The delay before the first retry.
+    pub initial_delay: Duration,
+    /// The longest that a delay between attempts may be.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 4,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before the given retry, where `jitter` is a factor between 0 and 1.
+    fn delay(&self, retry: u32, jitter: f64) -> Duration {
+        let delay = self.initial_delay.saturating_mul(2u32.saturating_pow(retry));
+        let delay = delay.min(self.max_delay);
+        delay.mul_f64(1.0 - jitter.clamp(0.0, 1.0) / 2.0)
+    }
+}
+
+/// Sets the retry policy used by all remote fetches.
+pub fn set_retry_policy(policy: RetryPolicy) {
+    *POLICY.write().unwrap_or_else(|why| why.into_inner()) = policy;
+}
+
+/// The retry policy used by all remote fetches.
+pub fn retry_policy() -> RetryPolicy { *POLICY.read().unwrap_or_else(|why| why.into_inner()) }
+
+/// Performs the operation until it succeeds, fails with an error which is not transient, the
+/// retry policy's attempts are exhausted, or it is cancelled.
+///
+/// The last error is returned if every attempt fails.
+pub(crate) fn retry<T, E, F, P>(
+    description: &str,
+    cancellable: Option<&CancellationToken>,
+    is_transient: P,
+    mut operation: F,
+) -> Result<T, E>
+where
+    E: std::error::Error,
+    F: FnMut() -> Result<T, E>,
+    P: Fn(&E) -> bool,
+{
+    let policy = retry_policy();
+    let is_cancelled = || cancellable.map_or(false, CancellationToken::is_cancelled);
+    let mut retry = 0;
+
+    loop {
+        let why = match operation() {
+            Ok(value) => return Ok(value),
+            Err(why) => why,
+        };
+
+        if retry + 1 >= policy.attempts || !is_transient(&why) || is_cancelled() {
+            return Err(why);
+        }
+
+        let delay = policy.delay(retry, jitter());
+        warn!(
+            "failed to {} (attempt {} of {}), retrying in {:?}: {}",
+            description,
+            retry + 1,
+            policy.attempts,
+            delay,
+            crate::format_error(&why)
+        );
+
+        // Sleep in short intervals, so that a cancellation is not held up by the delay.
+        let wake = SystemTime::now() + delay;
+        while SystemTime::now() < wake && !is_cancelled() {
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        retry += 1;
+    }
+}
+
+/// A pseudo-random factor between 0 and 1, derived from the current time.
+fn jitter() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.subsec_nanos());
+
+    // Scramble the bits, as the low bits of the clock may have a coarse resolution.
+    let scrambled = nanos.wrapping_mul(2_654_435_761) >> 8;
+    f64::from(scrambled) / f64::from(u32::MAX >> 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use std::{cell::Cell, io, time::Duration};
+
+    #[test]
+    fn delay() {
+        let policy = RetryPolicy {
+            attempts: 5,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+        };
+
+        assert_eq!(policy.delay(0, 0.0), Duration::from_secs(1));
+        assert_eq!(policy.delay(1, 0.0), Duration::from_secs(2));
+        assert_eq!(policy.delay(2, 0.0), Duration::from_secs(4));
+        assert_eq!(policy.delay(3, 0.0), Duration::from_secs(5));
+        assert_eq!(policy.delay(1, 1.0), Duration::from_secs(1));
+        assert_eq!(policy.delay(40, 0.0), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn jitter() {
+        for _ in 0..100 {
+            let jitter = super::jitter();
+            assert!((0.0..=1.0).contains(&jitter));
+        }
+    }
+
+    #[test]
+    fn retry_permanent() {
+        let attempts = Cell::new(0);
+        let result: Result<(), io::Error> = super::retry("test", None, |_| false, || {
+            attempts.set(attempts.get() + 1);
+            Err(io::Error::new(io::ErrorKind::Other, "permanent"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+}
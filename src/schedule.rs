@@ -0,0 +1,272 @@
+//! Schedules periodic checks for firmware updates.
+//!
+//! On the desktop, checks are performed by the notification binary, which is run by a systemd
+//! user timer. The interval of that timer is set with a drop-in override, so that the installed
+//! unit does not need to be modified. Long-running services may instead use a [`CheckTimer`].
+
+use crate::{cache, timestamp};
+use std::{
+    fs, io,
+    path::PathBuf,
+    process::Command,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// The systemd user timer which periodically runs the notification binary.
+pub const CHECK_TIMER: &str = "com.system76.FirmwareManager.Notify.timer";
+
+/// The service which is activated by the [`CHECK_TIMER`].
+const CHECK_SERVICE: &str = "com.system76.FirmwareManager.Notify.service";
+
+/// The file in the cache which the time of the last check is recorded in.
+const LAST_CHECK: &str = "last_check";
+
+/// An error that may occur when scheduling firmware checks.
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    /// The location of the timer override could not be determined.
+    #[error("failed to get XDG base directory")]
+    BaseDirectory(#[from] xdg::BaseDirectoriesError),
+    /// The directory of the timer override could not be created.
+    #[error("failed to create the timer override directory")]
+    Place(#[source] io::Error),
+    /// The timer override could not be written.
+    #[error("failed to write the timer override")]
+    Write(#[source] io::Error),
+    /// The systemctl command could not be executed.
+    #[error("failed to execute systemctl")]
+    Systemctl(#[source] io::Error),
+    /// The systemctl command exited with an error.
+    #[error("systemctl {} failed: {}", _0, _1)]
+    Status(Box<str>, Box<str>),
+    /// The location of the time of the last check could not be determined.
+    #[error("failed to get the cache location of the last check")]
+    Cache(#[from] cache::Error),
+    /// The time of the last check could not be written.
+    #[error("failed to write the time of the last check")]
+    Record(#[source] io::Error),
+}
+
+/// Enables the check timer, applying the check interval from the configuration file.
+pub fn register_check_timer() -> Result<(), ScheduleError> {
+    refresh_check_timer()?;
+    systemctl(&["enable", "--now", CHECK_TIMER]).map(|_| ())
+}
+
+/// Applies the check interval from the configuration file to the check timer, if it changed.
+pub fn refresh_check_timer() -> Result<(), ScheduleError> {
+    apply_check_interval(crate::config().check_interval)
+}
+
+/// Sets the interval between checks, in seconds, and reloads the check timer to apply it.
+///
+/// This does not modify the configuration file, so the interval will be reset to the configured
+/// interval when the check timer is next refreshed.
+pub fn set_check_interval(seconds: u64) -> Result<(), ScheduleError> {
+    apply_check_interval(seconds)
+}
+
+/// The time at which the check timer will next check for updates, if it is active.
+pub fn next_check() -> Option<SystemTime> {
+    systemctl(&["is-active", "--quiet", CHECK_TIMER]).ok()?;
+
+    let property = |unit: &str, property: &str| {
+        systemctl(&["show", "--timestamp=unix", "--value", "-p", property, unit])
+            .map_err(|why| warn!("failed to query {}: {}", unit, why))
+            .ok()
+            .and_then(|value| parse_timestamp(&value))
+    };
+
+    // The timer elapses once the interval has passed since the service last ran.
+    let elapsed = match property(CHECK_SERVICE, "InactiveEnterTimestamp") {
+        Some(last) => last + crate::config().check_interval,
+        None => property(CHECK_TIMER, "NextElapseUSecRealtime")?,
+    };
+
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(elapsed))
+}
+
+/// The time at which firmware updates were last checked for, by a scan or by the check timer.
+pub fn last_check() -> Option<SystemTime> {
+    let path = cache::cache(LAST_CHECK).ok()?;
+    parse_last_check(&fs::read_to_string(path).ok()?)
+}
+
+/// Records that firmware updates were checked for just now, to be reported by [`last_check`].
+pub fn record_check() -> Result<(), ScheduleError> {
+    let path = cache::cache(LAST_CHECK)?;
+    fs::write(path, timestamp::current().to_string()).map_err(ScheduleError::Record)
+}
+
+/// Parses the time of the last check, which is recorded in seconds since the UNIX epoch.
+fn parse_last_check(value: &str) -> Option<SystemTime> {
+    let seconds = value.trim().parse().ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Writes the timer override if the interval differs from that which is already applied.
+fn apply_check_interval(seconds: u64) -> Result<(), ScheduleError> {
+    let path = override_path()?;
+    let contents = timer_override(seconds);
+
+    if fs::read_to_string(&path).ok().as_deref() == Some(&contents) {
+        return Ok(());
+    }
+
+    info!("checking for firmware updates every {} seconds", seconds);
+
+    let parent = path.parent().expect("timer override does not have a parent directory");
+    fs::create_dir_all(parent).map_err(ScheduleError::Place)?;
+    fs::write(&path, contents).map_err(ScheduleError::Write)?;
+
+    systemctl(&["daemon-reload"]).map(|_| ())
+}
+
+/// The location of the drop-in override for the check timer.
+fn override_path() -> Result<PathBuf, ScheduleError> {
+    let base = xdg::BaseDirectories::new()?;
+    Ok(base.get_config_home().join(["systemd/user/", CHECK_TIMER, ".d/interval.conf"].concat()))
+}
+
+/// A drop-in override which replaces the interval of the check timer.
+fn timer_override(seconds: u64) -> String {
+    // The empty assignment clears the interval of the installed unit.
+    format!("[Timer]\nOnUnitInactiveSec=\nOnUnitInactiveSec={}s\n", seconds.max(60))
+}
+
+/// Parses a timestamp formatted by systemctl with `--timestamp=unix`.
+fn parse_timestamp(value: &str) -> Option<u64> { value.trim().strip_prefix('@')?.parse().ok() }
+
+/// Runs a systemctl command for the user's service manager, returning its output.
+fn systemctl(args: &[&str]) -> Result<String, ScheduleError> {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .map_err(ScheduleError::Systemctl)?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(ScheduleError::Status(args[0].into(), stderr.trim().into()))
+    }
+}
+
+/// Periodically invokes a check from a background thread, for services which are not run by
+/// the check timer.
+///
+/// The thread stops when the timer is dropped.
+pub struct CheckTimer {
+    state: Arc<(Mutex<TimerState>, Condvar)>,
+}
+
+struct TimerState {
+    interval: Duration,
+    next: SystemTime,
+    stopped: bool,
+}
+
+impl CheckTimer {
+    /// Spawns a thread which calls `check` every `interval`, beginning after the first interval.
+    pub fn spawn<F: Fn() + Send + 'static>(interval: Duration, check: F) -> Self {
+        let state = TimerState { interval, next: SystemTime::now() + interval, stopped: false };
+        let state = Arc::new((Mutex::new(state), Condvar::new()));
+
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            let (lock, condvar) = &*thread_state;
+            let mut state = lock_state(lock);
+
+            loop {
+                if state.stopped {
+                    break;
+                }
+
+                match state.next.duration_since(SystemTime::now()) {
+                    Ok(remaining) => {
+                        state = condvar
+                            .wait_timeout(state, remaining)
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .0;
+                    }
+                    Err(_) => {
+                        state.next = SystemTime::now() + state.interval;
+                        drop(state);
+                        check();
+                        state = lock_state(lock);
+                    }
+                }
+            }
+        });
+
+        Self { state }
+    }
+
+    /// The time at which the next check will be performed.
+    pub fn next_check(&self) -> SystemTime { lock_state(&self.state.0).next }
+
+    /// Changes the interval between checks, rescheduling the next check from now.
+    pub fn set_interval(&self, interval: Duration) {
+        let mut state = lock_state(&self.state.0);
+        state.interval = interval;
+        state.next = SystemTime::now() + interval;
+        self.state.1.notify_one();
+    }
+}
+
+impl Drop for CheckTimer {
+    fn drop(&mut self) {
+        lock_state(&self.state.0).stopped = true;
+        self.state.1.notify_one();
+    }
+}
+
+fn lock_state(lock: &Mutex<TimerState>) -> MutexGuard<'_, TimerState> {
+    lock.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timer_override() {
+        assert_eq!(
+            super::timer_override(3600),
+            "[Timer]\nOnUnitInactiveSec=\nOnUnitInactiveSec=3600s\n"
+        );
+        assert_eq!(
+            super::timer_override(0),
+            "[Timer]\nOnUnitInactiveSec=\nOnUnitInactiveSec=60s\n"
+        );
+    }
+
+    #[test]
+    fn parse_timestamp() {
+        assert_eq!(super::parse_timestamp("@1700000000\n"), Some(1_700_000_000));
+        assert_eq!(super::parse_timestamp("\n"), None);
+        assert_eq!(super::parse_timestamp("n/a"), None);
+    }
+
+    #[test]
+    fn parse_last_check() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(super::parse_last_check("1700000000\n"), Some(time));
+        assert_eq!(super::parse_last_check(""), None);
+    }
+
+    #[test]
+    fn check_timer() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let timer = CheckTimer::spawn(Duration::from_secs(3600), move || {
+            let _res = tx.send(());
+        });
+
+        assert!(timer.next_check() > SystemTime::now());
+        timer.set_interval(Duration::from_millis(10));
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+}
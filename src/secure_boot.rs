@@ -0,0 +1,49 @@
+//! Detects whether the firmware enforces Secure Boot.
+//!
+//! Firmware which enforces Secure Boot only starts bootloaders, and only installs capsules, which
+//! are signed by keys that it trusts. Firmware from another branch, such as coreboot, may not
+//! support Secure Boot at all, or may trust different keys, so the user is warned before it is
+//! installed.
+
+use crate::capsule::efivar;
+use std::fs;
+
+/// Whether the firmware enforces Secure Boot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecureBoot {
+    /// Only signed bootloaders and capsules are accepted.
+    Enabled,
+    /// The firmware supports Secure Boot, but does not enforce it.
+    Disabled,
+    /// No platform key has been enrolled, so Secure Boot is not enforced until one is.
+    SetupMode,
+}
+
+/// Whether the firmware enforces Secure Boot.
+///
+/// Returns `None` if the system was not booted with UEFI, or if the firmware does not support
+/// Secure Boot.
+pub fn secure_boot() -> Option<SecureBoot> {
+    let variable = |name: &str| fs::read(efivar(name)).ok().and_then(|contents| flag(&contents));
+
+    let state = match (variable("SecureBoot")?, variable("SetupMode")) {
+        (_, Some(true)) => SecureBoot::SetupMode,
+        (true, _) => SecureBoot::Enabled,
+        (false, _) => SecureBoot::Disabled,
+    };
+
+    Some(state)
+}
+
+/// Parses an efivarfs file holding a boolean, which follows the attributes of the variable.
+fn flag(contents: &[u8]) -> Option<bool> { contents.get(4).map(|&value| value == 1) }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn flag() {
+        assert_eq!(super::flag(&[6, 0, 0, 0, 1]), Some(true));
+        assert_eq!(super::flag(&[6, 0, 0, 0, 0]), Some(false));
+        assert_eq!(super::flag(&[6, 0, 0, 0]), None);
+    }
+}
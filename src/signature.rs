@@ -0,0 +1,299 @@
+//! Verification of the signatures which are published alongside System76 firmware.
+//!
+//! System76 firmware is identified by the digest of its archive, which holds its changelog and
+//! every payload. The archive is downloaded from the [`SIGNATURE_LOCATION`], and must have a
+//! detached [minisign] signature, whose trusted comment names the digest, as `minisign -Sm`
+//! writes it when the archive is named by its digest. The signature therefore covers the
+//! contents of the archive, as well as the digest which the daemon is asked to flash.
+//!
+//! Signatures are checked against the public keys installed in [`TRUSTED_KEYS`], which the
+//! distribution's package installs along with the location of the archives. Verification is
+//! enabled by installing at least one key, after which firmware is refused if its signature does
+//! not verify, or if the location is not configured. Until then, System76 firmware is handed to
+//! the daemon unverified, as it was before signatures were published.
+//!
+//! [minisign]: https://jedisct1.github.io/minisign/
+
+use crate::{cache, download, DownloadError};
+use minisign_verify::{PublicKey, Signature};
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The directory of minisign public keys which firmware must be signed with.
+pub const TRUSTED_KEYS: &str = "/usr/share/firmware-manager/keys";
+
+/// The file which holds the URL that signed System76 firmware archives are published at.
+pub const SIGNATURE_LOCATION: &str = "/usr/share/firmware-manager/signatures";
+
+/// An error that may occur when verifying the signature of firmware.
+#[derive(Debug, Error)]
+pub enum SignatureError {
+    /// The trusted public keys could not be read from their directory.
+    #[error("failed to read trusted keys from {}", _0.display())]
+    Keys(PathBuf, #[source] io::Error),
+    /// No trusted public keys are installed in the directory, so nothing can be verified.
+    #[error("no trusted keys are installed in {}", _0.display())]
+    NoTrustedKeys(PathBuf),
+    /// The location of signed firmware archives could not be read.
+    #[error("failed to read the location of signed firmware from {}", SIGNATURE_LOCATION)]
+    Location(#[source] io::Error),
+    /// The firmware or its signature could not be downloaded.
+    #[error("failed to download the signature of {}", _0)]
+    Download(Box<str>, #[source] DownloadError),
+    /// The signature is not a valid minisign signature.
+    #[error("the signature of {} is malformed", _0)]
+    Malformed(Box<str>, #[source] minisign_verify::Error),
+    /// The signature was not made with any of the trusted keys.
+    #[error("{} was not signed by a trusted key", _0)]
+    Untrusted(Box<str>),
+    /// The signature was made with a trusted key, but not for this firmware.
+    #[error("{} does not match its signature", _0)]
+    Invalid(Box<str>),
+    /// A signed archive which was carried to this system could not be kept.
+    #[error("failed to keep the signed archive of {}", _0)]
+    Stage(Box<str>, #[source] io::Error),
+}
+
+/// Verifies that the archive of the System76 firmware identified by `digest`, with its changelog
+/// and payloads, was signed by a trusted key.
+///
+/// An archive which was staged from an offline bundle is verified in place of the published one.
+pub(crate) fn verify_digest(digest: &str) -> Result<(), SignatureError> {
+    if !is_enabled() {
+        warn!("not verifying {}: no trusted keys are installed in {}", digest, TRUSTED_KEYS);
+        return Ok(());
+    }
+
+    let keys = keys(Path::new(TRUSTED_KEYS))?;
+    let (archive, signature) = match staged_archive(digest) {
+        Some(staged) => staged,
+        None => download_archive(digest)?,
+    };
+
+    verify_named(&keys, digest, &archive, &signature)?;
+    info!("verified the signature of {}", digest);
+    Ok(())
+}
+
+/// Downloads the archive of the System76 firmware identified by `digest`, with its signature,
+/// once the signature has been verified, so that it may be carried to another system.
+pub(crate) fn fetch_archive(digest: &str) -> Result<(Vec<u8>, String), SignatureError> {
+    let keys = keys(Path::new(TRUSTED_KEYS))?;
+    let (archive, signature) = download_archive(digest)?;
+    verify_named(&keys, digest, &archive, &signature)?;
+    Ok((archive, signature))
+}
+
+/// Keeps an archive which was carried to this system, once its signature has been verified, so
+/// that [`verify_digest`] verifies it without downloading it.
+pub(crate) fn stage_archive(
+    digest: &str,
+    archive: &[u8],
+    signature: &str,
+) -> Result<(), SignatureError> {
+    let keys = keys(Path::new(TRUSTED_KEYS))?;
+    verify_named(&keys, digest, archive, signature)?;
+
+    let stage = |why| SignatureError::Stage(digest.into(), why);
+    let path = staged_path(digest).map_err(stage)?;
+    fs::write(&path, archive).map_err(stage)?;
+    fs::write(signature_path(&path), signature).map_err(stage)
+}
+
+/// Downloads the archive identified by `digest`, and its signature.
+fn download_archive(digest: &str) -> Result<(Vec<u8>, String), SignatureError> {
+    let url = published_url(digest)?;
+    let download = |why| SignatureError::Download(digest.into(), why);
+    let archive = download::fetch_bytes(&url).map_err(download)?;
+    let signature = download::fetch_text(&[&url, ".minisig"].concat()).map_err(download)?;
+    Ok((archive, signature))
+}
+
+/// The archive identified by `digest`, and its signature, if they were staged from a bundle.
+fn staged_archive(digest: &str) -> Option<(Vec<u8>, String)> {
+    let path = staged_path(digest).ok()?;
+    let signature = fs::read_to_string(signature_path(&path)).ok()?;
+    Some((fs::read(&path).ok()?, signature))
+}
+
+/// Where an archive which was staged from a bundle is kept, in the cache.
+fn staged_path(digest: &str) -> io::Result<PathBuf> {
+    cache::cache(["signed/", digest].concat()).map_err(|why| match why {
+        cache::Error::Place(why) => why,
+        why => io::Error::new(io::ErrorKind::Other, why),
+    })
+}
+
+/// The detached signature beside a file.
+fn signature_path(path: &Path) -> PathBuf {
+    let mut signature = path.as_os_str().to_owned();
+    signature.push(".minisig");
+    PathBuf::from(signature)
+}
+
+/// The URL of the `file`, relative to the location which signed System76 firmware is published at.
+pub(crate) fn published_url(file: &str) -> Result<String, SignatureError> {
+    let location = signature_location(Path::new(SIGNATURE_LOCATION))?;
+    Ok([location.trim_end_matches('/'), "/", file].concat())
+}
+
+/// Verifies that the `contents` of the file called `name` were signed by one of the keys in the
+/// `keys` directory, with the detached `signature`, whose trusted comment must name the file, as
+/// `file:<name>`.
+pub(crate) fn verify_file(
+    keys: &Path,
+    name: &str,
+    contents: &[u8],
+    signature: &str,
+) -> Result<(), SignatureError> {
+    let keys = self::keys(keys)?;
+    verify_named(&keys, name, contents, signature)?;
+    info!("verified the signature of {}", name);
+    Ok(())
+}
+
+/// Verifies a detached signature of the `contents` of the file called `name`, whose trusted
+/// comment must name it. Archives are named by their digest.
+fn verify_named(
+    keys: &[PublicKey],
+    name: &str,
+    contents: &[u8],
+    signature: &str,
+) -> Result<(), SignatureError> {
+    verify_contents(keys, name, contents, signature)?;
+
+    let decoded =
+        Signature::decode(signature).map_err(|why| SignatureError::Malformed(name.into(), why))?;
+
+    // The trusted comment is covered by the signature, which binds the contents to their name.
+    let file = ["file:", name].concat();
+    if decoded.trusted_comment().split('\t').any(|field| field == file) {
+        Ok(())
+    } else {
+        Err(SignatureError::Invalid(name.into()))
+    }
+}
+
+/// Verifies a detached signature of the `contents` of `name`.
+fn verify_contents(
+    keys: &[PublicKey],
+    name: &str,
+    contents: &[u8],
+    signature: &str,
+) -> Result<(), SignatureError> {
+    let signature =
+        Signature::decode(signature).map_err(|why| SignatureError::Malformed(name.into(), why))?;
+
+    let mut result = Err(SignatureError::Untrusted(name.into()));
+
+    for key in keys {
+        match key.verify(contents, &signature, false) {
+            Ok(()) => return Ok(()),
+            Err(minisign_verify::Error::UnexpectedKeyId) => (),
+            Err(_) => result = Err(SignatureError::Invalid(name.into())),
+        }
+    }
+
+    result
+}
+
+/// Checks if verification was enabled by installing trusted keys. Keys which could not be read
+/// are assumed to be installed, so that they are verified, and their error is reported then.
+pub(crate) fn is_enabled() -> bool {
+    trusted_keys(Path::new(TRUSTED_KEYS)).map_or(true, |keys| !keys.is_empty())
+}
+
+/// Loads the trusted public keys in the `directory`, of which there must be at least one.
+fn keys(directory: &Path) -> Result<Vec<PublicKey>, SignatureError> {
+    let keys = trusted_keys(directory)
+        .map_err(|why| SignatureError::Keys(directory.to_path_buf(), why))?;
+    if keys.is_empty() {
+        return Err(SignatureError::NoTrustedKeys(directory.to_path_buf()));
+    }
+
+    Ok(keys)
+}
+
+/// Reads the URL which signed firmware archives are published at from the file at `path`.
+fn signature_location(path: &Path) -> Result<String, SignatureError> {
+    let location = fs::read_to_string(path).map_err(SignatureError::Location)?;
+    let location = location.trim();
+    if location.is_empty() {
+        let why = io::Error::new(io::ErrorKind::InvalidData, "no location is configured");
+        return Err(SignatureError::Location(why));
+    }
+
+    Ok(location.to_owned())
+}
+
+/// Loads every public key in the `directory`, which is empty if the directory does not exist.
+fn trusted_keys(directory: &Path) -> io::Result<Vec<PublicKey>> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(why) if why.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(why) => return Err(why),
+    };
+
+    let mut keys = Vec::new();
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension() != Some(OsStr::new("pub")) {
+            continue;
+        }
+
+        match PublicKey::from_file(&path) {
+            Ok(key) => keys.push(key),
+            Err(why) => warn!("ignoring invalid key at {}: {}", path.display(), why),
+        }
+    }
+
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A key and signature of `test` from the minisign test vectors.
+    const KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==";
+
+    #[test]
+    fn verify_named() {
+        let keys = [PublicKey::from_base64(KEY).unwrap()];
+        let verify = |keys: &[PublicKey], name, contents: &[u8], signature| {
+            super::verify_named(keys, name, contents, signature)
+        };
+
+        assert!(verify(&keys, "test", b"test", SIGNATURE).is_ok());
+        let invalid = verify(&keys, "test", b"Test", SIGNATURE);
+        assert!(matches!(invalid, Err(SignatureError::Invalid(_))));
+        let unbound = verify(&keys, "other", b"test", SIGNATURE);
+        assert!(matches!(unbound, Err(SignatureError::Invalid(_))));
+        let untrusted = verify(&[], "test", b"test", SIGNATURE);
+        assert!(matches!(untrusted, Err(SignatureError::Untrusted(_))));
+        assert!(matches!(
+            verify(&keys, "test", b"test", "not a signature"),
+            Err(SignatureError::Malformed(..))
+        ));
+    }
+
+    #[test]
+    fn signature_location() {
+        let location = super::signature_location(Path::new("/nonexistent/firmware-manager"));
+        assert!(matches!(location, Err(SignatureError::Location(_))));
+    }
+
+    #[test]
+    fn trusted_keys() {
+        let keys = super::trusted_keys(Path::new("/nonexistent/firmware-manager/keys"));
+        assert!(keys.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+//! A backend which describes the system from its SMBIOS tables.
+//!
+//! The kernel exposes the fields of the SMBIOS tables which identify the system in
+//! `/sys/class/dmi/id`. The serial number is only readable by root, so it is omitted when the
+//! firmware manager runs as another user. Whether the firmware enforces Secure Boot is read from
+//! its EFI variables.
+
+use crate::{secure_boot, FirmwareBackend, FirmwareEvent, FirmwareSignal, SecureBoot};
+use std::{fs, path::Path, sync::mpsc::Sender};
+
+/// Where the kernel exposes the identifying fields of the SMBIOS tables.
+const DMI_ID: &str = "/sys/class/dmi/id";
+
+/// Values which vendors leave in fields that they did not fill in.
+const PLACEHOLDERS: &[&str] = &[
+    "default string",
+    "not applicable",
+    "not specified",
+    "none",
+    "o.e.m.",
+    "system manufacturer",
+    "system product name",
+    "system serial number",
+    "to be filled by o.e.m.",
+];
+
+/// A summary of the system, which frontends may show above its firmware.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SystemSummary {
+    /// The manufacturer of the system.
+    pub vendor: Option<Box<str>>,
+    /// The model of the system.
+    pub product: Option<Box<str>>,
+    /// The serial number of the system, which is only known to root.
+    pub serial: Option<Box<str>>,
+    /// The release date of the installed system firmware, as `YYYY-MM-DD`.
+    pub bios_date: Option<Box<str>>,
+    /// Whether the firmware enforces Secure Boot, if the system was booted with UEFI.
+    pub secure_boot: Option<SecureBoot>,
+}
+
+/// A backend which sends the [`SystemSummary`] with each scan.
+#[derive(Default)]
+pub struct SmbiosBackend;
+
+impl FirmwareBackend for SmbiosBackend {
+    fn name(&self) -> &str { "smbios" }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        let summary =
+            SystemSummary { secure_boot: secure_boot(), ..system_summary(Path::new(DMI_ID)) };
+        if summary != SystemSummary::default() {
+            let _res = sender.send(FirmwareSignal::SystemSummary(summary));
+        }
+    }
+
+    /// The summary describes the system, rather than firmware which may be updated.
+    fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+        None
+    }
+}
+
+/// Reads the summary of the system from the DMI attributes in `dmi`.
+fn system_summary(dmi: &Path) -> SystemSummary {
+    let field = |name: &str| filled(&fs::read_to_string(dmi.join(name)).ok()?);
+
+    SystemSummary {
+        vendor: field("sys_vendor"),
+        product: field("product_name"),
+        serial: field("product_serial"),
+        bios_date: field("bios_date").map(|date| iso_date(&date).map_or(date, Box::from)),
+        secure_boot: None,
+    }
+}
+
+/// The value of a field, unless the vendor left it empty or with a placeholder.
+fn filled(value: &str) -> Option<Box<str>> {
+    let value = value.trim();
+    let placeholder = PLACEHOLDERS.iter().any(|&filler| value.eq_ignore_ascii_case(filler));
+    Some(value.into()).filter(|_| !value.is_empty() && !placeholder)
+}
+
+/// Converts a date in the `MM/DD/YYYY` format of SMBIOS to `YYYY-MM-DD`.
+fn iso_date(date: &str) -> Option<String> {
+    let mut parts = date.split('/');
+    let (month, day, year) = (parts.next()?, parts.next()?, parts.next()?);
+
+    let digits =
+        |part: &str, len: usize| part.len() == len && part.bytes().all(|b| b.is_ascii_digit());
+
+    if parts.next().is_some() || !digits(month, 2) || !digits(day, 2) || !digits(year, 4) {
+        return None;
+    }
+
+    Some(format!("{}-{}-{}", year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn filled() {
+        assert_eq!(super::filled("System76\n"), Some("System76".into()));
+        assert_eq!(super::filled("To Be Filled By O.E.M.\n"), None);
+        assert_eq!(super::filled("Default string"), None);
+        assert_eq!(super::filled(" \n"), None);
+    }
+
+    #[test]
+    fn iso_date() {
+        assert_eq!(super::iso_date("08/14/2023").as_deref(), Some("2023-08-14"));
+        assert_eq!(super::iso_date("2023-08-14"), None);
+        assert_eq!(super::iso_date("8/14/2023"), None);
+        assert_eq!(super::iso_date("08/14/2023/1"), None);
+    }
+}
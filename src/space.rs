@@ -0,0 +1,183 @@
+//! Preflight checks for the disk space that a firmware update requires, and for the EFI System
+//! Partition that capsule updates are staged on.
+
+use std::{
+    ffi::CString,
+    fs, io,
+    mem::MaybeUninit,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+/// The mount points where the EFI System Partition is commonly found, in order of preference.
+const ESP_MOUNTS: &[&str] = &["/boot/efi", "/efi", "/boot"];
+
+/// There is not enough free space on a partition to perform an update.
+#[derive(Debug, Error)]
+#[error(
+    "need {} free on {}, but only {} is available",
+    human_size(*.required),
+    .path.display(),
+    human_size(*.available)
+)]
+pub struct InsufficientSpace {
+    /// The location which lacks the required space.
+    pub path: PathBuf,
+    /// The number of bytes required.
+    pub required: u64,
+    /// The number of bytes that are available.
+    pub available: u64,
+}
+
+/// The EFI System Partition can not be used to stage a capsule update.
+#[derive(Debug, Error)]
+pub enum EspError {
+    /// The EFI System Partition is not mounted at any of the usual mount points.
+    #[error("the EFI System Partition is not mounted")]
+    NotMounted,
+    /// The EFI System Partition is mounted, but may not be written to.
+    #[error("the EFI System Partition at {} is mounted read-only", _0.display())]
+    ReadOnly(PathBuf),
+    /// There is not enough free space on the EFI System Partition to stage the update.
+    #[error("not enough space on the EFI System Partition")]
+    Space(#[source] InsufficientSpace),
+}
+
+/// A mount of the EFI System Partition.
+#[derive(Debug)]
+struct EspMount {
+    path: PathBuf,
+    read_only: bool,
+}
+
+/// Ensures that the partition containing `path` has at least `required` bytes free.
+///
+/// If the free space could not be determined, the update will be allowed to proceed.
+pub(crate) fn ensure_available(path: &Path, required: u64) -> Result<(), InsufficientSpace> {
+    // The path may not have been created yet, so measure its closest existing ancestor.
+    let path = path.ancestors().find(|path| path.exists()).unwrap_or(path);
+
+    match available(path) {
+        Ok(available) if available < required => {
+            Err(InsufficientSpace { path: path.to_path_buf(), required, available })
+        }
+        Ok(_) => Ok(()),
+        Err(why) => {
+            warn!("failed to get free space of {}: {}", path.display(), why);
+            Ok(())
+        }
+    }
+}
+
+/// Ensures that the EFI System Partition is mounted, writable, and has at least `required` bytes
+/// free, before a capsule update is staged on it.
+///
+/// Returns the mount point of the partition.
+pub(crate) fn ensure_esp(required: u64) -> Result<PathBuf, EspError> {
+    let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
+    let esp = esp_from_mounts(&mounts).ok_or(EspError::NotMounted)?;
+
+    if esp.read_only {
+        return Err(EspError::ReadOnly(esp.path));
+    }
+
+    ensure_available(&esp.path, required).map_err(EspError::Space)?;
+    Ok(esp.path)
+}
+
+/// The number of bytes available to unprivileged users on the partition containing `path`.
+fn available(path: &Path) -> io::Result<u64> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let stat = unsafe { stat.assume_init() };
+
+    // The widths of these fields vary between platforms.
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Finds the first ESP mount point candidate which is mounted as a FAT filesystem.
+///
+/// A candidate which is an automount point is also accepted, as it is mounted when the space on
+/// it is measured.
+fn esp_from_mounts(mounts: &str) -> Option<EspMount> {
+    ESP_MOUNTS.iter().find_map(|&candidate| {
+        let mut entries = mounts.lines().filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let (path, kind, options) = (fields.next()?, fields.next()?, fields.next()?);
+            (path == candidate && (kind == "vfat" || kind == "autofs")).then_some((kind, options))
+        });
+
+        // Once an automount point has been mounted, the FAT filesystem is listed after it.
+        let mut entry = entries.next()?;
+        if let Some(mounted) = entries.find(|(kind, _)| *kind == "vfat") {
+            entry = mounted;
+        }
+
+        let read_only = entry.1.split(',').any(|option| option == "ro");
+        Some(EspMount { path: PathBuf::from(candidate), read_only })
+    })
+}
+
+/// Formats a number of bytes with decimal units, such as `45 MB`.
+pub(crate) fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["kB", "MB", "GB", "TB"];
+
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if value < 1000.0 {
+            break;
+        }
+
+        value /= 1000.0;
+        unit = next;
+    }
+
+    if value < 10.0 {
+        format!("{:.1} {}", value, unit)
+    } else {
+        format!("{:.0} {}", value, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    #[test]
+    fn esp_from_mounts() {
+        let esp = |mounts| super::esp_from_mounts(mounts).map(|esp| (esp.path, esp.read_only));
+
+        let mounts = "/dev/nvme0n1p2 / ext4 rw,relatime 0 0\n\
+                      /dev/nvme0n1p1 /boot/efi vfat rw,relatime 0 0\n";
+        assert_eq!(esp(mounts), Some((PathBuf::from("/boot/efi"), false)));
+
+        let mounts = "/dev/sda2 /boot ext4 rw 0 0\n/dev/sda1 /efi vfat ro,relatime 0 0\n";
+        assert_eq!(esp(mounts), Some((PathBuf::from("/efi"), true)));
+
+        let mounts = "systemd-1 /efi autofs rw,relatime 0 0\n\
+                      /dev/sda1 /efi vfat ro,relatime 0 0\n";
+        assert_eq!(esp(mounts), Some((PathBuf::from("/efi"), true)));
+
+        let mounts = "/dev/sda2 /boot ext4 rw 0 0\n";
+        assert_eq!(esp(mounts), None);
+    }
+
+    #[test]
+    fn human_size() {
+        assert_eq!(super::human_size(999), "999 B");
+        assert_eq!(super::human_size(1_500_000), "1.5 MB");
+        assert_eq!(super::human_size(45_000_000), "45 MB");
+        assert_eq!(super::human_size(128_000_000_000), "128 GB");
+    }
+}
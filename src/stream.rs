@@ -0,0 +1,109 @@
+//! An asynchronous interface to the event loop for frontends that are not built on channels.
+
+use crate::{FirmwareBackend, FirmwareEvent, FirmwareSignal};
+use futures::{channel::mpsc as async_mpsc, stream::Stream};
+use std::{
+    pin::Pin,
+    sync::mpsc::{self, Receiver, Sender},
+    task::{Context, Poll},
+    thread::{self, JoinHandle},
+};
+
+/// A handle to an event loop that is running in a background thread.
+///
+/// Requests are sent to the event loop with the methods of this type, while the signals that it
+/// emits are received from the [`SignalStream`] that was created alongside it. The event loop
+/// is stopped when the handle is dropped.
+pub struct FirmwareManager {
+    sender: Sender<FirmwareEvent>,
+    background: Option<JoinHandle<()>>,
+}
+
+impl FirmwareManager {
+    /// Spawns the event loop with the built-in backends.
+    pub fn spawn() -> (Self, SignalStream) { Self::spawn_with_backends(Vec::new()) }
+
+    /// Spawns the event loop with additional backends registered after the built-in backends.
+    pub fn spawn_with_backends(additional: Vec<Box<dyn FirmwareBackend>>) -> (Self, SignalStream) {
+        let (sender, receiver) = mpsc::channel();
+        let (tx_signals, rx_signals) = mpsc::channel();
+
+        let background = thread::spawn(move || {
+            crate::event_loop_with_backends(receiver, tx_signals, additional);
+            info!("firmware manager event loop stopped");
+        });
+
+        (Self { sender, background: Some(background) }, SignalStream::forward(rx_signals))
+    }
+
+    /// Requests the event loop to perform the given event.
+    pub fn send(&self, event: FirmwareEvent) { let _ = self.sender.send(event); }
+
+    /// Requests the event loop to scan for firmware.
+    pub fn scan(&self) { self.send(FirmwareEvent::Scan); }
+
+    /// A sender which may also be used to send requests to the event loop.
+    pub fn sender(&self) -> Sender<FirmwareEvent> { self.sender.clone() }
+}
+
+impl Drop for FirmwareManager {
+    fn drop(&mut self) {
+        self.send(FirmwareEvent::Stop);
+
+        if let Some(handle) = self.background.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// A stream of the signals emitted by the event loop.
+///
+/// The stream ends when the event loop stops.
+pub struct SignalStream(async_mpsc::UnboundedReceiver<FirmwareSignal>);
+
+impl SignalStream {
+    /// Forwards signals from a channel receiver into a stream, from a background thread.
+    fn forward(receiver: Receiver<FirmwareSignal>) -> Self {
+        let (tx, rx) = async_mpsc::unbounded();
+
+        thread::spawn(move || {
+            for signal in receiver {
+                if tx.unbounded_send(signal).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self(rx)
+    }
+}
+
+impl Stream for SignalStream {
+    type Item = FirmwareSignal;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.0).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignalStream;
+    use crate::FirmwareSignal;
+    use futures::{executor::block_on, stream::StreamExt};
+
+    #[test]
+    fn forward() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let stream = SignalStream::forward(rx);
+
+        let _ = tx.send(FirmwareSignal::Scanning);
+        let _ = tx.send(FirmwareSignal::ScanningComplete);
+        drop(tx);
+
+        let signals = block_on(stream.collect::<Vec<_>>());
+        assert_eq!(signals.len(), 2);
+        assert!(matches!(signals[0], FirmwareSignal::Scanning));
+        assert!(matches!(signals[1], FirmwareSignal::ScanningComplete));
+    }
+}
@@ -1,19 +1,323 @@
 //! Functions specific to working with system76 firmware.
 
-use crate::{lowest_revision, FirmwareInfo, FirmwareSignal};
-use std::{error::Error as _, sync::mpsc::Sender};
+use crate::{
+    history, inhibit, journal, open_firmware, retry::retry, signature, space, Entity,
+    FirmwareBackend, FirmwareError, FirmwareEvent, FirmwareInfo, FirmwareSignal, RebootPolicy,
+    ReleaseChannel, SignatureError, System76Digest, UpdateResult,
+};
+use std::{
+    collections::BTreeMap,
+    error::Error as _,
+    sync::{
+        mpsc::{self, Sender},
+        Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+};
 use system76_firmware_daemon::{
-    Client as System76Client, SystemInfo as S76SystemInfo, ThelioIoInfo,
+    Changelog as System76Changelog, Client as System76Client, SystemInfo as S76SystemInfo,
+    ThelioIoInfo,
 };
 
+/// A conservative estimate of the space that the daemon needs to stage system firmware on the
+/// EFI System Partition, as the size of the firmware is not known in advance.
+const SYSTEM_FIRMWARE_STAGING_SIZE: u64 = 32 * 1024 * 1024;
+
+/// The name of the embedded controller, which is updated with the system firmware.
+const EC_NAME: &str = "Embedded Controller";
+
+/// The name of Thelio I/O boards, which are numbered when a system has several of them.
+pub(crate) const THELIO_IO: &str = "Thelio I/O";
+
+/// A firmware backend for System76 system firmware and Thelio I/O boards.
+pub struct System76Backend {
+    client: Mutex<System76Client>,
+    scanned: Mutex<Scanned>,
+}
+
+/// The firmware found by the last scan, which describes updates in the update history.
+#[derive(Default)]
+struct Scanned {
+    system: Option<FirmwareInfo>,
+    /// Thelio I/O boards, by their IDs.
+    thelio: BTreeMap<Box<str>, FirmwareInfo>,
+}
+
+impl System76Backend {
+    /// Connects to the system76-firmware daemon, if the service is active.
+    pub fn connect() -> Option<Self> {
+        crate::get_client("system76", s76_firmware_is_active, System76Client::new)
+            .map(|client| Self { client: Mutex::new(client), scanned: Mutex::default() })
+    }
+
+    /// Locks the client, so that requests to the daemon are made one at a time.
+    fn client(&self) -> MutexGuard<'_, System76Client> {
+        self.client.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn scanned(&self) -> MutexGuard<'_, Scanned> {
+        self.scanned.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl FirmwareBackend for System76Backend {
+    fn name(&self) -> &str { "system76" }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        // The daemon only offers stable releases, which is an error if another channel was
+        // chosen for this backend alone, rather than for every backend.
+        let config = crate::config();
+        match config.channels.get(self.name()).copied() {
+            Some(ReleaseChannel::Stable) => (),
+            Some(channel) => {
+                let why = FirmwareError::Channel(self.name().into(), channel);
+                let _res = sender.send(FirmwareSignal::Error(None, why));
+            }
+            None if config.release_channel_of(self.name()) != ReleaseChannel::Stable => {
+                info!("system76-firmware only provides stable releases: ignoring the channel");
+            }
+            None => (),
+        }
+
+        // Signals are relayed, so that the firmware that was found may be remembered.
+        let (tx, rx) = mpsc::channel();
+        s76_scan(&self.client(), tx);
+
+        let mut scanned = self.scanned();
+        scanned.thelio.clear();
+        for signal in rx {
+            match signal {
+                FirmwareSignal::S76System(ref info, _) => scanned.system = Some(info.clone()),
+                FirmwareSignal::ThelioIo(ref board, ref info, _) => {
+                    scanned.thelio.insert(board.clone(), info.clone());
+                }
+                _ => (),
+            }
+
+            let _res = sender.send(signal);
+        }
+    }
+
+    fn update(
+        &self,
+        event: &FirmwareEvent,
+        sender: &Sender<FirmwareSignal>,
+    ) -> Option<FirmwareSignal> {
+        let signal = match event {
+            FirmwareEvent::S76System(entity, digest) => {
+                if let Err(why) = approved(self.scanned().system.as_ref()) {
+                    return Some(FirmwareSignal::Error(Some(*entity), why));
+                }
+
+                if let Err(why) = verify(digest) {
+                    return Some(self.record(event, verification_failed(*entity, why)));
+                }
+
+                let preflight = space::ensure_esp(SYSTEM_FIRMWARE_STAGING_SIZE);
+
+                let result = preflight.map_err(FirmwareError::from).and_then(|_| {
+                    info_span!("schedule")
+                        .in_scope(|| self.client().schedule(digest))
+                        .map_err(|why| FirmwareError::from_daemon(FirmwareError::Schedule(why)))
+                });
+
+                match result {
+                    Ok(_) if crate::config().reboot_policy == RebootPolicy::NextBoot => {
+                        FirmwareSignal::Staged(*entity)
+                    }
+                    Ok(_) => FirmwareSignal::SystemScheduled,
+                    Err(why) => FirmwareSignal::Error(Some(*entity), why),
+                }
+            }
+            FirmwareEvent::ThelioIo(entity, .., cancellable) if cancellable.is_cancelled() => {
+                FirmwareSignal::Cancelled(*entity)
+            }
+            FirmwareEvent::ThelioIo(entity, board, digest, _) => {
+                let info = self.scanned().thelio.get(board).cloned();
+                if let Err(why) = approved(info.as_ref()) {
+                    return Some(FirmwareSignal::Error(Some(*entity), why));
+                }
+
+                // The daemon flashes every board which is out of date at once, so each of the
+                // other boards must be approved as well, and is reported as flashing with this one.
+                let others = self.outdated_boards(board);
+                if let Err(why) = others.iter().try_for_each(|(_, info)| approved(Some(info))) {
+                    return Some(FirmwareSignal::Error(Some(*entity), why));
+                }
+
+                if let Err(why) = verify(digest) {
+                    return Some(self.record(event, verification_failed(*entity, why)));
+                }
+
+                let latest = info.as_ref().and_then(|info| info.latest.as_deref());
+
+                // The daemon flashes every board which is out of date at once, so this board may
+                // already have been updated along with another board.
+                if latest.is_some() && self.board_revision(board).as_deref() == latest {
+                    return Some(self.record(event, FirmwareSignal::DeviceUpdated(*entity)));
+                }
+
+                let name = info.as_ref().map_or(THELIO_IO, |info| &info.name);
+                let _inhibitor = inhibit::flashing(name);
+                let _flashing = info.as_ref().map(|info| {
+                    journal::begin(&info.name, &info.current, latest.unwrap_or_default())
+                });
+
+                let _res = sender.send(FirmwareSignal::DeviceFlashing(*entity));
+                for (other, _) in &others {
+                    let _res = sender.send(FirmwareSignal::ThelioIoFlashing(other.clone()));
+                }
+
+                // The flash takes its own connection to the daemon, as it may take minutes, and
+                // the shared client must remain free for scans and the other updates meanwhile.
+                let span = info_span!("flash", device_id = "thelio-io", board = &**board);
+                let result = span.in_scope(|| {
+                    System76Client::new().and_then(|client| client.thelio_io_update(digest))
+                });
+
+                // Each of the other boards reports its own result, from the revision that it now
+                // has, whether or not the daemon reported an error.
+                for (other, info) in others {
+                    let error = self.board_error(&other, &info);
+                    let result = match error {
+                        Some(ref why) => UpdateResult::Failed(crate::format_error(why).into()),
+                        None => UpdateResult::Success,
+                    };
+
+                    let latest = info.latest.as_deref().unwrap_or_default();
+                    history::record_result(&info.name, &info.current, latest, result);
+                    let _res = sender.send(FirmwareSignal::ThelioIoFlashed(other, error));
+                }
+
+                match result {
+                    Ok(_) => match info.as_ref().and_then(|info| self.board_error(board, info)) {
+                        Some(why) => FirmwareSignal::Error(Some(*entity), why),
+                        None => FirmwareSignal::DeviceUpdated(*entity),
+                    },
+                    Err(why) => {
+                        FirmwareSignal::Error(Some(*entity), FirmwareError::from_daemon(why))
+                    }
+                }
+            }
+            _ => return None,
+        };
+
+        Some(self.record(event, signal))
+    }
+}
+
+impl System76Backend {
+    /// The Thelio I/O boards of the last scan, other than the given board, which are out of date,
+    /// and so are flashed along with it.
+    fn outdated_boards(&self, board: &str) -> Vec<(Box<str>, FirmwareInfo)> {
+        self.scanned()
+            .thelio
+            .iter()
+            .filter(|(other, info)| {
+                &***other != board
+                    && info.latest.as_deref().map_or(false, |latest| latest != &*info.current)
+            })
+            .map(|(other, info)| (other.clone(), info.clone()))
+            .collect()
+    }
+
+    /// The error of a Thelio I/O board which was flashed, if it does not now have the latest
+    /// revision of its scan.
+    fn board_error(&self, board: &str, info: &FirmwareInfo) -> Option<FirmwareError> {
+        let latest = info.latest.as_deref()?;
+        match self.board_revision(board) {
+            Some(revision) if &*revision != latest => {
+                Some(FirmwareError::ThelioIoBoard(info.name.clone(), revision))
+            }
+            _ => None,
+        }
+    }
+
+    /// The revision that the daemon reports for a Thelio I/O board, if it is still connected.
+    fn board_revision(&self, board: &str) -> Option<Box<str>> {
+        match self.client().thelio_io_list() {
+            Ok(mut boards) => boards.remove(board),
+            Err(why) => {
+                warn!("failed to list Thelio I/O boards: {}", why);
+                None
+            }
+        }
+    }
+
+    /// Records the outcome of an update in the update history.
+    fn record(&self, event: &FirmwareEvent, signal: FirmwareSignal) -> FirmwareSignal {
+        let scanned = self.scanned();
+        let info = match event {
+            FirmwareEvent::S76System(..) => scanned.system.as_ref(),
+            FirmwareEvent::ThelioIo(_, board, ..) => scanned.thelio.get(board),
+            _ => None,
+        };
+
+        if let Some(info) = info {
+            let latest = info.latest.as_deref().unwrap_or_default();
+            history::record(&info.name, &info.current, latest, &signal);
+        }
+
+        signal
+    }
+}
+
+/// Checks if the firmware policy of the system approved the latest firmware of the last scan.
+fn approved(scanned: Option<&FirmwareInfo>) -> Result<(), FirmwareError> {
+    match scanned {
+        Some(FirmwareInfo { latest: Some(latest), policy: Some(reason), .. }) => {
+            Err(FirmwareError::Policy(latest.clone(), *reason))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Verifies the signature of firmware before it is handed to the daemon to be flashed.
+fn verify(digest: &System76Digest) -> Result<(), SignatureError> {
+    info_span!("verify").in_scope(|| signature::verify_digest(&digest.0))
+}
+
+/// The signal for firmware which could not be verified. Firmware which could not be downloaded
+/// to be verified has failed to download, rather than failed verification.
+fn verification_failed(entity: Entity, why: SignatureError) -> FirmwareSignal {
+    match why {
+        SignatureError::Download(_, why) => FirmwareSignal::Error(Some(entity), why.into()),
+        why => FirmwareSignal::VerificationFailed(entity, why),
+    }
+}
+
 /// Scan for available System76 firmware
+///
+/// System firmware and Thelio I/O boards are checked concurrently, with a separate connection to
+/// the daemon for the Thelio I/O boards.
 pub fn s76_scan(client: &System76Client, sender: Sender<FirmwareSignal>) {
+    thread::scope(|scope| {
+        let thelio_sender = sender.clone();
+        scope.spawn(move || match System76Client::new() {
+            Ok(client) => thelio_io_scan(&client, &thelio_sender),
+            Err(why) => error!("failed to connect to system76-firmware-daemon: {}", why),
+        });
+
+        system_scan(client, &sender);
+    });
+}
+
+/// Checks if System76 system firmware has an update.
+fn system_scan(client: &System76Client, sender: &Sender<FirmwareSignal>) {
     info!("scanning for system76 devices");
 
     // Thelio system firmware check.
     if let Ok(current) = client.bios() {
-        let info = match client.download() {
-            Ok(S76SystemInfo { digest, changelog }) => Some((digest, changelog)),
+        let download = || client.download();
+        let info = match retry("download system76 changelog", None, |_| true, download) {
+            Ok(S76SystemInfo { digest, mut changelog }) => {
+                // The daemon only summarizes the releases of open firmware.
+                if open_firmware::is_open_firmware() {
+                    open_firmware::add_release_notes(&mut changelog);
+                }
+
+                Some((digest, changelog))
+            }
             Err(why) => {
                 let mut error_message = format!("{}", why);
                 let mut cause = why.source();
@@ -28,63 +332,136 @@ pub fn s76_scan(client: &System76Client, sender: Sender<FirmwareSignal>) {
 
         let name: Box<str> = crate::system_board_identity().map(Box::from).unwrap_or(current.model);
 
-        let fw = FirmwareInfo {
-            name,
-            current: current.version,
-            latest: info.as_ref().map(|(_, changelog)| {
-                changelog.versions.iter().next().expect("empty changelog").bios.clone()
-            }),
-            install_duration: 1,
-        };
+        let latest = info.as_ref().map(|(_, changelog)| {
+            changelog.versions.iter().next().expect("empty changelog").bios.clone()
+        });
+
+        let policy =
+            latest.as_ref().and_then(|latest| crate::check_policy(&name, &[], latest).err());
+
+        let fw =
+            FirmwareInfo { name, current: current.version, latest, install_duration: 1, policy };
+
+        let ec = crate::ec_firmware_release().ok().map(|current| {
+            let changelog = info.as_ref().map(|(_, changelog)| changelog);
+            let latest = changelog.and_then(|changelog| bundled_ec(changelog, &current));
+            FirmwareInfo {
+                name: EC_NAME.into(),
+                latest: latest.map(Box::from),
+                current: current.into(),
+                install_duration: 0,
+                policy: None,
+            }
+        });
 
         let _res = sender.send(FirmwareSignal::S76System(fw, info));
-    }
 
-    info!("scanning for Thelio I/O devices");
+        if let Some(ec) = ec {
+            let _res = sender.send(FirmwareSignal::S76Ec(ec));
+        }
+    }
 
-    // Thelio I/O system firmware check.
-    let event = match client.thelio_io_list() {
-        Ok(list) => {
-            if list.is_empty() {
-                None
-            } else {
-                let lowest_revision = lowest_revision(list.iter().map(|(_, rev)| rev.as_ref()));
+    info!("finished scanning for system76 devices")
+}
 
-                let current =
-                    Box::from(if lowest_revision.is_empty() { "N/A" } else { lowest_revision });
+/// The version of embedded controller firmware which the latest system firmware of the
+/// `changelog` installs, if it differs from the `current` version of the embedded controller.
+///
+/// The `current` version is the major and minor release which the DMI tables report, whereas
+/// the changelog gives the full version of the firmware, so both are compared as EC releases.
+/// Versions which have no such release, as the dated builds of open firmware, are not compared.
+pub fn bundled_ec<'a>(changelog: &'a System76Changelog, current: &str) -> Option<&'a str> {
+    let current = ec_release(current)?;
+    let latest = changelog.versions.first()?.ec.as_deref()?;
+    Some(latest).filter(|_| ec_release(latest).map_or(false, |release| release != current))
+}
 
-                let (latest, digest) = match client.thelio_io_download() {
-                    Ok(info) => {
-                        let ThelioIoInfo { digest, revision } = info;
-                        (Some(revision), Some(digest))
-                    }
-                    Err(why) => {
-                        error!("failed to download Thelio I/O digest: {:?}", why);
-                        (None, None)
-                    }
-                };
+/// The major and minor release of an embedded controller `version`, as reported by SMBIOS.
+///
+/// SMBIOS reports `255.255` when the release is not known.
+fn ec_release(version: &str) -> Option<(u8, u8)> {
+    let mut components = version.split('.').map(|component| component.parse::<u8>().ok());
+    match (components.next()??, components.next()??) {
+        (u8::MAX, u8::MAX) => None,
+        release => Some(release),
+    }
+}
 
-                let fw = FirmwareInfo {
-                    name: "Thelio I/O".into(),
-                    current,
-                    latest,
-                    install_duration: 15,
-                };
+/// Checks if Thelio I/O boards have an update.
+///
+/// Every board is updated with the same firmware, which is downloaded once for all of them.
+fn thelio_io_scan(client: &System76Client, sender: &Sender<FirmwareSignal>) {
+    info!("scanning for Thelio I/O devices");
 
-                Some(FirmwareSignal::ThelioIo(fw, digest))
-            }
+    let boards = match client.thelio_io_list() {
+        Ok(boards) => boards,
+        Err(why) => {
+            let _res = sender.send(FirmwareSignal::Error(None, why.into()));
+            return;
         }
-        Err(why) => Some(FirmwareSignal::Error(None, why.into())),
     };
 
-    if let Some(event) = event {
-        let _res = sender.send(event);
+    if !boards.is_empty() {
+        let download = || client.thelio_io_download();
+        let result = retry("download Thelio I/O digest", None, |_| true, download);
+        let (latest, digest) = match result {
+            Ok(ThelioIoInfo { digest, revision }) => (Some(revision), Some(digest)),
+            Err(why) => {
+                error!("failed to download Thelio I/O digest: {:?}", why);
+                (None, None)
+            }
+        };
+
+        let count = boards.len();
+        for (index, (board, revision)) in boards.into_iter().enumerate() {
+            let name = thelio_io_name(index, count);
+            let policy = latest.as_ref().and_then(|latest| {
+                crate::check_policy(THELIO_IO, &[&*name, &*board], latest).err()
+            });
+
+            let fw = FirmwareInfo {
+                name: name.into(),
+                current: if revision.is_empty() { "N/A".into() } else { revision },
+                latest: latest.clone(),
+                install_duration: 15,
+                policy,
+            };
+
+            let _res = sender.send(FirmwareSignal::ThelioIo(board, fw, digest.clone()));
+        }
     }
 
-    info!("finished scanning for system76 devices")
+    info!("finished scanning for Thelio I/O devices")
+}
+
+/// The name of a Thelio I/O board, which is numbered when there are several boards.
+fn thelio_io_name(index: usize, count: usize) -> String {
+    if count == 1 {
+        THELIO_IO.into()
+    } else {
+        format!("{} {}", THELIO_IO, index + 1)
+    }
 }
 
 /// Check if the system76-firmware-daemon service is active.
 pub fn s76_firmware_is_active() -> bool {
     crate::systemd_service_is_active("system76-firmware-daemon")
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn ec_release() {
+        assert_eq!(super::ec_release("1.7"), Some((1, 7)));
+        assert_eq!(super::ec_release("1.07.02"), Some((1, 7)));
+        assert_eq!(super::ec_release("255.255"), None);
+        assert_eq!(super::ec_release("2023-03-01_0a1b2c3"), None);
+    }
+
+    #[test]
+    fn thelio_io_name() {
+        assert_eq!(super::thelio_io_name(0, 1), "Thelio I/O");
+        assert_eq!(super::thelio_io_name(0, 2), "Thelio I/O 1");
+        assert_eq!(super::thelio_io_name(1, 2), "Thelio I/O 2");
+    }
+}
@@ -0,0 +1,260 @@
+//! The topology of composite devices, such as docks, whose devices must be updated in order.
+//!
+//! fwupd describes a composite device as a tree, in which every device but the root names its
+//! parent. Flashing a parent may reset or re-enumerate its children, so when several devices of
+//! a composite device are updated together, only one of them is flashed at a time, and children
+//! are flashed before their parents.
+
+use fwupd_dbus::Device as FwupdDevice;
+use std::{
+    collections::HashMap,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+/// The parent of every device which is a child of a composite device, by device ID.
+#[derive(Clone, Debug, Default)]
+pub struct Topology {
+    parents: HashMap<Box<str>, Box<str>>,
+}
+
+impl Topology {
+    /// Records the parents of the given devices.
+    pub fn from_devices(devices: &[FwupdDevice]) -> Self {
+        let mut topology = Self::default();
+        for device in devices {
+            topology.insert(&device.device_id, device.parent_device_id.as_deref());
+        }
+
+        topology
+    }
+
+    /// Records the parent of a device, if it has one.
+    pub fn insert(&mut self, device_id: &str, parent: Option<&str>) {
+        match parent {
+            Some(parent) => self.parents.insert(device_id.into(), parent.into()),
+            None => self.parents.remove(device_id),
+        };
+    }
+
+    /// Forgets a device, such as after it was unplugged.
+    pub fn remove(&mut self, device_id: &str) {
+        self.parents.remove(device_id);
+        self.parents.retain(|_, parent| &**parent != device_id);
+    }
+
+    /// The parent of a device, if it is a child of a composite device.
+    pub fn parent(&self, device_id: &str) -> Option<&str> {
+        self.parents.get(device_id).map(AsRef::as_ref)
+    }
+
+    /// The devices which are direct children of a device.
+    pub fn children<'a>(&'a self, device_id: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.parents
+            .iter()
+            .filter(move |(_, parent)| &***parent == device_id)
+            .map(|(child, _)| child.as_ref())
+    }
+
+    /// The root of the composite device which a device belongs to, which is the device itself if
+    /// it does not have a parent.
+    pub fn root<'a>(&'a self, device_id: &'a str) -> &'a str {
+        self.ancestors(device_id).last().unwrap_or(device_id)
+    }
+
+    /// The number of ancestors of a device, which is zero for the root of a composite device.
+    pub fn depth(&self, device_id: &str) -> usize { self.ancestors(device_id).count() }
+
+    /// Orders the updates of devices so that children are updated before their parents, as when
+    /// the updates of a composite device are queued together.
+    pub fn sort_for_update<T, F>(&self, updates: &mut [T], device_id: F)
+    where
+        F: Fn(&T) -> &str,
+    {
+        updates.sort_by_key(|update| std::cmp::Reverse(self.depth(device_id(update))));
+    }
+
+    /// The parent of a device, its parent, and so on, up to the root of the composite device.
+    fn ancestors<'a>(&'a self, device_id: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        // The number of ancestors is bounded, in case fwupd describes a cycle.
+        std::iter::successors(self.parent(device_id), move |parent| self.parent(parent))
+            .take(self.parents.len())
+    }
+}
+
+/// Orders the flashing of devices which belong to the same composite device.
+#[derive(Debug, Default)]
+pub(crate) struct Sequencer {
+    state: Mutex<SequencerState>,
+    condvar: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct SequencerState {
+    /// Updates which have been scheduled, but have not begun flashing.
+    scheduled: Vec<Scheduled>,
+    /// The roots of composite devices which have a device that is being flashed.
+    flashing: Vec<Box<str>>,
+    next_ticket: u64,
+}
+
+#[derive(Debug)]
+struct Scheduled {
+    ticket: u64,
+    root: Box<str>,
+    depth: usize,
+}
+
+impl Scheduled {
+    /// Checks if this update must be flashed before the `other` update.
+    fn precedes(&self, other: &Scheduled) -> bool {
+        self.root == other.root
+            && (self.depth > other.depth
+                || (self.depth == other.depth && self.ticket < other.ticket))
+    }
+}
+
+impl Sequencer {
+    /// Schedules an update of the device, which must wait for its turn before it is flashed.
+    ///
+    /// The update is forgotten when the ticket is dropped.
+    pub fn schedule(&self, topology: &Topology, device_id: &str) -> Ticket<'_> {
+        let mut state = self.state();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+
+        let root: Box<str> = topology.root(device_id).into();
+        let depth = topology.depth(device_id);
+        state.scheduled.push(Scheduled { ticket, root: root.clone(), depth });
+
+        Ticket { sequencer: self, ticket, root, flashing: false }
+    }
+
+    fn state(&self) -> MutexGuard<'_, SequencerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// An update which was scheduled with a [`Sequencer`].
+pub(crate) struct Ticket<'a> {
+    sequencer: &'a Sequencer,
+    ticket: u64,
+    root: Box<str>,
+    flashing: bool,
+}
+
+impl Ticket<'_> {
+    /// Blocks until no other device of the composite device is being flashed, and every device
+    /// which must be flashed before this one has been.
+    pub fn wait(&mut self) {
+        let mut state = self.sequencer.state();
+
+        loop {
+            let scheduled = state
+                .scheduled
+                .iter()
+                .find(|scheduled| scheduled.ticket == self.ticket)
+                .expect("scheduled update was lost");
+
+            let blocked = state.flashing.contains(&self.root)
+                || state.scheduled.iter().any(|other| other.precedes(scheduled));
+
+            if !blocked {
+                break;
+            }
+
+            info!("waiting for other devices of {} to be flashed", self.root);
+            state = self.sequencer.condvar.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+
+        state.scheduled.retain(|scheduled| scheduled.ticket != self.ticket);
+        state.flashing.push(self.root.clone());
+        self.flashing = true;
+    }
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        let mut state = self.sequencer.state();
+        state.scheduled.retain(|scheduled| scheduled.ticket != self.ticket);
+
+        if self.flashing {
+            if let Some(position) = state.flashing.iter().position(|root| *root == self.root) {
+                state.flashing.remove(position);
+            }
+        }
+
+        self.sequencer.condvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, thread, time::Duration};
+
+    /// A dock, with a hub whose child is a controller, and a device which is not part of it.
+    fn topology() -> Topology {
+        let mut topology = Topology::default();
+        topology.insert("dock", None);
+        topology.insert("hub", Some("dock"));
+        topology.insert("controller", Some("hub"));
+        topology.insert("webcam", None);
+        topology
+    }
+
+    #[test]
+    fn topology_depth() {
+        let topology = topology();
+
+        assert_eq!(topology.root("controller"), "dock");
+        assert_eq!(topology.depth("controller"), 2);
+        assert_eq!(topology.depth("dock"), 0);
+        assert_eq!(topology.root("webcam"), "webcam");
+        assert_eq!(topology.children("dock").collect::<Vec<_>>(), ["hub"]);
+
+        let mut updates = ["dock", "webcam", "controller", "hub"];
+        topology.sort_for_update(&mut updates, |device_id| device_id);
+        assert_eq!(updates, ["controller", "hub", "dock", "webcam"]);
+
+        let mut cycle = Topology::default();
+        cycle.insert("a", Some("b"));
+        cycle.insert("b", Some("a"));
+        assert_eq!(cycle.depth("a"), 2);
+    }
+
+    #[test]
+    fn sequencer() {
+        let topology = topology();
+        let sequencer = Sequencer::default();
+        let (tx, rx) = mpsc::channel();
+
+        thread::scope(|scope| {
+            // The dock is scheduled first, but must wait for its descendants.
+            let dock = sequencer.schedule(&topology, "dock");
+            let mut controller = sequencer.schedule(&topology, "controller");
+            let hub = sequencer.schedule(&topology, "hub");
+            let mut webcam = sequencer.schedule(&topology, "webcam");
+
+            // Unrelated devices are not held up by the composite device.
+            webcam.wait();
+            drop(webcam);
+
+            for (name, mut ticket) in [("dock", dock), ("hub", hub)] {
+                let tx = tx.clone();
+                scope.spawn(move || {
+                    ticket.wait();
+                    let _res = tx.send(name);
+                });
+            }
+
+            thread::sleep(Duration::from_millis(50));
+            assert!(rx.try_recv().is_err());
+
+            controller.wait();
+            drop(controller);
+
+            assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("hub"));
+            assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("dock"));
+        });
+    }
+}
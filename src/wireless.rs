@@ -0,0 +1,212 @@
+//! A backend which reports the firmware which is loaded by Wi-Fi and Bluetooth adapters.
+//!
+//! Wireless adapters load their firmware from linux-firmware each time that their driver probes
+//! them, so it is updated with the distribution's packages rather than flashed. The versions are
+//! only reported for users diagnosing connectivity issues to see them alongside the rest of the
+//! system's firmware.
+//!
+//! The firmware of Wi-Fi adapters is reported by their drivers through ethtool, while Bluetooth
+//! drivers describe their firmware in debugfs, which is only readable by root.
+
+use crate::{DeviceId, DeviceSignal, FirmwareBackend, FirmwareEvent, FirmwareInfo, FirmwareSignal};
+use std::{
+    fs, io,
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd},
+    path::Path,
+    sync::mpsc::Sender,
+};
+
+/// The name of the wireless backend, which identifies its devices.
+const BACKEND: &str = "wireless";
+
+/// Where the network interfaces of the system are described.
+const NET_INTERFACES: &str = "/sys/class/net";
+
+/// Where the Bluetooth controllers of the system are described.
+const BLUETOOTH_CONTROLLERS: &str = "/sys/class/bluetooth";
+
+/// Where Bluetooth drivers describe the firmware of their controllers.
+const BLUETOOTH_DEBUGFS: &str = "/sys/kernel/debug/bluetooth";
+
+/// The ethtool command which gets the driver information of an interface.
+const ETHTOOL_GDRVINFO: u32 = 0x3;
+
+/// The length of the strings of `EthtoolDrvinfo`.
+const ETHTOOL_STRING_LEN: usize = 32;
+
+/// The driver information of a network interface, as `struct ethtool_drvinfo`.
+///
+/// Only the driver and firmware version are read, but the kernel writes the whole structure.
+#[allow(dead_code)]
+#[repr(C)]
+struct EthtoolDrvinfo {
+    cmd: u32,
+    driver: [u8; ETHTOOL_STRING_LEN],
+    version: [u8; ETHTOOL_STRING_LEN],
+    fw_version: [u8; ETHTOOL_STRING_LEN],
+    bus_info: [u8; ETHTOOL_STRING_LEN],
+    erom_version: [u8; ETHTOOL_STRING_LEN],
+    reserved2: [u8; 12],
+    n_priv_flags: u32,
+    n_stats: u32,
+    testinfo_len: u32,
+    eedump_len: u32,
+    regdump_len: u32,
+}
+
+/// A request for an interface, as `struct ifreq` with its `ifr_data` member.
+#[repr(C)]
+struct IfReq {
+    name: [u8; libc::IFNAMSIZ],
+    data: *mut EthtoolDrvinfo,
+    /// Pads the request to the size of the largest member of the union.
+    padding: [u8; 16],
+}
+
+/// A firmware backend which reports the firmware loaded by wireless adapters.
+#[derive(Default)]
+pub struct WirelessBackend;
+
+impl FirmwareBackend for WirelessBackend {
+    fn name(&self) -> &str { BACKEND }
+
+    fn scan(&self, sender: &Sender<FirmwareSignal>) {
+        let adapters = wifi_adapters(Path::new(NET_INTERFACES))
+            .into_iter()
+            .chain(bluetooth_adapters(Path::new(BLUETOOTH_CONTROLLERS)));
+
+        for (id, name, version) in adapters {
+            let _res = sender.send(FirmwareSignal::Device(DeviceSignal {
+                device: DeviceId { backend: BACKEND.into(), id },
+                info: FirmwareInfo {
+                    name,
+                    current: version,
+                    latest: None,
+                    install_duration: 0,
+                    policy: None,
+                },
+                read_only: true,
+            }));
+        }
+    }
+
+    /// The firmware of wireless adapters is updated with the linux-firmware package.
+    fn update(&self, _: &FirmwareEvent, _: &Sender<FirmwareSignal>) -> Option<FirmwareSignal> {
+        None
+    }
+}
+
+/// The interface, name, and firmware version of each Wi-Fi adapter.
+fn wifi_adapters(interfaces: &Path) -> Vec<(Box<str>, Box<str>, Box<str>)> {
+    let entries = match fs::read_dir(interfaces) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut adapters = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().join("wireless").exists())
+        .filter_map(|entry| {
+            let interface = entry.file_name().to_string_lossy().into_owned();
+            let drvinfo = drvinfo(&interface)
+                .map_err(|why| warn!("failed to get the driver of {}: {}", interface, why))
+                .ok()?;
+
+            let version = c_string(&drvinfo.fw_version);
+            if version.is_empty() {
+                return None;
+            }
+
+            let name = format!("Wi-Fi Adapter ({})", c_string(&drvinfo.driver));
+            Some((interface.into(), name.into(), version.into()))
+        })
+        .collect::<Vec<_>>();
+
+    adapters.sort();
+    adapters
+}
+
+/// The controller, name, and firmware description of each Bluetooth adapter.
+fn bluetooth_adapters(controllers: &Path) -> Vec<(Box<str>, Box<str>, Box<str>)> {
+    let entries = match fs::read_dir(controllers) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut adapters = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let controller = entry.file_name().to_string_lossy().into_owned();
+            let info = Path::new(BLUETOOTH_DEBUGFS).join(&controller).join("firmware_info");
+            let info = fs::read_to_string(info).ok()?;
+            let version = firmware_info(&info)?;
+            let name = format!("Bluetooth Adapter ({})", controller);
+            Some((controller.into(), name.into(), version.into()))
+        })
+        .collect::<Vec<_>>();
+
+    adapters.sort();
+    adapters
+}
+
+/// Gets the driver information of a network interface through ethtool.
+fn drvinfo(interface: &str) -> io::Result<EthtoolDrvinfo> {
+    // The name must be terminated by a nul byte.
+    if interface.len() >= libc::IFNAMSIZ {
+        return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    }
+
+    let mut name = [0; libc::IFNAMSIZ];
+    name[..interface.len()].copy_from_slice(interface.as_bytes());
+
+    // Safety: the drvinfo is plain data, for which zeroes are valid.
+    let mut drvinfo: EthtoolDrvinfo = unsafe { std::mem::zeroed() };
+    drvinfo.cmd = ETHTOOL_GDRVINFO;
+    let mut request = IfReq { name, data: &mut drvinfo, padding: [0; 16] };
+
+    // Safety: the socket is owned as soon as it is opened, and the request and the drvinfo that
+    // it points to outlive the ioctl.
+    unsafe {
+        let socket = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if socket < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let socket = OwnedFd::from_raw_fd(socket);
+        if libc::ioctl(socket.as_raw_fd(), libc::SIOCETHTOOL, &mut request) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(drvinfo)
+}
+
+/// The contents of a nul-terminated string of fixed length.
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_owned()
+}
+
+/// Parses the `firmware_info` that a Bluetooth driver writes to debugfs.
+fn firmware_info(info: &str) -> Option<&str> {
+    Some(info.trim()).filter(|info| !info.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn c_string() {
+        let mut bytes = [0; super::ETHTOOL_STRING_LEN];
+        bytes[..14].copy_from_slice(b"72.daa05125.0 ");
+        assert_eq!(super::c_string(&bytes), "72.daa05125.0");
+        assert_eq!(super::c_string(&[0; super::ETHTOOL_STRING_LEN]), "");
+        assert_eq!(super::c_string(b"iwlwifi"), "iwlwifi");
+    }
+
+    #[test]
+    fn firmware_info() {
+        let info = "INTEL platform=55 variant=3 revision=0\n";
+        assert_eq!(super::firmware_info(info), Some("INTEL platform=55 variant=3 revision=0"));
+        assert_eq!(super::firmware_info("\n"), None);
+    }
+}